@@ -0,0 +1,75 @@
+use report::{ErrorLocation, Report};
+
+/// GitHub Actions workflow commands for the compile errors and failed tests of the report,
+/// which GitHub shows inline in pull requests:
+///
+/// ```text
+/// ::error file=src/lib.rs,line=10,col=9,title=Test failed%3A tests%3A%3Abad::assertion failed
+/// ```
+///
+/// A failed run without errors pointing to the code gets a single annotation with its title.
+pub fn github(report: &Report) -> Vec<String> {
+    let mut annotations = vec![];
+    for error in &report.compile_errors {
+        let message = error.location.rendered.as_ref().unwrap_or(&error.message);
+        annotations.push(github_error(Some(&error.location), &error.message, message.trim_end()));
+    }
+    for failure in &report.failures {
+        let title = format!("Test failed: {}", failure.name);
+        let message = failure.message.as_deref().unwrap_or("test failed");
+        annotations.push(github_error(failure.location.as_ref(), &title, message));
+    }
+    if annotations.is_empty() && !report.outcome.is_success() {
+        let body = report.body().unwrap_or_default();
+        annotations.push(github_error(report.error_location.as_ref(), &report.title(), &body));
+    }
+    annotations
+}
+
+fn github_error(location: Option<&ErrorLocation>, title: &str, message: &str) -> String {
+    let mut properties = vec![];
+    if let Some(location) = location {
+        properties.push(format!("file={}", escape_property(&location.file)));
+        properties.push(format!("line={}", location.line));
+        properties.push(format!("col={}", location.column));
+    }
+    properties.push(format!("title={}", escape_property(title)));
+    format!("::error {}::{}", properties.join(","), escape_data(message))
+}
+
+/// Escape the message of a workflow command, which has to fit into a single line.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape a property value, which additionally can not contain `:` and `,`.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::{Outcome, TestFailure};
+
+    #[test]
+    fn test_github() {
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.failures.push(TestFailure {
+            name: "tests::bad".to_string(),
+            message: Some("100% wrong".to_string()),
+            location: Some(ErrorLocation { file: "src/lib.rs".to_string(), line: 10, column: 9, rendered: None })
+        });
+        report.failures.push(TestFailure { name: "it_works".to_string(), message: None, location: None });
+        assert_eq!(github(&report), vec![
+            "::error file=src/lib.rs,line=10,col=9,title=Test failed%3A tests%3A%3Abad::100%25 wrong",
+            "::error title=Test failed%3A it_works::test failed"
+        ]);
+
+        let report = Report::new(Outcome::TimedOut, Some("Killed after 10s".to_string()));
+        assert_eq!(github(&report), vec!["::error title=Tests timed out::Killed after 10s"]);
+
+        assert!(github(&Report::new(Outcome::TestsPassed, None)).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::{Path, PathBuf};
+
+use regex::{Captures, Regex};
+
+use manifest;
+use ssh;
+
+/// Environment variables telling a custom command what has changed.
+const PATH_VAR: &str = "TESTIFY_PATH";
+const PATHS_VAR: &str = "TESTIFY_PATHS";
+const CRATE_VAR: &str = "TESTIFY_CRATE";
+
+/// Files which have triggered a run, passed to a custom command with the placeholders
+/// `{path}`, `{paths}` and `{crate}`, and with the environment variables.
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    /// The file which has changed last, relative to the project root
+    pub path: Option<PathBuf>,
+    /// Every file changed since the previous run, in the order of their last changes
+    pub paths: Vec<PathBuf>,
+    /// Name of the package the last changed file belongs to
+    pub package: Option<String>
+}
+
+impl Changes {
+    /// Changes of the `changed` files, relative to `project_dir` unless they are outside of it, in the order
+    /// they have changed. A file changed several times is kept in the place of its last change.
+    pub fn new(project_dir: &Path, changed: Vec<PathBuf>) -> Self {
+        let mut paths: Vec<PathBuf> = vec![];
+        for path in changed {
+            paths.retain(|other| *other != path);
+            paths.push(path);
+        }
+        let path = paths.last().cloned();
+        let package = path.as_ref().and_then(|path| package_name(&project_dir.join(path)));
+        Changes { path, paths, package }
+    }
+
+    /// `command` with the placeholders replaced by the values quoted for the system shell, or removed
+    /// if there are none, e.g. when the run was requested with a key. `${path}` of the shell is left alone.
+    pub fn expand(&self, command: &str) -> String {
+        self.expand_with(command, shell_quote)
+    }
+
+    fn expand_with(&self, command: &str, quote: fn(&str) -> String) -> String {
+        let placeholder_re = Regex::new(r"(\$?)\{(path|paths|crate)\}").unwrap();
+        placeholder_re.replace_all(command, |caps: &Captures| {
+            if !caps[1].is_empty() {
+                return caps[0].to_string();
+            }
+            match &caps[2] {
+                "path" => self.path.iter().map(|path| quote(&path.to_string_lossy())).collect(),
+                "paths" => self.paths.iter().map(|path| quote(&path.to_string_lossy())).collect::<Vec<_>>().join(" "),
+                _ => self.package.iter().map(|name| quote(name)).collect()
+            }
+        }).into_owned()
+    }
+
+    /// The same values as environment variables, empty if there are none. The paths are separated
+    /// by newlines.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        let paths: Vec<String> = self.paths.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        vec![
+            (PATH_VAR, self.path.as_ref().map(|path| path.to_string_lossy().into_owned()).unwrap_or_default()),
+            (PATHS_VAR, paths.join("\n")),
+            (CRATE_VAR, self.package.clone().unwrap_or_default())
+        ]
+    }
+}
+
+/// Quote an argument for `sh -c`, which runs custom commands.
+#[cfg(not(target_os = "windows"))]
+fn shell_quote(arg: &str) -> String {
+    ssh::quote(arg)
+}
+
+/// Quote an argument for `cmd /C`, which runs custom commands on Windows.
+#[cfg(target_os = "windows")]
+fn shell_quote(arg: &str) -> String {
+    cmd_quote(arg)
+}
+
+/// cmd does not know single quotes: arguments are enclosed in double quotes, which are doubled within.
+#[cfg_attr(not(any(target_os = "windows", test)), allow(dead_code))]
+fn cmd_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_=+./:,@\\".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("\"{}\"", arg.replace('"', "\"\""))
+    }
+}
+
+/// Name of the package `path` belongs to, from the nearest `Cargo.toml` above it.
+fn package_name(path: &Path) -> Option<String> {
+    let dir = manifest::find_package_dir(path.parent()?)?;
+    manifest::read(&dir)?
+        .get("package")?
+        .get("name")?
+        .as_str()
+        .map(String::from)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn files(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = env::temp_dir().join("testify-changes-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("crates/parser/src")).unwrap();
+        fs::write(dir.join("crates/parser/Cargo.toml"), "[package]\nname = \"parser\"\n").unwrap();
+
+        let changes = Changes::new(&dir, files(&["crates/parser/src/my lexer.rs", "README.md", "crates/parser/src/my lexer.rs"]));
+        assert_eq!(changes.package, Some("parser".to_string()));
+        assert_eq!(changes.expand_with("cargo test -p {crate} && lint {paths}", ssh::quote),
+                   "cargo test -p parser && lint README.md 'crates/parser/src/my lexer.rs'");
+        assert_eq!(changes.expand_with("rustfmt --check {path} ${path}", ssh::quote), "rustfmt --check 'crates/parser/src/my lexer.rs' ${path}");
+        assert_eq!(changes.env()[1], (PATHS_VAR, "README.md\ncrates/parser/src/my lexer.rs".to_string()));
+
+        let windows = Changes::new(&dir, files(&["src\\lib.rs", "tests\\my \"quoted\" test.rs"]));
+        assert_eq!(windows.expand_with("lint {paths}", cmd_quote), "lint src\\lib.rs \"tests\\my \"\"quoted\"\" test.rs\"");
+
+        let none = Changes::new(&dir, files(&[]));
+        assert_eq!(none.expand("pytest {path}"), "pytest ");
+        assert_eq!(none.env()[2], (CRATE_VAR, String::new()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,27 +1,1278 @@
 use std::time::Duration;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::fs::File;
+use std::io::Read;
+use std::env;
+use std::collections::BTreeMap;
+
+use toml;
 
 use errors::*;
+use report::Outcome;
+use secrets;
+
+/// Name of the configuration file, which is looked up in the project root.
+pub const CONFIG_FILE_NAME: &str = ".testify.toml";
+
+/// File with environment variables for the commands, relative to the project root.
+const DEFAULT_ENV_FILE: &str = ".env";
+
+/// Environment variable with Slack webhook URL, takes precedence over the config file.
+const SLACK_WEBHOOK_VAR: &str = "TESTIFY_SLACK_WEBHOOK";
+
+/// Environment variable with Discord webhook URL, takes precedence over the config file.
+const DISCORD_WEBHOOK_VAR: &str = "TESTIFY_DISCORD_WEBHOOK";
+
+/// Environment variables with Telegram bot token and chat id, take precedence over the config file.
+const TELEGRAM_TOKEN_VAR: &str = "TESTIFY_TELEGRAM_TOKEN";
+const TELEGRAM_CHAT_ID_VAR: &str = "TESTIFY_TELEGRAM_CHAT_ID";
+
+/// Environment variable with the password of the SMTP server, takes precedence over the config file.
+const SMTP_PASSWORD_VAR: &str = "TESTIFY_SMTP_PASSWORD";
+
+/// Environment variables with ntfy and Gotify tokens, take precedence over the config file.
+const NTFY_TOKEN_VAR: &str = "TESTIFY_NTFY_TOKEN";
+const GOTIFY_TOKEN_VAR: &str = "TESTIFY_GOTIFY_TOKEN";
+
+/// Environment variable with the password of the MQTT broker, takes precedence over the config file.
+const MQTT_PASSWORD_VAR: &str = "TESTIFY_MQTT_PASSWORD";
+
+/// Which notifier is used to display the result of a test run.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum NotifierKind {
+    /// Desktop notification if there is a desktop session, otherwise the console one
+    #[serde(rename = "auto")]
+    Auto,
+
+    /// Native desktop notification, falling back to the console one when it fails
+    #[serde(rename = "desktop")]
+    Desktop,
+
+    /// Message to a Slack channel via incoming webhook
+    #[serde(rename = "slack")]
+    Slack,
+
+    /// Embed posted to a Discord channel via webhook
+    #[serde(rename = "discord")]
+    Discord,
+
+    /// Message sent to a Telegram chat by a bot
+    #[serde(rename = "telegram")]
+    Telegram,
+
+    /// Email sent through an SMTP server, with the output of a failed run
+    #[serde(rename = "email")]
+    Email,
+
+    /// Push notification published to an ntfy topic
+    #[serde(rename = "ntfy")]
+    Ntfy,
+
+    /// Push notification posted to a Gotify server
+    #[serde(rename = "gotify")]
+    Gotify,
+
+    /// Report published as JSON to a topic of an MQTT broker
+    #[serde(rename = "mqtt")]
+    Mqtt,
+
+    /// Command of the user run with the report in its environment and on stdin
+    #[serde(rename = "exec")]
+    Exec,
+
+    /// JSON body rendered from a template, posted to an arbitrary URL
+    #[serde(rename = "http")]
+    Http,
+
+    /// Terminal bell and a notification escape sequence
+    #[serde(rename = "console")]
+    Console,
+
+    /// Do not notify at all, only print the output to the console
+    #[serde(rename = "none")]
+    Disabled
+}
+
+impl NotifierKind {
+    /// Name used in the configuration, e.g. `desktop`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NotifierKind::Auto => "auto",
+            NotifierKind::Desktop => "desktop",
+            NotifierKind::Slack => "slack",
+            NotifierKind::Discord => "discord",
+            NotifierKind::Telegram => "telegram",
+            NotifierKind::Email => "email",
+            NotifierKind::Ntfy => "ntfy",
+            NotifierKind::Gotify => "gotify",
+            NotifierKind::Mqtt => "mqtt",
+            NotifierKind::Exec => "exec",
+            NotifierKind::Http => "http",
+            NotifierKind::Console => "console",
+            NotifierKind::Disabled => "none"
+        }
+    }
+}
+
+impl FromStr for NotifierKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(NotifierKind::Auto),
+            "desktop" => Ok(NotifierKind::Desktop),
+            "slack" => Ok(NotifierKind::Slack),
+            "discord" => Ok(NotifierKind::Discord),
+            "telegram" => Ok(NotifierKind::Telegram),
+            "email" => Ok(NotifierKind::Email),
+            "ntfy" => Ok(NotifierKind::Ntfy),
+            "gotify" => Ok(NotifierKind::Gotify),
+            "mqtt" => Ok(NotifierKind::Mqtt),
+            "exec" => Ok(NotifierKind::Exec),
+            "http" => Ok(NotifierKind::Http),
+            "console" => Ok(NotifierKind::Console),
+            "none" => Ok(NotifierKind::Disabled),
+            _ => Err(ErrorKind::UnknownNotifier(s.to_string()).into())
+        }
+    }
+}
+
+/// When a notification is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum NotifyMode {
+    /// After every run
+    #[serde(rename = "always")]
+    Always,
+
+    /// Only when tests start passing or stop passing
+    #[serde(rename = "changes")]
+    Changes
+}
+
+impl FromStr for NotifyMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "always" => Ok(NotifyMode::Always),
+            "changes" => Ok(NotifyMode::Changes),
+            _ => Err(ErrorKind::UnknownNotifyMode(s.to_string()).into())
+        }
+    }
+}
+
+/// Whether formatting is checked before the tests.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum FmtMode {
+    /// Formatting is not checked
+    #[serde(rename = "off")]
+    Off,
+
+    /// `cargo fmt --check`, unformatted files fail the run
+    #[serde(rename = "check")]
+    Check,
+
+    /// `cargo fmt`, the code is formatted and the run goes on
+    #[serde(rename = "fix")]
+    Fix
+}
+
+impl FromStr for FmtMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "off" => Ok(FmtMode::Off),
+            "check" => Ok(FmtMode::Check),
+            "fix" => Ok(FmtMode::Fix),
+            _ => Err(ErrorKind::UnknownFmtMode(s.to_string()).into())
+        }
+    }
+}
+
+/// Which test runner is used when no custom command is set.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Runner {
+    /// `cargo test`
+    #[serde(rename = "cargo")]
+    Cargo,
+
+    /// `cargo nextest run`, with results reported as libtest JSON
+    #[serde(rename = "nextest")]
+    Nextest,
+
+    /// `cargo test` (or the custom command) run on the host of the `[ssh]` section
+    #[serde(rename = "ssh")]
+    Ssh,
+
+    /// `cargo test` (or the custom command) run in a container of the `[docker]` section
+    #[serde(rename = "docker")]
+    Docker,
+
+    /// `wasm-pack test` in the environment chosen by `wasm_env`, for crates compiled to WASM
+    #[serde(rename = "wasm-pack")]
+    WasmPack
+}
+
+impl FromStr for Runner {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "cargo" => Ok(Runner::Cargo),
+            "nextest" => Ok(Runner::Nextest),
+            "ssh" => Ok(Runner::Ssh),
+            "docker" => Ok(Runner::Docker),
+            "wasm-pack" => Ok(Runner::WasmPack),
+            _ => Err(ErrorKind::UnknownRunner(s.to_string()).into())
+        }
+    }
+}
+
+/// Where `wasm-pack test` runs the tests.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum WasmEnv {
+    /// Headless Chrome
+    #[serde(rename = "chrome")]
+    Chrome,
+
+    /// Headless Firefox
+    #[serde(rename = "firefox")]
+    Firefox,
+
+    /// Headless Safari
+    #[serde(rename = "safari")]
+    Safari,
+
+    /// Node.js
+    #[serde(rename = "node")]
+    Node
+}
+
+impl WasmEnv {
+    /// Options of `wasm-pack test` selecting the environment.
+    pub fn flags(&self) -> Vec<&'static str> {
+        match *self {
+            WasmEnv::Chrome => vec!["--headless", "--chrome"],
+            WasmEnv::Firefox => vec!["--headless", "--firefox"],
+            WasmEnv::Safari => vec!["--headless", "--safari"],
+            WasmEnv::Node => vec!["--node"]
+        }
+    }
+}
+
+impl FromStr for WasmEnv {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "chrome" => Ok(WasmEnv::Chrome),
+            "firefox" => Ok(WasmEnv::Firefox),
+            "safari" => Ok(WasmEnv::Safari),
+            "node" => Ok(WasmEnv::Node),
+            _ => Err(ErrorKind::UnknownWasmEnv(s.to_string()).into())
+        }
+    }
+}
+
+/// How testify reports to its own stdout.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Output of the test command and messages for a human
+    Human,
+
+    /// One JSON line per event, for editor plugins and other tools
+    Json
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(ErrorKind::UnknownOutputFormat(s.to_string()).into())
+        }
+    }
+}
+
+/// Format of annotations printed for compile errors and failed tests, so CI shows them inline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnotationFormat {
+    /// Workflow commands of GitHub Actions: `::error file=src/lib.rs,line=2::message`
+    Github
+}
+
+impl FromStr for AnnotationFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "github" => Ok(AnnotationFormat::Github),
+            _ => Err(ErrorKind::UnknownAnnotationFormat(s.to_string()).into())
+        }
+    }
+}
+
+/// Shell commands run around every test run, `[hooks]` section of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Run before the tests. If it fails, the tests are not run.
+    pub before_run: Option<String>,
+    /// Run after the tests, no matter what the outcome is
+    pub after_run: Option<String>,
+    /// Run after the tests have passed
+    pub on_success: Option<String>,
+    /// Run after the tests have failed or did not compile
+    pub on_failure: Option<String>
+}
+
+/// Remote host the commands run on with `runner = "ssh"`, `[ssh]` section of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ssh {
+    /// Destination passed to `ssh`, e.g. `build@devbox` or a host from `~/.ssh/config`
+    pub host: Option<String>,
+    /// Project directory on the host, by default the name of the local one in the remote home
+    pub dir: Option<String>,
+    /// Mirror the working tree into the remote directory with rsync before every run
+    pub rsync: Option<bool>
+}
+
+impl Ssh {
+    pub fn rsync(&self) -> bool {
+        self.rsync == Some(true)
+    }
+}
+
+/// Container the commands run in with `runner = "docker"`, `[docker]` section of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Docker {
+    /// Image the container is created from, e.g. `rust:1.80`
+    pub image: Option<String>,
+    /// Additional options of `docker run`, e.g. `["--network=none"]`
+    pub args: Option<Vec<String>>
+}
+
+/// SMTP server and addresses of the `email` notifier, `[email]` section of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Email {
+    /// SMTP server, e.g. `smtp.example.com`
+    pub host: Option<String>,
+    /// Port of the server, by default 587 with STARTTLS, 465 with TLS and 25 without encryption
+    pub port: Option<u16>,
+    /// `starttls` (the default), `tls` or `none`
+    pub security: Option<SmtpSecurity>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender, e.g. `testify <testify@example.com>`
+    pub from: Option<String>,
+    /// Recipients
+    pub to: Option<Vec<String>>
+}
+
+/// How the connection to the SMTP server is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS
+    #[serde(rename = "starttls")]
+    StartTls,
+
+    /// TLS from the start
+    #[serde(rename = "tls")]
+    Tls,
+
+    /// No encryption, e.g. for a relay on localhost
+    #[serde(rename = "none")]
+    None
+}
+
+/// Broker of the `mqtt` notifier, `[mqtt]` section of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mqtt {
+    /// Host of the broker with an optional port, e.g. `localhost` or `homeassistant.local:1883`
+    pub broker: Option<String>,
+    /// Topic reports are published to, `testify/<project>` by default
+    pub topic: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Ask the broker to keep the latest report for new subscribers
+    pub retain: Option<bool>
+}
 
-pub struct Config<'a> {
+/// Push service of the `ntfy` or `gotify` notifier, `[ntfy]` and `[gotify]` sections of `.testify.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Push {
+    /// URL of the ntfy topic, e.g. `https://ntfy.sh/my-tests`, or of the Gotify server
+    pub url: Option<String>,
+    /// Access token of ntfy, or token of the Gotify application
+    pub token: Option<String>,
+    /// Priorities of outcomes named as in `[notify_on]`, e.g. `{ failures = 5, compile_error = 4 }`
+    pub priority: Option<BTreeMap<String, u8>>
+}
+
+impl Push {
+    /// Priority configured for `outcome`, an entry for the outcome itself wins over a group.
+    pub fn priority(&self, outcome: Outcome) -> Option<u8> {
+        let priorities = self.priority.as_ref()?;
+        priorities.get(outcome.name()).cloned()
+            .or_else(|| priorities.iter().find(|&(name, _)| outcome_matches(name, outcome)).map(|(_, &priority)| priority))
+    }
+}
+
+/// Rule which maps changed files to tests, an entry of `test_map` in `.testify.toml`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestMapping {
+    /// Glob matched against paths relative to the project root, e.g. `src/storage/**`
+    pub path: String,
+    /// Filter passed to the test binary, e.g. `db::`
+    pub filter: String
+}
+
+/// Rule which picks the command run on changes of some files, an entry of `command_map` in `.testify.toml`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandMapping {
+    /// Glob matched against paths relative to the project root, e.g. `docs/**`
+    pub path: String,
+    /// Command run through the system shell instead of the tests, e.g. `cargo doc --no-deps`
+    pub command: String
+}
+
+/// Rule deciding the outcome from the output of the command, an entry of `outcome_rules` in `.testify.toml`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutcomeRule {
+    /// Regex searched for in stdout and stderr, e.g. `(?m)^FAIL: (.*)$`
+    pub pattern: String,
+    /// Outcome reported when the pattern matches: `passed`, `failed`, `compile_error`,
+    /// `format_error`, `undefined_behavior`, `timed_out` or `no_tests`
+    pub outcome: String,
+    /// Capture group shown as the message of the notification, the whole match by default
+    pub message: Option<usize>
+}
+
+/// Outcomes of `[notify_on]` which stand for a group of outcomes.
+const OUTCOME_GROUPS: &[&str] = &["failures", "successes"];
+
+/// Outcomes a notifier is notified about, an entry of `[notify_on]` in `.testify.toml`,
+/// e.g. `slack = ["failures"]`. Notifiers without an entry are notified about everything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub notifier: NotifierKind,
+    /// Names of outcomes (`compile_error`), or of groups: `failures` and `successes`
+    pub outcomes: Vec<String>
+}
+
+impl Route {
+    pub fn matches(&self, outcome: Outcome) -> bool {
+        self.outcomes.iter().any(|name| outcome_matches(name, outcome))
+    }
+}
+
+/// Is `name` the name of `outcome`, or of a group it's in?
+fn outcome_matches(name: &str, outcome: Outcome) -> bool {
+    match name {
+        "failures" => !outcome.is_success() && outcome != Outcome::NoTests,
+        "successes" => outcome.is_success(),
+        name => name == outcome.name()
+    }
+}
+
+fn is_outcome_name(name: &str) -> bool {
+    Outcome::NAMES.contains(&name) || OUTCOME_GROUPS.contains(&name)
+}
+
+/// Sounds played with notifications, `[sounds]` section of `.testify.toml`.
+/// A sound is a system sound name or a path to a sound file, an empty string means silence.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sounds {
+    /// `false` silences all notifications
+    pub enabled: Option<bool>,
+    /// Played when the tests have passed, with or without warnings
+    pub passed: Option<String>,
+    /// Played when the tests have failed or timed out
+    pub failed: Option<String>,
+    /// Played when the code did not compile
+    pub compile_error: Option<String>
+}
+
+impl Sounds {
+    /// Sound to play for `outcome`: the configured one, or `default` of the notifier
+    /// if none is configured. `None` means silence.
+    pub fn choose<'a>(&'a self, outcome: Outcome, default: Option<&'a str>) -> Option<&'a str> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let configured = match outcome {
+            Outcome::TestsPassed | Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) => &self.passed,
+            Outcome::TestsFailed | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::MutantsMissed(_) | Outcome::TimedOut | Outcome::Unknown => &self.failed,
+            Outcome::CompileError => &self.compile_error,
+            Outcome::NoTests => &None
+        };
+        match *configured {
+            Some(ref sound) if sound.is_empty() => None,
+            Some(ref sound) => Some(sound),
+            None => default
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+
+    /// Is `sound` a path to a file rather than a system sound name?
+    pub fn is_file(sound: &str) -> bool {
+        sound.contains('/') || sound.contains('\\') || Path::new(sound).extension().is_some()
+    }
+
+    /// Make paths to sound files absolute, relative ones are relative to `project_dir`.
+    fn resolve(self, project_dir: &Path) -> Self {
+        let resolve = |sound: Option<String>| sound.map(|sound| {
+            if Sounds::is_file(&sound) {
+                project_dir.join(&sound).to_string_lossy().into_owned()
+            } else {
+                sound
+            }
+        });
+        Sounds {
+            enabled: self.enabled,
+            passed: resolve(self.passed),
+            failed: resolve(self.failed),
+            compile_error: resolve(self.compile_error)
+        }
+    }
+}
+
+pub struct Config {
+    /// Time after a command has exited during which changes of the files cargo writes itself are ignored
     pub ignore_duration: Duration,
+    /// Wait for changes to settle during this time before running tests
+    pub delay: Duration,
+    /// Kill the test command if it runs longer than this, `None` means no limit
+    pub timeout: Option<Duration>,
     pub project_dir: PathBuf,
-    pub cargo_test_args: Vec<&'a str>
+    pub cargo_test_args: Vec<String>,
+    /// Arguments passed to the test binary, after `--`
+    pub test_args: Vec<String>,
+    /// Environment variables of the chosen `[env.NAME]` profiles, set for the commands
+    pub env: BTreeMap<String, String>,
+    /// File with environment variables loaded before every run and watched, `None` if it's turned off
+    pub env_file: Option<PathBuf>,
+    pub runner: Runner,
+    /// Where the tests run with the `wasm-pack` runner
+    pub wasm_env: WasmEnv,
+    /// Cargo steps run in order until one fails, `test` stands for the test command
+    pub pipeline: Vec<String>,
+    /// Run `cargo check` before the tests, so compile errors are reported sooner
+    pub check_first: bool,
+    /// Check formatting before anything else, or format the code
+    pub fmt: FmtMode,
+    /// Run `cargo clippy` before the tests and report the numbers of its warnings and errors
+    pub clippy: bool,
+    /// Fail the run if clippy reports warnings
+    pub deny_warnings: bool,
+    /// Run the tests with Miri after they pass, to detect undefined behavior
+    pub miri: bool,
+    /// Run `cargo mutants` on the changed file in the background after a green run
+    pub mutants: bool,
+    /// Feature sets the suite is run with, one run per entry, e.g. `--all-features`
+    pub matrix: Vec<String>,
+    /// Toolchains the suite is run with one after another, e.g. `stable` and `nightly`
+    pub toolchains: Vec<String>,
+    /// Target triples the suite is run for one after another, e.g. `x86_64-unknown-linux-musl`
+    pub targets: Vec<String>,
+    /// Run the tests with `cross` instead of cargo, so tests for foreign targets can run
+    pub cross: bool,
+    /// Custom command to run instead of `cargo test`
+    pub command: Option<String>,
+    /// Rules picking the command for changes of some files, the first matching one wins
+    pub command_map: Vec<CommandMapping>,
+    /// Rules deciding the outcome from the output, tried in order before the built-in detection
+    pub outcome_rules: Vec<OutcomeRule>,
+    /// Kill a running command and start it over when a new change arrives
+    pub restart: bool,
+    /// Run the command in a pseudo-terminal to preserve colored output
+    pub pty: bool,
+    /// Run the tests with `cargo llvm-cov` and report the line coverage
+    pub coverage: bool,
+    /// Run previously failed tests first, and the whole suite only if they pass
+    pub failed_first: bool,
+    /// Rerun failed tests up to this many times, tests which pass on a retry are reported as flaky
+    pub retries: u32,
+    /// Warn when a passing run takes this many times longer than the recent passing runs on average,
+    /// 0 disables the warning
+    pub slowdown_threshold: f64,
+    /// Run tests related to the changed file first, and the whole suite only if they pass
+    pub targeted: bool,
+    /// Rules which map changed files to tests, checked before the module of the file is guessed
+    pub test_map: Vec<TestMapping>,
+    /// Run only the tests affected by the files changed since this git ref, e.g. `HEAD`
+    pub changed_since: Option<String>,
+    /// Handle keys pressed in the terminal (rerun, clear, pause, quit)
+    pub interactive: bool,
+    /// Clear the terminal and print a header before each run
+    pub clear: bool,
+    /// Do not print the output of the command, only the results
+    pub quiet: bool,
+    /// Show a full-screen terminal UI with the output, the failing tests and the recent runs
+    /// instead of printing them. Not available with JSON output
+    pub ui: bool,
+    /// Ask which tests to run before watching, only they are run until all tests are asked for again
+    pub pick: bool,
+    /// Record every run into `.testify/history.jsonl` and results of every test into `.testify/stats.json`
+    pub history: bool,
+    /// Keep the state of the latest run in `.testify/status.json`
+    pub status_file: bool,
+    /// Write a JUnit XML report of every run to this file
+    pub junit: Option<PathBuf>,
+    /// Address of the HTTP server reporting the latest run and taking run requests
+    pub listen: Option<String>,
+    /// Format of testify's own stdout, can be set only on the command line
+    pub output: OutputFormat,
+    /// Print annotations for compile errors and failed tests, can be set only on the command line
+    pub annotate: Option<AnnotationFormat>,
+    pub watch: Vec<String>,
+    /// Watch the sources of the path dependencies declared in `Cargo.toml` as well
+    pub watch_dependencies: bool,
+    /// Do not run the tests when the content of the changed file is the same as before
+    pub skip_unchanged: bool,
+    /// Poll the file system with this interval instead of waiting for native events,
+    /// `None` uses them if they are available
+    pub poll: Option<Duration>,
+    /// Gitignore-style patterns (relative to the project root) of files which never trigger a run
+    pub ignore: Vec<String>,
+    /// Globs (relative to the project root) of files which trigger a run, even if they would not otherwise
+    pub watch_patterns: Vec<String>,
+    pub hooks: Hooks,
+    /// Remote host of the `ssh` runner
+    pub ssh: Ssh,
+    /// Container of the `docker` runner
+    pub docker: Docker,
+    /// SMTP server of the `email` notifier
+    pub email: Email,
+    pub ntfy: Push,
+    pub gotify: Push,
+    /// Broker of the `mqtt` notifier
+    pub mqtt: Mqtt,
+    pub sounds: Sounds,
+    /// Every report is delivered to all of them
+    pub notifiers: Vec<NotifierKind>,
+    /// Outcomes some of the notifiers are limited to
+    pub routes: Vec<Route>,
+    pub notify: NotifyMode,
+    /// Skip desktop notifications while the terminal running testify has focus
+    pub skip_when_focused: bool,
+    pub slack_webhook: Option<String>,
+    pub discord_webhook: Option<String>,
+    pub telegram_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    pub http_url: Option<String>,
+    /// Template of the JSON body posted by the HTTP notifier
+    pub http_body: Option<String>,
+    /// Shell command run by the exec notifier
+    pub exec_command: Option<String>,
+    /// Template of the notification title, replacing the default one
+    pub notification_title: Option<String>,
+    /// Template of the notification body, replacing the default one
+    pub notification_body: Option<String>
+}
+
+impl Config {
+    /// Read `.testify.toml` (or any other file in the same format) and return a builder
+    /// populated with its values, so options passed on the command line can still be
+    /// applied on top of them. A missing file is not an error.
+    pub fn from_file(path: &Path) -> Result<ConfigBuilder> {
+        let builder = ConfigBuilder::new();
+        if !path.is_file() { return Ok(builder); }
+
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .chain_err(|| ErrorKind::ConfigFile(path.to_path_buf()))?;
+        let file_config: FileConfig = toml::from_str(&content)
+            .chain_err(|| ErrorKind::ConfigFile(path.to_path_buf()))?;
+
+        builder.file_config(file_config).chain_err(|| ErrorKind::ConfigFile(path.to_path_buf()))
+    }
+}
+
+/// Representation of `.testify.toml`. Every field is optional: missing values
+/// fall back to the built-in defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// Time in milliseconds after a command has exited during which changes of `Cargo.lock` and `target/` are ignored
+    ignore_duration: Option<u64>,
+    /// Wait for changes to settle during this time before running tests, e.g. `"500ms"` or `"1s"`
+    delay: Option<String>,
+    /// Kill the test command if it runs longer than this, e.g. `"10m"`
+    timeout: Option<String>,
+    cargo_test_args: Option<Vec<String>>,
+    /// Arguments passed to the test binary, e.g. `["--nocapture", "--test-threads=1"]`
+    test_args: Option<Vec<String>>,
+    /// Named sets of environment variables for the commands: `[env.debug] RUST_LOG = "debug"`
+    env: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    /// Profiles of `env` used unless `--env-profile` chooses others, e.g. `["debug"]`
+    env_profiles: Option<Vec<String>>,
+    /// File with environment variables for the commands, `".env"` by default, `""` turns it off
+    env_file: Option<String>,
+    /// Test runner: `cargo`, `nextest`, `ssh`, `docker` or `wasm-pack`
+    runner: Option<Runner>,
+    /// `chrome`, `firefox`, `safari` or `node`
+    wasm_env: Option<WasmEnv>,
+    /// Cargo steps run in order until one fails, e.g. `["check", "clippy -- -D warnings", "test"]`
+    pipeline: Option<Vec<String>>,
+    /// Run `cargo check` before the tests, so compile errors are reported sooner
+    check_first: Option<bool>,
+    /// `off`, `check` or `fix`
+    fmt: Option<FmtMode>,
+    /// Run `cargo clippy` before the tests and report the numbers of its warnings and errors
+    clippy: Option<bool>,
+    /// Fail the run if clippy reports warnings
+    deny_warnings: Option<bool>,
+    /// Run the tests with Miri after they pass, to detect undefined behavior
+    miri: Option<bool>,
+    /// Run `cargo mutants` on the changed file in the background after a green run
+    mutants: Option<bool>,
+    /// Feature sets the suite is run with, e.g. `["--no-default-features", "--all-features"]`
+    matrix: Option<Vec<String>>,
+    /// Toolchains the suite is run with, e.g. `["stable", "nightly"]`
+    toolchains: Option<Vec<String>>,
+    /// Target triples the suite is run for, e.g. `["x86_64-unknown-linux-musl", "wasm32-wasip1"]`
+    targets: Option<Vec<String>>,
+    /// Run the tests with `cross` instead of cargo
+    cross: Option<bool>,
+    /// Command to run instead of `cargo test`, e.g. `make check`
+    command: Option<String>,
+    /// Commands run on changes of some files instead: `[{ path = "docs/**", command = "cargo doc --no-deps" }]`
+    command_map: Option<Vec<CommandMapping>>,
+    /// Rules deciding the outcome from the output: `[{ pattern = "^FAIL: (.*)$", outcome = "failed", message = 1 }]`
+    outcome_rules: Option<Vec<OutcomeRule>>,
+    /// Kill a running command and start it over when a new change arrives
+    restart: Option<bool>,
+    /// Run the command in a pseudo-terminal to preserve colored output
+    pty: Option<bool>,
+    /// Run the tests with `cargo llvm-cov` and report the line coverage
+    coverage: Option<bool>,
+    /// Run previously failed tests first, and the whole suite only if they pass
+    failed_first: Option<bool>,
+    /// Rerun failed tests up to this many times
+    retries: Option<u32>,
+    /// Warn when a run takes this many times longer than usual, e.g. `1.5`, 0 disables the warning
+    slowdown_threshold: Option<f64>,
+    /// Run tests related to the changed file first, and the whole suite only if they pass
+    targeted: Option<bool>,
+    /// Rules which map changed files to tests: `[{ path = "src/storage/**", filter = "db::" }]`
+    test_map: Option<Vec<TestMapping>>,
+    /// Run only the tests of the packages or modules changed since this git ref, e.g. `"HEAD"` or `"origin/main"`
+    changed_since: Option<String>,
+    /// Handle keys pressed in the terminal (rerun, clear, pause, quit)
+    interactive: Option<bool>,
+    /// Clear the terminal and print a header before each run
+    clear: Option<bool>,
+    /// Do not print the output of the command, only the results
+    quiet: Option<bool>,
+    /// Show the full-screen terminal UI
+    ui: Option<bool>,
+    /// Record every run into `.testify/history.jsonl` and results of every test into `.testify/stats.json`
+    history: Option<bool>,
+    /// Keep the state of the latest run in `.testify/status.json`
+    status_file: Option<bool>,
+    /// Write a JUnit XML report of every run to this file, relative to the project root
+    junit: Option<PathBuf>,
+    /// Address of the HTTP server, e.g. `"127.0.0.1:7878"`
+    listen: Option<String>,
+    /// Additional files and directories (relative to the project root) to watch,
+    /// on top of the built-in list
+    watch: Option<Vec<String>>,
+    /// Watch the `src/` directories of the path dependencies declared in `Cargo.toml`
+    watch_dependencies: Option<bool>,
+    /// Compare the content of changed files with what it was, and skip the run if it's the same
+    skip_unchanged: Option<bool>,
+    /// Poll the file system for changes with this interval, e.g. `"1s"`, on mounts which do not deliver native events
+    poll: Option<String>,
+    /// Gitignore-style patterns of files which never trigger a run, e.g. `["src/generated/"]`
+    ignore: Option<Vec<String>>,
+    /// Globs of files which trigger a run even if they are not watched otherwise, e.g. `["**/*.graphql"]`
+    watch_patterns: Option<Vec<String>>,
+    hooks: Option<Hooks>,
+    ssh: Option<Ssh>,
+    docker: Option<Docker>,
+    email: Option<Email>,
+    ntfy: Option<Push>,
+    gotify: Option<Push>,
+    mqtt: Option<Mqtt>,
+    sounds: Option<Sounds>,
+    notifier: Option<NotifierKind>,
+    /// Several notifiers at once, e.g. `["desktop", "slack"]`, instead of `notifier`
+    notifiers: Option<Vec<NotifierKind>>,
+    /// Outcomes a notifier is limited to: `[notify_on] slack = ["failures"]`
+    notify_on: Option<BTreeMap<String, Vec<String>>>,
+    /// `always` or `changes`
+    notify: Option<NotifyMode>,
+    /// Skip desktop notifications while the terminal running testify has focus
+    skip_when_focused: Option<bool>,
+    slack: Option<SlackSection>,
+    discord: Option<DiscordSection>,
+    telegram: Option<TelegramSection>,
+    http: Option<HttpSection>,
+    exec: Option<ExecSection>,
+    notification: Option<NotificationSection>
+}
+
+/// `[slack]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SlackSection {
+    /// URL of the incoming webhook
+    webhook: Option<String>
+}
+
+/// `[discord]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscordSection {
+    /// URL of the channel webhook
+    webhook: Option<String>
+}
+
+/// `[telegram]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramSection {
+    /// Token of the bot, given by @BotFather
+    token: Option<String>,
+    /// Chat the bot writes to, e.g. `"123456789"` or `"@channel"`
+    chat_id: Option<String>
+}
+
+/// `[http]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpSection {
+    /// URL the report is posted to
+    url: Option<String>,
+    /// Template of the JSON body, e.g. `{"text": "{project}: {title}"}`
+    body: Option<String>
+}
+
+/// `[exec]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExecSection {
+    /// Shell command run in the project directory, e.g. `./scripts/on-tests.sh`
+    command: Option<String>
+}
+
+/// `[notification]` section of `.testify.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationSection {
+    /// Template of the title, e.g. `{project}: {title}`
+    title: Option<String>,
+    /// Template of the body, e.g. `{failed} failed, {passed} passed in {duration}`
+    body: Option<String>
 }
 
-pub struct ConfigBuilder<'a> {
-    ignore_duration: Duration,
+pub struct ConfigBuilder {
+    ignore_duration: Option<Duration>,
+    delay: Option<Duration>,
+    timeout: Option<Duration>,
     project_dir: Option<PathBuf>,
-    cargo_test_args: Vec<&'a str>
+    cargo_test_args: Option<Vec<String>>,
+    test_args: Option<Vec<String>>,
+    env: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    env_profiles: Option<Vec<String>>,
+    env_file: Option<String>,
+    runner: Option<Runner>,
+    wasm_env: Option<WasmEnv>,
+    pipeline: Option<Vec<String>>,
+    check_first: Option<bool>,
+    fmt: Option<FmtMode>,
+    clippy: Option<bool>,
+    deny_warnings: Option<bool>,
+    miri: Option<bool>,
+    mutants: Option<bool>,
+    matrix: Option<Vec<String>>,
+    toolchains: Option<Vec<String>>,
+    targets: Option<Vec<String>>,
+    cross: Option<bool>,
+    command: Option<String>,
+    command_map: Option<Vec<CommandMapping>>,
+    outcome_rules: Option<Vec<OutcomeRule>>,
+    restart: Option<bool>,
+    pty: Option<bool>,
+    coverage: Option<bool>,
+    failed_first: Option<bool>,
+    retries: Option<u32>,
+    slowdown_threshold: Option<f64>,
+    targeted: Option<bool>,
+    test_map: Option<Vec<TestMapping>>,
+    changed_since: Option<String>,
+    interactive: Option<bool>,
+    clear: Option<bool>,
+    quiet: Option<bool>,
+    ui: Option<bool>,
+    pick: bool,
+    history: Option<bool>,
+    status_file: Option<bool>,
+    watch_dependencies: Option<bool>,
+    skip_unchanged: Option<bool>,
+    poll: Option<Duration>,
+    junit: Option<PathBuf>,
+    listen: Option<String>,
+    output: Option<OutputFormat>,
+    annotate: Option<AnnotationFormat>,
+    watch: Vec<String>,
+    ignore: Vec<String>,
+    watch_patterns: Vec<String>,
+    hooks: Option<Hooks>,
+    ssh: Option<Ssh>,
+    docker: Option<Docker>,
+    email: Option<Email>,
+    ntfy: Option<Push>,
+    gotify: Option<Push>,
+    mqtt: Option<Mqtt>,
+    sounds: Option<Sounds>,
+    notifiers: Option<Vec<NotifierKind>>,
+    notify_on: Option<BTreeMap<String, Vec<String>>>,
+    notify: Option<NotifyMode>,
+    skip_when_focused: Option<bool>,
+    slack_webhook: Option<String>,
+    discord_webhook: Option<String>,
+    telegram_token: Option<String>,
+    telegram_chat_id: Option<String>,
+    http_url: Option<String>,
+    http_body: Option<String>,
+    exec_command: Option<String>,
+    notification_title: Option<String>,
+    notification_body: Option<String>
 }
 
-impl<'a> ConfigBuilder<'a> {
+impl Default for ConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigBuilder {
     pub fn new() -> Self {
         Self {
-            ignore_duration: Duration::from_millis(300),
+            ignore_duration: None,
+            delay: None,
+            timeout: None,
             project_dir: None,
-            cargo_test_args: vec![]
+            cargo_test_args: None,
+            test_args: None,
+            env: None,
+            env_profiles: None,
+            env_file: None,
+            runner: None,
+            wasm_env: None,
+            pipeline: None,
+            check_first: None,
+            fmt: None,
+            clippy: None,
+            deny_warnings: None,
+            miri: None,
+            mutants: None,
+            matrix: None,
+            toolchains: None,
+            targets: None,
+            cross: None,
+            command: None,
+            command_map: None,
+            outcome_rules: None,
+            restart: None,
+            pty: None,
+            coverage: None,
+            failed_first: None,
+            retries: None,
+            slowdown_threshold: None,
+            targeted: None,
+            test_map: None,
+            changed_since: None,
+            interactive: None,
+            clear: None,
+            quiet: None,
+            ui: None,
+            pick: false,
+            history: None,
+            status_file: None,
+            watch_dependencies: None,
+            skip_unchanged: None,
+            poll: None,
+            junit: None,
+            listen: None,
+            output: None,
+            annotate: None,
+            watch: vec![],
+            ignore: vec![],
+            watch_patterns: vec![],
+            hooks: None,
+            ssh: None,
+            docker: None,
+            email: None,
+            ntfy: None,
+            gotify: None,
+            mqtt: None,
+            sounds: None,
+            notifiers: None,
+            notify_on: None,
+            notify: None,
+            skip_when_focused: None,
+            slack_webhook: None,
+            discord_webhook: None,
+            telegram_token: None,
+            telegram_chat_id: None,
+            http_url: None,
+            http_body: None,
+            exec_command: None,
+            notification_title: None,
+            notification_body: None
+        }
+    }
+
+    pub fn file_config(mut self, file_config: FileConfig) -> Result<Self> {
+        if let Some(millis) = file_config.ignore_duration {
+            self.ignore_duration = Some(Duration::from_millis(millis));
+        }
+        if let Some(delay) = file_config.delay {
+            self.delay = Some(parse_duration(&delay)?);
+        }
+        if let Some(timeout) = file_config.timeout {
+            self.timeout = Some(parse_duration(&timeout)?);
+        }
+        if let Some(args) = file_config.cargo_test_args {
+            self.cargo_test_args = Some(args);
+        }
+        if let Some(args) = file_config.test_args {
+            self.test_args = Some(args);
+        }
+        if let Some(env) = file_config.env {
+            self.env = Some(env);
+        }
+        if let Some(env_profiles) = file_config.env_profiles {
+            self.env_profiles = Some(env_profiles);
+        }
+        if let Some(env_file) = file_config.env_file {
+            self.env_file = Some(env_file);
+        }
+        if let Some(runner) = file_config.runner {
+            self.runner = Some(runner);
+        }
+        if let Some(wasm_env) = file_config.wasm_env {
+            self.wasm_env = Some(wasm_env);
+        }
+        if let Some(pipeline) = file_config.pipeline {
+            self.pipeline = Some(pipeline);
+        }
+        if let Some(check_first) = file_config.check_first {
+            self.check_first = Some(check_first);
+        }
+        if let Some(fmt) = file_config.fmt {
+            self.fmt = Some(fmt);
+        }
+        if let Some(clippy) = file_config.clippy {
+            self.clippy = Some(clippy);
+        }
+        if let Some(deny_warnings) = file_config.deny_warnings {
+            self.deny_warnings = Some(deny_warnings);
+        }
+        if let Some(miri) = file_config.miri {
+            self.miri = Some(miri);
+        }
+        if let Some(mutants) = file_config.mutants {
+            self.mutants = Some(mutants);
+        }
+        if let Some(matrix) = file_config.matrix {
+            self.matrix = Some(matrix);
+        }
+        if let Some(toolchains) = file_config.toolchains {
+            self.toolchains = Some(toolchains);
+        }
+        if let Some(targets) = file_config.targets {
+            self.targets = Some(targets);
+        }
+        if let Some(cross) = file_config.cross {
+            self.cross = Some(cross);
+        }
+        if let Some(command) = file_config.command {
+            self.command = Some(command);
+        }
+        if let Some(command_map) = file_config.command_map {
+            self.command_map = Some(command_map);
+        }
+        if let Some(outcome_rules) = file_config.outcome_rules {
+            self.outcome_rules = Some(outcome_rules);
+        }
+        if let Some(restart) = file_config.restart {
+            self.restart = Some(restart);
+        }
+        if let Some(pty) = file_config.pty {
+            self.pty = Some(pty);
+        }
+        if let Some(coverage) = file_config.coverage {
+            self.coverage = Some(coverage);
+        }
+        if let Some(failed_first) = file_config.failed_first {
+            self.failed_first = Some(failed_first);
+        }
+        if let Some(retries) = file_config.retries {
+            self.retries = Some(retries);
+        }
+        if let Some(slowdown_threshold) = file_config.slowdown_threshold {
+            self.slowdown_threshold = Some(slowdown_threshold);
+        }
+        if let Some(targeted) = file_config.targeted {
+            self.targeted = Some(targeted);
+        }
+        if let Some(changed_since) = file_config.changed_since {
+            self.changed_since = Some(changed_since);
+        }
+        if let Some(test_map) = file_config.test_map {
+            self.test_map = Some(test_map);
+        }
+        if let Some(interactive) = file_config.interactive {
+            self.interactive = Some(interactive);
+        }
+        if let Some(clear) = file_config.clear {
+            self.clear = Some(clear);
+        }
+        if let Some(quiet) = file_config.quiet {
+            self.quiet = Some(quiet);
+        }
+        if let Some(ui) = file_config.ui {
+            self.ui = Some(ui);
+        }
+        if let Some(history) = file_config.history {
+            self.history = Some(history);
+        }
+        if let Some(status_file) = file_config.status_file {
+            self.status_file = Some(status_file);
+        }
+        if let Some(junit) = file_config.junit {
+            self.junit = Some(junit);
+        }
+        if let Some(listen) = file_config.listen {
+            self.listen = Some(listen);
+        }
+        if let Some(watch) = file_config.watch {
+            self.watch = watch;
+        }
+        if let Some(watch_dependencies) = file_config.watch_dependencies {
+            self.watch_dependencies = Some(watch_dependencies);
+        }
+        if let Some(skip_unchanged) = file_config.skip_unchanged {
+            self.skip_unchanged = Some(skip_unchanged);
+        }
+        if let Some(poll) = file_config.poll {
+            self.poll = Some(parse_duration(&poll)?);
         }
+        if let Some(ignore) = file_config.ignore {
+            self.ignore = ignore;
+        }
+        if let Some(watch_patterns) = file_config.watch_patterns {
+            self.watch_patterns = watch_patterns;
+        }
+        if let Some(hooks) = file_config.hooks {
+            self.hooks = Some(hooks);
+        }
+        if let Some(ssh) = file_config.ssh {
+            self.ssh = Some(ssh);
+        }
+        if let Some(docker) = file_config.docker {
+            self.docker = Some(docker);
+        }
+        if let Some(email) = file_config.email {
+            self.email = Some(email);
+        }
+        if let Some(ntfy) = file_config.ntfy {
+            self.ntfy = Some(ntfy);
+        }
+        if let Some(gotify) = file_config.gotify {
+            self.gotify = Some(gotify);
+        }
+        if let Some(mqtt) = file_config.mqtt {
+            self.mqtt = Some(mqtt);
+        }
+        if let Some(sounds) = file_config.sounds {
+            self.sounds = Some(sounds);
+        }
+        if let Some(notifier) = file_config.notifier {
+            self.notifiers = Some(vec![notifier]);
+        }
+        if let Some(notifiers) = file_config.notifiers {
+            self.notifiers = Some(notifiers);
+        }
+        if let Some(notify_on) = file_config.notify_on {
+            self.notify_on = Some(notify_on);
+        }
+        if let Some(notify) = file_config.notify {
+            self.notify = Some(notify);
+        }
+        if let Some(skip_when_focused) = file_config.skip_when_focused {
+            self.skip_when_focused = Some(skip_when_focused);
+        }
+        if let Some(webhook) = file_config.slack.and_then(|slack| slack.webhook) {
+            self.slack_webhook = Some(webhook);
+        }
+        if let Some(webhook) = file_config.discord.and_then(|discord| discord.webhook) {
+            self.discord_webhook = Some(webhook);
+        }
+        if let Some(telegram) = file_config.telegram {
+            if let Some(token) = telegram.token {
+                self.telegram_token = Some(token);
+            }
+            if let Some(chat_id) = telegram.chat_id {
+                self.telegram_chat_id = Some(chat_id);
+            }
+        }
+        if let Some(http) = file_config.http {
+            if let Some(url) = http.url {
+                self.http_url = Some(url);
+            }
+            if let Some(body) = http.body {
+                self.http_body = Some(body);
+            }
+        }
+        if let Some(command) = file_config.exec.and_then(|exec| exec.command) {
+            self.exec_command = Some(command);
+        }
+        if let Some(notification) = file_config.notification {
+            if let Some(title) = notification.title {
+                self.notification_title = Some(title);
+            }
+            if let Some(body) = notification.body {
+                self.notification_body = Some(body);
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn poll(mut self, interval: Duration) -> Self {
+        self.poll = Some(interval);
+        self
     }
 
     pub fn project_dir(mut self, dir: PathBuf) -> Self {
@@ -29,19 +1280,703 @@ impl<'a> ConfigBuilder<'a> {
         self
     }
 
-    pub fn cargo_test_args(mut self, args: Vec<&'a str>) -> Self {
-        self.cargo_test_args = args;
+    pub fn cargo_test_args(mut self, args: Vec<String>) -> Self {
+        self.cargo_test_args = Some(args);
+        self
+    }
+
+    pub fn test_args(mut self, args: Vec<String>) -> Self {
+        self.test_args = Some(args);
+        self
+    }
+
+    /// Set the variables of the `[env.NAME]` profiles `names` for the commands, the later ones win.
+    pub fn env_profiles(mut self, names: Vec<String>) -> Self {
+        self.env_profiles = Some(names);
+        self
+    }
+
+    /// Load the environment variables from `file`, relative to the project directory, an empty one turns it off.
+    pub fn env_file(mut self, file: String) -> Self {
+        self.env_file = Some(file);
+        self
+    }
+
+    pub fn runner(mut self, runner: Runner) -> Self {
+        self.runner = Some(runner);
+        self
+    }
+
+    pub fn wasm_env(mut self, wasm_env: WasmEnv) -> Self {
+        self.wasm_env = Some(wasm_env);
+        self
+    }
+
+    pub fn pipeline(mut self, pipeline: Vec<String>) -> Self {
+        self.pipeline = Some(pipeline);
+        self
+    }
+
+    pub fn check_first(mut self, check_first: bool) -> Self {
+        self.check_first = Some(check_first);
+        self
+    }
+
+    pub fn fmt(mut self, fmt: FmtMode) -> Self {
+        self.fmt = Some(fmt);
+        self
+    }
+
+    pub fn clippy(mut self, clippy: bool) -> Self {
+        self.clippy = Some(clippy);
+        self
+    }
+
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.deny_warnings = Some(deny_warnings);
+        self
+    }
+
+    pub fn miri(mut self, miri: bool) -> Self {
+        self.miri = Some(miri);
+        self
+    }
+
+    pub fn mutants(mut self, mutants: bool) -> Self {
+        self.mutants = Some(mutants);
+        self
+    }
+
+    pub fn toolchains(mut self, toolchains: Vec<String>) -> Self {
+        self.toolchains = Some(toolchains);
+        self
+    }
+
+    pub fn targets(mut self, targets: Vec<String>) -> Self {
+        self.targets = Some(targets);
+        self
+    }
+
+    pub fn cross(mut self, cross: bool) -> Self {
+        self.cross = Some(cross);
+        self
+    }
+
+    pub fn command(mut self, command: String) -> Self {
+        self.command = Some(command);
+        self
+    }
+
+    pub fn restart(mut self, restart: bool) -> Self {
+        self.restart = Some(restart);
+        self
+    }
+
+    pub fn pty(mut self, pty: bool) -> Self {
+        self.pty = Some(pty);
+        self
+    }
+
+    pub fn coverage(mut self, coverage: bool) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    pub fn failed_first(mut self, failed_first: bool) -> Self {
+        self.failed_first = Some(failed_first);
+        self
+    }
+
+    pub fn targeted(mut self, targeted: bool) -> Self {
+        self.targeted = Some(targeted);
+        self
+    }
+
+    /// Restrict the runs to the changes since the git ref `base`, or run the whole suite with `None`.
+    pub fn changed_since(mut self, base: Option<String>) -> Self {
+        self.changed_since = base;
+        self
+    }
+
+    pub fn interactive(mut self, interactive: bool) -> Self {
+        self.interactive = Some(interactive);
+        self
+    }
+
+    pub fn clear(mut self, clear: bool) -> Self {
+        self.clear = Some(clear);
+        self
+    }
+
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = Some(quiet);
+        self
+    }
+
+    pub fn ui(mut self, ui: bool) -> Self {
+        self.ui = Some(ui);
+        self
+    }
+
+    pub fn pick(mut self, pick: bool) -> Self {
+        self.pick = pick;
+        self
+    }
+
+    pub fn junit(mut self, junit: PathBuf) -> Self {
+        self.junit = Some(junit);
+        self
+    }
+
+    pub fn listen(mut self, listen: String) -> Self {
+        self.listen = Some(listen);
+        self
+    }
+
+    pub fn output(mut self, output: OutputFormat) -> Self {
+        self.output = Some(output);
         self
     }
 
-    pub fn build(self) -> Result<Config<'a>> {
+    pub fn annotate(mut self, annotate: AnnotationFormat) -> Self {
+        self.annotate = Some(annotate);
+        self
+    }
+
+    /// Add files and directories to watch, on top of those from the config file.
+    pub fn watch(mut self, paths: Vec<String>) -> Self {
+        self.watch.extend(paths);
+        self
+    }
+
+    /// Add patterns of files to ignore, on top of those from the config file.
+    pub fn ignore(mut self, patterns: Vec<String>) -> Self {
+        self.ignore.extend(patterns);
+        self
+    }
+
+    /// Add globs of files to watch, on top of those from the config file.
+    pub fn watch_patterns(mut self, patterns: Vec<String>) -> Self {
+        self.watch_patterns.extend(patterns);
+        self
+    }
+
+    pub fn notifier(mut self, notifier: NotifierKind) -> Self {
+        self.notifiers = Some(vec![notifier]);
+        self
+    }
+
+    pub fn notifiers(mut self, notifiers: Vec<NotifierKind>) -> Self {
+        self.notifiers = Some(notifiers);
+        self
+    }
+
+    pub fn notify(mut self, notify: NotifyMode) -> Self {
+        self.notify = Some(notify);
+        self
+    }
+
+    pub fn skip_when_focused(mut self, skip_when_focused: bool) -> Self {
+        self.skip_when_focused = Some(skip_when_focused);
+        self
+    }
+
+    pub fn build(self) -> Result<Config> {
         let project_dir = self.project_dir.ok_or(ErrorKind::ProjectDirMissing)?;
+        let ssh = self.ssh.unwrap_or_default();
+        if self.runner == Some(Runner::Ssh) && ssh.host.is_none() {
+            return Err(ErrorKind::SettingMissing("ssh.host").into());
+        }
+        let docker = self.docker.unwrap_or_default();
+        if self.runner == Some(Runner::Docker) && docker.image.is_none() {
+            return Err(ErrorKind::SettingMissing("docker.image").into());
+        }
+        let mut email = self.email.unwrap_or_default();
+        if let Ok(password) = env::var(SMTP_PASSWORD_VAR) {
+            email.password = Some(password);
+        }
+        email.password = secret(email.password)?;
+        let ntfy = push_section(self.ntfy, "ntfy", NTFY_TOKEN_VAR)?;
+        let gotify = push_section(self.gotify, "gotify", GOTIFY_TOKEN_VAR)?;
+        let mut mqtt = self.mqtt.unwrap_or_default();
+        if let Ok(password) = env::var(MQTT_PASSWORD_VAR) {
+            mqtt.password = Some(password);
+        }
+        mqtt.password = secret(mqtt.password)?;
+        let mut profiles = self.env.unwrap_or_default();
+        let mut env = BTreeMap::new();
+        for name in self.env_profiles.unwrap_or_default() {
+            match profiles.remove(&name) {
+                Some(variables) => env.extend(variables),
+                None => return Err(ErrorKind::UnknownEnvProfile(name).into())
+            }
+        }
+        let mut routes = vec![];
+        for (notifier, outcomes) in self.notify_on.unwrap_or_default() {
+            let notifier = notifier.parse::<NotifierKind>()?;
+            if let Some(outcome) = outcomes.iter().find(|outcome| !is_outcome_name(outcome)) {
+                return Err(ErrorKind::UnknownRouteOutcome(notifier.name(), outcome.clone()).into());
+            }
+            routes.push(Route { notifier, outcomes });
+        }
 
         let config = Config {
-            ignore_duration: self.ignore_duration,
-            cargo_test_args: self.cargo_test_args,
-            project_dir: project_dir
+            ignore_duration: self.ignore_duration.unwrap_or(Duration::from_millis(300)),
+            delay: self.delay.unwrap_or(Duration::from_millis(100)),
+            timeout: self.timeout,
+            cargo_test_args: self.cargo_test_args.unwrap_or(vec![]),
+            test_args: self.test_args.unwrap_or(vec![]),
+            env,
+            env_file: match self.env_file {
+                Some(ref file) if file.is_empty() => None,
+                file => Some(project_dir.join(file.unwrap_or(DEFAULT_ENV_FILE.to_string())))
+            },
+            runner: self.runner.unwrap_or(Runner::Cargo),
+            wasm_env: self.wasm_env.unwrap_or(WasmEnv::Chrome),
+            pipeline: self.pipeline.unwrap_or(vec![]),
+            check_first: self.check_first.unwrap_or(false),
+            fmt: self.fmt.unwrap_or(FmtMode::Off),
+            clippy: self.clippy.unwrap_or(false),
+            deny_warnings: self.deny_warnings.unwrap_or(false),
+            miri: self.miri.unwrap_or(false),
+            mutants: self.mutants.unwrap_or(false),
+            matrix: self.matrix.unwrap_or(vec![]),
+            toolchains: self.toolchains.unwrap_or(vec![]),
+            targets: self.targets.unwrap_or(vec![]),
+            cross: self.cross.unwrap_or(false),
+            command: self.command,
+            command_map: self.command_map.unwrap_or(vec![]),
+            outcome_rules: self.outcome_rules.unwrap_or(vec![]),
+            restart: self.restart.unwrap_or(false),
+            pty: self.pty.unwrap_or(false),
+            coverage: self.coverage.unwrap_or(false),
+            failed_first: self.failed_first.unwrap_or(false),
+            retries: self.retries.unwrap_or(0),
+            slowdown_threshold: self.slowdown_threshold.unwrap_or(1.5),
+            targeted: self.targeted.unwrap_or(false),
+            test_map: self.test_map.unwrap_or(vec![]),
+            changed_since: self.changed_since,
+            interactive: self.interactive.unwrap_or(true),
+            clear: self.clear.unwrap_or(false),
+            quiet: self.quiet.unwrap_or(false),
+            ui: self.ui.unwrap_or(false) && self.output != Some(OutputFormat::Json),
+            pick: self.pick,
+            history: self.history.unwrap_or(true),
+            status_file: self.status_file.unwrap_or(true),
+            junit: self.junit.map(|junit| project_dir.join(junit)),
+            listen: self.listen,
+            output: self.output.unwrap_or(OutputFormat::Human),
+            annotate: self.annotate,
+            watch: self.watch,
+            watch_dependencies: self.watch_dependencies.unwrap_or(true),
+            skip_unchanged: self.skip_unchanged.unwrap_or(true),
+            poll: self.poll,
+            ignore: self.ignore,
+            watch_patterns: self.watch_patterns,
+            hooks: self.hooks.unwrap_or_default(),
+            ssh,
+            docker,
+            email,
+            ntfy,
+            gotify,
+            mqtt,
+            sounds: self.sounds.unwrap_or_default().resolve(&project_dir),
+            notifiers: self.notifiers.unwrap_or(vec![NotifierKind::Auto]),
+            routes,
+            notify: self.notify.unwrap_or(NotifyMode::Always),
+            skip_when_focused: self.skip_when_focused.unwrap_or(false),
+            slack_webhook: secret(env::var(SLACK_WEBHOOK_VAR).ok().or(self.slack_webhook))?,
+            discord_webhook: secret(env::var(DISCORD_WEBHOOK_VAR).ok().or(self.discord_webhook))?,
+            telegram_token: secret(env::var(TELEGRAM_TOKEN_VAR).ok().or(self.telegram_token))?,
+            telegram_chat_id: env::var(TELEGRAM_CHAT_ID_VAR).ok().or(self.telegram_chat_id),
+            http_url: secret(self.http_url)?,
+            http_body: self.http_body,
+            exec_command: self.exec_command,
+            notification_title: self.notification_title,
+            notification_body: self.notification_body,
+            project_dir
         };
         Ok(config)
     }
 }
+
+/// Value of a setting which may name a secret in the credential store, e.g. `keyring:slack_webhook`.
+fn secret(value: Option<String>) -> Result<Option<String>> {
+    value.map(secrets::resolve).transpose()
+}
+
+/// Section of a push service with the token from the environment, and its priorities validated.
+fn push_section(push: Option<Push>, name: &'static str, token_var: &str) -> Result<Push> {
+    let mut push = push.unwrap_or_default();
+    if let Ok(token) = env::var(token_var) {
+        push.token = Some(token);
+    }
+    push.token = secret(push.token)?;
+    if let Some(outcome) = push.priority.iter().flat_map(|priority| priority.keys()).find(|outcome| !is_outcome_name(outcome)) {
+        return Err(ErrorKind::UnknownPriorityOutcome(name, outcome.clone()).into());
+    }
+    Ok(push)
+}
+
+/// Parse a duration like `"300ms"`, `"1s"`, `"1.5s"` or `"2m"`. A number without unit means milliseconds.
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let text = text.trim();
+    let split_at = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split_at);
+    let number: f64 = number.parse().map_err(|_| ErrorKind::InvalidDuration(text.to_string()))?;
+    let millis = match unit.trim() {
+        "" | "ms" => number,
+        "s" => number * 1000.0,
+        "m" => number * 60_000.0,
+        _ => return Err(ErrorKind::InvalidDuration(text.to_string()).into())
+    };
+    Ok(Duration::from_millis(millis.round() as u64))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> FileConfig {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_file_config_values_are_applied() {
+        let file_config = parse(r#"
+            ignore_duration = 1000
+            delay = "1s"
+            timeout = "2m"
+            cargo_test_args = ["--lib"]
+            test_args = ["--nocapture"]
+            env_file = ".env.test"
+            runner = "nextest"
+            wasm_env = "node"
+            pipeline = ["check", "test"]
+            check_first = true
+            fmt = "fix"
+            clippy = true
+            deny_warnings = true
+            miri = true
+            mutants = true
+            coverage = true
+            matrix = ["--no-default-features", "--all-features"]
+            toolchains = ["stable", "nightly"]
+            targets = ["x86_64-unknown-linux-musl"]
+            cross = true
+            retries = 2
+            slowdown_threshold = 2.0
+            targeted = true
+            test_map = [{ path = "src/storage/**", filter = "db::" }]
+            changed_since = "origin/main"
+            command = "make check"
+            command_map = [{ path = "docs/**", command = "cargo doc --no-deps" }]
+            outcome_rules = [{ pattern = "FAIL: (.*)", outcome = "failed", message = 1 }]
+            junit = "target/junit.xml"
+            listen = "127.0.0.1:7878"
+            watch = ["templates"]
+            watch_dependencies = false
+            skip_unchanged = false
+            poll = "2s"
+            ignore = ["src/generated/"]
+            watch_patterns = ["**/*.graphql"]
+            quiet = true
+            ui = true
+            notifier = "none"
+            notify = "changes"
+            skip_when_focused = true
+
+            [hooks]
+            before_run = "make proto"
+            on_success = "touch .deploy"
+
+            [ssh]
+            host = "build@devbox"
+            rsync = true
+
+            [docker]
+            image = "rust:1.80"
+            args = ["--network=none"]
+
+            [sounds]
+            passed = "sounds/pass.wav"
+            compile_error = ""
+
+            [slack]
+            webhook = "https://hooks.slack.com/services/T0/B0/X"
+
+            [discord]
+            webhook = "https://discord.com/api/webhooks/1/X"
+
+            [telegram]
+            token = "123:ABC"
+            chat_id = "42"
+
+            [email]
+            host = "smtp.example.com"
+            security = "tls"
+            from = "testify@example.com"
+            to = ["dev@example.com"]
+
+            [mqtt]
+            broker = "homeassistant.local"
+            retain = true
+
+            [http]
+            url = "https://ntfy.sh/testify"
+            body = '{"message": "{title}"}'
+
+            [exec]
+            command = "./on-tests.sh"
+
+            [notification]
+            title = "{project}: {title}"
+        "#);
+        let config = ConfigBuilder::new()
+            .file_config(file_config).unwrap()
+            .project_dir(PathBuf::from("/project"))
+            .build()
+            .unwrap();
+
+        assert_eq!(config.ignore_duration, Duration::from_millis(1000));
+        assert_eq!(config.delay, Duration::from_secs(1));
+        assert_eq!(config.timeout, Some(Duration::from_secs(120)));
+        assert_eq!(config.cargo_test_args, vec!["--lib"]);
+        assert_eq!(config.test_args, vec!["--nocapture"]);
+        assert_eq!(config.env_file, Some(PathBuf::from("/project/.env.test")));
+        assert_eq!(config.runner, Runner::Nextest);
+        assert_eq!(config.wasm_env, WasmEnv::Node);
+        assert_eq!(config.pipeline, vec!["check", "test"]);
+        assert!(config.check_first);
+        assert_eq!(config.fmt, FmtMode::Fix);
+        assert!(config.clippy);
+        assert!(config.deny_warnings);
+        assert!(config.miri);
+        assert!(config.mutants);
+        assert!(config.coverage);
+        assert_eq!(config.matrix, vec!["--no-default-features", "--all-features"]);
+        assert_eq!(config.toolchains, vec!["stable", "nightly"]);
+        assert_eq!(config.targets, vec!["x86_64-unknown-linux-musl"]);
+        assert!(config.cross);
+        assert_eq!(config.retries, 2);
+        assert_eq!(config.slowdown_threshold, 2.0);
+        assert!(config.targeted);
+        assert_eq!(config.test_map, vec![TestMapping { path: "src/storage/**".to_string(), filter: "db::".to_string() }]);
+        assert_eq!(config.changed_since, Some("origin/main".to_string()));
+        assert_eq!(config.command, Some("make check".to_string()));
+        assert_eq!(config.command_map, vec![CommandMapping { path: "docs/**".to_string(), command: "cargo doc --no-deps".to_string() }]);
+        assert_eq!(config.outcome_rules, vec![OutcomeRule { pattern: "FAIL: (.*)".to_string(), outcome: "failed".to_string(), message: Some(1) }]);
+        assert_eq!(config.junit, Some(PathBuf::from("/project/target/junit.xml")));
+        assert_eq!(config.listen, Some("127.0.0.1:7878".to_string()));
+        assert_eq!(config.watch, vec!["templates"]);
+        assert!(!config.watch_dependencies);
+        assert!(!config.skip_unchanged);
+        assert_eq!(config.poll, Some(Duration::from_secs(2)));
+        assert_eq!(config.ignore, vec!["src/generated/"]);
+        assert_eq!(config.watch_patterns, vec!["**/*.graphql"]);
+        assert!(config.quiet);
+        assert!(config.ui);
+        assert_eq!(config.notify, NotifyMode::Changes);
+        assert!(config.skip_when_focused);
+        assert_eq!(config.hooks.before_run, Some("make proto".to_string()));
+        assert_eq!(config.hooks.on_success, Some("touch .deploy".to_string()));
+        assert_eq!(config.hooks.after_run, None);
+        assert_eq!(config.ssh.host, Some("build@devbox".to_string()));
+        assert!(config.ssh.rsync());
+        assert_eq!(config.docker.image, Some("rust:1.80".to_string()));
+        assert_eq!(config.docker.args, Some(vec!["--network=none".to_string()]));
+        assert_eq!(config.sounds.passed, Some("/project/sounds/pass.wav".to_string()));
+        assert_eq!(config.sounds.compile_error, Some("".to_string()));
+        assert_eq!(config.notifiers, vec![NotifierKind::Disabled]);
+        assert_eq!(config.slack_webhook, Some("https://hooks.slack.com/services/T0/B0/X".to_string()));
+        assert_eq!(config.discord_webhook, Some("https://discord.com/api/webhooks/1/X".to_string()));
+        assert_eq!(config.telegram_token, Some("123:ABC".to_string()));
+        assert_eq!(config.telegram_chat_id, Some("42".to_string()));
+        assert_eq!(config.email.host, Some("smtp.example.com".to_string()));
+        assert_eq!(config.email.security, Some(SmtpSecurity::Tls));
+        assert_eq!(config.email.to, Some(vec!["dev@example.com".to_string()]));
+        assert_eq!(config.mqtt.broker, Some("homeassistant.local".to_string()));
+        assert_eq!(config.mqtt.retain, Some(true));
+        assert_eq!(config.http_url, Some("https://ntfy.sh/testify".to_string()));
+        assert_eq!(config.exec_command, Some("./on-tests.sh".to_string()));
+        assert_eq!(config.http_body, Some(r#"{"message": "{title}"}"#.to_string()));
+        assert_eq!(config.notification_title, Some("{project}: {title}".to_string()));
+        assert_eq!(config.notification_body, None);
+    }
+
+    #[test]
+    fn test_sounds_choose() {
+        let sounds = Sounds {
+            enabled: None,
+            passed: Some("".to_string()),
+            failed: Some("Glass".to_string()),
+            compile_error: None
+        };
+        assert_eq!(sounds.choose(Outcome::TestsPassed, Some("Ping")), None);
+        assert_eq!(sounds.choose(Outcome::TimedOut, Some("Basso")), Some("Glass"));
+        assert_eq!(sounds.choose(Outcome::CompileError, Some("Basso")), Some("Basso"));
+
+        let disabled = Sounds { enabled: Some(false), ..sounds };
+        assert_eq!(disabled.choose(Outcome::TestsFailed, Some("Basso")), None);
+    }
+
+    #[test]
+    fn test_cli_values_override_file_values() {
+        let file_config = parse(r#"
+            cargo_test_args = ["--lib"]
+            notifier = "none"
+        "#);
+        let config = ConfigBuilder::new()
+            .file_config(file_config).unwrap()
+            .project_dir(PathBuf::from("/project"))
+            .cargo_test_args(vec!["--doc".to_string()])
+            .notifier(NotifierKind::Desktop)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.cargo_test_args, vec!["--doc"]);
+        assert_eq!(config.notifiers, vec![NotifierKind::Desktop]);
+        assert_eq!(config.notify, NotifyMode::Always);
+    }
+
+    #[test]
+    fn test_notifiers() {
+        let file_config = parse(r#"
+            notifiers = ["desktop", "slack"]
+        "#);
+        let config = ConfigBuilder::new()
+            .file_config(file_config).unwrap()
+            .project_dir(PathBuf::from("/project"))
+            .build()
+            .unwrap();
+        assert_eq!(config.notifiers, vec![NotifierKind::Desktop, NotifierKind::Slack]);
+    }
+
+    #[test]
+    fn test_routes() {
+        let file_config = parse(r#"
+            notifiers = ["desktop", "slack"]
+
+            [notify_on]
+            slack = ["failures", "passed"]
+        "#);
+        let config = ConfigBuilder::new()
+            .file_config(file_config).unwrap()
+            .project_dir(PathBuf::from("/project"))
+            .build()
+            .unwrap();
+        assert_eq!(config.routes.len(), 1);
+        let route = &config.routes[0];
+        assert_eq!(route.notifier, NotifierKind::Slack);
+        assert!(route.matches(Outcome::CompileError));
+        assert!(route.matches(Outcome::TestsPassed));
+        assert!(!route.matches(Outcome::Flaky(1)));
+        assert!(!route.matches(Outcome::NoTests));
+
+        let file_config = parse(r#"
+            [notify_on]
+            desktop = ["compile_errors"]
+        "#);
+        let result = ConfigBuilder::new().file_config(file_config).unwrap().project_dir(PathBuf::from("/project")).build();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_env_profiles() {
+        let content = r#"
+            env_profiles = ["debug"]
+
+            [env.debug]
+            RUST_LOG = "debug"
+            RUST_BACKTRACE = "0"
+
+            [env.ci]
+            RUST_BACKTRACE = "1"
+        "#;
+        let builder = || ConfigBuilder::new().file_config(parse(content)).unwrap().project_dir(PathBuf::from("/project"));
+        let config = builder().build().unwrap();
+        assert_eq!(config.env.get("RUST_LOG").map(String::as_str), Some("debug"));
+        assert_eq!(config.env.get("RUST_BACKTRACE").map(String::as_str), Some("0"));
+
+        let config = builder().env_profiles(vec!["debug".to_string(), "ci".to_string()]).build().unwrap();
+        assert_eq!(config.env.get("RUST_BACKTRACE").map(String::as_str), Some("1"));
+        assert_eq!(config.env.len(), 2);
+
+        assert!(builder().env_profiles(vec!["release".to_string()]).build().is_err());
+        assert_eq!(builder().env_file(String::new()).build().unwrap().env_file, None);
+    }
+
+    #[test]
+    fn test_push_priority() {
+        let file_config = parse(r#"
+            [ntfy]
+            url = "https://ntfy.sh/tests"
+            priority = { failures = 4, compile_error = 5 }
+        "#);
+        let config = ConfigBuilder::new().file_config(file_config).unwrap().project_dir(PathBuf::from("/project")).build().unwrap();
+        assert_eq!(config.ntfy.priority(Outcome::CompileError), Some(5));
+        assert_eq!(config.ntfy.priority(Outcome::TestsFailed), Some(4));
+        assert_eq!(config.ntfy.priority(Outcome::TestsPassed), None);
+        assert_eq!(config.gotify.priority(Outcome::TestsFailed), None);
+
+        let file_config = parse("[gotify]\npriority = { failed_tests = 8 }");
+        let err = ConfigBuilder::new().file_config(file_config).unwrap().project_dir(PathBuf::from("/project")).build().err().unwrap();
+        assert!(err.to_string().starts_with("unknown outcome `failed_tests` in gotify.priority"));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = ConfigBuilder::new()
+            .file_config(parse("")).unwrap()
+            .project_dir(PathBuf::from("/project"))
+            .build()
+            .unwrap();
+
+        assert_eq!(config.ignore_duration, Duration::from_millis(300));
+        assert!(config.cargo_test_args.is_empty());
+        assert_eq!(config.runner, Runner::Cargo);
+        assert_eq!(config.timeout, None);
+        assert!(config.watch.is_empty());
+        assert!(config.watch_patterns.is_empty());
+        assert_eq!(config.changed_since, None);
+        assert!(config.command_map.is_empty());
+        assert!(config.env.is_empty());
+        assert_eq!(config.env_file, Some(PathBuf::from("/project/.env")));
+        assert!(config.watch_dependencies);
+        assert!(config.skip_unchanged);
+        assert_eq!(config.poll, None);
+        assert_eq!(config.notifiers, vec![NotifierKind::Auto]);
+    }
+
+    #[test]
+    fn test_ssh_runner_requires_host() {
+        let builder = || ConfigBuilder::new().project_dir(PathBuf::from("/project")).runner(Runner::Ssh);
+        assert!(builder().build().is_err());
+
+        let config = builder().file_config(parse("[ssh]\nhost = \"devbox\"")).unwrap().build().unwrap();
+        assert_eq!(config.ssh.host, Some("devbox".to_string()));
+        assert!(!config.ssh.rsync());
+
+        let builder = ConfigBuilder::new().project_dir(PathBuf::from("/project")).runner(Runner::Docker);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("250").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1s").unwrap(), Duration::from_secs(1));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("fast").is_err());
+        assert!(parse_duration("1h").is_err());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_rejected() {
+        assert!(toml::from_str::<FileConfig>("foo = 1").is_err());
+    }
+}
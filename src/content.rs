@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+/// Bigger files are not remembered, any event of theirs counts as a change.
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Hashes of the content of the watched files, to tell whether an event has actually changed
+/// anything: touching a file, changing its metadata or checking out the same content does not.
+#[derive(Default)]
+pub struct ContentCache {
+    hashes: HashMap<PathBuf, u64>
+}
+
+impl ContentCache {
+    /// Remember the content of the files of the project which `allows` accepts.
+    /// Files ignored by git are skipped, like the hidden ones.
+    pub fn scan<F>(&mut self, project_dir: &Path, allows: F)
+        where F: Fn(&Path) -> bool
+    {
+        let files = WalkBuilder::new(project_dir).build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+            .map(|entry| entry.into_path())
+            .filter(|path| allows(path));
+        for path in files {
+            if let Some(hash) = hash_file(&path) {
+                self.hashes.insert(path, hash);
+            }
+        }
+    }
+
+    /// Has the content of `path` changed since it was seen last? Files not seen before,
+    /// directories, and files which can't be read (e.g. deleted ones) count as changed.
+    pub fn changed(&mut self, path: &Path) -> bool {
+        match hash_file(path) {
+            Some(hash) => self.hashes.insert(path.to_path_buf(), hash) != Some(hash),
+            None => {
+                self.hashes.remove(path);
+                true
+            }
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let metadata = fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > MAX_FILE_SIZE { return None; }
+    let content = fs::read(path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_changed() {
+        let dir = env::temp_dir().join("testify-content-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        let (lib, main) = (dir.join("src/lib.rs"), dir.join("src/main.rs"));
+        fs::write(&lib, "fn a() {}").unwrap();
+        fs::write(&main, "fn main() {}").unwrap();
+
+        let mut cache = ContentCache::default();
+        cache.scan(&dir, |path| path != main);
+        assert!(!cache.changed(&lib));
+        // Not scanned, so it's not known what it was like
+        assert!(cache.changed(&main));
+        assert!(!cache.changed(&main));
+
+        fs::write(&lib, "fn b() {}").unwrap();
+        assert!(cache.changed(&lib));
+        assert!(!cache.changed(&lib));
+
+        fs::remove_file(&lib).unwrap();
+        assert!(cache.changed(&lib));
+        assert!(cache.changed(&dir.join("src")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
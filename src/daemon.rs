@@ -0,0 +1,269 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json;
+
+use status::{LatestRun, Status};
+use errors::*;
+
+/// Control socket of the daemon, relative to the project root.
+#[cfg(not(target_os = "windows"))]
+pub const SOCKET_FILE: &str = ".testify/daemon.sock";
+
+/// File with the loopback address the daemon listens on, relative to the project root.
+/// Windows has no unix sockets in the standard library, so a local TCP port is used instead.
+#[cfg(target_os = "windows")]
+pub const ADDRESS_FILE: &'static str = ".testify/daemon.addr";
+
+/// Output of the daemon, relative to the project root.
+pub const LOG_FILE: &str = ".testify/daemon.log";
+
+/// Set for the detached process, so it runs the daemon instead of spawning one more.
+const DAEMON_VAR: &str = "CARGO_TESTIFY_DAEMON";
+
+/// How long `cargo testify daemon` waits for the control interface of the new daemon.
+const STARTUP_TIMEOUT_MS: u64 = 5000;
+
+/// How long the daemon waits for a control request after a client has connected.
+const REQUEST_TIMEOUT_MS: u64 = 5000;
+
+/// Request sent to the daemon by a control subcommand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    /// `cargo testify run-now`: run the tests now
+    Run,
+
+    /// `cargo testify stop`: kill the running tests and exit
+    Stop
+}
+
+/// Reply to `cargo testify status`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    /// State of the latest run, `None` before the first one has started
+    pub status: Option<Status>
+}
+
+#[cfg(not(target_os = "windows"))]
+type Stream = ::std::os::unix::net::UnixStream;
+
+#[cfg(target_os = "windows")]
+type Stream = ::std::net::TcpStream;
+
+/// Whether this process is the detached daemon spawned by `cargo testify daemon`.
+pub fn is_detached() -> bool {
+    env::var_os(DAEMON_VAR).is_some()
+}
+
+/// Run the same command line again as a detached process with its output going to the log file,
+/// and wait until its control interface is up. Returns the process id of the daemon.
+pub fn spawn(project_dir: &Path) -> Result<u32> {
+    if request(project_dir, "status").is_ok() {
+        return Err(ErrorKind::DaemonRunning.into());
+    }
+
+    let log_path = project_dir.join(LOG_FILE);
+    let start = || -> ::std::io::Result<process::Child> {
+        if let Some(dir) = log_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let log = File::create(&log_path)?;
+        let mut command = Command::new(env::current_exe()?);
+        command.args(env::args_os().skip(1))
+            .env(DAEMON_VAR, "1")
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+        detach(&mut command);
+        command.spawn()
+    };
+    let mut child = start().chain_err(|| ErrorKind::DaemonFailed(format!("could not spawn it, see {:?}", log_path)))?;
+
+    let deadline = Instant::now() + Duration::from_millis(STARTUP_TIMEOUT_MS);
+    while Instant::now() < deadline {
+        if request(project_dir, "status").is_ok() {
+            return Ok(child.id());
+        }
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(ErrorKind::DaemonFailed(format!("it has exited with {}, see {:?}", status, log_path)).into());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Err(ErrorKind::DaemonFailed(format!("it does not respond, see {:?}", log_path)).into())
+}
+
+/// Start the control interface in a separate thread. Status requests are answered with
+/// the state of the `latest` run, other requests are passed to `on_control`.
+pub fn listen<F>(project_dir: &Path, latest: Arc<Mutex<LatestRun>>, on_control: F) -> Result<()>
+    where F: Fn(Control) + Send + 'static
+{
+    let listener = bind(project_dir).chain_err(|| ErrorKind::DaemonFailed("could not start the control interface".to_string()))?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue
+            };
+            let _ = stream.set_read_timeout(Some(Duration::from_millis(REQUEST_TIMEOUT_MS)));
+            let mut line = String::new();
+            if BufReader::new(&stream).read_line(&mut line).is_err() { continue; }
+
+            let (control, reply) = handle(line.trim(), &latest.lock().unwrap().status);
+            let _ = writeln!(stream, "{}", reply);
+            if let Some(control) = control {
+                on_control(control);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Remove the control endpoint, once the daemon stops.
+pub fn remove(project_dir: &Path) {
+    let _ = fs::remove_file(endpoint_path(project_dir));
+}
+
+/// Send a request (`status`, `run` or `stop`) to the daemon of the project and return its reply.
+pub fn request(project_dir: &Path, command: &str) -> Result<serde_json::Value> {
+    let mut stream = connect(project_dir).chain_err(|| ErrorKind::DaemonNotRunning)?;
+    let mut exchange = || -> ::std::io::Result<String> {
+        stream.set_read_timeout(Some(Duration::from_millis(REQUEST_TIMEOUT_MS)))?;
+        writeln!(stream, "{}", command)?;
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply)?;
+        Ok(reply)
+    };
+    let reply = exchange().chain_err(|| ErrorKind::DaemonNotRunning)?;
+    serde_json::from_str(&reply).chain_err(|| ErrorKind::DaemonFailed(format!("unexpected reply {:?}", reply.trim())))
+}
+
+/// Print the state of the daemon: as a JSON line for scripts, or for a human.
+pub fn print_status(project_dir: &Path, json: bool) -> Result<()> {
+    let reply = request(project_dir, "status")?;
+    if json {
+        println!("{}", reply);
+        return Ok(());
+    }
+    let daemon: DaemonStatus = serde_json::from_value(reply)
+        .chain_err(|| ErrorKind::DaemonFailed("unexpected status".to_string()))?;
+    println!("Daemon is running (pid {})", daemon.pid);
+    match daemon.status {
+        Some(ref status) if status.state == "running" => println!("Tests are running"),
+        Some(ref status) => {
+            let mut line = format!("Last run: {}", status.state);
+            if let (Some(passed), Some(failed), Some(ignored)) = (status.passed, status.failed, status.ignored) {
+                line.push_str(&format!(", {} passed; {} failed; {} ignored", passed, failed, ignored));
+            }
+            println!("{} at {}", line, status.timestamp);
+        },
+        None => println!("No runs yet")
+    }
+    Ok(())
+}
+
+/// Reply to a single request, and what the reactor has to do about it.
+fn handle(request: &str, status: &Option<Status>) -> (Option<Control>, serde_json::Value) {
+    match request {
+        "status" => {
+            let daemon = DaemonStatus { pid: process::id(), status: status.clone() };
+            (None, serde_json::to_value(daemon).expect("status is always serializable"))
+        },
+        "run" => (Some(Control::Run), json!({ "ok": true })),
+        "stop" => (Some(Control::Stop), json!({ "ok": true })),
+        _ => (None, json!({ "ok": false, "error": format!("unknown request `{}`", request) }))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn endpoint_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(SOCKET_FILE)
+}
+
+#[cfg(target_os = "windows")]
+fn endpoint_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(ADDRESS_FILE)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn bind(project_dir: &Path) -> ::std::io::Result<::std::os::unix::net::UnixListener> {
+    let path = endpoint_path(project_dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // A socket left behind by a daemon which has been killed
+    if path.exists() {
+        fs::remove_file(&path)?;
+    }
+    ::std::os::unix::net::UnixListener::bind(&path)
+}
+
+#[cfg(target_os = "windows")]
+fn bind(project_dir: &Path) -> ::std::io::Result<::std::net::TcpListener> {
+    let listener = ::std::net::TcpListener::bind("127.0.0.1:0")?;
+    let path = endpoint_path(project_dir);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, listener.local_addr()?.to_string())?;
+    Ok(listener)
+}
+
+#[cfg(not(target_os = "windows"))]
+fn connect(project_dir: &Path) -> ::std::io::Result<Stream> {
+    Stream::connect(endpoint_path(project_dir))
+}
+
+#[cfg(target_os = "windows")]
+fn connect(project_dir: &Path) -> ::std::io::Result<Stream> {
+    let address = fs::read_to_string(endpoint_path(project_dir))?;
+    Stream::connect(address.trim())
+}
+
+/// Start the daemon in a new session, so it has no controlling terminal and survives its closing.
+#[cfg(not(target_os = "windows"))]
+fn detach(command: &mut Command) {
+    use libc;
+    use std::os::unix::process::CommandExt;
+
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+}
+
+/// Start the daemon without a console, in its own process group, so Ctrl+C does not reach it.
+#[cfg(target_os = "windows")]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    use winapi::um::winbase::{CREATE_NEW_PROCESS_GROUP, DETACHED_PROCESS};
+
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle() {
+        let (control, reply) = handle("status", &Some(Status::running()));
+        assert_eq!(control, None);
+        assert_eq!(reply["pid"], process::id());
+        assert_eq!(reply["status"]["state"], "running");
+
+        assert_eq!(handle("status", &None).1["status"], serde_json::Value::Null);
+        assert_eq!(handle("run", &None).0, Some(Control::Run));
+        assert_eq!(handle("stop", &None).0, Some(Control::Stop));
+        assert_eq!(handle("restart", &None), (None, json!({ "ok": false, "error": "unknown request `restart`" })));
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use filter::normalize;
+use manifest;
+
+/// Sections of the project's `Cargo.toml` whose path dependencies are watched. Dev-dependencies
+/// of the dependencies themselves are not, since they do not affect the tests of the project.
+const PROJECT_SECTIONS: &[&str] = &["dependencies", "dev-dependencies", "build-dependencies"];
+const DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "build-dependencies"];
+
+/// Directories of the crates the project depends on by path (`foo = { path = "../foo" }`),
+/// including the path dependencies of those crates, and the ones inherited from
+/// `[workspace.dependencies]` with `foo = { workspace = true }`.
+pub fn path_dependencies(project_dir: &Path) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = vec![];
+    let mut queue = vec![(project_dir.to_path_buf(), PROJECT_SECTIONS)];
+    while let Some((dir, sections)) = queue.pop() {
+        for dependency in dependency_dirs(&dir, sections) {
+            if dependency != project_dir && !found.contains(&dependency) {
+                found.push(dependency.clone());
+                queue.push((dependency, DEPENDENCY_SECTIONS));
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+/// Directories of the path dependencies listed in `sections` of the manifest in `dir`,
+/// also within `[target.'cfg(...)'.dependencies]`.
+fn dependency_dirs(dir: &Path, sections: &[&str]) -> Vec<PathBuf> {
+    let manifest = match manifest::read(dir) {
+        Some(manifest) => manifest,
+        None => return vec![]
+    };
+    let mut tables = vec![&manifest];
+    if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+        tables.extend(targets.values());
+    }
+    let mut dirs = vec![];
+    for table in tables {
+        for section in sections {
+            let dependencies = match table.get(*section).and_then(toml::Value::as_table) {
+                Some(dependencies) => dependencies,
+                None => continue
+            };
+            for (name, dependency) in dependencies {
+                if let Some(path) = dependency.get("path").and_then(toml::Value::as_str) {
+                    dirs.push(normalize(&dir.join(path)));
+                } else if dependency.get("workspace").and_then(toml::Value::as_bool) == Some(true) {
+                    dirs.extend(workspace_dependency_dir(dir, name));
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Directory of a path dependency declared in `[workspace.dependencies]` of the workspace `dir` belongs to.
+fn workspace_dependency_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    let root = manifest::workspace_root(dir)?;
+    let path = manifest::read(&root)?
+        .get("workspace")?
+        .get("dependencies")?
+        .get(name)?
+        .get("path")?
+        .as_str()
+        .map(String::from)?;
+    Some(normalize(&root.join(path)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_path_dependencies() {
+        let root = env::temp_dir().join("testify-dependencies-test");
+        let _ = fs::remove_dir_all(&root);
+        write(&root.join("Cargo.toml"), "[workspace]\nmembers = [\"app\"]\n[workspace.dependencies]\nshared = { path = \"libs/shared\" }\n");
+        write(&root.join("app/Cargo.toml"), "\
+[package]
+name = \"app\"
+
+[dependencies]
+regex = \"1\"
+core = { path = \"../core\" }
+shared = { workspace = true }
+
+[target.'cfg(unix)'.dev-dependencies]
+fixtures = { path = \"../fixtures\" }
+");
+        write(&root.join("core/Cargo.toml"), "\
+[dependencies]
+util = { path = \"../util\" }
+
+[dev-dependencies]
+mocks = { path = \"../mocks\" }
+");
+        write(&root.join("util/Cargo.toml"), "[dependencies]\ncore = { path = \"../core\" }\n");
+
+        let dependencies = path_dependencies(&root.join("app"));
+        let expected: Vec<PathBuf> = ["core", "fixtures", "libs/shared", "util"].iter().map(|dir| root.join(dir)).collect();
+        assert_eq!(dependencies, expected);
+        assert!(path_dependencies(&root.join("missing")).is_empty());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
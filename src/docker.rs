@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use config::Docker;
+
+/// Where the project is mounted in the container.
+const WORK_DIR: &str = "/work";
+
+/// Build output of the container is kept apart from the one of the host, as its binaries
+/// are built for another system.
+const TARGET_DIR: &str = "/work/target/docker";
+
+static CONTAINERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Name for a new container, unique for this process, so the container can be stopped
+/// when its command is cancelled.
+pub fn container_name() -> String {
+    format!("testify-{}-{}", process::id(), CONTAINERS.fetch_add(1, Ordering::SeqCst))
+}
+
+/// Command line running `argv` with `env` in a new container of the configured image,
+/// with the project mounted in it. With `pty` a terminal is allocated, so it prints colors.
+pub fn command_line(docker: &Docker, project_dir: &Path, name: &str, argv: &[String], env: &[(&str, &str)], pty: bool) -> Vec<String> {
+    let mut command_line: Vec<String> = vec!["docker", "run", "--rm", "--name", name].into_iter().map(String::from).collect();
+    if pty {
+        command_line.push("-t".to_string());
+    }
+    command_line.push("-v".to_string());
+    command_line.push(format!("{}:{}", project_dir.display(), WORK_DIR));
+    command_line.push("-w".to_string());
+    command_line.push(WORK_DIR.to_string());
+    command_line.extend(user());
+    command_line.push("-e".to_string());
+    command_line.push(format!("CARGO_TARGET_DIR={}", TARGET_DIR));
+    for &(name, value) in env {
+        command_line.push("-e".to_string());
+        command_line.push(format!("{}={}", name, value));
+    }
+    command_line.extend(docker.args.iter().flat_map(|args| args.iter().cloned()));
+    command_line.push(docker.image.clone().unwrap_or_default());
+    command_line.extend(argv.iter().cloned());
+    command_line
+}
+
+/// Remove the container, once its command has been killed. Killing `docker run` does not stop it.
+pub fn remove(name: &str) {
+    let _ = Command::new("docker").args(["rm", "--force", name])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Run as the user of testify, so the files created in the project are not owned by root.
+#[cfg(not(target_os = "windows"))]
+fn user() -> Vec<String> {
+    use libc;
+
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    vec!["--user".to_string(), format!("{}:{}", uid, gid)]
+}
+
+/// Docker Desktop maps the owner of mounted files itself.
+#[cfg(target_os = "windows")]
+fn user() -> Vec<String> {
+    vec![]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let docker = Docker { image: Some("rust:1.80".to_string()), args: Some(vec!["--network=none".to_string()]) };
+        let argv = vec!["cargo".to_string(), "test".to_string()];
+        let command_line = command_line(&docker, Path::new("/home/me/app"), "testify-1-0", &argv, &[("RUST_LOG", "debug")], true);
+        assert_eq!(&command_line[..6], &["docker", "run", "--rm", "--name", "testify-1-0", "-t"]);
+        assert_eq!(&command_line[6..10], &["-v", "/home/me/app:/work", "-w", "/work"]);
+        assert_eq!(&command_line[command_line.len() - 8..], &[
+            "-e", "CARGO_TARGET_DIR=/work/target/docker", "-e", "RUST_LOG=debug", "--network=none", "rust:1.80", "cargo", "test"
+        ]);
+    }
+}
@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use config::{Config, NotifierKind, Runner};
+use notifier;
+use report::{Outcome, Report};
+
+/// Watches below this number are likely to run out on a bigger project.
+#[cfg(target_os = "linux")]
+const MIN_INOTIFY_WATCHES: u64 = 8192;
+
+/// Result of a single check, with what to do about a problem.
+#[derive(Debug, PartialEq)]
+enum Status {
+    Ok(String),
+    /// Testify works, but not as well as it could
+    Warning(String, String),
+    /// Testify does not work as configured
+    Error(String, String)
+}
+
+/// A tool testify runs, and when it's needed.
+struct Tool {
+    name: &'static str,
+    /// Command line printing its version
+    version: &'static [&'static str],
+    install: &'static str,
+    /// The configuration needs it
+    required: bool
+}
+
+/// Check the environment testify runs in, print the results with fixes for the problems,
+/// and return the exit code: 1 if anything required is broken.
+pub fn run(config: &Config) -> i32 {
+    let mut checks: Vec<(String, Status)> = config.notifiers.iter()
+        .map(|&kind| (format!("notifier {}", kind.name()), check_notifier(kind, config)))
+        .collect();
+    #[cfg(target_os = "linux")]
+    checks.push(("inotify".to_string(), check_inotify(&config.project_dir)));
+    for tool in tools(config) {
+        checks.push((tool.name.to_string(), check_tool(&tool)));
+    }
+
+    let mut failed = false;
+    for (name, status) in checks {
+        match status {
+            Status::Ok(message) => println!("[ok]      {}: {}", name, message),
+            Status::Warning(message, fix) => println!("[warning] {}: {}\n          fix: {}", name, message, fix),
+            Status::Error(message, fix) => {
+                failed = true;
+                println!("[error]   {}: {}\n          fix: {}", name, message, fix);
+            }
+        }
+    }
+    if failed { 1 } else { 0 }
+}
+
+/// Send a test notification with one of the configured notifiers.
+fn check_notifier(kind: NotifierKind, config: &Config) -> Status {
+    let notifier = match notifier::obtain(kind, config) {
+        Ok(notifier) => notifier,
+        Err(err) => return Status::Error(err.to_string(), "set the missing setting in .testify.toml".to_string())
+    };
+    let report = Report::new(Outcome::TestsPassed, Some("Test notification from cargo testify doctor".to_string()));
+    if let Err(err) = notifier.notify(&report) {
+        return Status::Error(err.to_string(), "check the settings of the notifier, or choose another one with --notifier".to_string());
+    }
+    match kind {
+        NotifierKind::Auto if !notifier::desktop_available() => Status::Ok(
+            "no desktop session found, a test notification has been sent to the terminal".to_string()
+        ),
+        NotifierKind::Desktop if !notifier::desktop_available() => Status::Warning(
+            "no desktop session found, notifications fall back to the terminal if they fail".to_string(),
+            "set DBUS_SESSION_BUS_ADDRESS, or use `--notifier auto`".to_string()
+        ),
+        NotifierKind::Disabled => Status::Ok("disabled".to_string()),
+        _ => Status::Ok("a test notification has been sent, check that it has arrived".to_string())
+    }
+}
+
+/// Every watched directory takes an inotify watch, and watching fails once they run out.
+#[cfg(target_os = "linux")]
+fn check_inotify(project_dir: &Path) -> Status {
+    let limit = fs::read_to_string("/proc/sys/fs/inotify/max_user_watches").ok()
+        .and_then(|limit| limit.trim().parse().ok());
+    match limit {
+        Some(limit) => inotify_status(limit, count_dirs(project_dir)),
+        None => Status::Warning("could not read the watch limit".to_string(), "check /proc/sys/fs/inotify/max_user_watches".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn inotify_status(limit: u64, dirs: u64) -> Status {
+    let fix = "sudo sysctl fs.inotify.max_user_watches=524288".to_string();
+    if dirs >= limit {
+        Status::Error(format!("the project has {} directories, but only {} can be watched", dirs, limit), fix)
+    } else if limit < MIN_INOTIFY_WATCHES {
+        Status::Warning(format!("only {} directories can be watched by all the programs together", limit), fix)
+    } else {
+        Status::Ok(format!("{} of {} watches needed", dirs, limit))
+    }
+}
+
+/// Number of directories in the project, without `target` and `.git`, which are not watched for changes.
+#[cfg(target_os = "linux")]
+fn count_dirs(dir: &Path) -> u64 {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 1
+    };
+    1 + entries.filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false))
+        .filter(|entry| entry.file_name() != "target" && entry.file_name() != ".git")
+        .map(|entry| count_dirs(&entry.path()))
+        .sum::<u64>()
+}
+
+/// Cargo, and the optional tools, marked as required when the configuration uses them.
+fn tools(config: &Config) -> Vec<Tool> {
+    vec![
+        Tool { name: "cargo", version: &["cargo", "--version"], install: "install Rust from https://rustup.rs", required: true },
+        Tool { name: "cargo-nextest", version: &["cargo", "nextest", "--version"], install: "cargo install cargo-nextest", required: config.runner == Runner::Nextest },
+        Tool { name: "cargo-llvm-cov", version: &["cargo", "llvm-cov", "--version"], install: "cargo install cargo-llvm-cov", required: config.coverage },
+        Tool { name: "cargo-mutants", version: &["cargo", "mutants", "--version"], install: "cargo install cargo-mutants", required: config.mutants },
+        Tool { name: "miri", version: &["cargo", "+nightly", "miri", "--version"], install: "rustup +nightly component add miri", required: config.miri },
+        Tool { name: "wasm-pack", version: &["wasm-pack", "--version"], install: "cargo install wasm-pack", required: config.runner == Runner::WasmPack },
+        Tool { name: "cross", version: &["cross", "--version"], install: "cargo install cross", required: config.cross }
+    ]
+}
+
+fn check_tool(tool: &Tool) -> Status {
+    let output = Command::new(tool.version[0])
+        .args(&tool.version[1..])
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(ref output) if output.status.success() => {
+            let version = String::from_utf8_lossy(&output.stdout);
+            Status::Ok(version.lines().next().unwrap_or("").trim().to_string())
+        },
+        _ if tool.required => Status::Error("not found, but the configuration uses it".to_string(), tool.install.to_string()),
+        _ => Status::Ok(format!("not installed (optional, `{}` to use it)", tool.install))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_inotify_status() {
+        assert_eq!(inotify_status(524288, 120), Status::Ok("120 of 524288 watches needed".to_string()));
+        match inotify_status(4096, 120) {
+            Status::Warning(_, fix) => assert_eq!(fix, "sudo sysctl fs.inotify.max_user_watches=524288"),
+            status => panic!("unexpected {:?}", status)
+        }
+        match inotify_status(8192, 9000) {
+            Status::Error(message, _) => assert_eq!(message, "the project has 9000 directories, but only 8192 can be watched"),
+            status => panic!("unexpected {:?}", status)
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use errors::*;
+
+/// Variables of the `.env` file at `path`, none if there is no such file.
+pub fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err).chain_err(|| ErrorKind::EnvFile(path.to_path_buf()))
+    };
+    parse(&content).map_err(|line| ErrorKind::InvalidEnvFile(path.to_path_buf(), line).into())
+}
+
+/// Parse `NAME=value` lines, optionally prefixed with `export`. Blank lines and lines starting with `#`
+/// are skipped. Values may be quoted: escapes (`\n`, `\"`) work within double quotes only, and an
+/// unquoted value ends before ` #`. Variables are not expanded. Returns the number of an invalid line.
+fn parse(content: &str) -> ::std::result::Result<Vec<(String, String)>, usize> {
+    let mut variables = vec![];
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') { continue; }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let mut parts = line.splitn(2, '=');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().ok_or(index + 1)?.trim();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            return Err(index + 1);
+        }
+        let value = unquote(value).ok_or(index + 1)?;
+        variables.push((name.to_string(), value));
+    }
+    Ok(variables)
+}
+
+/// The value without quotes, `None` if a quote is not closed.
+fn unquote(value: &str) -> Option<String> {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted.find('\'')?;
+        return Some(quoted[..end].to_string());
+    }
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut unquoted = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => return Some(unquoted),
+                '\\' => match chars.next()? {
+                    'n' => unquoted.push('\n'),
+                    't' => unquoted.push('\t'),
+                    other => unquoted.push(other)
+                },
+                _ => unquoted.push(c)
+            }
+        }
+        return None;
+    }
+    let value = value.find(" #").map_or(value, |index| &value[..index]);
+    Some(value.trim_end().to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse() {
+        let content = r#"
+# Local settings
+DATABASE_URL=postgres://localhost/app_test
+export RUST_LOG = debug # the tests are quiet otherwise
+GREETING="Hello,\n\"world\""
+PATTERN='a\nb # c'
+EMPTY=
+"#;
+        assert_eq!(parse(content), Ok(vec![
+            variable("DATABASE_URL", "postgres://localhost/app_test"),
+            variable("RUST_LOG", "debug"),
+            variable("GREETING", "Hello,\n\"world\""),
+            variable("PATTERN", "a\\nb # c"),
+            variable("EMPTY", "")
+        ]));
+        assert_eq!(parse("A=1\nnot a variable\n"), Err(2));
+        assert_eq!(parse("TOKEN=\"unclosed\n"), Err(1));
+    }
+}
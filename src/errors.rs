@@ -1,5 +1,139 @@
+use std::path::PathBuf;
+
 error_chain! {
     errors {
         ProjectDirMissing { description("project directory is missing") }
+        ConfigFile(path: PathBuf) {
+            description("failed to load configuration file")
+            display("failed to load configuration file {:?}", path)
+        }
+        ConfigExists(path: PathBuf) {
+            description("configuration file already exists")
+            display("configuration file {:?} already exists", path)
+        }
+        UnknownNotifier(name: String) {
+            description("unknown notifier")
+            display("unknown notifier `{}`, expected `auto`, `desktop`, `slack`, `discord`, `telegram`, `email`, `ntfy`, `gotify`, `mqtt`, `exec`, `http`, `console` or `none`", name)
+        }
+        SettingMissing(name: &'static str) {
+            description("required setting is missing")
+            display("setting `{}` is required by the chosen notifier or runner", name)
+        }
+        UnknownNotifyMode(name: String) {
+            description("unknown notify mode")
+            display("unknown notify mode `{}`, expected `always` or `changes`", name)
+        }
+        UnknownFmtMode(name: String) {
+            description("unknown fmt mode")
+            display("unknown fmt mode `{}`, expected `off`, `check` or `fix`", name)
+        }
+        UnknownRunner(name: String) {
+            description("unknown test runner")
+            display("unknown test runner `{}`, expected `cargo`, `nextest`, `ssh`, `docker` or `wasm-pack`", name)
+        }
+        UnknownWasmEnv(name: String) {
+            description("unknown WASM environment")
+            display("unknown WASM environment `{}`, expected `chrome`, `firefox`, `safari` or `node`", name)
+        }
+        UnknownOutputFormat(name: String) {
+            description("unknown output format")
+            display("unknown output format `{}`, expected `human` or `json`", name)
+        }
+        UnknownAnnotationFormat(name: String) {
+            description("unknown annotation format")
+            display("unknown annotation format `{}`, expected `github`", name)
+        }
+        UnknownOutcome(name: String) {
+            description("unknown outcome")
+            display("unknown outcome `{}` in outcome_rules, expected `passed`, `failed`, `compile_error`, `format_error`, `undefined_behavior`, `timed_out` or `no_tests`", name)
+        }
+        UnknownRouteOutcome(notifier: &'static str, name: String) {
+            description("unknown outcome")
+            display("unknown outcome `{}` in notify_on.{}, expected `failures`, `successes` or the name of an outcome, e.g. `compile_error`", name, notifier)
+        }
+        UnknownPriorityOutcome(section: &'static str, name: String) {
+            description("unknown outcome")
+            display("unknown outcome `{}` in {}.priority, expected `failures`, `successes` or the name of an outcome, e.g. `compile_error`", name, section)
+        }
+        InvalidOutcomeRule(pattern: String, reason: String) {
+            description("invalid outcome rule")
+            display("invalid pattern `{}` in outcome_rules: {}", pattern, reason)
+        }
+        InvalidSetting(section: &'static str, reason: String) {
+            description("invalid setting")
+            display("invalid setting in [{}] section: {}", section, reason)
+        }
+        SecretStore(name: String, reason: String) {
+            description("failed to access the credential store")
+            display("failed to access secret `{}` in the credential store: {}", name, reason)
+        }
+        InvalidDuration(text: String) {
+            description("invalid duration")
+            display("invalid duration `{}`, expected e.g. `500ms`, `1s` or `2m`", text)
+        }
+        HistoryFile(path: PathBuf) {
+            description("failed to access history file")
+            display("failed to access history file {:?}", path)
+        }
+        StatusFile(path: PathBuf) {
+            description("failed to write status file")
+            display("failed to write status file {:?}", path)
+        }
+        StatsFile(path: PathBuf) {
+            description("failed to access test statistics file")
+            display("failed to access test statistics file {:?}", path)
+        }
+        JunitFile(path: PathBuf) {
+            description("failed to write JUnit report")
+            display("failed to write JUnit report {:?}", path)
+        }
+        DaemonRunning {
+            description("daemon is already running")
+            display("a daemon is already running for this project, stop it with `cargo testify stop`")
+        }
+        DaemonNotRunning {
+            description("daemon is not running")
+            display("no daemon is running for this project, start it with `cargo testify daemon`")
+        }
+        DaemonFailed(reason: String) {
+            description("daemon has failed")
+            display("daemon has failed: {}", reason)
+        }
+        ServerFailed(address: String) {
+            description("failed to start HTTP server")
+            display("failed to start HTTP server on {}", address)
+        }
+        NotificationFailed(reason: String) {
+            description("failed to send notification")
+            display("failed to send notification: {}", reason)
+        }
+        NotifiersFailed(failures: String) {
+            description("some notifiers have failed")
+            display("{}", failures)
+        }
+        SeedsFile(path: PathBuf) {
+            description("failed to access recorded failing cases")
+            display("failed to access recorded failing cases {:?}", path)
+        }
+        TestListFailed(reason: String) {
+            description("failed to list the tests")
+            display("failed to list the tests: {}", reason)
+        }
+        EnvFile(path: PathBuf) {
+            description("failed to read env file")
+            display("failed to read env file {:?}", path)
+        }
+        InvalidEnvFile(path: PathBuf, line: usize) {
+            description("invalid env file")
+            display("invalid line {} of env file {:?}, expected `NAME=value`", line, path)
+        }
+        UnknownEnvProfile(name: String) {
+            description("unknown env profile")
+            display("unknown env profile `{}`, expected a section [env.{}] in .testify.toml", name, name)
+        }
+        ChangesUnknown(reason: String) {
+            description("failed to find out what has changed")
+            display("failed to find out what has changed: {}", reason)
+        }
     }
 }
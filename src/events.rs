@@ -0,0 +1,114 @@
+use serde_json::Value;
+
+use report::{Outcome, Report};
+
+/// A test run has started. `trigger` is `start`, `change` or `key`, `file` is the changed file.
+pub fn run_started(run: u32, trigger: &str, file: Option<&str>, command: &str) {
+    emit(json!({
+        "event": "run_started",
+        "run": run,
+        "trigger": trigger,
+        "file": file,
+        "command": command
+    }));
+}
+
+/// A line printed by the test command to stdout.
+pub fn stdout_line(line: &str) {
+    emit(json!({
+        "event": "stdout_line",
+        "line": line
+    }));
+}
+
+/// A test run has finished and the report has been built.
+pub fn run_finished(run: u32, report: &Report) {
+    emit(json!({
+        "event": "run_finished",
+        "run": run,
+        "report": report_json(report)
+    }));
+}
+
+/// Print the event as a single line, so it can be read line by line.
+fn emit(event: Value) {
+    println!("{}", event);
+}
+
+/// The report as included in events, also served by the HTTP server.
+pub fn report_json(report: &Report) -> Value {
+    let warnings = match report.outcome {
+        Outcome::TestsPassedWithWarnings(count) => count,
+        _ => 0
+    };
+    let failures: Vec<Value> = report.failures.iter().map(|failure| json!({
+        "name": failure.name,
+        "message": failure.message
+    })).collect();
+    let counts = report.counts.map(|counts| json!({
+        "passed": counts.passed,
+        "failed": counts.failed,
+        "ignored": counts.ignored
+    }));
+    let error_location = report.error_location.as_ref().map(|location| json!({
+        "file": location.file,
+        "line": location.line,
+        "column": location.column
+    }));
+    let lints = report.lints.map(|lints| json!({
+        "warnings": lints.warnings,
+        "errors": lints.errors
+    }));
+    let variants: Vec<Value> = report.variants.iter().map(|variant| json!({
+        "name": variant.name,
+        "outcome": variant.outcome.name()
+    })).collect();
+    let duration_ms = report.duration.map(|duration| duration.as_secs() * 1000 + u64::from(duration.subsec_millis()));
+    json!({
+        "outcome": report.outcome.name(),
+        "success": report.outcome.is_success(),
+        "title": report.title(),
+        "body": report.body(),
+        "project": report.project,
+        "warnings": warnings,
+        "failures": failures,
+        "counts": counts,
+        "stage": report.stage,
+        "variants": variants,
+        "flaky": report.flaky,
+        "snapshots": report.snapshots,
+        "coverage": report.coverage,
+        "coverage_delta": report.coverage_delta,
+        "lints": lints,
+        "error_location": error_location,
+        "duration_ms": duration_ms,
+        "exit_code": report.exit_code
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use report::{TestCounts, TestFailure};
+
+    #[test]
+    fn test_report_json() {
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.failures.push(TestFailure { name: "tests::test_parse".to_string(), message: Some("boom".to_string()), location: None });
+        report.counts = Some(TestCounts { passed: 2, failed: 1, ignored: 0 });
+        report.duration = Some(Duration::from_millis(1500));
+        report.exit_code = Some(101);
+
+        let value = report_json(&report);
+        assert_eq!(value["outcome"], "failed");
+        assert_eq!(value["success"], false);
+        assert_eq!(value["failures"][0]["name"], "tests::test_parse");
+        assert_eq!(value["failures"][0]["message"], "boom");
+        assert_eq!(value["counts"]["passed"], 2);
+        assert_eq!(value["duration_ms"], 1500);
+        assert_eq!(value["exit_code"], 101);
+        assert_eq!(value["error_location"], Value::Null);
+    }
+}
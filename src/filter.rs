@@ -0,0 +1,463 @@
+use std::path::{Component, Path, PathBuf};
+
+use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+
+/// Files and directories which always trigger a test run.
+const FILES: &[&str] = &[
+    "src",
+    "tests",
+    "Cargo.toml",
+    "Cargo.lock",
+    "build.rs",
+];
+
+/// Endings of the names of files editors keep next to the edited ones: swap files of vim and kate,
+/// backups of vim and emacs, and the files JetBrains IDEs write during a save.
+const EDITOR_SUFFIXES: &[&str] = &[".swp", ".swo", ".swx", "~", "___jb_tmp___", "___jb_old___", ".kate-swp"];
+
+/// Beginnings of the names of editor files: lock files of emacs, and temporary files of gedit.
+const EDITOR_PREFIXES: &[&str] = &[".#", ".goutputstream-"];
+
+/// File in the project root with gitignore-style patterns of files which never trigger a test run,
+/// meant to be committed along with the code.
+pub const IGNORE_FILE_NAME: &str = ".testifyignore";
+
+/// Directories which never trigger a test run, even if they are not ignored by git.
+pub const IGNORED_DIRS: &[&str] = &[
+    ".git",
+    ".testify",
+    "target",
+];
+
+/// Decides whether a change of a file should trigger running the test suite.
+///
+/// Files matched by the `ignore` setting, `.testifyignore` or `.gitignore` files in directories below the project root, and temporary
+/// files of editors are never allowed. Files from the built-in list,
+/// from `watch` setting and the ones matched by `watch_patterns` are always allowed otherwise. If the project is within
+/// a git repository, any other file is allowed too, unless it's matched by `.gitignore` or located in `.git/`, `.testify/`
+/// or `target/`.
+pub struct PathFilter {
+    project_dir: PathBuf,
+    /// Absolute paths of the `watch` entries
+    watch: Vec<PathBuf>,
+    /// `None` when the project is not within a git repository
+    gitignores: Option<Vec<Gitignore>>,
+    /// `.gitignore` files of the directories below the project directory
+    nested_gitignores: Vec<Gitignore>,
+    /// Patterns of the `ignore` setting
+    ignore: Option<Gitignore>,
+    /// Path of `.testifyignore`, which is read again when it changes
+    ignore_file_path: Option<PathBuf>,
+    /// Patterns of `.testifyignore`
+    ignore_file: Option<Gitignore>,
+    /// Globs of the `watch_patterns` setting
+    watch_patterns: Option<Override>
+}
+
+impl PathFilter {
+    /// `watch` entries are relative to the project directory, and may point outside of it (`../shared`).
+    pub fn new(project_dir: PathBuf, watch: Vec<String>, gitignores: Option<Vec<Gitignore>>) -> Self {
+        let watch = watch.iter().map(|entry| normalize(&project_dir.join(entry))).collect();
+        Self { project_dir, watch, gitignores, nested_gitignores: vec![], ignore: None, ignore_file_path: None, ignore_file: None, watch_patterns: None }
+    }
+
+    /// Never allow files matched by `.gitignore` files of the directories below the project directory.
+    pub fn nested_gitignores(mut self, gitignores: Vec<Gitignore>) -> Self {
+        self.nested_gitignores = gitignores;
+        self
+    }
+
+    /// Never allow files matched by gitignore-style `patterns`, relative to the project directory.
+    pub fn ignore(mut self, patterns: &[String]) -> Self {
+        if patterns.is_empty() { return self; }
+        let mut builder = GitignoreBuilder::new(&self.project_dir);
+        for pattern in patterns {
+            if let Err(err) = builder.add_line(None, pattern) {
+                eprintln!("Warning: invalid pattern {:?} in ignore: {}", pattern, err);
+            }
+        }
+        match builder.build() {
+            Ok(ignore) => self.ignore = Some(ignore),
+            Err(err) => eprintln!("Warning: failed to use ignore patterns: {}", err)
+        }
+        self
+    }
+
+    /// Never allow files matched by the patterns of a gitignore-style `file`, if it exists.
+    pub fn ignore_file(mut self, file: &Path) -> Self {
+        self.ignore_file_path = Some(file.to_path_buf());
+        self.load_ignore_file();
+        self
+    }
+
+    /// Read the ignore file again if `path` is that file. Returns whether it was.
+    pub fn reload_ignore_file(&mut self, path: &Path) -> bool {
+        if self.ignore_file_path.as_deref() != Some(path) { return false; }
+        self.load_ignore_file();
+        true
+    }
+
+    fn load_ignore_file(&mut self) {
+        self.ignore_file = match self.ignore_file_path {
+            Some(ref file) if file.is_file() => {
+                let (ignore_file, err) = Gitignore::new(file);
+                if let Some(err) = err {
+                    eprintln!("Warning: failed to parse {:?}: {}", file, err);
+                }
+                Some(ignore_file)
+            },
+            _ => None
+        };
+    }
+
+    /// Always allow files matched by `patterns`, globs relative to the project directory, unless they are ignored.
+    pub fn watch_patterns(mut self, patterns: &[String]) -> Self {
+        if patterns.is_empty() { return self; }
+        let mut builder = OverrideBuilder::new(&self.project_dir);
+        for pattern in patterns {
+            if let Err(err) = builder.add(pattern) {
+                eprintln!("Warning: invalid glob {:?} in watch_patterns: {}", pattern, err);
+            }
+        }
+        match builder.build() {
+            Ok(watch_patterns) => self.watch_patterns = Some(watch_patterns),
+            Err(err) => eprintln!("Warning: failed to use watch patterns: {}", err)
+        }
+        self
+    }
+
+    /// Build a filter for the project, loading `.gitignore` files if the project is
+    /// within a git repository, and `.testifyignore` of the project.
+    pub fn detect(project_dir: &Path, watch: &[String], ignore: &[String]) -> Self {
+        let repo_root = find_repo_root(project_dir);
+        let gitignores = repo_root.as_ref().map(|root| load_gitignores(root, project_dir));
+        let nested_gitignores = if repo_root.is_some() { load_nested_gitignores(project_dir) } else { vec![] };
+        Self::new(project_dir.to_path_buf(), watch.to_vec(), gitignores)
+            .nested_gitignores(nested_gitignores)
+            .ignore(ignore)
+            .ignore_file(&project_dir.join(IGNORE_FILE_NAME))
+    }
+
+    /// Should changes in `path` file trigger running the test suite?
+    pub fn allows(&self, path: &Path) -> bool {
+        if is_editor_file(path) { return false; }
+        let mut ignores = self.ignore.iter().chain(self.ignore_file.iter()).chain(self.nested_gitignores.iter());
+        if ignores.any(|ignore| is_ignored(ignore, path)) {
+            return false;
+        }
+        if self.is_listed(path) || self.matches_watch_pattern(path) { return true; }
+
+        match self.gitignores {
+            Some(ref gitignores) => {
+                path.starts_with(&self.project_dir) &&
+                    !self.is_in_ignored_dir(path) &&
+                    !gitignores.iter().any(|gitignore| is_ignored(gitignore, path))
+            },
+            None => false
+        }
+    }
+
+    /// `watch` entries outside of the project directory, which have to be watched separately.
+    pub fn external_paths(&self) -> Vec<&Path> {
+        self.watch.iter()
+            .filter(|path| !path.starts_with(&self.project_dir))
+            .map(PathBuf::as_path)
+            .collect()
+    }
+
+    fn is_listed(&self, path: &Path) -> bool {
+        FILES.iter().any(|file| path.starts_with(self.project_dir.join(file))) ||
+            self.watch.iter().any(|watched| path.starts_with(watched))
+    }
+
+    fn matches_watch_pattern(&self, path: &Path) -> bool {
+        match self.watch_patterns {
+            Some(ref watch_patterns) => path.starts_with(&self.project_dir) && watch_patterns.matched(path, false).is_whitelist(),
+            None => false
+        }
+    }
+
+    fn is_in_ignored_dir(&self, path: &Path) -> bool {
+        IGNORED_DIRS.iter().any(|dir| path.starts_with(self.project_dir.join(dir)))
+    }
+}
+
+/// Is it a file an editor writes while a file is edited or saved, e.g. `.main.rs.swp` or `#main.rs#`?
+/// vim also creates and deletes `4913` to check whether it can write into a directory.
+fn is_editor_file(path: &Path) -> bool {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return false
+    };
+    name == "4913" ||
+        (name.len() > 1 && name.starts_with('#') && name.ends_with('#')) ||
+        EDITOR_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) ||
+        EDITOR_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+fn is_ignored(gitignore: &Gitignore, path: &Path) -> bool {
+    // Gitignore panics on paths outside of its root
+    if !path.starts_with(gitignore.path()) { return false; }
+    gitignore.matched_path_or_any_parents(path, path.is_dir()).is_ignore()
+}
+
+/// Resolve `.` and `..` components without touching the file system,
+/// so the path can be compared with paths reported by the watcher.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => { normalized.pop(); },
+            other => normalized.push(other.as_os_str())
+        }
+    }
+    normalized
+}
+
+/// Find the root of the git repository which contains `dir`.
+pub fn find_repo_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Load `.gitignore` files of every directory from the repository root down to the project
+/// directory, and `.git/info/exclude` of the repository.
+fn load_gitignores(repo_root: &Path, project_dir: &Path) -> Vec<Gitignore> {
+    let mut gitignores = vec![];
+
+    let mut builder = GitignoreBuilder::new(repo_root);
+    builder.add(repo_root.join(".git/info/exclude"));
+    if let Ok(gitignore) = builder.build() {
+        gitignores.push(gitignore);
+    }
+
+    let dirs = project_dir.ancestors().take_while(|dir| dir.starts_with(repo_root));
+    for dir in dirs {
+        let file = dir.join(".gitignore");
+        if !file.is_file() { continue; }
+        let (gitignore, err) = Gitignore::new(&file);
+        if let Some(err) = err {
+            eprintln!("Warning: failed to parse {:?}: {}", file, err);
+        }
+        gitignores.push(gitignore);
+    }
+    gitignores
+}
+
+/// Load `.gitignore` files of the directories below the project directory, e.g. `tests/fixtures/.gitignore`.
+/// Directories which are ignored already are not searched.
+fn load_nested_gitignores(project_dir: &Path) -> Vec<Gitignore> {
+    let walker = WalkBuilder::new(project_dir)
+        .hidden(false)
+        .filter_entry(|entry| !IGNORED_DIRS.iter().any(|dir| entry.file_name() == *dir))
+        .build();
+    walker
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.depth() > 1 && entry.file_name() == ".gitignore")
+        .map(|entry| {
+            let (gitignore, err) = Gitignore::new(entry.path());
+            if let Some(err) = err {
+                eprintln!("Warning: failed to parse {:?}: {}", entry.path(), err);
+            }
+            gitignore
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    const PROJECT_DIR: &str = "/project";
+
+    fn whitelist_filter(watch: &[&str]) -> PathFilter {
+        let watch = watch.iter().map(|w| w.to_string()).collect();
+        PathFilter::new(PathBuf::from(PROJECT_DIR), watch, None)
+    }
+
+    fn git_filter(gitignore_lines: &[&str]) -> PathFilter {
+        let mut builder = GitignoreBuilder::new(PROJECT_DIR);
+        for line in gitignore_lines {
+            builder.add_line(None, line).unwrap();
+        }
+        let gitignores = vec![builder.build().unwrap()];
+        PathFilter::new(PathBuf::from(PROJECT_DIR), vec![], Some(gitignores))
+    }
+
+    fn must_allow(filter: &PathFilter, path: &str) {
+        assert!(filter.allows(Path::new(path)), "must allow {}", path);
+    }
+
+    fn must_not_allow(filter: &PathFilter, path: &str) {
+        assert!(!filter.allows(Path::new(path)), "must not allow {}", path);
+    }
+
+    #[test]
+    fn test_whitelist() {
+        let filter = whitelist_filter(&[]);
+
+        must_allow(&filter, "/project/src/main.rs");
+        must_allow(&filter, "/project/src/lib/os.rs");
+        must_allow(&filter, "/project/tests/watch.rs");
+        must_allow(&filter, "/project/Cargo.toml");
+        must_allow(&filter, "/project/Cargo.lock");
+        must_allow(&filter, "/project/build.rs");
+
+        must_not_allow(&filter, "/project/README.md");
+        must_not_allow(&filter, "/tmp/file.rs");
+        must_not_allow(&filter, "/tmp/src/file.rs");
+    }
+
+    #[test]
+    fn test_whitelist_with_watch() {
+        let filter = whitelist_filter(&["templates", "migrations/", ".env", "../shared/fixtures"]);
+
+        must_allow(&filter, "/project/templates/index.html");
+        must_allow(&filter, "/project/migrations/001_init.sql");
+        must_allow(&filter, "/project/.env");
+        must_allow(&filter, "/shared/fixtures/users.json");
+        must_allow(&filter, "/project/src/main.rs");
+
+        must_not_allow(&filter, "/project/README.md");
+        must_not_allow(&filter, "/project/.envrc");
+        must_not_allow(&filter, "/shared/other/users.json");
+
+        assert_eq!(filter.external_paths(), vec![Path::new("/shared/fixtures")]);
+    }
+
+    #[test]
+    fn test_gitignore() {
+        let filter = git_filter(&["*.log", "/generated"]);
+
+        must_allow(&filter, "/project/src/main.rs");
+        must_allow(&filter, "/project/README.md");
+        must_allow(&filter, "/project/examples/demo.rs");
+
+        must_not_allow(&filter, "/project/debug.log");
+        must_not_allow(&filter, "/project/generated/schema.rs");
+        must_not_allow(&filter, "/project/target/debug/build.rs");
+        must_not_allow(&filter, "/project/.testify/history.jsonl");
+        must_not_allow(&filter, "/project/.git/index");
+        must_not_allow(&filter, "/tmp/file.rs");
+    }
+
+    #[test]
+    fn test_nested_gitignore() {
+        let dir = env::temp_dir().join("testify-nested-gitignore-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("tests/fixtures")).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(dir.join("src/.gitignore"), "generated.rs\n").unwrap();
+        fs::write(dir.join("tests/fixtures/.gitignore"), "*.out\n").unwrap();
+        let filter = PathFilter::detect(&dir, &[], &[]);
+
+        assert!(filter.allows(&dir.join("src/main.rs")));
+        assert!(filter.allows(&dir.join("tests/fixtures/input.txt")));
+        assert!(!filter.allows(&dir.join("src/generated.rs")));
+        assert!(!filter.allows(&dir.join("tests/fixtures/parser.out")));
+        assert!(!filter.allows(&dir.join("debug.log")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_ignore() {
+        let filter = git_filter(&[]).ignore(&["src/generated/**".to_string(), "*.snap".to_string()]);
+
+        must_allow(&filter, "/project/src/main.rs");
+        must_allow(&filter, "/project/README.md");
+
+        must_not_allow(&filter, "/project/src/generated/schema.rs");
+        must_not_allow(&filter, "/project/tests/snapshots/parser.snap");
+    }
+
+    #[test]
+    fn test_ignore_file() {
+        let dir = env::temp_dir().join("testify-ignore-file-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(IGNORE_FILE_NAME), "# Large data sets\ntests/data/\n*.bin\n").unwrap();
+        let filter = PathFilter::new(dir.clone(), vec![], None).ignore_file(&dir.join(IGNORE_FILE_NAME));
+
+        assert!(filter.allows(&dir.join("tests/parser.rs")));
+        assert!(!filter.allows(&dir.join("tests/data/users.json")));
+        assert!(!filter.allows(&dir.join("src/model.bin")));
+
+        let filter = PathFilter::new(dir.clone(), vec![], None).ignore_file(&dir.join("missing"));
+        assert!(filter.allows(&dir.join("src/model.bin")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_ignore_file() {
+        let dir = env::temp_dir().join("testify-reload-ignore-file-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(IGNORE_FILE_NAME);
+        fs::write(&file, "*.bin\n").unwrap();
+        let mut filter = PathFilter::new(dir.clone(), vec![], None).ignore_file(&file);
+        assert!(filter.allows(&dir.join("tests/data/users.json")));
+
+        fs::write(&file, "*.bin\ntests/data/\n").unwrap();
+        assert!(!filter.reload_ignore_file(&dir.join("src/main.rs")));
+        assert!(filter.reload_ignore_file(&file));
+        assert!(!filter.allows(&dir.join("tests/data/users.json")));
+
+        fs::remove_file(&file).unwrap();
+        assert!(filter.reload_ignore_file(&file));
+        assert!(filter.allows(&dir.join("src/model.bin")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_patterns() {
+        let patterns = vec!["**/*.graphql".to_string(), "data/*.csv".to_string()];
+        let filter = whitelist_filter(&[]).watch_patterns(&patterns).ignore(&["schema/old/".to_string()]);
+
+        must_allow(&filter, "/project/schema/user.graphql");
+        must_allow(&filter, "/project/data/users.csv");
+        must_allow(&filter, "/project/src/main.rs");
+
+        must_not_allow(&filter, "/project/data/nested/users.csv");
+        must_not_allow(&filter, "/project/schema/old/user.graphql");
+        must_not_allow(&filter, "/project/README.md");
+        must_not_allow(&filter, "/elsewhere/user.graphql");
+
+        let filter = git_filter(&["*.csv"]).watch_patterns(&patterns);
+        must_allow(&filter, "/project/data/users.csv");
+    }
+
+    #[test]
+    fn test_editor_files() {
+        let filter = git_filter(&[]);
+
+        must_allow(&filter, "/project/src/main.rs");
+        must_allow(&filter, "/project/src/#[cfg].rs");
+
+        must_not_allow(&filter, "/project/src/.main.rs.swp");
+        must_not_allow(&filter, "/project/src/.main.rs.swx");
+        must_not_allow(&filter, "/project/src/4913");
+        must_not_allow(&filter, "/project/src/main.rs~");
+        must_not_allow(&filter, "/project/src/#main.rs#");
+        must_not_allow(&filter, "/project/src/.#main.rs");
+        must_not_allow(&filter, "/project/src/main.rs___jb_tmp___");
+        must_not_allow(&filter, "/project/src/main.rs___jb_old___");
+        must_not_allow(&filter, "/project/src/.main.rs.kate-swp");
+        must_not_allow(&filter, "/project/src/.goutputstream-1ZQ2X1");
+    }
+
+    #[test]
+    fn test_whitelist_has_priority_over_gitignore() {
+        let filter = git_filter(&["Cargo.lock"]);
+
+        must_allow(&filter, "/project/Cargo.lock");
+    }
+}
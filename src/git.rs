@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use filter::find_repo_root;
+
+/// Files git keeps in its directory while an operation is stopped, e.g. on conflicts.
+const OPERATIONS: &[(&str, &str)] = &[
+    ("MERGE_HEAD", "merge"),
+    ("rebase-merge", "rebase"),
+    ("rebase-apply", "rebase"),
+    ("CHERRY_PICK_HEAD", "cherry-pick"),
+    ("REVERT_HEAD", "revert"),
+];
+
+/// The git repository the project is in, to tell when git itself is changing the working tree.
+pub struct Repository {
+    work_dir: PathBuf,
+    /// `.git/` of the repository, or the directory of the worktree it points to
+    git_dir: PathBuf
+}
+
+impl Repository {
+    /// The repository which contains `dir`, `None` if there is none.
+    pub fn find(dir: &Path) -> Option<Self> {
+        let work_dir = find_repo_root(dir)?;
+        let dot_git = work_dir.join(".git");
+        // In a worktree or a submodule `.git` is a file: `gitdir: ../.git/worktrees/feature`
+        let git_dir = if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            work_dir.join(content.trim().trim_start_matches("gitdir:").trim())
+        } else {
+            dot_git
+        };
+        Some(Self { work_dir, git_dir })
+    }
+
+    /// What is checked out: the name of the branch, or the abbreviated commit of a detached `HEAD`.
+    pub fn head(&self) -> Option<String> {
+        let head = fs::read_to_string(self.git_dir.join("HEAD")).ok()?;
+        let head = head.trim();
+        Some(match head.split("ref: refs/heads/").nth(1) {
+            Some(branch) => branch.to_string(),
+            None => head.chars().take(7).collect()
+        })
+    }
+
+    /// Is git changing the index and the working tree now, e.g. during a checkout?
+    pub fn is_locked(&self) -> bool {
+        self.git_dir.join("index.lock").exists()
+    }
+
+    /// Name of the operation in progress: `merge`, `rebase`, `cherry-pick` or `revert`.
+    pub fn operation(&self) -> Option<&'static str> {
+        OPERATIONS.iter()
+            .find(|&&(file, _)| self.git_dir.join(file).exists())
+            .map(|&(_, operation)| operation)
+    }
+
+    /// Are there files with unresolved conflicts?
+    pub fn has_conflicts(&self) -> bool {
+        Command::new("git")
+            .args(["diff", "--name-only", "--diff-filter=U"])
+            .current_dir(&self.work_dir)
+            .output()
+            .map(|output| output.status.success() && !output.stdout.is_empty())
+            .unwrap_or(false)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_repository() {
+        let dir = env::temp_dir().join("testify-git-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("crates/app/src")).unwrap();
+        fs::write(dir.join(".git/HEAD"), "ref: refs/heads/feature/parser\n").unwrap();
+
+        let repository = Repository::find(&dir.join("crates/app/src")).unwrap();
+        assert_eq!(repository.head(), Some("feature/parser".to_string()));
+        assert!(!repository.is_locked());
+        assert_eq!(repository.operation(), None);
+
+        fs::write(dir.join(".git/HEAD"), "4c3d8ab05e7f1d2c3b4a5f6e7d8c9b0a1f2e3d4c\n").unwrap();
+        fs::write(dir.join(".git/index.lock"), "").unwrap();
+        fs::create_dir_all(dir.join(".git/rebase-merge")).unwrap();
+        assert_eq!(repository.head(), Some("4c3d8ab".to_string()));
+        assert!(repository.is_locked());
+        assert_eq!(repository.operation(), Some("rebase"));
+
+        fs::create_dir_all(dir.join("worktree")).unwrap();
+        fs::write(dir.join("worktree/.git"), "gitdir: ../.git\n").unwrap();
+        assert_eq!(Repository::find(&dir.join("worktree")).unwrap().head(), Some("4c3d8ab".to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json;
+
+use report::Report;
+use errors::*;
+
+/// History file, relative to the project root. Each line is a JSON object describing a run.
+pub const HISTORY_FILE: &str = ".testify/history.jsonl";
+
+/// Number of the latest passing runs the usual duration is averaged over.
+const ROLLING_WINDOW: usize = 10;
+
+/// The usual duration is not known until this many runs have passed.
+const MIN_SAMPLES: usize = 3;
+
+/// A run slower than usual by less than this is not reported, whatever the ratio is.
+const MIN_SLOWDOWN_MS: u64 = 1000;
+
+/// A single test run, as recorded in the history file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Local time the run has finished at, RFC 3339
+    pub timestamp: String,
+    /// Changed file which has triggered the run, relative to the project root
+    pub file: Option<String>,
+    /// `passed`, `passed_with_warnings`, `failed` or `compile_error`
+    pub outcome: String,
+    pub duration_ms: u64,
+    pub passed: Option<usize>,
+    pub failed: Option<usize>
+}
+
+impl Entry {
+    pub fn new(report: &Report, file: Option<String>) -> Self {
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            file,
+            outcome: report.outcome.name().to_string(),
+            duration_ms: report.duration.map(as_millis).unwrap_or(0),
+            passed: report.counts.map(|counts| counts.passed),
+            failed: report.counts.map(|counts| counts.failed)
+        }
+    }
+
+    fn is_success(&self) -> bool {
+        self.outcome == "passed" || self.outcome == "passed_with_warnings" || self.outcome == "flaky"
+    }
+}
+
+/// Aggregated numbers over the whole history.
+#[derive(Debug, PartialEq)]
+pub struct Statistics {
+    pub runs: usize,
+    pub average_duration: Duration,
+    /// Share of runs which did not succeed, from 0 to 1
+    pub failure_rate: f64
+}
+
+impl Statistics {
+    pub fn compute(entries: &[Entry]) -> Option<Self> {
+        if entries.is_empty() { return None; }
+
+        let runs = entries.len();
+        let total_ms: u64 = entries.iter().map(|entry| entry.duration_ms).sum();
+        let failures = entries.iter().filter(|entry| !entry.is_success()).count();
+        Some(Self {
+            runs,
+            average_duration: Duration::from_millis(total_ms / runs as u64),
+            failure_rate: failures as f64 / runs as f64
+        })
+    }
+}
+
+/// Average duration of the latest passing runs, `None` if too few runs have passed.
+pub fn rolling_average(entries: &[Entry]) -> Option<Duration> {
+    let durations: Vec<u64> = entries.iter().rev()
+        .filter(|entry| entry.is_success())
+        .take(ROLLING_WINDOW)
+        .map(|entry| entry.duration_ms)
+        .collect();
+    if durations.len() < MIN_SAMPLES { return None; }
+    Some(Duration::from_millis(durations.iter().sum::<u64>() / durations.len() as u64))
+}
+
+/// Has a run taking `duration` been slower than `average` by more than `threshold` times?
+pub fn is_slowdown(duration: Duration, average: Duration, threshold: f64) -> bool {
+    let (duration_ms, average_ms) = (as_millis(duration), as_millis(average));
+    threshold > 0.0 && duration_ms as f64 > average_ms as f64 * threshold && duration_ms - average_ms >= MIN_SLOWDOWN_MS
+}
+
+/// Append `entry` to the history file of the project, creating the file if needed.
+pub fn record(project_dir: &Path, entry: &Entry) -> Result<()> {
+    let path = project_dir.join(HISTORY_FILE);
+    let write = || -> ::std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let line = serde_json::to_string(entry).expect("history entry is always serializable");
+        writeln!(file, "{}", line)
+    };
+    write().chain_err(|| ErrorKind::HistoryFile(path.clone()))
+}
+
+/// Read all recorded runs, oldest first. Lines which can't be parsed are skipped.
+pub fn load(project_dir: &Path) -> Result<Vec<Entry>> {
+    let path = project_dir.join(HISTORY_FILE);
+    if !path.is_file() { return Ok(vec![]); }
+
+    let file = File::open(&path).chain_err(|| ErrorKind::HistoryFile(path.clone()))?;
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        let line = line.chain_err(|| ErrorKind::HistoryFile(path.clone()))?;
+        if let Ok(entry) = serde_json::from_str(&line) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// Print the latest `limit` runs and statistics over the whole history.
+pub fn print(project_dir: &Path, limit: usize) -> Result<()> {
+    let entries = load(project_dir)?;
+    let statistics = match Statistics::compute(&entries) {
+        Some(statistics) => statistics,
+        None => {
+            println!("No runs recorded in {}", history_path(project_dir).display());
+            return Ok(());
+        }
+    };
+
+    let skip = entries.len().saturating_sub(limit);
+    for entry in &entries[skip..] {
+        println!("{}", format_entry(entry));
+    }
+    println!();
+    println!("Runs: {}", statistics.runs);
+    println!("Average duration: {}", format_duration(statistics.average_duration));
+    println!("Failure rate: {:.0}%", statistics.failure_rate * 100.0);
+    Ok(())
+}
+
+fn history_path(project_dir: &Path) -> PathBuf {
+    project_dir.join(HISTORY_FILE)
+}
+
+fn format_entry(entry: &Entry) -> String {
+    // Drop fractions of seconds and the offset: 2017-09-13T10:15:42
+    let timestamp = entry.timestamp.get(..19).unwrap_or(&entry.timestamp).replace('T', " ");
+    let counts = match (entry.passed, entry.failed) {
+        (Some(passed), Some(failed)) => format!("{} passed, {} failed", passed, failed),
+        _ => "-".to_string()
+    };
+    let file = entry.file.as_deref().unwrap_or("-");
+    format!("{}  {:<20}  {:>7}  {:<22}  {}", timestamp, entry.outcome, format_duration(Duration::from_millis(entry.duration_ms)), counts, file)
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{}.{}s", duration.as_secs(), duration.subsec_millis() / 100)
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(outcome: &str, duration_ms: u64) -> Entry {
+        Entry {
+            timestamp: "2017-09-13T10:15:42.123+02:00".to_string(),
+            file: Some("src/lib.rs".to_string()),
+            outcome: outcome.to_string(),
+            duration_ms,
+            passed: Some(3),
+            failed: Some(1)
+        }
+    }
+
+    #[test]
+    fn test_statistics() {
+        assert_eq!(Statistics::compute(&[]), None);
+
+        let entries = vec![entry("passed", 1000), entry("failed", 2000), entry("compile_error", 0), entry("passed_with_warnings", 1000)];
+        let statistics = Statistics::compute(&entries).unwrap();
+        assert_eq!(statistics.runs, 4);
+        assert_eq!(statistics.average_duration, Duration::from_millis(1000));
+        assert_eq!(statistics.failure_rate, 0.5);
+    }
+
+    #[test]
+    fn test_rolling_average() {
+        assert_eq!(rolling_average(&[entry("passed", 1000), entry("passed", 2000)]), None);
+
+        let mut entries = vec![entry("passed", 9000)];
+        entries.extend((0..10).map(|_| entry("passed", 2000)));
+        entries.push(entry("failed", 100));
+        assert_eq!(rolling_average(&entries), Some(Duration::from_millis(2000)));
+
+        let average = Duration::from_millis(2000);
+        assert!(is_slowdown(Duration::from_millis(3500), average, 1.5));
+        assert!(!is_slowdown(Duration::from_millis(2900), average, 1.5));
+        assert!(!is_slowdown(Duration::from_millis(3500), average, 0.0));
+        assert!(!is_slowdown(Duration::from_millis(900), Duration::from_millis(300), 1.5));
+    }
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(
+            format_entry(&entry("failed", 1234)),
+            "2017-09-13 10:15:42  failed                   1.2s  3 passed, 1 failed      src/lib.rs"
+        );
+    }
+}
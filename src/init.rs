@@ -0,0 +1,152 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use config::CONFIG_FILE_NAME;
+use notifier;
+use errors::*;
+
+/// Directories with data the tests read, watched if the project has them.
+const TEST_DATA_DIRS: &[&str] = &["fixtures", "testdata", "test-data", "resources"];
+
+/// What is detected about the project to fill in the configuration.
+#[derive(Debug, PartialEq)]
+struct Project {
+    /// Directories of the workspace members, relative to the project root
+    members: Vec<String>,
+    /// Directories with test data, relative to the project root
+    test_data: Vec<String>,
+    /// Notifier which works on this machine
+    notifier: &'static str
+}
+
+/// Write an annotated `.testify.toml` into the project root, with the settings which are
+/// detected filled in and the rest commented out. An existing file is never overwritten.
+pub fn write(project_dir: &Path) -> Result<PathBuf> {
+    let path = project_dir.join(CONFIG_FILE_NAME);
+    if path.exists() {
+        return Err(ErrorKind::ConfigExists(path).into());
+    }
+    let project = detect(project_dir);
+    fs::write(&path, template(&project)).chain_err(|| ErrorKind::ConfigFile(path.clone()))?;
+    Ok(path)
+}
+
+fn detect(project_dir: &Path) -> Project {
+    let test_data = TEST_DATA_DIRS.iter()
+        .filter(|dir| project_dir.join(dir).is_dir())
+        .map(|dir| dir.to_string())
+        .collect();
+    let notifier = if notifier::desktop_available() { "desktop" } else { "console" };
+    Project { members: workspace_members(project_dir), test_data, notifier }
+}
+
+/// Directories of the `[workspace]` members listed in `Cargo.toml`. Their changes trigger a run only
+/// if they are watched, unless the project is in a git repository. Globs like `crates/*` are
+/// watched as a whole directory.
+fn workspace_members(project_dir: &Path) -> Vec<String> {
+    let mut content = String::new();
+    let read = File::open(project_dir.join("Cargo.toml")).and_then(|mut file| file.read_to_string(&mut content));
+    if read.is_err() { return vec![]; }
+    let manifest: toml::Value = match toml::from_str(&content) {
+        Ok(manifest) => manifest,
+        Err(_) => return vec![]
+    };
+    let members = manifest.get("workspace")
+        .and_then(|workspace| workspace.get("members"))
+        .and_then(|members| members.as_array())
+        .map(|members| members.iter().filter_map(|member| member.as_str()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let mut dirs: Vec<String> = vec![];
+    for member in members {
+        let dir = member.split('*').next().unwrap_or("").trim_end_matches('/');
+        if dir.is_empty() || dir == "." { continue; }
+        let dir = format!("{}/", dir);
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Content of the configuration file.
+fn template(project: &Project) -> String {
+    let mut watch: Vec<String> = project.members.iter().chain(&project.test_data).map(|dir| format!("{:?}", dir)).collect();
+    let watch = if watch.is_empty() {
+        "# watch = [\"fixtures/\", \".env\"]".to_string()
+    } else {
+        watch.sort();
+        format!("watch = [{}]", watch.join(", "))
+    };
+    format!(r#"# Configuration of cargo testify, see https://github.com/greyblake/cargo-testify#configuration
+# Options passed on the command line take precedence over the ones set here.
+
+# How to notify about the result: "auto", "desktop", "slack", "discord", "telegram", "email", "ntfy", "gotify", "mqtt", "exec", "http", "console" or "none"
+notifier = "{notifier}"
+
+# Notify after every run ("always") or only when tests start or stop passing ("changes")
+notify = "always"
+
+# Wait for changes to settle during this time before running the tests
+delay = "500ms"
+
+# Files and directories to watch on top of src/, tests/, Cargo.toml, Cargo.lock and build.rs
+{watch}
+
+# Gitignore-style patterns of files which never trigger a run
+# ignore = ["src/generated/"]
+
+# Test runner: "cargo", "nextest", "ssh", "docker" or "wasm-pack"
+runner = "cargo"
+
+# Arguments passed to `cargo test`, and to the test binaries
+# cargo_test_args = ["--workspace"]
+# test_args = ["--nocapture"]
+
+# Cargo steps run in order until one fails, "test" stands for the test runner
+# pipeline = ["check", "clippy", "test"]
+
+# Kill the tests if they run longer than this
+# timeout = "10m"
+
+# Kill a running command and start it over when a new change arrives
+restart = false
+
+# Rerun failed tests up to this many times, report the ones which pass on a retry as flaky
+retries = 0
+
+# Clear the terminal before each run
+clear = false
+
+# Shell commands run around every test run
+[hooks]
+# before_run = "make proto"
+# on_failure = "say failed"
+"#, notifier = project.notifier, watch = watch)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::FileConfig;
+
+    #[test]
+    fn test_template_is_valid_config() {
+        let project = Project {
+            members: vec!["crates/".to_string(), "xtask/".to_string()],
+            test_data: vec!["fixtures".to_string()],
+            notifier: "console"
+        };
+        let content = template(&project);
+        assert!(content.contains("notifier = \"console\"\n"));
+        assert!(content.contains("watch = [\"crates/\", \"fixtures\", \"xtask/\"]\n"));
+        assert!(toml::from_str::<FileConfig>(&content).is_ok());
+
+        let project = Project { members: vec![], test_data: vec![], notifier: "desktop" };
+        assert!(toml::from_str::<FileConfig>(&template(&project)).is_ok());
+    }
+}
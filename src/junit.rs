@@ -0,0 +1,154 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::Local;
+
+use report::Report;
+use errors::*;
+
+/// Replace the JUnit XML file at `path` with the results of the tests in `report`.
+/// The new content is written to a temporary file first and then renamed.
+pub fn write(path: &Path, report: &Report) -> Result<()> {
+    let temp_path = path.with_extension("xml.tmp");
+    let write = || -> ::std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&temp_path)?;
+        file.write_all(to_xml(report).as_bytes())?;
+        drop(file);
+        fs::rename(&temp_path, path)
+    };
+    write().chain_err(|| ErrorKind::JunitFile(path.to_path_buf()))
+}
+
+/// A single `<testcase>` element.
+struct TestCase<'a> {
+    name: &'a str,
+    passed: bool,
+    message: Option<&'a str>,
+    duration: Option<Duration>
+}
+
+/// Render the report as a single test suite. A run which has failed before any test ran,
+/// e.g. on a compile error, is rendered as a single erroneous test case, so it is not
+/// mistaken for an empty successful run.
+fn to_xml(report: &Report) -> String {
+    let duration = |name: &str| report.timings.iter().find(|timing| timing.name == name).map(|timing| timing.duration);
+    let message = |name: &str| report.failures.iter().find(|failure| failure.name == name).and_then(|failure| failure.message.as_deref());
+
+    let mut cases: Vec<TestCase> = report.results.iter()
+        .map(|result| TestCase {
+            name: &result.name,
+            passed: result.passed || report.flaky.contains(&result.name),
+            message: message(&result.name),
+            duration: duration(&result.name)
+        })
+        .collect();
+    // Structured output of some runners reports failures only
+    for failure in &report.failures {
+        if !cases.iter().any(|case| case.name == failure.name) {
+            cases.push(TestCase { name: &failure.name, passed: false, message: failure.message.as_deref(), duration: duration(&failure.name) });
+        }
+    }
+
+    let title = report.title();
+    let error = if cases.is_empty() && !report.outcome.is_success() {
+        let stage = report.stage.as_deref().unwrap_or("tests");
+        Some(format!(
+            "    <testcase name=\"{}\" classname=\"testify\">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+            escape(stage), escape(&title), escape(report.body().as_deref().unwrap_or(""))
+        ))
+    } else {
+        None
+    };
+
+    let tests = cases.len() + error.iter().count();
+    let failures = cases.iter().filter(|case| !case.passed).count();
+    let skipped = report.counts.map(|counts| counts.ignored).unwrap_or(0);
+    let time = report.duration.map(seconds).unwrap_or(0.0);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!("<testsuites name=\"cargo testify\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+                          tests, failures, error.iter().count(), time));
+    xml.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\" timestamp=\"{}\">\n",
+                          escape(&title), tests, failures, error.iter().count(), skipped, time, Local::now().format("%Y-%m-%dT%H:%M:%S")));
+    for case in &cases {
+        xml.push_str(&format_case(case));
+    }
+    if let Some(error) = error {
+        xml.push_str(&error);
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// `tests::parser::test_parse` becomes the test case `test_parse` of the class `tests::parser`.
+fn format_case(case: &TestCase) -> String {
+    let (classname, name) = match case.name.rfind("::") {
+        Some(index) => (&case.name[..index], &case.name[index + 2..]),
+        None => ("", case.name)
+    };
+    let time = case.duration.map(|duration| format!(" time=\"{:.3}\"", seconds(duration))).unwrap_or_default();
+    let mut xml = format!("    <testcase name=\"{}\" classname=\"{}\"{}", escape(name), escape(classname), time);
+    if case.passed {
+        xml.push_str("/>\n");
+    } else {
+        let message = case.message.unwrap_or("");
+        let summary = message.lines().next().unwrap_or("test failed");
+        xml.push_str(&format!(">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n", escape(summary), escape(message)));
+    }
+    xml
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + f64::from(duration.subsec_millis()) / 1000.0
+}
+
+/// Escape text for use in XML content and attribute values, dropping characters XML 1.0 does not allow.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' | '\r' | '\t' => escaped.push(c),
+            c if c < ' ' => {}
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::{Outcome, TestFailure, TestResult, TestTiming};
+
+    #[test]
+    fn test_to_xml() {
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.results = vec![
+            TestResult { name: "tests::test_add".to_string(), passed: true },
+            TestResult { name: "tests::test_parse".to_string(), passed: false }
+        ];
+        report.failures.push(TestFailure { name: "tests::test_parse".to_string(), message: Some("assertion failed: a < b".to_string()), location: None });
+        report.timings.push(TestTiming { name: "tests::test_add".to_string(), duration: Duration::from_millis(1500) });
+
+        let xml = to_xml(&report);
+        assert!(xml.contains("tests=\"2\" failures=\"1\" errors=\"0\""));
+        assert!(xml.contains("<testcase name=\"test_add\" classname=\"tests\" time=\"1.500\"/>"));
+        assert!(xml.contains("<failure message=\"assertion failed: a &lt; b\">assertion failed: a &lt; b</failure>"));
+
+        let report = Report::new(Outcome::CompileError, Some("expected `;`".to_string()));
+        let xml = to_xml(&report);
+        assert!(xml.contains("tests=\"1\" failures=\"0\" errors=\"1\""));
+        assert!(xml.contains("<error message=\"Error\">expected `;`</error>"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::{self, Read};
+use std::thread;
+
+/// Action requested by pressing a key in the terminal while testify is watching.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Key {
+    /// `r`: run the tests now
+    Rerun,
+
+    /// `c`: clear the screen
+    Clear,
+
+    /// `p`: pause or resume watching for changes
+    Pause,
+
+    /// `n`: turn notifications off or back on
+    ToggleNotifications,
+
+    /// `a`: run all tests again instead of the picked ones
+    AllTests,
+
+    /// `w`: run the whole suite now, even if runs are restricted to the changes since a git ref
+    FullRun,
+
+    /// `f`: run only the first failing test with its output until it passes, or stop following it
+    Follow,
+
+    /// `s`: accept the snapshots which have changed in the latest run, and run the tests again
+    AcceptSnapshots,
+
+    /// `x`: reject the snapshots which have changed in the latest run
+    RejectSnapshots,
+
+    /// `q`: quit
+    Quit
+}
+
+impl Key {
+    fn from_byte(byte: u8) -> Option<Key> {
+        match byte {
+            b'r' | b'R' => Some(Key::Rerun),
+            b'c' | b'C' => Some(Key::Clear),
+            b'p' | b'P' => Some(Key::Pause),
+            b'n' | b'N' => Some(Key::ToggleNotifications),
+            b'a' | b'A' => Some(Key::AllTests),
+            b'w' | b'W' => Some(Key::FullRun),
+            b'f' | b'F' => Some(Key::Follow),
+            b's' | b'S' => Some(Key::AcceptSnapshots),
+            b'x' | b'X' => Some(Key::RejectSnapshots),
+            b'q' | b'Q' => Some(Key::Quit),
+            _ => None
+        }
+    }
+}
+
+pub const HELP: &str = "Press r to run tests, c to clear the screen, p to pause/resume watching, n to mute/unmute notifications, f to follow the first failing test, a to run all tests again, w to run the whole suite, s/x to accept/reject changed snapshots, q to quit";
+
+/// Switch the terminal to read single keystrokes (without waiting for Enter and without echo),
+/// and call `on_key` from a separate thread for every recognized key.
+/// Returns `None` if stdin is not a terminal. The terminal mode is restored when the returned
+/// guard is dropped.
+pub fn listen<F>(on_key: F) -> Option<TerminalGuard>
+    where F: Fn(Key) + Send + 'static
+{
+    let guard = TerminalGuard::enable(true)?;
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for byte in stdin.lock().bytes() {
+            match byte {
+                Ok(byte) => {
+                    if let Some(key) = Key::from_byte(byte) { on_key(key); }
+                },
+                Err(_) => break
+            }
+        }
+    });
+    Some(guard)
+}
+
+/// Stop echoing what is typed into the terminal, e.g. a password, while lines are still read on Enter.
+/// Returns `None` if stdin is not a terminal. The terminal mode is restored when the returned guard is dropped.
+pub fn hide_input() -> Option<TerminalGuard> {
+    TerminalGuard::enable(false)
+}
+
+/// Keeps the original terminal mode, to restore it on drop.
+#[cfg(not(target_os = "windows"))]
+pub struct TerminalGuard {
+    original: ::libc::termios
+}
+
+#[cfg(not(target_os = "windows"))]
+impl TerminalGuard {
+    /// Read single `keystrokes`, or only turn off echo.
+    fn enable(keystrokes: bool) -> Option<Self> {
+        use libc;
+        use std::mem;
+
+        unsafe {
+            if libc::isatty(libc::STDIN_FILENO) != 1 { return None; }
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 { return None; }
+
+            // Unlike raw mode, output processing stays untouched, so printed lines are not broken
+            let mut mode = original;
+            mode.c_lflag &= !libc::ECHO;
+            if keystrokes {
+                mode.c_lflag &= !libc::ICANON;
+                mode.c_cc[libc::VMIN] = 1;
+                mode.c_cc[libc::VTIME] = 0;
+            }
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &mode) != 0 { return None; }
+
+            Some(Self { original })
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        use libc;
+
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original); }
+    }
+}
+
+/// Keeps the original console mode, to restore it on drop.
+#[cfg(target_os = "windows")]
+pub struct TerminalGuard {
+    original: ::winapi::shared::minwindef::DWORD
+}
+
+#[cfg(target_os = "windows")]
+impl TerminalGuard {
+    /// Read single `keystrokes`, or only turn off echo.
+    fn enable(keystrokes: bool) -> Option<Self> {
+        use winapi::um::consoleapi::{GetConsoleMode, SetConsoleMode};
+        use winapi::um::wincon::{ENABLE_ECHO_INPUT, ENABLE_LINE_INPUT};
+
+        unsafe {
+            let handle = stdin_handle();
+            let mut original = 0;
+            // Fails if stdin is not a console
+            if GetConsoleMode(handle, &mut original) == 0 { return None; }
+            let mode = if keystrokes { original & !(ENABLE_LINE_INPUT | ENABLE_ECHO_INPUT) } else { original & !ENABLE_ECHO_INPUT };
+            if SetConsoleMode(handle, mode) == 0 { return None; }
+            Some(Self { original })
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        use winapi::um::consoleapi::SetConsoleMode;
+
+        unsafe { SetConsoleMode(stdin_handle(), self.original); }
+    }
+}
+
+#[cfg(target_os = "windows")]
+unsafe fn stdin_handle() -> ::winapi::um::winnt::HANDLE {
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_INPUT_HANDLE;
+
+    GetStdHandle(STD_INPUT_HANDLE)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_from_byte() {
+        assert_eq!(Key::from_byte(b'r'), Some(Key::Rerun));
+        assert_eq!(Key::from_byte(b'C'), Some(Key::Clear));
+        assert_eq!(Key::from_byte(b'p'), Some(Key::Pause));
+        assert_eq!(Key::from_byte(b'n'), Some(Key::ToggleNotifications));
+        assert_eq!(Key::from_byte(b'f'), Some(Key::Follow));
+        assert_eq!(Key::from_byte(b'q'), Some(Key::Quit));
+        assert_eq!(Key::from_byte(b'x'), Some(Key::RejectSnapshots));
+        assert_eq!(Key::from_byte(b'w'), Some(Key::FullRun));
+        assert_eq!(Key::from_byte(b'z'), None);
+    }
+}
@@ -1,24 +1,331 @@
+// error_chain! needs more than the default with this many error kinds
+#![recursion_limit = "256"]
+
 extern crate notify;
 extern crate regex;
-#[cfg(not(target_os = "windows"))]
+extern crate ignore;
+extern crate portable_pty;
+extern crate strip_ansi_escapes;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
 extern crate notify_rust;
+#[cfg(target_os = "macos")]
+extern crate mac_notification_sys;
+#[cfg(target_os = "windows")]
+extern crate tauri_winrt_notification;
+#[macro_use] extern crate clap;
+#[cfg(not(target_os = "windows"))]
+extern crate libc;
 #[cfg(target_os = "windows")]
-extern crate winrt_notification;
-extern crate clap;
+extern crate winapi;
+extern crate toml;
+extern crate chrono;
+extern crate ureq;
+extern crate sha1_smol;
+extern crate base64;
+extern crate lettre;
+extern crate keyring;
+extern crate ratatui;
+extern crate cargo_metadata;
+extern crate ctrlc;
+#[macro_use] extern crate serde_json;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
 #[macro_use] extern crate error_chain;
 
-use clap::{Arg, App, SubCommand};
+use clap::{Arg, App, AppSettings, ArgMatches, Shell, SubCommand};
 
-mod errors;
-mod report;
-mod config;
+pub mod errors;
+pub mod report;
+pub mod config;
 mod reactor;
 mod report_builder;
-use config::ConfigBuilder;
-use reactor::Reactor;
+mod process_tree;
+mod filter;
+pub mod notifier;
+mod keyboard;
+mod history;
+mod stats;
+mod targeting;
+mod status;
+mod junit;
+mod annotations;
+mod daemon;
+mod server;
+mod websocket;
+mod ssh;
+mod docker;
+mod mutants;
+mod init;
+mod doctor;
+mod secrets;
+mod events;
+mod watcher;
+mod ui;
+mod picker;
+mod snapshots;
+mod seeds;
+mod dependencies;
+mod manifest;
+mod content;
+mod scope;
+mod git;
+mod changes;
+mod dotenv;
+use config::{AnnotationFormat, Config, FmtMode, NotifierKind, NotifyMode, OutputFormat, Runner, WasmEnv, CONFIG_FILE_NAME};
+pub use reactor::Reactor;
 
 pub fn run() {
-    let matches = App::new("cargo")
+    let matches = app().get_matches();
+
+    let empty_matches = ArgMatches::default();
+    let matches = matches.subcommand_matches("testify").unwrap_or(&empty_matches);
+
+    if let Some(completions_matches) = matches.subcommand_matches("completions") {
+        // Testify is run by cargo as `cargo-testify testify ...`, and can be run the same way directly
+        let shell = value_t!(completions_matches, "shell", Shell).unwrap_or_else(|err| err.exit());
+        app().gen_completions_to("cargo-testify", shell, &mut std::io::stdout());
+        return;
+    }
+    if let Some(secret_matches) = matches.subcommand_matches("secret") {
+        match secret_matches.subcommand() {
+            ("set", Some(set_matches)) => {
+                let name = set_matches.value_of("name").unwrap();
+                secrets::set(name).unwrap_or_else(|err| exit_with_error(err));
+                println!("Stored, reference it as \"{}{}\" in {}", secrets::PREFIX, name, CONFIG_FILE_NAME);
+            },
+            ("delete", Some(delete_matches)) => {
+                secrets::delete(delete_matches.value_of("name").unwrap()).unwrap_or_else(|err| exit_with_error(err));
+                println!("Deleted");
+            },
+            _ => unreachable!("secret requires a subcommand")
+        }
+        return;
+    }
+
+    let projects: Vec<std::path::PathBuf> = matches.values_of("project")
+        .map(|dirs| dirs.map(find_project_dir).collect())
+        .unwrap_or_default();
+    if projects.len() > 1 {
+        if matches.subcommand_name().is_some() || matches.is_present("once") {
+            eprintln!("Error: several projects can only be watched, `--once` and the subcommands take a single one");
+            std::process::exit(1);
+        }
+        let configs = projects.iter().map(|dir| build_config(matches, dir)).collect();
+        std::process::exit(reactor::watch_projects(configs));
+    }
+    let project_dir = match projects.into_iter().next() {
+        Some(dir) => dir,
+        None => detect_project_dir(matches.value_of("manifest_path"))
+    };
+
+    if matches.subcommand_matches("init").is_some() {
+        let path = init::write(&project_dir).unwrap_or_else(|err| exit_with_error(err));
+        println!("Created {}", path.display());
+        return;
+    }
+    if let Some(history_matches) = matches.subcommand_matches("history") {
+        let limit = value_t!(history_matches, "limit", usize).unwrap_or_else(|err| err.exit());
+        history::print(&project_dir, limit).unwrap_or_else(|err| exit_with_error(err));
+        return;
+    }
+    if let Some(flaky_matches) = matches.subcommand_matches("flaky") {
+        let limit = value_t!(flaky_matches, "limit", usize).unwrap_or_else(|err| err.exit());
+        stats::print(&project_dir, limit).unwrap_or_else(|err| exit_with_error(err));
+        return;
+    }
+    if let Some(status_matches) = matches.subcommand_matches("status") {
+        daemon::print_status(&project_dir, status_matches.is_present("json")).unwrap_or_else(|err| exit_with_error(err));
+        return;
+    }
+    if matches.subcommand_matches("run-now").is_some() {
+        daemon::request(&project_dir, "run").unwrap_or_else(|err| exit_with_error(err));
+        println!("Run requested");
+        return;
+    }
+    if matches.subcommand_matches("stop").is_some() {
+        daemon::request(&project_dir, "stop").unwrap_or_else(|err| exit_with_error(err));
+        println!("Daemon stopped");
+        return;
+    }
+
+    let config = build_config(matches, &project_dir);
+    if matches.subcommand_matches("doctor").is_some() {
+        std::process::exit(doctor::run(&config));
+    }
+    let is_daemon = matches.subcommand_matches("daemon").is_some();
+    if is_daemon && !daemon::is_detached() {
+        let pid = daemon::spawn(&project_dir).unwrap_or_else(|err| exit_with_error(err));
+        println!("Daemon started (pid {}), its output goes to {}", pid, daemon::LOG_FILE);
+        return;
+    }
+    let mut reactor = Reactor::new(config).unwrap_or_else(|err| exit_with_error(err));
+    if is_daemon {
+        std::process::exit(reactor.start_daemon());
+    }
+    if matches.is_present("once") {
+        std::process::exit(reactor.run_once());
+    }
+    std::process::exit(reactor.start());
+}
+
+/// Configuration of the project in `project_dir`: its configuration file, overridden by the options
+/// given on the command line.
+fn build_config(matches: &ArgMatches, project_dir: &std::path::Path) -> Config {
+    let mut builder = Config::from_file(&project_dir.join(CONFIG_FILE_NAME))
+        .unwrap_or_else(|err| exit_with_error(err))
+        .project_dir(project_dir.to_path_buf());
+
+    let (cargo_test_args, test_args) = command_line_args(matches);
+    if let Some(args) = cargo_test_args {
+        builder = builder.cargo_test_args(args);
+    }
+    if let Some(args) = test_args {
+        builder = builder.test_args(args);
+    }
+    if let Some(delay) = matches.value_of("delay") {
+        let delay = config::parse_duration(delay).unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.delay(delay);
+    }
+    if let Some(timeout) = matches.value_of("timeout") {
+        let timeout = config::parse_duration(timeout).unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.timeout(timeout);
+    }
+    if let Some(env_file) = matches.value_of("env_file") {
+        builder = builder.env_file(env_file.to_string());
+    }
+    if matches.is_present("no_env_file") {
+        builder = builder.env_file(String::new());
+    }
+    if let Some(profiles) = matches.values_of("env_profile") {
+        builder = builder.env_profiles(profiles.map(String::from).collect());
+    }
+    if matches.is_present("poll") {
+        let interval = match matches.value_of("poll") {
+            Some(interval) => config::parse_duration(interval).unwrap_or_else(|err| exit_with_error(err)),
+            None => std::time::Duration::from_millis(watcher::DEFAULT_POLL_INTERVAL_MS)
+        };
+        builder = builder.poll(interval);
+    }
+    if let Some(runner) = matches.value_of("runner") {
+        let runner = runner.parse::<Runner>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.runner(runner);
+    }
+    if let Some(wasm_env) = matches.value_of("wasm_env") {
+        let wasm_env = wasm_env.parse::<WasmEnv>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.wasm_env(wasm_env);
+    }
+    if let Some(steps) = matches.values_of("pipeline") {
+        builder = builder.pipeline(steps.map(String::from).collect());
+    }
+    if matches.is_present("check_first") {
+        builder = builder.check_first(true);
+    }
+    if let Some(fmt) = matches.value_of("fmt") {
+        let fmt = fmt.parse::<FmtMode>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.fmt(fmt);
+    }
+    if matches.is_present("clippy") {
+        builder = builder.clippy(true);
+    }
+    if matches.is_present("deny_warnings") {
+        builder = builder.deny_warnings(true);
+    }
+    if matches.is_present("miri") {
+        builder = builder.miri(true);
+    }
+    if matches.is_present("mutants") {
+        builder = builder.mutants(true);
+    }
+    if let Some(toolchains) = matches.values_of("toolchain") {
+        builder = builder.toolchains(toolchains.map(String::from).collect());
+    }
+    if let Some(targets) = matches.values_of("target") {
+        builder = builder.targets(targets.map(String::from).collect());
+    }
+    if matches.is_present("cross") {
+        builder = builder.cross(true);
+    }
+    if let Some(command) = matches.value_of("command") {
+        builder = builder.command(command.to_string());
+    }
+    if let Some(paths) = matches.values_of("watch") {
+        builder = builder.watch(paths.map(String::from).collect());
+    }
+    if let Some(patterns) = matches.values_of("ignore") {
+        builder = builder.ignore(patterns.map(String::from).collect());
+    }
+    if let Some(patterns) = matches.values_of("watch_pattern") {
+        builder = builder.watch_patterns(patterns.map(String::from).collect());
+    }
+    if matches.is_present("restart") {
+        builder = builder.restart(true);
+    }
+    if matches.is_present("pty") {
+        builder = builder.pty(true);
+    }
+    if matches.is_present("coverage") {
+        builder = builder.coverage(true);
+    }
+    if matches.is_present("failed_first") {
+        builder = builder.failed_first(true);
+    }
+    if matches.is_present("targeted") {
+        builder = builder.targeted(true);
+    }
+    if matches.is_present("changed_since") {
+        builder = builder.changed_since(Some(matches.value_of("changed_since").unwrap_or("HEAD").to_string()));
+    }
+    if matches.is_present("full") {
+        builder = builder.changed_since(None);
+    }
+    if matches.is_present("non_interactive") {
+        builder = builder.interactive(false);
+    }
+    if matches.is_present("clear") {
+        builder = builder.clear(true);
+    }
+    if matches.is_present("quiet") {
+        builder = builder.quiet(true);
+    }
+    if matches.is_present("ui") {
+        builder = builder.ui(true);
+    }
+    if matches.is_present("pick") {
+        builder = builder.pick(true);
+    }
+    if let Some(junit) = matches.value_of("junit") {
+        builder = builder.junit(std::path::PathBuf::from(junit));
+    }
+    if let Some(output) = matches.value_of("output") {
+        let output = output.parse::<OutputFormat>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.output(output);
+    }
+    if let Some(listen) = matches.value_of("listen") {
+        builder = builder.listen(listen.to_string());
+    }
+    if let Some(annotate) = matches.value_of("annotate") {
+        let annotate = annotate.parse::<AnnotationFormat>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.annotate(annotate);
+    }
+    if let Some(notifiers) = matches.values_of("notifier") {
+        let notifiers = notifiers.map(|notifier| notifier.parse::<NotifierKind>().unwrap_or_else(|err| exit_with_error(err))).collect();
+        builder = builder.notifiers(notifiers);
+    }
+
+    if let Some(notify) = matches.value_of("notify") {
+        let notify = notify.parse::<NotifyMode>().unwrap_or_else(|err| exit_with_error(err));
+        builder = builder.notify(notify);
+    }
+    if matches.is_present("skip_when_focused") {
+        builder = builder.skip_when_focused(true);
+    }
+
+    builder.build().unwrap_or_else(|err| exit_with_error(err))
+}
+
+/// Command line interface: `cargo testify` with its options and subcommands.
+fn app() -> App<'static, 'static> {
+    App::new("cargo")
         .bin_name("cargo")
         .help_message("")
         .version_message("")
@@ -27,44 +334,371 @@ pub fn run() {
             .version("0.2.0")
             .author("Sergey Potapov <blake131313@gmail.com>")
             .about("Automatically runs tests for Rust project and notifies about the result.\nSource code: https://github.com/greyblake/cargo-testify")
-            .arg(Arg::with_name("cargo_test_args")
+            .arg(Arg::with_name("manifest_path")
+                 .long("manifest-path")
+                 .takes_value(true)
+                 .value_name("PATH")
+                 .help("Path to Cargo.toml of the project, found in the current directory or its parents by default"))
+            .arg(Arg::with_name("project")
+                 .long("project")
+                 .short("p")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .value_name("DIR")
+                 .conflicts_with("manifest_path")
+                 .help("Directory of a project to watch, can be repeated to watch several projects at once"))
+            .arg(Arg::with_name("delay")
+                 .long("delay")
+                 .short("d")
+                 .takes_value(true)
+                 .value_name("DURATION")
+                 .help("Wait for changes to settle during this time before running tests, e.g. 500ms or 1s"))
+            .arg(Arg::with_name("timeout")
+                 .long("timeout")
+                 .takes_value(true)
+                 .value_name("DURATION")
+                 .help("Kill the tests if they run longer than this, e.g. 10m"))
+            .arg(Arg::with_name("env_profile")
+                 .long("env-profile")
+                 .takes_value(true)
+                 .use_delimiter(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .value_name("NAME")
+                 .help("Set the environment variables of [env.NAME] in .testify.toml for the commands (overrides `env_profiles` in .testify.toml)"))
+            .arg(Arg::with_name("env_file")
+                 .long("env-file")
+                 .takes_value(true)
+                 .value_name("FILE")
+                 .help("Load environment variables for the commands from this file instead of .env"))
+            .arg(Arg::with_name("no_env_file")
+                 .long("no-env-file")
+                 .conflicts_with("env_file")
+                 .help("Do not load environment variables from .env"))
+            .arg(Arg::with_name("poll")
+                 .long("poll")
+                 .takes_value(true)
+                 .min_values(0)
+                 .require_equals(true)
+                 .value_name("INTERVAL")
+                 .help("Poll the file system for changes, every second or with the given interval, e.g. --poll=500ms"))
+            .arg(Arg::with_name("runner")
+                 .long("runner")
+                 .takes_value(true)
+                 .possible_values(&["cargo", "nextest", "ssh", "docker", "wasm-pack"])
+                 .help("Test runner to use (overrides `runner` in .testify.toml)"))
+            .arg(Arg::with_name("wasm_env")
+                 .long("wasm-env")
+                 .takes_value(true)
+                 .possible_values(&["chrome", "firefox", "safari", "node"])
+                 .help("Where the `wasm-pack` runner runs the tests (overrides `wasm_env` in .testify.toml)"))
+            .arg(Arg::with_name("pipeline")
+                 .long("pipeline")
+                 .takes_value(true)
+                 .use_delimiter(true)
+                 .value_name("STEPS")
+                 .help("Comma separated cargo steps to run in order until one fails, e.g. \"check,clippy,test\""))
+            .arg(Arg::with_name("check_first")
+                 .long("check-first")
+                 .help("Run `cargo check` before the tests and skip them if the code does not compile"))
+            .arg(Arg::with_name("fmt")
+                 .long("fmt")
+                 .takes_value(true)
+                 .possible_values(&["off", "check", "fix"])
+                 .help("Check formatting before the tests, or format the code with `cargo fmt`"))
+            .arg(Arg::with_name("clippy")
+                 .long("clippy")
+                 .help("Run `cargo clippy` before the tests and report the numbers of its warnings and errors"))
+            .arg(Arg::with_name("deny_warnings")
+                 .long("deny-warnings")
+                 .help("Fail the run if clippy reports warnings"))
+            .arg(Arg::with_name("miri")
+                 .long("miri")
+                 .help("Run the tests with `cargo +nightly miri test` after they pass, to detect undefined behavior"))
+            .arg(Arg::with_name("mutants")
+                 .long("mutants")
+                 .help("Run `cargo mutants` on the changed file in the background after the tests pass, and report surviving mutants"))
+            .arg(Arg::with_name("toolchain")
+                 .long("toolchain")
+                 .takes_value(true)
+                 .use_delimiter(true)
+                 .value_name("TOOLCHAINS")
+                 .help("Run tests with the given toolchain, or comma separated toolchains one after another, e.g. \"stable,nightly\""))
+            .arg(Arg::with_name("target")
+                 .long("target")
+                 .takes_value(true)
+                 .use_delimiter(true)
+                 .value_name("TARGETS")
+                 .help("Run tests for the given target triple, or for comma separated triples one after another"))
+            .arg(Arg::with_name("cross")
+                 .long("cross")
+                 .help("Run tests with `cross` instead of cargo, e.g. for foreign targets"))
+            .arg(Arg::with_name("command")
+                 .long("command")
+                 .short("c")
+                 .takes_value(true)
+                 .value_name("COMMAND")
+                 .help("Command to run instead of `cargo test`, e.g. \"make check\""))
+            .arg(Arg::with_name("watch")
+                 .long("watch")
+                 .short("w")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .value_name("PATH")
+                 .help("Additional file or directory to watch, relative to the project root (can be repeated)"))
+            .arg(Arg::with_name("ignore")
+                 .long("ignore")
+                 .short("i")
+                 .takes_value(true)
                  .multiple(true)
-                 .last(true))
+                 .number_of_values(1)
+                 .value_name("PATTERN")
+                 .help("Gitignore-style pattern of files which never trigger a run, e.g. \"src/generated/**\" (can be repeated)"))
+            .arg(Arg::with_name("watch_pattern")
+                 .long("watch-pattern")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .value_name("GLOB")
+                 .help("Glob of files which trigger a run, even if they are not watched otherwise, e.g. \"**/*.graphql\" (can be repeated)"))
+            .arg(Arg::with_name("restart")
+                 .long("restart")
+                 .help("Kill a running command and start it over when a new change arrives"))
+            .arg(Arg::with_name("pty")
+                 .long("pty")
+                 .help("Run the command in a pseudo-terminal to preserve colored output"))
+            .arg(Arg::with_name("coverage")
+                 .long("coverage")
+                 .help("Run the tests with `cargo llvm-cov` and report the line coverage"))
+            .arg(Arg::with_name("failed_first")
+                 .long("failed-first")
+                 .help("Run previously failed tests first, and all tests only if they pass"))
+            .arg(Arg::with_name("targeted")
+                 .long("targeted")
+                 .help("Run tests related to the changed file first, and all tests only if they pass"))
+            .arg(Arg::with_name("changed_since")
+                 .long("changed-since")
+                 .takes_value(true)
+                 .min_values(0)
+                 .require_equals(true)
+                 .value_name("REF")
+                 .help("Run only the tests of the packages or modules changed since HEAD or the given git ref, e.g. --changed-since=origin/main"))
+            .arg(Arg::with_name("full")
+                 .long("full")
+                 .conflicts_with("changed_since")
+                 .help("Run the whole suite, even if `changed_since` is set in .testify.toml"))
+            .arg(Arg::with_name("non_interactive")
+                 .long("non-interactive")
+                 .help("Do not handle keys pressed in the terminal"))
+            .arg(Arg::with_name("clear")
+                 .long("clear")
+                 .help("Clear the terminal and print a header before each run"))
+            .arg(Arg::with_name("quiet")
+                 .long("quiet")
+                 .short("q")
+                 .help("Do not print the output of the tests, only the results"))
+            .arg(Arg::with_name("ui")
+                 .long("ui")
+                 .conflicts_with("output")
+                 .help("Show a full-screen terminal UI with the output, the failing tests and the recent runs"))
+            .arg(Arg::with_name("pick")
+                 .long("pick")
+                 .conflicts_with("once")
+                 .help("Pick the tests to run from a list before watching, press `a` to run all tests again"))
+            .arg(Arg::with_name("output")
+                 .long("output")
+                 .takes_value(true)
+                 .possible_values(&["human", "json"])
+                 .help("Print one JSON line per event (run_started, stdout_line, run_finished) instead of human readable output"))
+            .arg(Arg::with_name("listen")
+                 .long("listen")
+                 .takes_value(true)
+                 .value_name("ADDRESS")
+                 .help("Serve the latest result at GET /status and run tests on POST /run, e.g. 127.0.0.1:7878"))
+            .arg(Arg::with_name("annotate")
+                 .long("annotate")
+                 .takes_value(true)
+                 .possible_values(&["github"])
+                 .help("Print annotations for compile errors and failed tests, e.g. for GitHub Actions"))
+            .arg(Arg::with_name("junit")
+                 .long("junit")
+                 .takes_value(true)
+                 .value_name("PATH")
+                 .help("Write a JUnit XML report of every run to the file, relative to the project root"))
+            .arg(Arg::with_name("once")
+                 .long("once")
+                 .help("Run tests a single time, notify about the result and exit with the exit code of the test command"))
+            .arg(Arg::with_name("notifier")
+                 .long("notifier")
+                 .takes_value(true)
+                 .use_delimiter(true)
+                 .possible_values(&["auto", "desktop", "slack", "discord", "telegram", "email", "ntfy", "gotify", "mqtt", "exec", "http", "console", "none"])
+                 .help("How to notify about the result, or comma separated notifiers which all get it (overrides `notifier` in .testify.toml)"))
+            .arg(Arg::with_name("notify")
+                 .long("notify")
+                 .takes_value(true)
+                 .possible_values(&["always", "changes"])
+                 .help("Notify after every run, or only when tests start or stop passing"))
+            .arg(Arg::with_name("skip_when_focused")
+                 .long("skip-when-focused")
+                 .help("Skip desktop notifications while this terminal has focus"))
+            .arg(Arg::with_name("cargo_arg")
+                 .long("cargo-arg")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .allow_hyphen_values(true)
+                 .value_name("ARG")
+                 .help("Argument passed to `cargo test`, e.g. `--cargo-arg --lib` (overrides `cargo_test_args` in .testify.toml)"))
+            .arg(Arg::with_name("test_args")
+                 .multiple(true)
+                 .last(true)
+                 .value_name("ARGS")
+                 .help("Arguments after `--` are passed to the test binary, e.g. `-- --nocapture --test-threads=1`"))
+            .subcommand(
+                SubCommand::with_name("history")
+                .about("Print recent test runs and statistics")
+                .arg(Arg::with_name("limit")
+                     .long("limit")
+                     .short("n")
+                     .takes_value(true)
+                     .value_name("COUNT")
+                     .default_value("10")
+                     .help("Number of recent runs to print")))
+            .subcommand(
+                SubCommand::with_name("daemon")
+                .about("Watch the project in the background, controlled by the status, run-now and stop subcommands"))
+            .subcommand(
+                SubCommand::with_name("status")
+                .about("Print the state of the daemon and its latest run")
+                .arg(Arg::with_name("json")
+                     .long("json")
+                     .help("Print the state as a JSON line")))
+            .subcommand(
+                SubCommand::with_name("run-now")
+                .about("Make the daemon run the tests now"))
+            .subcommand(
+                SubCommand::with_name("stop")
+                .about("Stop the daemon"))
+            .subcommand(
+                SubCommand::with_name("flaky")
+                .about("Print tests which fail intermittently, according to the recorded runs")
+                .arg(Arg::with_name("limit")
+                     .long("limit")
+                     .short("n")
+                     .takes_value(true)
+                     .value_name("COUNT")
+                     .default_value("10")
+                     .help("Number of tests to print")))
+            .subcommand(
+                SubCommand::with_name("init")
+                .about("Write an annotated .testify.toml with the settings detected for the project"))
+            .subcommand(
+                SubCommand::with_name("doctor")
+                .about("Check the notifications, the inotify limits and the tools testify runs, and suggest fixes"))
+            .subcommand(
+                SubCommand::with_name("secret")
+                .about("Keep webhook URLs, tokens and passwords in the credential store of the OS, referenced as \"keyring:<name>\" in .testify.toml")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("set")
+                    .about("Store a secret, typed in or read from stdin")
+                    .arg(Arg::with_name("name")
+                         .required(true)
+                         .help("Name of the secret, e.g. slack_webhook")))
+                .subcommand(
+                    SubCommand::with_name("delete")
+                    .about("Delete a secret")
+                    .arg(Arg::with_name("name")
+                         .required(true)
+                         .help("Name of the secret"))))
+            .subcommand(
+                SubCommand::with_name("completions")
+                .about("Print a completion script for the shell")
+                .arg(Arg::with_name("shell")
+                     .required(true)
+                     .possible_values(&Shell::variants())
+                     .help("Shell to complete in")))
         )
-        .get_matches();
+}
+
+/// Arguments of `cargo test` given with `--cargo-arg`, and the ones of the test binary, which are
+/// everything after `--`: `cargo testify --cargo-arg --lib -- --nocapture`.
+fn command_line_args(matches: &ArgMatches) -> (Option<Vec<String>>, Option<Vec<String>>) {
+    let values = |name| matches.values_of(name).map(|vals| vals.map(String::from).collect());
+    (values("cargo_arg"), values("test_args"))
+}
 
-    let cargo_test_args =
-        if let Some(matches) = matches.subcommand_matches("testify") {
-            matches.values_of("cargo_test_args").map(|vals| vals.collect::<Vec<_>>()).unwrap_or(vec![])
-        } else {
-            vec![]
+/// Directory of the `Cargo.toml` given with `--manifest-path`, or else search for Cargo.toml
+/// file starting from the current directory, going with every step to parent directory.
+/// If directory with Cargo.toml is found return it, otherwise print error message and
+/// terminate the process.
+fn detect_project_dir(manifest_path: Option<&str>) -> std::path::PathBuf {
+    if let Some(manifest_path) = manifest_path {
+        let path = std::path::Path::new(manifest_path);
+        if path.file_name().is_none_or(|name| name != "Cargo.toml") {
+            eprintln!("Error: the manifest path must point to a `Cargo.toml` file, got {:?}", path);
+            std::process::exit(1);
+        }
+        return match path.canonicalize() {
+            Ok(path) => path.parent().map(std::path::Path::to_path_buf).unwrap_or(path),
+            Err(err) => {
+                eprintln!("Error: failed to read manifest {:?}: {}", path, err);
+                std::process::exit(1);
+            }
         };
+    }
+    let current_dir = std::env::current_dir().unwrap_or_else(|err| {
+        eprintln!("Error: failed to get the current directory: {}", err);
+        std::process::exit(1);
+    });
+    manifest::find_package_dir(&current_dir).unwrap_or_else(|| {
+        eprintln!("Error: could not find `Cargo.toml` in {:?} or any parent directory.", current_dir);
+        std::process::exit(1);
+    })
+}
 
-    let project_dir = detect_project_dir();
-    let config = ConfigBuilder::new()
-        .project_dir(project_dir)
-        .cargo_test_args(cargo_test_args)
-        .build()
-        .unwrap();
+/// Directory of the project given with `--project`: the nearest one containing `Cargo.toml`,
+/// starting from `dir`. Prints an error and terminates the process if there is none.
+fn find_project_dir(dir: &str) -> std::path::PathBuf {
+    let dir = std::path::Path::new(dir).canonicalize().unwrap_or_else(|err| {
+        eprintln!("Error: failed to read project directory {:?}: {}", dir, err);
+        std::process::exit(1);
+    });
+    manifest::find_package_dir(&dir).unwrap_or_else(|| {
+        eprintln!("Error: could not find `Cargo.toml` in {:?} or any parent directory.", dir);
+        std::process::exit(1);
+    })
+}
 
-    Reactor::new(config).start()
+/// Print the error together with its causes and terminate the process.
+fn exit_with_error(err: errors::Error) -> ! {
+    eprintln!("Error: {}", err);
+    for cause in err.iter().skip(1) {
+        eprintln!("  caused by: {}", cause);
+    }
+    std::process::exit(1);
 }
 
-/// Search for Cargo.toml file starting from the current directory,
-/// going with every step to parent directory. If directory with
-/// Cargo.toml is found return it, otherwise print error message and
-/// terminate the process.
-fn detect_project_dir() -> std::path::PathBuf {
-    let current_dir = std::env::current_dir().expect("Failed to get current directory");
-    let mut optional_dir = Some(current_dir.as_path());
 
-    while let Some(dir) = optional_dir {
-        let cargo_toml = dir.join("Cargo.toml");
-        if cargo_toml.is_file() { return dir.to_path_buf(); }
-        optional_dir = dir.parent();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(command_line: &[&str]) -> (Option<Vec<String>>, Option<Vec<String>>) {
+        let matches = app().get_matches_from(command_line);
+        command_line_args(matches.subcommand_matches("testify").unwrap())
     }
 
-    eprintln!("Error: could not find `Cargo.toml` in {:?} or any parent directory.", current_dir);
-    std::process::exit(1);
+    #[test]
+    fn test_command_line_args() {
+        let strings = |args: &[&str]| Some(args.iter().map(|arg| arg.to_string()).collect::<Vec<String>>());
+        assert_eq!(args(&["cargo", "testify", "--", "--nocapture", "--test-threads=1"]),
+                   (None, strings(&["--nocapture", "--test-threads=1"])));
+        assert_eq!(args(&["cargo", "testify", "--cargo-arg", "--lib", "--cargo-arg=--features=cli", "--", "--nocapture"]),
+                   (strings(&["--lib", "--features=cli"]), strings(&["--nocapture"])));
+        assert_eq!(args(&["cargo", "testify", "--cargo-arg", "--lib"]), (strings(&["--lib"]), None));
+    }
 }
-
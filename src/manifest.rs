@@ -0,0 +1,75 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml;
+
+use filter::normalize;
+
+/// Directory of the nearest `Cargo.toml`, starting from `dir` and going up.
+pub fn find_package_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join("Cargo.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Root of the workspace the package in `package_dir` belongs to, the package itself if it's the root,
+/// or `None` if it's not in a workspace: either the one set with `package.workspace`, or the nearest
+/// manifest with a `[workspace]` section which does not exclude the package.
+pub fn workspace_root(package_dir: &Path) -> Option<PathBuf> {
+    let explicit = read(package_dir)
+        .and_then(|manifest| manifest.get("package")?.get("workspace")?.as_str().map(String::from));
+    if let Some(path) = explicit {
+        return Some(normalize(&package_dir.join(path)));
+    }
+    package_dir.ancestors()
+        .filter_map(|ancestor| read(ancestor).map(|manifest| (ancestor, manifest)))
+        .find(|(_, manifest)| manifest.get("workspace").is_some())
+        .filter(|&(root, ref manifest)| !is_excluded(package_dir, root, manifest))
+        .map(|(root, _)| root.to_path_buf())
+}
+
+/// Parsed `Cargo.toml` in `dir`, `None` if it's missing or invalid.
+pub fn read(dir: &Path) -> Option<toml::Value> {
+    let content = fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    toml::from_str(&content).ok()
+}
+
+fn is_excluded(package_dir: &Path, root: &Path, manifest: &toml::Value) -> bool {
+    manifest.get("workspace")
+        .and_then(|workspace| workspace.get("exclude"))
+        .and_then(toml::Value::as_array)
+        .is_some_and(|exclude| {
+            exclude.iter()
+                .filter_map(toml::Value::as_str)
+                .any(|dir| package_dir.starts_with(normalize(&root.join(dir))))
+        })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_workspace_root() {
+        let root = env::temp_dir().join("testify-manifest-test");
+        let _ = fs::remove_dir_all(&root);
+        write(&root.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"tools\"]\n");
+        write(&root.join("crates/app/Cargo.toml"), "[package]\nname = \"app\"\n");
+        write(&root.join("tools/gen/Cargo.toml"), "[package]\nname = \"gen\"\n");
+        write(&root.join("nested/Cargo.toml"), "[package]\nname = \"nested\"\nworkspace = \"..\"\n");
+
+        assert_eq!(find_package_dir(&root.join("crates/app/src/parser")), Some(root.join("crates/app")));
+        assert_eq!(workspace_root(&root.join("crates/app")), Some(root.clone()));
+        assert_eq!(workspace_root(&root), Some(root.clone()));
+        assert_eq!(workspace_root(&root.join("nested")), Some(root.clone()));
+        assert_eq!(workspace_root(&root.join("tools/gen")), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
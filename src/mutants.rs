@@ -0,0 +1,135 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use process_tree::{Output, ProcessTree};
+use report::{Outcome, Report};
+
+/// cargo-mutants writes its results into `mutants.out` in this directory, relative to the project root.
+const OUTPUT_DIR: &str = ".testify";
+
+/// Mutants no test has caught, one per line, e.g. `src/lib.rs:3:5: replace add -> i32 with 0`.
+const MISSED_FILE: &str = ".testify/mutants.out/missed.txt";
+
+/// Exit code of cargo-mutants when some mutants were not caught.
+const MISSED_EXIT_CODE: i32 = 2;
+
+/// How many surviving mutants are listed in a notification body.
+const LISTED_MUTANTS: usize = 3;
+
+/// How often the background job is checked for having finished.
+const POLL_INTERVAL_MS: u64 = 500;
+
+/// `cargo mutants` running in the background with the lowest priority.
+pub struct Job {
+    tree: Arc<Mutex<ProcessTree>>,
+    cancelled: Arc<AtomicBool>
+}
+
+impl Job {
+    /// Kill cargo-mutants together with the builds and tests it runs. Nothing is reported.
+    pub fn cancel(self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let _ = self.tree.lock().unwrap().kill();
+    }
+}
+
+/// Start `cargo mutants` for `file` (relative to the project root). Once it finds mutants
+/// which survive the tests, `on_missed` is called from another thread with their report.
+pub fn spawn<F>(project_dir: &Path, file: &Path, on_missed: F) -> io::Result<Job>
+    where F: FnOnce(Report) + Send + 'static
+{
+    let (tree, output) = ProcessTree::spawn(&command_line(file), &[], project_dir)?;
+    // Progress is not shown, but the pipes have to be drained so cargo-mutants does not block
+    match output {
+        Output::Pipes(stdout, stderr) => {
+            discard(stdout);
+            discard(stderr);
+        },
+        Output::Terminal(output) => discard(output)
+    }
+
+    let job = Job { tree: Arc::new(Mutex::new(tree)), cancelled: Arc::new(AtomicBool::new(false)) };
+    let tree = job.tree.clone();
+    let cancelled = job.cancelled.clone();
+    let missed_path = project_dir.join(MISSED_FILE);
+    thread::spawn(move || {
+        let exit_code = loop {
+            match tree.lock().unwrap().try_wait() {
+                Ok(Some(exit_code)) => break exit_code,
+                Ok(None) => {},
+                Err(_) => return
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+        };
+        if cancelled.load(Ordering::SeqCst) { return; }
+        match exit_code {
+            Some(0) => {},
+            Some(MISSED_EXIT_CODE) => match fs::read_to_string(&missed_path) {
+                Ok(missed) => on_missed(report(&missed)),
+                Err(err) => eprintln!("Warning: failed to read {:?}: {}", missed_path, err)
+            },
+            Some(exit_code) => eprintln!("Warning: `cargo mutants` has failed with exit code {}", exit_code),
+            None => eprintln!("Warning: `cargo mutants` has been killed")
+        }
+    });
+    Ok(job)
+}
+
+/// `cargo mutants` testing only the mutants of `file`, with the lowest CPU priority,
+/// so it does not slow down the test runs.
+fn command_line(file: &Path) -> Vec<String> {
+    let mut command_line: Vec<String> = if cfg!(target_os = "windows") {
+        vec![]
+    } else {
+        vec!["nice".to_string(), "-n".to_string(), "19".to_string()]
+    };
+    command_line.extend(vec!["cargo", "mutants", "--file"].into_iter().map(String::from));
+    command_line.push(file.to_string_lossy().into_owned());
+    command_line.push("--output".to_string());
+    command_line.push(OUTPUT_DIR.to_string());
+    command_line
+}
+
+/// Report of the mutants listed in `missed.txt`.
+fn report(missed: &str) -> Report {
+    let mutants: Vec<&str> = missed.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let mut detail: Vec<String> = mutants.iter().take(LISTED_MUTANTS).map(|mutant| mutant.to_string()).collect();
+    if mutants.len() > LISTED_MUTANTS {
+        detail.push(format!("and {} more", mutants.len() - LISTED_MUTANTS));
+    }
+    Report::new(Outcome::MutantsMissed(mutants.len()), Some(detail.join("\n")))
+}
+
+fn discard(mut output: Box<dyn Read + Send>) {
+    thread::spawn(move || { let _ = io::copy(&mut output, &mut io::sink()); });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let command_line = command_line(Path::new("src/lib.rs"));
+        assert_eq!(&command_line[command_line.len() - 6..], &["cargo", "mutants", "--file", "src/lib.rs", "--output", ".testify"]);
+    }
+
+    #[test]
+    fn test_report() {
+        let single = report("src/lib.rs:3:5: replace add -> i32 with 0\n");
+        assert_eq!(single.title(), "1 mutant survived");
+        assert_eq!(single.detail.unwrap(), "src/lib.rs:3:5: replace add -> i32 with 0");
+
+        let missed = "src/lib.rs:3:5: replace add -> i32 with 0\nsrc/lib.rs:3:7: replace + with - in add\n\
+                      src/lib.rs:3:7: replace + with * in add\nsrc/lib.rs:8:5: replace is_even -> bool with true\n";
+        let several = report(missed);
+        assert_eq!(several.outcome, Outcome::MutantsMissed(4));
+        assert_eq!(several.detail.unwrap().lines().last(), Some("and 1 more"));
+    }
+}
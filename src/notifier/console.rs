@@ -0,0 +1,127 @@
+use std::env;
+use std::io::{self, Write};
+
+use strip_ansi_escapes;
+
+use config::Sounds;
+use report::{Outcome, Report};
+use errors::*;
+use super::Notify;
+
+/// Escape sequence which starts an OSC command.
+const OSC: &str = "\x1B]";
+
+/// Escape sequence which terminates an OSC command.
+const ST: &str = "\x1B\\";
+
+/// Which escape sequence the terminal understands as a notification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Protocol {
+    /// OSC 9, supported by iTerm2, WezTerm, Windows Terminal and ConEmu
+    Osc9,
+
+    /// OSC 99 of kitty
+    Kitty,
+
+    /// OSC 777, supported by rxvt-unicode, foot and VTE based terminals
+    Osc777
+}
+
+/// Notification within the terminal itself: the bell is rung for a failed run, and the
+/// terminal is asked to show a notification with an escape sequence.
+/// Used when there is no desktop to notify on, e.g. in an SSH session or a container.
+pub struct ConsoleNotifier {
+    protocol: Protocol,
+    /// The terminal can only ring the bell, it's rung for any sound chosen for the outcome
+    sounds: Sounds,
+    /// Wrap escape sequences, so tmux passes them through to the terminal
+    tmux: bool
+}
+
+impl ConsoleNotifier {
+    pub fn new(sounds: Sounds) -> Self {
+        Self { protocol: detect_protocol(), sounds, tmux: env::var_os("TMUX").is_some() }
+    }
+
+    fn sequence(&self, report: &Report) -> String {
+        let title = sanitize(&report.title());
+        let body = report.body().map(|body| sanitize(&body));
+        let sequence = match self.protocol {
+            Protocol::Osc9 => match body {
+                Some(body) => format!("{}9;{}: {}{}", OSC, title, body, ST),
+                None => format!("{}9;{}{}", OSC, title, ST)
+            },
+            Protocol::Kitty => match body {
+                Some(body) => format!("{}99;i=1:d=0;{}{}{}99;i=1:d=1:p=body;{}{}", OSC, title, ST, OSC, body, ST),
+                None => format!("{}99;;{}{}", OSC, title, ST)
+            },
+            Protocol::Osc777 => format!("{}777;notify;{};{}{}", OSC, title.replace(';', ","), body.unwrap_or_default(), ST)
+        };
+        if self.tmux { tmux_passthrough(&sequence) } else { sequence }
+    }
+}
+
+impl Notify for ConsoleNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let mut output = self.sequence(report);
+        let bell = if report.outcome.is_success() || report.outcome == Outcome::NoTests { None } else { Some("bell") };
+        if self.sounds.choose(report.outcome, bell).is_some() {
+            output.push('\x07');
+        }
+        // stderr, so the notification does not get into the JSON output
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        stderr.write_all(output.as_bytes())
+            .and_then(|_| stderr.flush())
+            .chain_err(|| ErrorKind::NotificationFailed("failed to write to the terminal".to_string()))
+    }
+}
+
+fn detect_protocol() -> Protocol {
+    let term = env::var("TERM").unwrap_or_default();
+    if env::var_os("KITTY_WINDOW_ID").is_some() || term == "xterm-kitty" {
+        Protocol::Kitty
+    } else if term.starts_with("rxvt") || term.starts_with("foot") || env::var_os("VTE_VERSION").is_some() {
+        Protocol::Osc777
+    } else {
+        Protocol::Osc9
+    }
+}
+
+/// Put the text on a single line, without colors and control characters which would end the sequence.
+fn sanitize(text: &str) -> String {
+    let text = String::from_utf8_lossy(&strip_ansi_escapes::strip(text.as_bytes())).into_owned();
+    text.split('\n')
+        .map(|line| line.chars().filter(|c| !c.is_control()).collect::<String>())
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
+
+/// Wrap `sequence` into a DCS passthrough sequence of tmux, escaping its ESC characters.
+fn tmux_passthrough(sequence: &str) -> String {
+    format!("\x1BPtmux;{}\x1B\\", sequence.replace('\x1B', "\x1B\x1B"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::Outcome;
+
+    fn report() -> Report {
+        Report::new(Outcome::TestsFailed, Some("1 passed; 1 failed\n\x1B[31m✗ a".to_string()))
+    }
+
+    #[test]
+    fn test_sequence() {
+        let notifier = ConsoleNotifier { protocol: Protocol::Osc9, sounds: Sounds::default(), tmux: false };
+        assert_eq!(notifier.sequence(&report()), "\x1B]9;Tests failed: 1 passed; 1 failed · ✗ a\x1B\\");
+
+        let notifier = ConsoleNotifier { protocol: Protocol::Osc777, sounds: Sounds::default(), tmux: false };
+        assert_eq!(notifier.sequence(&report()), "\x1B]777;notify;Tests failed;1 passed; 1 failed · ✗ a\x1B\\");
+
+        let notifier = ConsoleNotifier { protocol: Protocol::Osc9, sounds: Sounds::default(), tmux: true };
+        let passed = Report::new(Outcome::TestsPassed, None);
+        assert_eq!(notifier.sequence(&passed), "\x1BPtmux;\x1B\x1B]9;Tests passed\x1B\x1B\\\x1B\\");
+    }
+}
@@ -0,0 +1,226 @@
+#[cfg(not(target_os = "windows"))]
+use std::thread;
+#[cfg(not(target_os = "windows"))]
+use std::sync::Arc;
+#[cfg(not(target_os = "windows"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_os = "windows"))]
+use notify_rust::{Notification, NotificationHint};
+#[cfg(target_os = "windows")]
+use std::process::{Command, Stdio};
+#[cfg(target_os = "windows")]
+use tauri_winrt_notification::{Duration, Sound, Toast};
+
+use config::Sounds;
+use report::{Outcome, Report};
+use errors::*;
+use super::{Action, ActionHandler, Notify};
+
+/// Identifier of the "Run again" action of DBus notifications.
+#[cfg(not(target_os = "windows"))]
+const RERUN_ACTION: &str = "rerun";
+
+/// Identifier of the "Accept snapshots" action, offered when snapshots of insta have changed.
+#[cfg(not(target_os = "windows"))]
+const ACCEPT_SNAPSHOTS_ACTION: &str = "accept-snapshots";
+
+/// AppUserModelID toasts are sent with, registered for the current user on start.
+#[cfg(target_os = "windows")]
+const APP_ID: &'static str = "CargoTestify.CargoTestify";
+
+/// Tag and group of toasts, a toast replaces the previous one with the same tag and group.
+#[cfg(target_os = "windows")]
+const TOAST_TAG: &'static str = "run";
+#[cfg(target_os = "windows")]
+const TOAST_GROUP: &'static str = "cargo-testify";
+
+/// Native desktop notification: DBus on Linux and toast on Windows.
+/// On macOS `MacosNotifier` is used instead, and under WSL `WslNotifier`.
+/// Every notification replaces the previous one, so they do not pile up during a long session.
+pub struct DesktopNotifier {
+    sounds: Sounds,
+    /// Receives clicks on notification buttons
+    actions: Option<ActionHandler>,
+    /// DBus id of the latest notification, 0 before the first one
+    #[cfg(not(target_os = "windows"))]
+    last_id: Arc<AtomicUsize>
+}
+
+#[cfg(not(target_os = "windows"))]
+impl DesktopNotifier {
+    pub fn new(sounds: Sounds) -> Self {
+        DesktopNotifier { sounds, actions: None, last_id: Arc::new(AtomicUsize::new(0)) }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Notify for DesktopNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let icon = match report.outcome {
+            Outcome::TestsPassed => "face-angel",
+            Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) | Outcome::MutantsMissed(_) => "face-worried",
+            Outcome::TestsFailed | Outcome::CompileError | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::TimedOut | Outcome::Unknown => "face-angry",
+            Outcome::NoTests => "face-plain"
+        };
+        let mut notification = Notification::new()
+            .summary(&report.title())
+            .icon(icon)
+            .finalize();
+        if let Some(body) = report.body() {
+            notification.body(&body);
+        }
+        let last_id = self.last_id.load(Ordering::SeqCst);
+        if last_id != 0 {
+            notification.id(last_id as u32);
+        }
+        // Sounds are played by the notification daemon, it's silent by default
+        match self.sounds.choose(report.outcome, None) {
+            Some(sound) if Sounds::is_file(sound) => notification.hint(NotificationHint::SoundFile(sound.to_string())),
+            Some(sound) => notification.hint(NotificationHint::SoundName(sound.to_string())),
+            None => notification.hint(NotificationHint::SuppressSound(true))
+        };
+
+        let handler = match self.actions {
+            Some(ref handler) if !report.outcome.is_success() && report.outcome != Outcome::NoTests => handler.clone(),
+            _ => {
+                return notification
+                    .show()
+                    .map(|handle| self.last_id.store(handle.id() as usize, Ordering::SeqCst))
+                    .map_err(|err| ErrorKind::NotificationFailed(format!("{:?}", err)).into());
+            }
+        };
+        notification.action(RERUN_ACTION, "Run again");
+        if !report.snapshots.is_empty() {
+            notification.action(ACCEPT_SNAPSHOTS_ACTION, "Accept snapshots");
+        }
+        // Waiting for the action blocks until the notification is closed, and the DBus connection
+        // can not be passed to another thread, so the notification is shown by the waiting thread.
+        let last_id = self.last_id.clone();
+        thread::spawn(move || {
+            match notification.show() {
+                Ok(handle) => {
+                    last_id.store(handle.id() as usize, Ordering::SeqCst);
+                    handle.wait_for_action(|action| {
+                        match action {
+                            RERUN_ACTION => handler(Action::Rerun),
+                            ACCEPT_SNAPSHOTS_ACTION => handler(Action::AcceptSnapshots),
+                            _ => {}
+                        }
+                    });
+                },
+                Err(err) => eprintln!("Warning: {}", Error::from(ErrorKind::NotificationFailed(format!("{:?}", err))))
+            }
+        });
+        Ok(())
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        self.actions = Some(handler);
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl DesktopNotifier {
+    pub fn new(sounds: Sounds) -> Self {
+        register_app_id();
+        DesktopNotifier { sounds: sounds, actions: None }
+    }
+
+    /// Toast sound for the outcome. Toasts can play only system sounds, so a sound file
+    /// is played separately and the toast is left silent.
+    fn sound(&self, outcome: Outcome) -> Option<Sound> {
+        let default = match outcome {
+            Outcome::TestsPassed | Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) | Outcome::CompileError | Outcome::NoTests => None,
+            Outcome::TestsFailed | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::MutantsMissed(_) | Outcome::TimedOut | Outcome::Unknown => Some("SMS")
+        };
+        match self.sounds.choose(outcome, default) {
+            Some(sound) if Sounds::is_file(sound) => {
+                play_file(sound);
+                None
+            },
+            Some(sound) => match sound.parse() {
+                Ok(sound) => Some(sound),
+                Err(_) => {
+                    eprintln!("Warning: unknown sound {:?}, playing the default one", sound);
+                    Some(Sound::Default)
+                }
+            },
+            None => None
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+impl Notify for DesktopNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let icon = match report.outcome {
+            Outcome::TestsPassed => "🔵",
+            Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) | Outcome::MutantsMissed(_) => "🟡",
+            Outcome::TestsFailed | Outcome::CompileError | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::TimedOut | Outcome::Unknown => "🔴",
+            Outcome::NoTests => "⚪"
+        };
+        let mut toast = Toast::new(APP_ID)
+            .title(&format!("{} {}", report.title(), icon))
+            .text1(&report.body().unwrap_or_default())
+            .sound(self.sound(report.outcome))
+            .duration(Duration::Short)
+            .tag(TOAST_TAG)
+            .group(TOAST_GROUP);
+        if let Some(ref handler) = self.actions {
+            let handler = handler.clone();
+            toast = toast
+                .add_button("Re-run tests", "rerun")
+                .add_button("Open output", "open-output");
+            if !report.snapshots.is_empty() {
+                toast = toast.add_button("Accept snapshots", "accept-snapshots");
+            }
+            toast = toast
+                .on_activated(move |argument| {
+                    match argument.as_ref().map(String::as_str) {
+                        Some("rerun") => handler(Action::Rerun),
+                        Some("open-output") => handler(Action::OpenOutput),
+                        Some("accept-snapshots") => handler(Action::AcceptSnapshots),
+                        _ => {}
+                    }
+                    Ok(())
+                });
+        }
+        toast
+            .show()
+            .map_err(|err| ErrorKind::NotificationFailed(format!("{:?}", err)).into())
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        self.actions = Some(handler);
+    }
+}
+
+/// Register the AppUserModelID for the current user, so toasts are shown with the name of
+/// testify and can be activated from the Action Center.
+#[cfg(target_os = "windows")]
+fn register_app_id() {
+    let key = format!("HKCU\\Software\\Classes\\AppUserModelId\\{}", APP_ID);
+    let status = Command::new("reg")
+        .args(&["add", &key, "/v", "DisplayName", "/t", "REG_SZ", "/d", "cargo-testify", "/f"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(ref status) if status.success() => {},
+        _ => eprintln!("Warning: failed to register AppUserModelID {}", APP_ID)
+    }
+}
+
+/// Play a sound file in the background with the .NET `SoundPlayer`.
+#[cfg(target_os = "windows")]
+fn play_file(path: &str) {
+    let script = format!("(New-Object Media.SoundPlayer '{}').PlaySync()", path.replace('\'', "''"));
+    let result = Command::new("powershell")
+        .args(&["-NoProfile", "-NonInteractive", "-Command", &script])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(err) = result {
+        eprintln!("Warning: failed to play {}: {}", path, err);
+    }
+}
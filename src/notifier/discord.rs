@@ -0,0 +1,81 @@
+use ureq;
+
+use report::{Outcome, Report};
+use errors::*;
+use super::{Notify, truncate};
+
+/// Maximum length of the failing test summary included into an embed.
+const MAX_DETAIL_LENGTH: usize = 1500;
+
+const GREEN: u32 = 0x2e_cc_71;
+const YELLOW: u32 = 0xf1_c4_0f;
+const RED: u32 = 0xe7_4c_3c;
+const GREY: u32 = 0x95_a5_a6;
+
+/// Posts reports to a Discord channel using a webhook, as a green, yellow or red embed.
+pub struct DiscordNotifier {
+    webhook_url: String,
+    project: String
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String, project: String) -> Self {
+        Self { webhook_url, project }
+    }
+
+    fn embed(&self, report: &Report) -> ::serde_json::Value {
+        let color = match report.outcome {
+            Outcome::TestsPassed => GREEN,
+            Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) | Outcome::MutantsMissed(_) => YELLOW,
+            Outcome::TestsFailed | Outcome::CompileError | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::TimedOut | Outcome::Unknown => RED,
+            Outcome::NoTests => GREY
+        };
+        let mut embed = json!({
+            "title": format!("{}: {}", self.project, report.title()),
+            "color": color
+        });
+        let mut details: Vec<String> = report.detail.iter().cloned().collect();
+        for failure in &report.failures {
+            match failure.message {
+                Some(ref panic_message) => details.push(format!("{}: {}", failure.name, panic_message)),
+                None => details.push(failure.name.clone())
+            }
+        }
+        if !details.is_empty() {
+            embed["description"] = json!(format!("```\n{}\n```", truncate(&details.join("\n"), MAX_DETAIL_LENGTH)));
+        }
+        embed
+    }
+}
+
+impl Notify for DiscordNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        ureq::post(&self.webhook_url)
+            .send_json(json!({ "embeds": [self.embed(report)] }))
+            .chain_err(|| ErrorKind::NotificationFailed("failed to post to Discord webhook".to_string()))?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::TestFailure;
+
+    #[test]
+    fn test_embed() {
+        let notifier = DiscordNotifier::new("https://discord.com/api/webhooks/1/x".to_string(), "app".to_string());
+
+        let mut report = Report::new(Outcome::TestsFailed, Some("2 passed; 1 failed".to_string()));
+        report.failures = vec![TestFailure { name: "tests::bad".to_string(), message: Some("boom".to_string()), location: None }];
+        assert_eq!(notifier.embed(&report), json!({
+            "title": "app: Tests failed",
+            "color": RED,
+            "description": "```\n2 passed; 1 failed\ntests::bad: boom\n```"
+        }));
+
+        let report = Report::new(Outcome::TestsPassed, None);
+        assert_eq!(notifier.embed(&report), json!({ "title": "app: Tests passed", "color": GREEN }));
+    }
+}
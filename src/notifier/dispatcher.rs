@@ -0,0 +1,104 @@
+use config::Route;
+use report::Report;
+use errors::*;
+use super::{ActionHandler, Notify};
+
+/// Delivers reports to several notifiers, e.g. desktop and Slack, each of them may be limited
+/// to some outcomes. A failing notifier does not keep the report from the others, its error
+/// is reported after all have been tried.
+#[derive(Default)]
+pub struct Dispatcher {
+    targets: Vec<Target>
+}
+
+struct Target {
+    /// Name of the notifier, used in error messages
+    name: &'static str,
+    notifier: Box<dyn Notify>,
+    /// Outcomes the notifier gets reports of, all if it's `None`
+    route: Option<Route>
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deliver reports to `notifier`, only the ones with outcomes of `route` if it's given.
+    pub fn add(mut self, name: &'static str, notifier: Box<dyn Notify>, route: Option<Route>) -> Self {
+        self.targets.push(Target { name, notifier, route });
+        self
+    }
+}
+
+impl Notify for Dispatcher {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let errors: Vec<String> = self.targets.iter()
+            .filter(|target| target.route.as_ref().map(|route| route.matches(report.outcome)).unwrap_or(true))
+            .filter_map(|target| target.notifier.notify(report).err().map(|err| format!("{} notifier: {}", target.name, err)))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ErrorKind::NotifiersFailed(errors.join("; ")).into())
+        }
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        for target in &mut self.targets {
+            target.notifier.on_action(handler.clone());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use config::NotifierKind;
+    use report::Outcome;
+
+    struct CountingNotifier {
+        calls: Rc<Cell<usize>>,
+        fails: bool
+    }
+
+    impl Notify for CountingNotifier {
+        fn notify(&self, _report: &Report) -> Result<()> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fails {
+                return Err(ErrorKind::NotificationFailed("webhook is gone".to_string()).into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failing_notifier_is_isolated() {
+        let slack_calls = Rc::new(Cell::new(0));
+        let desktop_calls = Rc::new(Cell::new(0));
+        let dispatcher = Dispatcher::new()
+            .add("slack", Box::new(CountingNotifier { calls: slack_calls.clone(), fails: true }), None)
+            .add("desktop", Box::new(CountingNotifier { calls: desktop_calls.clone(), fails: false }), None);
+        let err = dispatcher.notify(&Report::new(Outcome::TestsFailed, None)).unwrap_err();
+        assert_eq!(err.to_string(), "slack notifier: failed to send notification: webhook is gone");
+        assert_eq!(slack_calls.get(), 1);
+        assert_eq!(desktop_calls.get(), 1);
+    }
+
+    #[test]
+    fn test_routing() {
+        let slack_calls = Rc::new(Cell::new(0));
+        let desktop_calls = Rc::new(Cell::new(0));
+        let route = Route { notifier: NotifierKind::Slack, outcomes: vec!["failures".to_string()] };
+        let dispatcher = Dispatcher::new()
+            .add("slack", Box::new(CountingNotifier { calls: slack_calls.clone(), fails: false }), Some(route))
+            .add("desktop", Box::new(CountingNotifier { calls: desktop_calls.clone(), fails: false }), None);
+        dispatcher.notify(&Report::new(Outcome::TestsPassed, None)).unwrap();
+        dispatcher.notify(&Report::new(Outcome::CompileError, None)).unwrap();
+        assert_eq!(slack_calls.get(), 1);
+        assert_eq!(desktop_calls.get(), 2);
+    }
+}
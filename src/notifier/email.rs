@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::PathBuf;
+
+use lettre::{Message, SmtpTransport, Transport};
+use lettre::message::Mailbox;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+
+use config::{Email, SmtpSecurity};
+use report::Report;
+use errors::*;
+use super::Notify;
+
+/// Only the end of longer output is sent, mail servers refuse big messages.
+const MAX_OUTPUT_LENGTH: usize = 1_000_000;
+
+/// Emails reports through an SMTP server, with the whole output of the command when the run
+/// has not succeeded. Meant for testify running unattended on a server.
+pub struct EmailNotifier {
+    mailer: SmtpTransport,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+    project: String,
+    /// Where the reactor saves the output of the latest command
+    output_path: PathBuf
+}
+
+impl EmailNotifier {
+    pub fn new(email: &Email, project: String, output_path: PathBuf) -> Result<Self> {
+        let host = email.host.as_ref().ok_or(ErrorKind::SettingMissing("email.host"))?;
+        let from = email.from.as_ref().ok_or(ErrorKind::SettingMissing("email.from"))?;
+        let to = email.to.as_ref().filter(|to| !to.is_empty()).ok_or(ErrorKind::SettingMissing("email.to"))?;
+
+        let mut builder = match email.security.unwrap_or(SmtpSecurity::StartTls) {
+            SmtpSecurity::StartTls => SmtpTransport::starttls_relay(host).map_err(|err| invalid_setting(err.to_string()))?,
+            SmtpSecurity::Tls => SmtpTransport::relay(host).map_err(|err| invalid_setting(err.to_string()))?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host)
+        };
+        if let Some(port) = email.port {
+            builder = builder.port(port);
+        }
+        if let Some(ref username) = email.username {
+            builder = builder.credentials(Credentials::new(username.clone(), email.password.clone().unwrap_or_default()));
+        }
+        Ok(Self {
+            mailer: builder.build(),
+            from: parse_mailbox(from)?,
+            to: to.iter().map(|address| parse_mailbox(address)).collect::<Result<_>>()?,
+            project,
+            output_path
+        })
+    }
+
+    fn subject(&self, report: &Report) -> String {
+        format!("[{}] {}", self.project, report.title())
+    }
+
+    fn text(&self, report: &Report) -> String {
+        let mut text = report.body().unwrap_or_default();
+        if report.outcome.is_success() {
+            return text;
+        }
+        match fs::read_to_string(&self.output_path) {
+            Ok(output) => {
+                text.push_str("\n\nOutput:\n\n");
+                if output.len() > MAX_OUTPUT_LENGTH {
+                    let mut start = output.len() - MAX_OUTPUT_LENGTH;
+                    while !output.is_char_boundary(start) {
+                        start += 1;
+                    }
+                    text.push_str("[...]\n");
+                    text.push_str(&output[start..]);
+                } else {
+                    text.push_str(&output);
+                }
+            },
+            Err(err) => text.push_str(&format!("\n\nThe output could not be read from {}: {}", self.output_path.display(), err))
+        }
+        text
+    }
+}
+
+impl Notify for EmailNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(self.subject(report))
+            .header(ContentType::TEXT_PLAIN);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        let message = builder.body(self.text(report))
+            .map_err(|err| ErrorKind::NotificationFailed(format!("failed to compose email: {}", err)))?;
+        self.mailer.send(&message)
+            .map_err(|err| ErrorKind::NotificationFailed(format!("failed to send email: {}", err)))?;
+        Ok(())
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox> {
+    address.parse().map_err(|err| invalid_setting(format!("`{}` is not a valid email address: {}", address, err)))
+}
+
+fn invalid_setting(message: String) -> Error {
+    ErrorKind::InvalidSetting("email", message).into()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use report::Outcome;
+
+    #[test]
+    fn test_text() {
+        let email = Email {
+            host: Some("smtp.example.com".to_string()),
+            from: Some("testify <testify@example.com>".to_string()),
+            to: Some(vec!["dev@example.com".to_string()]),
+            ..Email::default()
+        };
+        let output_path = env::temp_dir().join("testify-email-test-output.log");
+        fs::write(&output_path, "test tests::bad ... FAILED\n").unwrap();
+        let notifier = EmailNotifier::new(&email, "app".to_string(), output_path.clone()).unwrap();
+
+        let report = Report::new(Outcome::TestsFailed, Some("2 passed; 1 failed".to_string()));
+        assert_eq!(notifier.subject(&report), "[app] Tests failed");
+        assert_eq!(notifier.text(&report), "2 passed; 1 failed\n\nOutput:\n\ntest tests::bad ... FAILED\n");
+        assert_eq!(notifier.text(&Report::new(Outcome::TestsPassed, Some("3 passed".to_string()))), "3 passed");
+        fs::remove_file(&output_path).unwrap();
+
+        let email = Email { to: None, ..email };
+        assert!(EmailNotifier::new(&email, "app".to_string(), output_path).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use serde_json;
+
+use events;
+use reactor::shell_command;
+use report::Report;
+use errors::*;
+use super::Notify;
+
+/// Runs a command of the user with the report in environment variables, and on stdin as JSON,
+/// for integrations testify does not have a notifier of its own for.
+pub struct ExecNotifier {
+    command: String,
+    project: String,
+    /// The command runs in the project directory, like the hooks
+    project_dir: PathBuf
+}
+
+impl ExecNotifier {
+    pub fn new(command: String, project: String, project_dir: PathBuf) -> Self {
+        Self { command, project, project_dir }
+    }
+
+    /// `TESTIFY_OUTCOME` (e.g. `failed`), `TESTIFY_TITLE`, `TESTIFY_DETAIL` (the body of the notification),
+    /// `TESTIFY_DURATION` (in seconds, empty if unknown) and `TESTIFY_PROJECT`.
+    fn env(&self, report: &Report) -> Vec<(&'static str, String)> {
+        let duration = report.duration.map(|duration| format!("{}.{:03}", duration.as_secs(), duration.subsec_millis()));
+        vec![
+            ("TESTIFY_OUTCOME", report.outcome.name().to_string()),
+            ("TESTIFY_TITLE", report.title()),
+            ("TESTIFY_DETAIL", report.body().unwrap_or_default()),
+            ("TESTIFY_DURATION", duration.unwrap_or_default()),
+            ("TESTIFY_PROJECT", self.project.clone())
+        ]
+    }
+}
+
+impl Notify for ExecNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let argv = shell_command(&self.command);
+        let mut child = Command::new(&argv[0])
+            .args(&argv[1..])
+            .envs(self.env(report))
+            .current_dir(&self.project_dir)
+            .stdin(Stdio::piped())
+            .spawn()
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to run `{}`", self.command)))?;
+
+        let mut payload = events::report_json(report);
+        payload["project"] = json!(self.project);
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command does not have to read the report
+            let _ = stdin.write_all(&serde_json::to_vec(&payload).expect("JSON values are always serializable"));
+        }
+        let status = child.wait()
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to run `{}`", self.command)))?;
+        if !status.success() {
+            return Err(ErrorKind::NotificationFailed(format!("`{}` has failed: {}", self.command, status)).into());
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(all(test, not(target_os = "windows")))]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::time::Duration;
+    use report::Outcome;
+
+    #[test]
+    fn test_notify() {
+        let dir = env::temp_dir().join("testify-exec-test");
+        fs::create_dir_all(&dir).unwrap();
+        let notifier = ExecNotifier::new("echo \"$TESTIFY_OUTCOME $TESTIFY_DURATION $TESTIFY_PROJECT\" > env.txt && cat > report.json".to_string(),
+                                         "app".to_string(), dir.clone());
+        let mut report = Report::new(Outcome::TestsFailed, Some("1 failed".to_string()));
+        report.duration = Some(Duration::from_millis(2340));
+        notifier.notify(&report).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("env.txt")).unwrap(), "failed 2.340 app\n");
+        let payload: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("report.json")).unwrap()).unwrap();
+        assert_eq!(payload["outcome"], "failed");
+        assert_eq!(payload["project"], "app");
+
+        let notifier = ExecNotifier::new("exit 3".to_string(), "app".to_string(), dir.clone());
+        assert!(notifier.notify(&report).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+
+use report::Report;
+use errors::*;
+use super::{ActionHandler, Notify};
+
+/// Tries notifiers one after another, e.g. the desktop one and then the console one.
+/// A notifier which has failed is not tried again, so a missing notification daemon
+/// is reported only once instead of after every run.
+pub struct FallbackNotifier {
+    /// Notifiers with their names, used in the warning about falling back
+    chain: Vec<(&'static str, Box<dyn Notify>)>,
+    /// Index of the notifier which is used now
+    current: Cell<usize>
+}
+
+impl FallbackNotifier {
+    pub fn new(chain: Vec<(&'static str, Box<dyn Notify>)>) -> Self {
+        assert!(!chain.is_empty(), "fallback chain must have a notifier");
+        Self { chain, current: Cell::new(0) }
+    }
+}
+
+impl Notify for FallbackNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        loop {
+            let index = self.current.get();
+            let (name, ref notifier) = self.chain[index];
+            // Some notification libraries panic instead of returning an error when the backend is missing
+            let result = panic::catch_unwind(AssertUnwindSafe(|| notifier.notify(report)))
+                .unwrap_or_else(|_| Err(ErrorKind::NotificationFailed(format!("{} notifier has panicked", name)).into()));
+            match result {
+                Err(err) if index + 1 < self.chain.len() => {
+                    eprintln!("Warning: {}, falling back to {} notifications", err, self.chain[index + 1].0);
+                    self.current.set(index + 1);
+                },
+                result => return result
+            }
+        }
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        for &mut (_, ref mut notifier) in &mut self.chain {
+            notifier.on_action(handler.clone());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use report::Outcome;
+
+    struct CountingNotifier {
+        calls: Rc<Cell<usize>>,
+        fails: bool
+    }
+
+    impl Notify for CountingNotifier {
+        fn notify(&self, _report: &Report) -> Result<()> {
+            self.calls.set(self.calls.get() + 1);
+            if self.fails {
+                return Err(ErrorKind::NotificationFailed("no daemon".to_string()).into());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_falls_back_once() {
+        let desktop_calls = Rc::new(Cell::new(0));
+        let console_calls = Rc::new(Cell::new(0));
+        let notifier = FallbackNotifier::new(vec![
+            ("desktop", Box::new(CountingNotifier { calls: desktop_calls.clone(), fails: true })),
+            ("console", Box::new(CountingNotifier { calls: console_calls.clone(), fails: false }))
+        ]);
+        let report = Report::new(Outcome::TestsPassed, None);
+        assert!(notifier.notify(&report).is_ok());
+        assert!(notifier.notify(&report).is_ok());
+        assert_eq!(desktop_calls.get(), 1);
+        assert_eq!(console_calls.get(), 2);
+
+        let last_calls = Rc::new(Cell::new(0));
+        let notifier = FallbackNotifier::new(vec![("console", Box::new(CountingNotifier { calls: last_calls.clone(), fails: true }))]);
+        assert!(notifier.notify(&report).is_err());
+        assert!(notifier.notify(&report).is_err());
+        assert_eq!(last_calls.get(), 2);
+    }
+}
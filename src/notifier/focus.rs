@@ -0,0 +1,167 @@
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use std::env;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use std::fs;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use std::process::{Command, Stdio};
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+use serde_json::{self, Value};
+
+use report::Report;
+use errors::*;
+use super::{ActionHandler, Notify};
+
+/// Skips notifications while the terminal testify runs in has focus, the output is in sight then.
+/// When the focus can not be detected, notifications are always sent.
+pub struct UnfocusedNotifier {
+    notifier: Box<dyn Notify>
+}
+
+impl UnfocusedNotifier {
+    pub fn new(notifier: Box<dyn Notify>) -> Self {
+        Self { notifier }
+    }
+}
+
+impl Notify for UnfocusedNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        if terminal_focused() == Some(true) {
+            return Ok(());
+        }
+        self.notifier.notify(report)
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        self.notifier.on_action(handler);
+    }
+}
+
+/// Does the terminal window running testify have focus? The focused window has to belong
+/// to testify or one of its ancestors, so inside tmux or over SSH it's never reported as focused.
+/// `None` if the focused window can not be found: the window manager is not supported
+/// (only X11, Sway and Hyprland are), or its tools are not installed.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn terminal_focused() -> Option<bool> {
+    let pid = if env::var_os("SWAYSOCK").is_some() {
+        run(&["swaymsg", "-t", "get_tree"]).and_then(|tree| serde_json::from_str(&tree).ok()).and_then(|tree| sway_focused_pid(&tree))
+    } else if env::var_os("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        run(&["hyprctl", "activewindow", "-j"]).and_then(|window| serde_json::from_str::<Value>(&window).ok())
+            .and_then(|window| window["pid"].as_u64())
+            .map(|pid| pid as u32)
+    } else if env::var_os("DISPLAY").is_some() {
+        run(&["xprop", "-root", "_NET_ACTIVE_WINDOW"]).and_then(|output| parse_xprop(&output))
+            .and_then(|window| run(&["xprop", "-id", &window, "_NET_WM_PID"]))
+            .and_then(|output| parse_xprop(&output))
+            .and_then(|pid| pid.parse().ok())
+    } else {
+        None
+    }?;
+    Some(ancestors().contains(&pid))
+}
+
+/// The console window of testify is the foreground one, or is owned by it as in Windows Terminal.
+#[cfg(target_os = "windows")]
+pub fn terminal_focused() -> Option<bool> {
+    use winapi::um::wincon::GetConsoleWindow;
+    use winapi::um::winuser::{GetAncestor, GetForegroundWindow, GA_ROOTOWNER};
+
+    unsafe {
+        let console = GetConsoleWindow();
+        if console.is_null() {
+            return None;
+        }
+        let foreground = GetForegroundWindow();
+        Some(foreground == console || foreground == GetAncestor(console, GA_ROOTOWNER))
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn terminal_focused() -> Option<bool> {
+    None
+}
+
+/// Stdout of a command, `None` if it could not be run or has failed.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn run(argv: &[&str]) -> Option<String> {
+    let output = Command::new(argv[0]).args(&argv[1..]).stdin(Stdio::null()).stderr(Stdio::null()).output().ok()?;
+    if output.status.success() {
+        String::from_utf8(output.stdout).ok()
+    } else {
+        None
+    }
+}
+
+/// Value of an xprop property, e.g. `0x3e00003` of `_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3e00003`.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn parse_xprop(output: &str) -> Option<String> {
+    let value = output.trim().rsplit([' ', '#']).next()?;
+    // No window has focus, or the property is missing
+    if value.is_empty() || value == "0x0" || output.contains("not found") {
+        return None;
+    }
+    Some(value.to_string())
+}
+
+/// PID of the focused window in the tree printed by `swaymsg -t get_tree`.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn sway_focused_pid(node: &Value) -> Option<u32> {
+    if node["focused"].as_bool() == Some(true) {
+        return node["pid"].as_u64().map(|pid| pid as u32);
+    }
+    ["nodes", "floating_nodes"].iter()
+        .filter_map(|key| node[*key].as_array())
+        .flat_map(|nodes| nodes.iter())
+        .filter_map(sway_focused_pid)
+        .next()
+}
+
+/// PIDs of testify and the processes above it, up to init.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn ancestors() -> Vec<u32> {
+    let mut pids = vec![];
+    let mut pid = ::std::process::id();
+    while pid > 1 && !pids.contains(&pid) {
+        pids.push(pid);
+        pid = match fs::read_to_string(format!("/proc/{}/stat", pid)).ok().and_then(|stat| parent_pid(&stat)) {
+            Some(parent) => parent,
+            None => break
+        };
+    }
+    pids
+}
+
+/// Parent PID in `/proc/<pid>/stat`, the second field after the command name, which may contain spaces.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn parent_pid(stat: &str) -> Option<u32> {
+    stat[stat.rfind(')')? + 1..].split_whitespace().nth(1)?.parse().ok()
+}
+
+
+#[cfg(all(test, not(any(target_os = "windows", target_os = "macos"))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xprop() {
+        assert_eq!(parse_xprop("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x3e00003\n"), Some("0x3e00003".to_string()));
+        assert_eq!(parse_xprop("_NET_ACTIVE_WINDOW(WINDOW): window id # 0x0\n"), None);
+        assert_eq!(parse_xprop("_NET_WM_PID(CARDINAL) = 4242\n"), Some("4242".to_string()));
+        assert_eq!(parse_xprop("_NET_WM_PID:  not found.\n"), None);
+    }
+
+    #[test]
+    fn test_sway_focused_pid() {
+        let tree: Value = serde_json::from_str(r#"{"focused": false, "nodes": [
+            {"focused": false, "nodes": [{"focused": false, "pid": 10, "nodes": []}],
+             "floating_nodes": [{"focused": true, "pid": 20, "nodes": []}]}
+        ]}"#).unwrap();
+        assert_eq!(sway_focused_pid(&tree), Some(20));
+    }
+
+    #[test]
+    fn test_ancestors() {
+        assert_eq!(parent_pid("42 (cargo test (x)) S 7 42 7 0"), Some(7));
+        assert_eq!(ancestors()[0], ::std::process::id());
+    }
+}
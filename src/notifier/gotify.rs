@@ -0,0 +1,63 @@
+use ureq;
+
+use config::Push;
+use report::{Outcome, Report};
+use errors::*;
+use super::Notify;
+
+/// Posts reports as messages of a Gotify application, which the Gotify app pushes to the phone.
+pub struct GotifyNotifier {
+    /// URL of the `message` endpoint of the server
+    url: String,
+    token: String,
+    push: Push,
+    project: String
+}
+
+impl GotifyNotifier {
+    pub fn new(push: Push, project: String) -> Result<Self> {
+        let url = push.url.as_ref().ok_or(ErrorKind::SettingMissing("gotify.url"))?;
+        let url = format!("{}/message", url.trim_end_matches('/'));
+        let token = push.token.clone().ok_or(ErrorKind::SettingMissing("gotify.token"))?;
+        Ok(Self { url, token, push, project })
+    }
+
+    fn message(&self, report: &Report) -> ::serde_json::Value {
+        // Gotify apps alert from priority 8 on, and hide messages of priority 0
+        let default_priority = match report.outcome {
+            outcome if outcome.is_success() => 2,
+            Outcome::NoTests => 5,
+            _ => 8
+        };
+        json!({
+            "title": format!("{}: {}", self.project, report.title()),
+            "message": report.body().unwrap_or_else(|| report.title()),
+            "priority": self.push.priority(report.outcome).unwrap_or(default_priority)
+        })
+    }
+}
+
+impl Notify for GotifyNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        ureq::post(&self.url)
+            .set("X-Gotify-Key", &self.token)
+            .send_json(self.message(report))
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to post to {}", self.url)))?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let push = Push { url: Some("https://gotify.example.com/".to_string()), token: Some("AbC".to_string()), priority: None };
+        let notifier = GotifyNotifier::new(push, "app".to_string()).unwrap();
+        assert_eq!(notifier.url, "https://gotify.example.com/message");
+        let message = notifier.message(&Report::new(Outcome::TestsPassed, None));
+        assert_eq!(message, json!({ "title": "app: Tests passed", "message": "Tests passed", "priority": 2 }));
+    }
+}
@@ -0,0 +1,75 @@
+use serde_json;
+use ureq;
+
+use report::Report;
+use errors::*;
+use super::Notify;
+use super::template;
+
+/// Body posted when no template is configured.
+const DEFAULT_TEMPLATE: &str =
+    r#"{"outcome": "{outcome}", "title": "{title}", "detail": "{detail}", "duration": "{duration}", "project": "{project}"}"#;
+
+/// Posts reports to an arbitrary URL, with a JSON body rendered from a user-defined template.
+///
+/// Supported placeholders are the ones of notification templates, e.g. `{outcome}`, `{title}`,
+/// `{detail}`, `{duration}` and `{project}`. Values are JSON-escaped, so placeholders are expected inside of string literals.
+pub struct HttpNotifier {
+    url: String,
+    template: String,
+    project: String
+}
+
+impl HttpNotifier {
+    pub fn new(url: String, template: Option<String>, project: String) -> Self {
+        let template = template.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+        Self { url, template, project }
+    }
+
+    fn body(&self, report: &Report) -> String {
+        template::render(&self.template, &template::values(report, &self.project), escape)
+    }
+}
+
+impl Notify for HttpNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&self.body(report))
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to post to {}", self.url)))?;
+        Ok(())
+    }
+}
+
+/// Escape `value` to be placed inside of a JSON string literal.
+fn escape(value: &str) -> String {
+    let quoted = serde_json::to_string(value).expect("strings are always serializable");
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use report::Outcome;
+    use serde_json::Value;
+
+    #[test]
+    fn test_body() {
+        let template = r#"{"topic": "ci", "message": "{project} {outcome} in {duration}: {detail}"}"#.to_string();
+        let notifier = HttpNotifier::new("https://ntfy.sh".to_string(), Some(template), "app".to_string());
+
+        let mut report = Report::new(Outcome::TestsFailed, Some("1 passed; 1 failed".to_string()));
+        report.duration = Some(Duration::from_millis(2340));
+        let body: Value = serde_json::from_str(&notifier.body(&report)).unwrap();
+        assert_eq!(body["message"], "app failed in 2.3s: 1 passed; 1 failed\nFinished in 2.3s");
+
+        let report = Report::new(Outcome::TestsPassed, Some("say \"hi\"".to_string()));
+        let notifier = HttpNotifier::new("https://example.com".to_string(), None, "app".to_string());
+        let body: Value = serde_json::from_str(&notifier.body(&report)).unwrap();
+        assert_eq!(body["title"], "Tests passed");
+        assert_eq!(body["detail"], "say \"hi\"");
+        assert_eq!(body["duration"], "");
+    }
+}
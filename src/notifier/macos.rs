@@ -0,0 +1,96 @@
+use std::env;
+use std::process::{Command, Stdio};
+use mac_notification_sys::{self, Notification};
+
+use config::Sounds;
+use report::{Outcome, Report};
+use errors::*;
+use super::Notify;
+
+/// Bundle identifier used when the terminal application can not be detected.
+const DEFAULT_BUNDLE_IDENTIFIER: &str = "com.apple.Terminal";
+
+/// Native notification of macOS Notification Center.
+///
+/// Notifications are sent on behalf of the terminal application `cargo testify` is running in,
+/// so they are shown with its name and icon and obey its notification settings.
+/// If Notification Center refuses to deliver them, `osascript` is used as a fallback.
+pub struct MacosNotifier {
+    sounds: Sounds
+}
+
+impl MacosNotifier {
+    pub fn new(sounds: Sounds) -> Self {
+        // May fail only if the application has already been set, which is fine.
+        let _ = mac_notification_sys::set_application(&bundle_identifier());
+        MacosNotifier { sounds: sounds }
+    }
+
+    /// Name of the system sound the notification is sent with. Notifications can play only
+    /// system sounds, so a sound file is played separately and the notification is left silent.
+    fn sound(&self, outcome: Outcome) -> Option<&str> {
+        let default = match outcome {
+            Outcome::TestsPassed | Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) | Outcome::NoTests => None,
+            Outcome::TestsFailed | Outcome::CompileError | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::MutantsMissed(_) | Outcome::TimedOut | Outcome::Unknown => Some("Basso")
+        };
+        match self.sounds.choose(outcome, default) {
+            Some(sound) if Sounds::is_file(sound) => {
+                play_file(sound);
+                None
+            },
+            sound => sound
+        }
+    }
+}
+
+impl Notify for MacosNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let title = report.title();
+        let body = report.body().unwrap_or_default();
+        let sound = self.sound(report.outcome);
+        let mut options = Notification::new();
+        options.maybe_sound(sound);
+        match mac_notification_sys::send_notification(&title, None, &body, Some(&options)) {
+            Ok(_) => Ok(()),
+            Err(_) => notify_with_osascript(&title, &body, sound)
+        }
+    }
+}
+
+/// Bundle identifier of the application which has launched the process.
+fn bundle_identifier() -> String {
+    env::var("__CFBundleIdentifier").unwrap_or_else(|_| DEFAULT_BUNDLE_IDENTIFIER.to_string())
+}
+
+fn notify_with_osascript(title: &str, body: &str, sound: Option<&str>) -> Result<()> {
+    let mut script = format!("display notification {} with title {}", quote(body), quote(title));
+    if let Some(sound) = sound {
+        script.push_str(&format!(" sound name {}", quote(sound)));
+    }
+    let status = Command::new("osascript")
+        .arg("-e")
+        .arg(&script)
+        .status()
+        .chain_err(|| ErrorKind::NotificationFailed("failed to run osascript".to_string()))?;
+    if !status.success() {
+        bail!(ErrorKind::NotificationFailed(format!("osascript exited with {}", status)));
+    }
+    Ok(())
+}
+
+/// Play a sound file in the background with `afplay`.
+fn play_file(path: &str) {
+    let result = Command::new("afplay")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    if let Err(err) = result {
+        eprintln!("Warning: failed to play {}: {}", path, err);
+    }
+}
+
+/// Quote `text` as an AppleScript string literal.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use config::{Config, NotifierKind, Sounds};
+use reactor;
+use report::Report;
+use errors::*;
+
+#[cfg(not(target_os = "macos"))]
+mod desktop;
+#[cfg(target_os = "macos")]
+mod macos;
+mod slack;
+mod discord;
+mod telegram;
+mod email;
+mod ntfy;
+mod gotify;
+mod mqtt;
+mod exec;
+mod http;
+mod console;
+mod fallback;
+mod dispatcher;
+mod template;
+mod focus;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+mod wsl;
+
+#[cfg(not(target_os = "macos"))]
+pub use self::desktop::DesktopNotifier;
+#[cfg(target_os = "macos")]
+pub use self::macos::MacosNotifier;
+pub use self::slack::SlackNotifier;
+pub use self::discord::DiscordNotifier;
+pub use self::telegram::TelegramNotifier;
+pub use self::email::EmailNotifier;
+pub use self::ntfy::NtfyNotifier;
+pub use self::gotify::GotifyNotifier;
+pub use self::mqtt::MqttNotifier;
+pub use self::exec::ExecNotifier;
+pub use self::http::HttpNotifier;
+pub use self::console::ConsoleNotifier;
+pub use self::fallback::FallbackNotifier;
+pub use self::dispatcher::Dispatcher;
+pub use self::template::TemplateNotifier;
+pub use self::focus::UnfocusedNotifier;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub use self::wsl::WslNotifier;
+
+/// Action chosen by the user in a notification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    /// Run the tests again
+    Rerun,
+
+    /// Open the output of the latest run
+    OpenOutput,
+
+    /// Accept the snapshots which have changed in the latest run, and run the tests again
+    AcceptSnapshots
+}
+
+/// Receives actions chosen by the user in notifications.
+pub type ActionHandler = Arc<dyn Fn(Action) + Send + Sync>;
+
+/// Delivers a report about a test run to the user.
+pub trait Notify {
+    fn notify(&self, report: &Report) -> Result<()>;
+
+    /// Pass actions chosen in notifications to `handler`. Notifiers which can not offer
+    /// actions ignore it.
+    fn on_action(&mut self, _handler: ActionHandler) {}
+}
+
+/// Notifier which does nothing, used when notifications are disabled.
+pub struct NullNotifier;
+
+impl Notify for NullNotifier {
+    fn notify(&self, _report: &Report) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Create the notifiers chosen in the configuration. Several of them, or the ones limited
+/// to some outcomes, are combined by a `Dispatcher`.
+pub fn obtain_notifier(config: &Config) -> Result<Box<dyn Notify>> {
+    let notifier = match config.notifiers[..] {
+        [kind] if config.routes.iter().all(|route| route.notifier != kind) => obtain_for_runs(kind, config)?,
+        _ => {
+            let mut dispatcher = Dispatcher::new();
+            for &kind in &config.notifiers {
+                let route = config.routes.iter().find(|route| route.notifier == kind).cloned();
+                dispatcher = dispatcher.add(kind.name(), obtain_for_runs(kind, config)?, route);
+            }
+            Box::new(dispatcher)
+        }
+    };
+    if config.notification_title.is_none() && config.notification_body.is_none() {
+        return Ok(notifier);
+    }
+    Ok(Box::new(TemplateNotifier::new(notifier, config.notification_title.clone(), config.notification_body.clone(), project_name(config))))
+}
+
+/// Create a notifier of the given kind. A desktop notifier falls back to the console one
+/// when it fails, and `auto` tries it only if there is a desktop session to notify on.
+pub fn obtain(kind: NotifierKind, config: &Config) -> Result<Box<dyn Notify>> {
+    let notifier: Box<dyn Notify> = match kind {
+        // Chosen explicitly, it's tried even if no desktop session is detected
+        NotifierKind::Desktop => desktop_with_fallback(config.sounds.clone()),
+        NotifierKind::Auto if desktop_available() => desktop_with_fallback(config.sounds.clone()),
+        NotifierKind::Auto | NotifierKind::Console => Box::new(ConsoleNotifier::new(config.sounds.clone())),
+        NotifierKind::Slack => {
+            let webhook = config.slack_webhook.clone().ok_or(ErrorKind::SettingMissing("slack.webhook"))?;
+            Box::new(SlackNotifier::new(webhook, project_name(config)))
+        },
+        NotifierKind::Discord => {
+            let webhook = config.discord_webhook.clone().ok_or(ErrorKind::SettingMissing("discord.webhook"))?;
+            Box::new(DiscordNotifier::new(webhook, project_name(config)))
+        },
+        NotifierKind::Telegram => {
+            let token = config.telegram_token.clone().ok_or(ErrorKind::SettingMissing("telegram.token"))?;
+            let chat_id = config.telegram_chat_id.clone().ok_or(ErrorKind::SettingMissing("telegram.chat_id"))?;
+            Box::new(TelegramNotifier::new(token, chat_id, project_name(config)))
+        },
+        NotifierKind::Email => {
+            Box::new(EmailNotifier::new(&config.email, project_name(config), config.project_dir.join(reactor::OUTPUT_FILE))?)
+        },
+        NotifierKind::Ntfy => Box::new(NtfyNotifier::new(config.ntfy.clone(), project_name(config))?),
+        NotifierKind::Gotify => Box::new(GotifyNotifier::new(config.gotify.clone(), project_name(config))?),
+        NotifierKind::Mqtt => Box::new(MqttNotifier::new(&config.mqtt, project_name(config))?),
+        NotifierKind::Exec => {
+            let command = config.exec_command.clone().ok_or(ErrorKind::SettingMissing("exec.command"))?;
+            Box::new(ExecNotifier::new(command, project_name(config), config.project_dir.clone()))
+        },
+        NotifierKind::Http => {
+            let url = config.http_url.clone().ok_or(ErrorKind::SettingMissing("http.url"))?;
+            Box::new(HttpNotifier::new(url, config.http_body.clone(), project_name(config)))
+        },
+        NotifierKind::Disabled => Box::new(NullNotifier)
+    };
+    Ok(notifier)
+}
+
+/// Like `obtain`, but desktop notifications are skipped while the terminal has focus
+/// if the configuration asks for it.
+fn obtain_for_runs(kind: NotifierKind, config: &Config) -> Result<Box<dyn Notify>> {
+    let notifier = obtain(kind, config)?;
+    let desktop = kind == NotifierKind::Desktop || (kind == NotifierKind::Auto && desktop_available());
+    if config.skip_when_focused && desktop {
+        return Ok(Box::new(UnfocusedNotifier::new(notifier)));
+    }
+    Ok(notifier)
+}
+
+/// Is there a notification daemon to talk to? On Linux it's reached through DBus, which is usually
+/// missing in SSH sessions and containers. Under WSL toasts are sent to the Windows host.
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub fn desktop_available() -> bool {
+    wsl::detected() || ["DBUS_SESSION_BUS_ADDRESS", "DISPLAY", "WAYLAND_DISPLAY"].iter().any(|var| std::env::var_os(var).is_some())
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub fn desktop_available() -> bool {
+    true
+}
+
+fn desktop_with_fallback(sounds: Sounds) -> Box<dyn Notify> {
+    Box::new(FallbackNotifier::new(vec![
+        ("desktop", desktop_notifier(sounds.clone())),
+        ("console", Box::new(ConsoleNotifier::new(sounds)))
+    ]))
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn desktop_notifier(sounds: Sounds) -> Box<dyn Notify> {
+    // WSL has no DBus, even with WSLg showing Linux windows
+    if wsl::detected() {
+        Box::new(WslNotifier::new(sounds))
+    } else {
+        Box::new(DesktopNotifier::new(sounds))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn desktop_notifier(sounds: Sounds) -> Box<dyn Notify> {
+    Box::new(DesktopNotifier::new(sounds))
+}
+
+#[cfg(target_os = "macos")]
+fn desktop_notifier(sounds: Sounds) -> Box<dyn Notify> {
+    Box::new(MacosNotifier::new(sounds))
+}
+
+/// Name of the project directory, used by notifiers which may be shared between projects.
+fn project_name(config: &Config) -> String {
+    config.project_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| config.project_dir.to_string_lossy().into_owned())
+}
+
+/// Cut `text` to at most `max_length` characters, marking the cut with an ellipsis.
+pub fn truncate(text: &str, max_length: usize) -> String {
+    if text.chars().count() <= max_length {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_length - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("0123456789abc", 10), "012345678…");
+    }
+}
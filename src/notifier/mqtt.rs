@@ -0,0 +1,201 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process;
+use std::time::Duration;
+
+use serde_json;
+
+use config::Mqtt;
+use events;
+use report::Report;
+use errors::*;
+use super::Notify;
+
+const DEFAULT_PORT: u16 = 1883;
+
+/// How long connecting to the broker and waiting for its replies may take.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Only one message is sent over a connection.
+const PACKET_ID: u16 = 1;
+
+/// Publishes reports as JSON to a topic of an MQTT broker, e.g. for home automation to react
+/// on them. Speaks just enough of MQTT 3.1.1 to publish a message with QoS 1: a connection
+/// is made for every report and closed once the broker has acknowledged it.
+pub struct MqttNotifier {
+    broker: String,
+    topic: String,
+    username: Option<String>,
+    password: Option<String>,
+    retain: bool,
+    project: String
+}
+
+impl MqttNotifier {
+    pub fn new(mqtt: &Mqtt, project: String) -> Result<Self> {
+        let broker = mqtt.broker.clone().ok_or(ErrorKind::SettingMissing("mqtt.broker"))?;
+        let broker = if broker.contains(':') { broker } else { format!("{}:{}", broker, DEFAULT_PORT) };
+        let topic = mqtt.topic.clone().unwrap_or_else(|| format!("testify/{}", project));
+        Ok(Self {
+            broker,
+            topic,
+            username: mqtt.username.clone(),
+            password: mqtt.password.clone(),
+            retain: mqtt.retain.unwrap_or(false),
+            project
+        })
+    }
+
+    fn payload(&self, report: &Report) -> Vec<u8> {
+        let mut payload = events::report_json(report);
+        payload["project"] = json!(self.project);
+        serde_json::to_vec(&payload).expect("JSON values are always serializable")
+    }
+
+    fn publish(&self, payload: &[u8]) -> io::Result<()> {
+        let address = self.broker.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the address of the broker could not be resolved"))?;
+        let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let client_id = format!("testify-{}", process::id());
+        stream.write_all(&connect_packet(&client_id, self.username.as_deref(), self.password.as_deref()))?;
+        let mut connack = [0; 4];
+        stream.read_exact(&mut connack)?;
+        if connack[0] != 0x20 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the broker did not accept the connection"));
+        }
+        if connack[3] != 0 {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, connect_error(connack[3])));
+        }
+
+        stream.write_all(&publish_packet(&self.topic, payload, self.retain, PACKET_ID))?;
+        let mut puback = [0; 4];
+        stream.read_exact(&mut puback)?;
+        if puback != [0x40, 0x02, (PACKET_ID >> 8) as u8, PACKET_ID as u8] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the broker did not acknowledge the message"));
+        }
+        // Disconnect
+        stream.write_all(&[0xe0, 0x00])
+    }
+}
+
+impl Notify for MqttNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        self.publish(&self.payload(report))
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to publish to {} on {}", self.topic, self.broker)))
+    }
+}
+
+fn connect_packet(client_id: &str, username: Option<&str>, password: Option<&str>) -> Vec<u8> {
+    // Clean session, with the flags of the credentials which follow the client id
+    let mut flags = 0x02;
+    let mut body = vec![];
+    push_string(&mut body, "MQTT");
+    body.push(4); // Protocol level of 3.1.1
+    if username.is_some() {
+        flags |= 0x80;
+    }
+    if password.is_some() {
+        flags |= 0x40;
+    }
+    body.push(flags);
+    body.extend_from_slice(&[0, 30]); // Keep alive of 30 seconds
+    push_string(&mut body, client_id);
+    for credential in username.into_iter().chain(password) {
+        push_string(&mut body, credential);
+    }
+    packet(0x10, body)
+}
+
+fn publish_packet(topic: &str, payload: &[u8], retain: bool, packet_id: u16) -> Vec<u8> {
+    let mut body = vec![];
+    push_string(&mut body, topic);
+    body.extend_from_slice(&[(packet_id >> 8) as u8, packet_id as u8]);
+    body.extend_from_slice(payload);
+    // QoS 1
+    packet(0x32 | retain as u8, body)
+}
+
+/// Fixed header with the remaining length encoded in 7 bits per byte, followed by `body`.
+fn packet(kind: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend(body);
+    packet
+}
+
+fn push_string(buffer: &mut Vec<u8>, text: &str) {
+    buffer.extend_from_slice(&[(text.len() >> 8) as u8, text.len() as u8]);
+    buffer.extend_from_slice(text.as_bytes());
+}
+
+fn connect_error(code: u8) -> &'static str {
+    match code {
+        1 => "the broker does not support MQTT 3.1.1",
+        2 => "the broker has rejected the client id",
+        3 => "the broker is unavailable",
+        4 => "the username or the password is wrong",
+        5 => "not authorized to connect",
+        _ => "the broker has refused the connection"
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+    use report::Outcome;
+
+    #[test]
+    fn test_packets() {
+        assert_eq!(connect_packet("id", Some("u"), None),
+                   vec![0x10, 17, 0, 4, b'M', b'Q', b'T', b'T', 4, 0x82, 0, 30, 0, 2, b'i', b'd', 0, 1, b'u']);
+        assert_eq!(publish_packet("t", b"{}", true, 1), vec![0x33, 7, 0, 1, b't', 0, 1, b'{', b'}']);
+        assert_eq!(&packet(0x30, vec![0; 200])[..3], &[0x30, 0xc8, 0x01]);
+    }
+
+    #[test]
+    fn test_publish() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let broker = listener.local_addr().unwrap().to_string();
+        let broker_thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut connect = [0; 2];
+            stream.read_exact(&mut connect).unwrap();
+            stream.read_exact(&mut vec![0; connect[1] as usize]).unwrap();
+            stream.write_all(&[0x20, 0x02, 0, 0]).unwrap();
+            // The report takes two bytes of remaining length
+            let mut header = [0; 3];
+            stream.read_exact(&mut header).unwrap();
+            let mut publish = vec![0; (header[1] & 0x7f) as usize + header[2] as usize * 128];
+            stream.read_exact(&mut publish).unwrap();
+            stream.write_all(&[0x40, 0x02, 0, 1]).unwrap();
+            (header[0], publish)
+        });
+
+        let mqtt = Mqtt { broker: Some(broker), ..Mqtt::default() };
+        let notifier = MqttNotifier::new(&mqtt, "app".to_string()).unwrap();
+        notifier.notify(&Report::new(Outcome::TestsPassed, None)).unwrap();
+        let (kind, publish) = broker_thread.join().unwrap();
+        assert_eq!(kind, 0x32);
+        assert_eq!(&publish[..13], b"\x00\x0btestify/app");
+        let payload: serde_json::Value = serde_json::from_slice(&publish[15..]).unwrap();
+        assert_eq!(payload["outcome"], "passed");
+        assert_eq!(payload["project"], "app");
+    }
+}
@@ -0,0 +1,95 @@
+use ureq;
+
+use config::Push;
+use report::{Outcome, Report};
+use errors::*;
+use super::Notify;
+
+/// Publishes reports to an ntfy topic (https://ntfy.sh, or a self-hosted server), so they
+/// arrive as push notifications on the phone.
+pub struct NtfyNotifier {
+    /// URL of the server, messages are published as JSON to its root
+    server: String,
+    topic: String,
+    push: Push,
+    project: String
+}
+
+impl NtfyNotifier {
+    pub fn new(push: Push, project: String) -> Result<Self> {
+        let url = push.url.clone().ok_or(ErrorKind::SettingMissing("ntfy.url"))?;
+        let url = url.trim_end_matches('/');
+        let (server, topic) = match url.rfind('/') {
+            Some(index) if !url[..index].ends_with('/') => (url[..index].to_string(), url[index + 1..].to_string()),
+            _ => return Err(ErrorKind::InvalidSetting("ntfy", format!("`{}` is not the URL of a topic, e.g. https://ntfy.sh/my-tests", url)).into())
+        };
+        Ok(Self { server, topic, push, project })
+    }
+
+    fn message(&self, report: &Report) -> ::serde_json::Value {
+        // Priorities of ntfy go from 1 (min) to 5 (max), 3 is the default
+        let default_priority = match report.outcome {
+            outcome if outcome.is_success() => 2,
+            Outcome::NoTests => 3,
+            _ => 4
+        };
+        let tag = match report.outcome {
+            Outcome::TestsPassed => "white_check_mark",
+            Outcome::TestsPassedWithWarnings(_) => "warning",
+            Outcome::Flaky(_) => "zap",
+            Outcome::TestsFailed => "x",
+            Outcome::CompileError => "boom",
+            Outcome::LintFailed(_) => "paperclip",
+            Outcome::FormatError => "art",
+            Outcome::UndefinedBehavior => "skull",
+            Outcome::MutantsMissed(_) => "bug",
+            Outcome::TimedOut => "hourglass",
+            Outcome::NoTests => "grey_question",
+            Outcome::Unknown => "interrobang"
+        };
+        json!({
+            "topic": self.topic,
+            "title": format!("{}: {}", self.project, report.title()),
+            "message": report.body().unwrap_or_else(|| report.title()),
+            "priority": self.push.priority(report.outcome).unwrap_or(default_priority),
+            "tags": [tag]
+        })
+    }
+}
+
+impl Notify for NtfyNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let mut request = ureq::post(&self.server);
+        if let Some(ref token) = self.push.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        request.send_json(self.message(report))
+            .chain_err(|| ErrorKind::NotificationFailed(format!("failed to publish to {}/{}", self.server, self.topic)))?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_message() {
+        let mut priority = BTreeMap::new();
+        priority.insert("compile_error".to_string(), 5);
+        let push = Push { url: Some("https://ntfy.example.com/my-tests/".to_string()), token: None, priority: Some(priority) };
+        let notifier = NtfyNotifier::new(push, "app".to_string()).unwrap();
+        assert_eq!(notifier.server, "https://ntfy.example.com");
+
+        let message = notifier.message(&Report::new(Outcome::TestsFailed, Some("2 passed; 1 failed".to_string())));
+        assert_eq!(message, json!({
+            "topic": "my-tests", "title": "app: Tests failed", "message": "2 passed; 1 failed", "priority": 4, "tags": ["x"]
+        }));
+        assert_eq!(notifier.message(&Report::new(Outcome::CompileError, None))["priority"], 5);
+
+        let push = Push { url: Some("https://ntfy.sh".to_string()), ..Push::default() };
+        assert!(NtfyNotifier::new(push, "app".to_string()).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use ureq;
+
+use report::{Outcome, Report};
+use errors::*;
+use super::{Notify, truncate};
+
+/// Maximum length of the detail included into a Slack message.
+const MAX_DETAIL_LENGTH: usize = 1500;
+
+/// Posts reports to a Slack channel using an incoming webhook.
+pub struct SlackNotifier {
+    webhook_url: String,
+    project: String
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: String, project: String) -> Self {
+        Self { webhook_url, project }
+    }
+
+    fn message(&self, report: &Report) -> String {
+        let emoji = match report.outcome {
+            Outcome::TestsPassed => ":white_check_mark:",
+            Outcome::TestsPassedWithWarnings(_) => ":warning:",
+            Outcome::Flaky(_) => ":zap:",
+            Outcome::TestsFailed => ":x:",
+            Outcome::CompileError => ":boom:",
+            Outcome::LintFailed(_) => ":paperclip:",
+            Outcome::FormatError => ":art:",
+            Outcome::UndefinedBehavior => ":skull:",
+            Outcome::MutantsMissed(_) => ":bug:",
+            Outcome::TimedOut => ":hourglass:",
+            Outcome::NoTests => ":grey_question:",
+            Outcome::Unknown => ":interrobang:"
+        };
+        let mut message = format!("{} *{}*: {}", emoji, self.project, report.title());
+        let mut details: Vec<String> = report.detail.iter().cloned().collect();
+        for failure in &report.failures {
+            match failure.message {
+                Some(ref panic_message) => details.push(format!("{}: {}", failure.name, panic_message)),
+                None => details.push(failure.name.clone())
+            }
+        }
+        if !details.is_empty() {
+            message.push_str(&format!("\n```{}```", truncate(&details.join("\n"), MAX_DETAIL_LENGTH)));
+        }
+        message
+    }
+}
+
+impl Notify for SlackNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        ureq::post(&self.webhook_url)
+            .send_json(json!({ "text": self.message(report) }))
+            .chain_err(|| ErrorKind::NotificationFailed("failed to post to Slack webhook".to_string()))?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::TestFailure;
+
+    #[test]
+    fn test_message() {
+        let notifier = SlackNotifier::new("https://hooks.slack.com/services/x".to_string(), "app".to_string());
+
+        let mut report = Report::new(Outcome::TestsFailed, Some("2 passed; 1 failed".to_string()));
+        report.failures = vec![TestFailure { name: "tests::bad".to_string(), message: Some("boom".to_string()), location: None }];
+        assert_eq!(notifier.message(&report), ":x: *app*: Tests failed\n```2 passed; 1 failed\ntests::bad: boom```");
+
+        let report = Report::new(Outcome::TestsPassed, None);
+        assert_eq!(notifier.message(&report), ":white_check_mark: *app*: Tests passed");
+    }
+}
@@ -0,0 +1,91 @@
+use ureq;
+
+use report::{Outcome, Report};
+use errors::*;
+use super::{Notify, truncate};
+
+/// Messages are limited to 4096 characters, the excerpt of failures is cut well before it.
+const MAX_DETAIL_LENGTH: usize = 3000;
+
+/// Sends reports to a Telegram chat through a bot, with the failures in a monospace block.
+pub struct TelegramNotifier {
+    token: String,
+    chat_id: String,
+    project: String
+}
+
+impl TelegramNotifier {
+    pub fn new(token: String, chat_id: String, project: String) -> Self {
+        Self { token, chat_id, project }
+    }
+
+    /// Text of the message, formatted with the HTML subset Telegram understands.
+    fn message(&self, report: &Report) -> String {
+        let emoji = match report.outcome {
+            Outcome::TestsPassed => "✅",
+            Outcome::TestsPassedWithWarnings(_) => "⚠️",
+            Outcome::Flaky(_) => "⚡",
+            Outcome::TestsFailed => "❌",
+            Outcome::CompileError => "💥",
+            Outcome::LintFailed(_) => "📎",
+            Outcome::FormatError => "🎨",
+            Outcome::UndefinedBehavior => "💀",
+            Outcome::MutantsMissed(_) => "🐛",
+            Outcome::TimedOut => "⌛",
+            Outcome::NoTests => "❔",
+            Outcome::Unknown => "⁉️"
+        };
+        let mut message = format!("{} <b>{}</b>: {}", emoji, html_escape(&self.project), html_escape(&report.title()));
+        let mut details: Vec<String> = report.detail.iter().cloned().collect();
+        for failure in &report.failures {
+            match failure.message {
+                Some(ref panic_message) => details.push(format!("{}: {}", failure.name, panic_message)),
+                None => details.push(failure.name.clone())
+            }
+        }
+        if !details.is_empty() {
+            message.push_str(&format!("\n<pre>{}</pre>", html_escape(&truncate(&details.join("\n"), MAX_DETAIL_LENGTH))));
+        }
+        message
+    }
+}
+
+impl Notify for TelegramNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        // The error of ureq would show the URL, which contains the token
+        ureq::post(&format!("https://api.telegram.org/bot{}/sendMessage", self.token))
+            .send_json(json!({
+                "chat_id": self.chat_id,
+                "text": self.message(report),
+                "parse_mode": "HTML"
+            }))
+            .map_err(|err| match err {
+                ureq::Error::Status(code, _) => ErrorKind::NotificationFailed(format!("Telegram has refused the message ({}), check the bot token and the chat id", code)),
+                ureq::Error::Transport(_) => ErrorKind::NotificationFailed("failed to reach Telegram".to_string())
+            })?;
+        Ok(())
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::TestFailure;
+
+    #[test]
+    fn test_message() {
+        let notifier = TelegramNotifier::new("123:ABC".to_string(), "42".to_string(), "app".to_string());
+
+        let mut report = Report::new(Outcome::TestsFailed, Some("2 passed; 1 failed".to_string()));
+        report.failures = vec![TestFailure { name: "tests::bad".to_string(), message: Some("expected <T> & U".to_string()), location: None }];
+        assert_eq!(notifier.message(&report), "❌ <b>app</b>: Tests failed\n<pre>2 passed; 1 failed\ntests::bad: expected &lt;T&gt; &amp; U</pre>");
+
+        let report = Report::new(Outcome::TestsPassed, None);
+        assert_eq!(notifier.message(&report), "✅ <b>app</b>: Tests passed");
+    }
+}
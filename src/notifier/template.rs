@@ -0,0 +1,89 @@
+use report::{format_duration, Report, TestCounts};
+use errors::*;
+use super::{ActionHandler, Notify};
+
+/// Values of the placeholders of notification templates: `{project}`, `{outcome}` (e.g. `failed`),
+/// `{title}` and `{detail}` (the default title and body), `{passed}`, `{failed}` and `{ignored}`
+/// (empty if the numbers are not known), `{duration}` and `{stage}`.
+pub fn values(report: &Report, project: &str) -> Vec<(&'static str, String)> {
+    let count = |count: fn(&TestCounts) -> usize| report.counts.as_ref().map(|counts| count(counts).to_string()).unwrap_or_default();
+    vec![
+        ("{project}", project.to_string()),
+        ("{outcome}", report.outcome.name().to_string()),
+        ("{title}", report.title()),
+        ("{detail}", report.body().unwrap_or_default()),
+        ("{passed}", count(|counts| counts.passed)),
+        ("{failed}", count(|counts| counts.failed)),
+        ("{ignored}", count(|counts| counts.ignored)),
+        ("{duration}", report.duration.map(format_duration).unwrap_or_default()),
+        ("{stage}", report.stage.clone().unwrap_or_default())
+    ]
+}
+
+/// Replace the placeholders of `template` with `values`, passed through `escape`.
+pub fn render(template: &str, values: &[(&'static str, String)], escape: fn(&str) -> String) -> String {
+    let mut text = template.to_string();
+    for &(placeholder, ref value) in values {
+        text = text.replace(placeholder, &escape(value));
+    }
+    text
+}
+
+/// Renders the title and the body of reports from the templates of the configuration,
+/// before passing them to the notifier.
+pub struct TemplateNotifier {
+    notifier: Box<dyn Notify>,
+    title: Option<String>,
+    body: Option<String>,
+    project: String
+}
+
+impl TemplateNotifier {
+    pub fn new(notifier: Box<dyn Notify>, title: Option<String>, body: Option<String>, project: String) -> Self {
+        Self { notifier, title, body, project }
+    }
+
+    fn apply(&self, report: &Report) -> Report {
+        let values = values(report, &self.project);
+        let mut report = report.clone();
+        report.custom_title = self.title.as_ref().map(|title| render(title, &values, str::to_string));
+        report.custom_body = self.body.as_ref().map(|body| render(body, &values, str::to_string));
+        report
+    }
+}
+
+impl Notify for TemplateNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        self.notifier.notify(&self.apply(report))
+    }
+
+    fn on_action(&mut self, handler: ActionHandler) {
+        self.notifier.on_action(handler);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use notifier::NullNotifier;
+    use report::{Outcome, TestCounts};
+
+    #[test]
+    fn test_apply() {
+        let notifier = TemplateNotifier::new(Box::new(NullNotifier), Some("{project} — {outcome}".to_string()),
+                                             Some("{failed} failed, {passed} passed in {duration}".to_string()), "app".to_string());
+        let mut report = Report::new(Outcome::TestsFailed, Some("1 passed; 2 failed".to_string()));
+        report.counts = Some(TestCounts { passed: 1, failed: 2, ignored: 0 });
+        report.duration = Some(Duration::from_millis(2340));
+        let report = notifier.apply(&report);
+        assert_eq!(report.title(), "app — failed");
+        assert_eq!(report.body().unwrap(), "2 failed, 1 passed in 2.3s");
+
+        let notifier = TemplateNotifier::new(Box::new(NullNotifier), None, Some("{detail}{stage}".to_string()), "app".to_string());
+        let report = notifier.apply(&Report::new(Outcome::TestsPassed, None));
+        assert_eq!(report.title(), "Tests passed");
+        assert_eq!(report.body(), None);
+    }
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use config::Sounds;
+use report::{Outcome, Report};
+use errors::*;
+use super::Notify;
+
+/// Created by WSL for running Windows programs from Linux.
+const INTEROP_FILE: &str = "/proc/sys/fs/binfmt_misc/WSLInterop";
+
+/// Helper sending toasts from WSL, https://github.com/stuartleeks/wsl-notify-send
+const NOTIFY_SEND: &str = "wsl-notify-send.exe";
+
+/// AppUserModelID of PowerShell, which is registered on every Windows installation,
+/// so toasts are shown without registering one for testify.
+const POWERSHELL_APP_ID: &str = "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+/// Toast on the Windows host of WSL, where there is no DBus to notify through.
+/// Sent with `wsl-notify-send.exe` if it's installed, otherwise with `powershell.exe`.
+pub struct WslNotifier {
+    sounds: Sounds
+}
+
+impl WslNotifier {
+    pub fn new(sounds: Sounds) -> Self {
+        WslNotifier { sounds }
+    }
+
+    /// Name of the Windows sound of the toast. Sound files are on the Linux side, so they can not be played.
+    fn sound(&self, outcome: Outcome) -> Option<&str> {
+        let default = if outcome.is_success() || outcome == Outcome::NoTests || outcome == Outcome::CompileError { None } else { Some("SMS") };
+        self.sounds.choose(outcome, default).filter(|sound| !Sounds::is_file(sound))
+    }
+}
+
+impl Notify for WslNotifier {
+    fn notify(&self, report: &Report) -> Result<()> {
+        let title = report.title();
+        let body = report.body().unwrap_or_default();
+        let message = if body.is_empty() { &title } else { &body };
+        let status = match Command::new(NOTIFY_SEND).args(["--category", &title, message]).stdin(Stdio::null()).status() {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                let script = toast_script(&title, &body, self.sound(report.outcome));
+                Command::new("powershell.exe")
+                    .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .status()
+            },
+            status => status
+        };
+        match status {
+            Ok(ref status) if status.success() => Ok(()),
+            Ok(status) => Err(ErrorKind::NotificationFailed(format!("the Windows host has refused the toast ({})", status)).into()),
+            Err(err) => Err(ErrorKind::NotificationFailed(format!("failed to reach the Windows host: {}", err)).into())
+        }
+    }
+}
+
+/// Is testify running under WSL?
+pub fn detected() -> bool {
+    env::var_os("WSL_DISTRO_NAME").is_some() || env::var_os("WSL_INTEROP").is_some() || Path::new(INTEROP_FILE).exists()
+}
+
+/// PowerShell script showing a toast through the WinRT API. It replaces the previous toast of testify.
+fn toast_script(title: &str, body: &str, sound: Option<&str>) -> String {
+    let audio = match sound {
+        Some(sound) => format!("<audio src=\"ms-winsoundevent:Notification.{}\"/>", xml_escape(sound)),
+        None => "<audio silent=\"true\"/>".to_string()
+    };
+    let xml = format!("<toast><visual><binding template=\"ToastGeneric\"><text>{}</text><text>{}</text></binding></visual>{}</toast>",
+                      xml_escape(title), xml_escape(body), audio);
+    format!("[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] > $null; \
+             [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime] > $null; \
+             $xml = New-Object Windows.Data.Xml.Dom.XmlDocument; \
+             $xml.LoadXml('{}'); \
+             $toast = [Windows.UI.Notifications.ToastNotification]::new($xml); \
+             $toast.Tag = 'run'; \
+             $toast.Group = 'cargo-testify'; \
+             [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{}').Show($toast)",
+            xml.replace('\'', "''"), POWERSHELL_APP_ID)
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toast_script() {
+        let script = toast_script("Tests failed", "can't compare <T> & \"U\"", Some("SMS"));
+        assert!(script.contains("$xml.LoadXml('<toast><visual><binding template=\"ToastGeneric\"><text>Tests failed</text>\
+                                 <text>can''t compare &lt;T&gt; &amp; &quot;U&quot;</text></binding></visual>\
+                                 <audio src=\"ms-winsoundevent:Notification.SMS\"/></toast>');"));
+        assert!(toast_script("Tests passed", "", None).contains("<audio silent=\"true\"/>"));
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{self, BufRead, Write};
+
+/// Matches shown at once by the prompt.
+const SHOWN: usize = 20;
+
+/// Names of the tests in the output of `cargo test -- --list`, sorted and without duplicates,
+/// e.g. `parser::tests::test_parse` from `parser::tests::test_parse: test`. Benchmarks are left out.
+pub fn parse_list(output: &str) -> Vec<String> {
+    let mut names: Vec<String> = output.lines()
+        .filter_map(|line| line.trim_end().strip_suffix(": test"))
+        .map(String::from)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Tests matching the fuzzy `query`, the best matches first. Every word of the query has to
+/// match: its characters have to appear in the name in the same order, e.g. `prs err` matches
+/// `parser::tests::test_error`.
+pub fn search<'a>(query: &str, names: &'a [String]) -> Vec<&'a String> {
+    let mut matches: Vec<(u32, &String)> = names.iter()
+        .filter_map(|name| score(query, name).map(|score| (score, name)))
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+    matches.into_iter().map(|(_, name)| name).collect()
+}
+
+fn score(query: &str, name: &str) -> Option<u32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut total = 0;
+    for word in query.to_lowercase().split_whitespace() {
+        total += score_word(word, &name)?;
+    }
+    Some(total)
+}
+
+/// Characters matching right after the previous one, or at the start of a word of the name,
+/// count more than scattered ones.
+fn score_word(word: &str, name: &[char]) -> Option<u32> {
+    let mut score = 0;
+    let mut start = 0;
+    let mut previous = None;
+    for c in word.chars() {
+        let index = start + name[start..].iter().position(|&n| n == c)?;
+        score += 1;
+        if index > 0 && previous == Some(index - 1) {
+            score += 2;
+        }
+        if index == 0 || !name[index - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(index);
+        start = index + 1;
+    }
+    Some(score)
+}
+
+/// Positions picked with e.g. `1 3-5`, or `*` for all of the `count` shown,
+/// `None` if the input is not understood.
+fn parse_picks(input: &str, count: usize) -> Option<Vec<usize>> {
+    if input.trim() == "*" {
+        return Some((0..count).collect());
+    }
+    let mut picks = vec![];
+    for part in input.split(|c: char| c == ',' || c.is_whitespace()).filter(|part| !part.is_empty()) {
+        let (first, last): (usize, usize) = match part.find('-') {
+            Some(index) => (part[..index].parse().ok()?, part[index + 1..].parse().ok()?),
+            None => {
+                let number = part.parse().ok()?;
+                (number, number)
+            }
+        };
+        if first == 0 || first > last || last > count {
+            return None;
+        }
+        picks.extend(first - 1..last);
+    }
+    Some(picks)
+}
+
+/// Ask in the terminal which of the tests to run: search for tests, pick some of the matches,
+/// and search again until the search is left empty.
+pub fn prompt(names: &[String]) -> io::Result<Vec<String>> {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut picked: Vec<String> = vec![];
+    eprintln!("{} tests found", names.len());
+    loop {
+        eprint!("Search tests (empty to finish): ");
+        io::stderr().flush()?;
+        let query = match lines.next() {
+            Some(line) => line?,
+            None => break
+        };
+        if query.trim().is_empty() { break; }
+
+        let matches = search(&query, names);
+        if matches.is_empty() {
+            eprintln!("No tests match `{}`", query.trim());
+            continue;
+        }
+        let shown = &matches[..matches.len().min(SHOWN)];
+        for (index, name) in shown.iter().enumerate() {
+            eprintln!("{:>3}) {}", index + 1, name);
+        }
+        if matches.len() > shown.len() {
+            eprintln!("     ... and {} more, search more precisely to see them", matches.len() - shown.len());
+        }
+        loop {
+            eprint!("Pick tests, e.g. `1 3-5` or `*` for all shown: ");
+            io::stderr().flush()?;
+            let input = match lines.next() {
+                Some(line) => line?,
+                None => break
+            };
+            match parse_picks(&input, shown.len()) {
+                Some(picks) => {
+                    for index in picks {
+                        if !picked.contains(shown[index]) {
+                            picked.push(shown[index].clone());
+                        }
+                    }
+                    break;
+                },
+                None => eprintln!("Expected numbers from 1 to {}", shown.len())
+            }
+        }
+        eprintln!("{} tests picked", picked.len());
+    }
+    picked.sort();
+    Ok(picked)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list() {
+        let output = "tests::b: test\ntests::a: test\nbench_parse: benchmark\n\n2 tests, 1 benchmark\ntests::a: test\n";
+        assert_eq!(parse_list(output), vec!["tests::a", "tests::b"]);
+    }
+
+    #[test]
+    fn test_search() {
+        let names: Vec<String> = ["parser::tests::test_error", "printer::tests::test_errors_are_red", "parser::tests::test_ok"]
+            .iter().map(|name| name.to_string()).collect();
+        assert_eq!(search("prs err", &names), vec!["parser::tests::test_error", "printer::tests::test_errors_are_red"]);
+        assert_eq!(search("OK", &names), vec!["parser::tests::test_ok"]);
+        assert_eq!(search("", &names).len(), 3);
+        assert!(search("xyz", &names).is_empty());
+    }
+
+    #[test]
+    fn test_parse_picks() {
+        assert_eq!(parse_picks("1 3-4", 5), Some(vec![0, 2, 3]));
+        assert_eq!(parse_picks("2,5", 5), Some(vec![1, 4]));
+        assert_eq!(parse_picks(" * ", 3), Some(vec![0, 1, 2]));
+        assert_eq!(parse_picks("6", 5), None);
+        assert_eq!(parse_picks("0", 5), None);
+        assert_eq!(parse_picks("x", 5), None);
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use portable_pty::{self, native_pty_system, CommandBuilder, MasterPty, PtySize};
+
+/// Exit code of a finished process, `None` if it was terminated by a signal.
+pub type ExitCode = Option<i32>;
+
+/// A spawned child process together with all processes it spawns (e.g. `cargo` and
+/// the test binaries it runs), so the whole tree can be terminated at once.
+///
+/// On Unix the child is placed in its own process group (so Ctrl+C in the terminal reaches
+/// only testify, which then kills the tree), on Windows it's assigned to a job object.
+pub struct ProcessTree {
+    child: ChildProcess,
+    #[cfg(target_os = "windows")]
+    job: Option<windows::Job>
+}
+
+/// Output streams of a spawned process.
+pub enum Output {
+    /// Stdout and stderr captured through separate pipes
+    Pipes(Box<dyn Read + Send>, Box<dyn Read + Send>),
+
+    /// Merged terminal output of a process running in a pseudo-terminal
+    Terminal(Box<dyn Read + Send>)
+}
+
+enum ChildProcess {
+    Plain(Child),
+    Pty {
+        child: Box<dyn portable_pty::Child + Send + Sync>,
+        // The master end has to stay open while the output is being read
+        _master: Box<dyn MasterPty + Send>
+    }
+}
+
+impl ProcessTree {
+    /// Spawn `argv` with stdout and stderr piped, and `env` variables added to the environment.
+    pub fn spawn(argv: &[String], env: &[(&str, &str)], dir: &Path) -> io::Result<(Self, Output)> {
+        let mut command = Command::new(&argv[0]);
+        command
+            .args(&argv[1..])
+            .envs(env.iter().cloned())
+            .current_dir(dir)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        Self::spawn_command(command)
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn spawn_command(mut command: Command) -> io::Result<(Self, Output)> {
+        use std::os::unix::process::CommandExt;
+
+        command.process_group(0);
+        let mut child = command.spawn()?;
+        let output = plain_output(&mut child);
+        Ok((Self { child: ChildProcess::Plain(child) }, output))
+    }
+
+    #[cfg(target_os = "windows")]
+    fn spawn_command(mut command: Command) -> io::Result<(Self, Output)> {
+        let job = windows::Job::new()?;
+        let mut child = command.spawn()?;
+        job.assign(&child)?;
+        let output = plain_output(&mut child);
+        Ok((Self { child: ChildProcess::Plain(child), job: Some(job) }, output))
+    }
+
+    /// Spawn `argv` inside a pseudo-terminal, so it behaves as if it was run
+    /// directly in a terminal (colors, progress bars).
+    /// The child is a session leader, so its process group can be killed as well.
+    pub fn spawn_pty(argv: &[String], env: &[(&str, &str)], dir: &Path) -> io::Result<(Self, Output)> {
+        let pair = native_pty_system().openpty(PtySize::default()).map_err(pty_error)?;
+
+        let mut command = CommandBuilder::new(&argv[0]);
+        command.args(&argv[1..]);
+        for &(key, value) in env {
+            command.env(key, value);
+        }
+        command.cwd(dir);
+        let child = pair.slave.spawn_command(command).map_err(pty_error)?;
+        // Otherwise reading the master end would never reach EOF
+        drop(pair.slave);
+
+        let reader = pair.master.try_clone_reader().map_err(pty_error)?;
+        let child = ChildProcess::Pty { child, _master: pair.master };
+        Ok((Self::with_own_group(child), Output::Terminal(reader)))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    fn with_own_group(child: ChildProcess) -> Self {
+        Self { child }
+    }
+
+    #[cfg(target_os = "windows")]
+    fn with_own_group(child: ChildProcess) -> Self {
+        Self { child, job: None }
+    }
+
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitCode>> {
+        match self.child {
+            ChildProcess::Plain(ref mut child) => Ok(child.try_wait()?.map(|status| status.code())),
+            ChildProcess::Pty { ref mut child, .. } => Ok(child.try_wait()?.map(pty_exit_code))
+        }
+    }
+
+    pub fn wait(&mut self) -> io::Result<ExitCode> {
+        match self.child {
+            ChildProcess::Plain(ref mut child) => Ok(child.wait()?.code()),
+            ChildProcess::Pty { ref mut child, .. } => Ok(pty_exit_code(child.wait()?))
+        }
+    }
+
+    fn id(&self) -> Option<u32> {
+        match self.child {
+            ChildProcess::Plain(ref child) => Some(child.id()),
+            ChildProcess::Pty { ref child, .. } => child.process_id()
+        }
+    }
+
+    fn kill_child(&mut self) -> io::Result<()> {
+        match self.child {
+            ChildProcess::Plain(ref mut child) => child.kill(),
+            ChildProcess::Pty { ref mut child, .. } => child.kill()
+        }
+    }
+
+    /// Kill every process in the tree and wait for the child to exit.
+    #[cfg(not(target_os = "windows"))]
+    pub fn kill(&mut self) -> io::Result<ExitCode> {
+        use libc;
+
+        // Negative PID addresses the whole process group
+        let result = match self.id() {
+            Some(pid) => unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) },
+            None => -1
+        };
+        if result != 0 {
+            // The group may be gone already, fall back to the direct child
+            let _ = self.kill_child();
+        }
+        self.wait()
+    }
+
+    /// Kill every process in the tree and wait for the child to exit.
+    #[cfg(target_os = "windows")]
+    pub fn kill(&mut self) -> io::Result<ExitCode> {
+        let terminated = match self.job {
+            Some(ref job) => job.terminate().is_ok(),
+            None => false
+        };
+        if !terminated {
+            let _ = self.kill_child();
+        }
+        self.wait()
+    }
+}
+
+fn plain_output(child: &mut Child) -> Output {
+    let stdout = child.stdout.take().expect("stdout must be piped");
+    let stderr = child.stderr.take().expect("stderr must be piped");
+    Output::Pipes(Box::new(stdout), Box::new(stderr))
+}
+
+fn pty_exit_code(status: portable_pty::ExitStatus) -> ExitCode {
+    Some(status.exit_code() as i32)
+}
+
+fn pty_error<E: ::std::fmt::Display>(err: E) -> io::Error {
+    io::Error::other(format!("failed to spawn process in a pseudo-terminal: {}", err))
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::io;
+    use std::ptr;
+    use std::process::Child;
+    use std::os::windows::io::AsRawHandle;
+
+    use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW, TerminateJobObject};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::winnt::HANDLE;
+
+    pub struct Job(HANDLE);
+
+    impl Job {
+        pub fn new() -> io::Result<Self> {
+            let handle = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Job(handle))
+            }
+        }
+
+        pub fn assign(&self, child: &Child) -> io::Result<()> {
+            let result = unsafe { AssignProcessToJobObject(self.0, child.as_raw_handle() as HANDLE) };
+            if result == 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+        }
+
+        pub fn terminate(&self) -> io::Result<()> {
+            let result = unsafe { TerminateJobObject(self.0, 1) };
+            if result == 0 { Err(io::Error::last_os_error()) } else { Ok(()) }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0); }
+        }
+    }
+}
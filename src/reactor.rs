@@ -1,221 +1,2386 @@
-use notify::{RecommendedWatcher, Watcher, Event};
+use notify::Event;
 
-#[cfg(not(target_os = "windows"))]
-use notify_rust::Notification;
-#[cfg(target_os = "windows")]
-use winrt_notification;
-
-use std::process::{Command, Stdio};
-use std::time::Instant;
-use std::io::{BufRead, BufReader};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::time::{Duration, Instant};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::thread::{self, JoinHandle};
 use std::process;
-use std::sync::mpsc::channel;
-use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::fs;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::mem;
+use std::slice;
+use std::env;
+use std::path::{Path, PathBuf};
+
+use cargo_metadata;
+use ctrlc;
+use chrono::Local;
+use serde_json;
 
-use config::Config;
+use config::{AnnotationFormat, Config, FmtMode, NotifyMode, OutputFormat, Runner};
 use report_builder::ReportBuilder;
-use report::{Outcome, Report};
+use report::{format_duration, Outcome, Report};
+use process_tree::{ExitCode, Output, ProcessTree};
+use filter::{normalize, PathFilter};
+use notifier::{self, Action, Notify};
+use history;
+use stats;
+use junit;
+use annotations;
+use targeting;
+use ssh;
+use docker;
+use mutants;
+use status::{self, LatestRun, Status};
+use server::{self, EventStream};
+use watcher;
+use events;
+use errors;
+use keyboard::{self, Key};
+use daemon::{self, Control};
+use ui;
+use picker;
+use snapshots;
+use seeds;
+use dependencies;
+use manifest;
+use content::ContentCache;
+use scope::{self, Scope};
+use git::Repository;
+use changes::Changes;
+use dotenv;
+
+/// How often the running child process is polled when in-flight runs can be cancelled,
+/// and whether another project has finished its run when several are watched.
+const POLL_INTERVAL_MS: u64 = 100;
+
+/// Quiet period after git has moved `HEAD`, since a rebase moves it once per commit.
+const CHECKOUT_SETTLE_MS: u64 = 1000;
+
+/// How long to wait for git to remove the lock of the index, a stale one is left by a crashed git.
+const GIT_LOCK_TIMEOUT_SECS: u64 = 30;
+
+/// Exit code after Ctrl+C, the same shells use for a process terminated by SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
 
-pub struct Reactor<'a> {
-    config: Config<'a>,
-    last_run_at: Instant,
-    report_builder: ReportBuilder
+/// Makes cargo print compiler messages as JSON lines to stdout, with the human readable
+/// (colored) rendering included.
+const CARGO_MESSAGE_FORMAT: &str = "--message-format=json-diagnostic-rendered-ansi";
+
+/// Step run before the tests with `check_first`. Tests are checked too, since `cargo test`
+/// compiles them.
+const CHECK_STEP: &str = "check --tests";
+
+/// Step run before the tests with `clippy`.
+const CLIPPY_STEP: &str = "clippy --all-targets";
+
+/// Step run after the tests with `miri`. Miri is available on nightly only.
+const MIRI_STEP: &str = "+nightly miri test";
+
+/// Where the output of the latest command is saved, so it can be opened from a notification.
+pub const OUTPUT_FILE: &str = ".testify/output.log";
+
+/// Input of the reactor: file system events, keys pressed by the user, actions chosen
+/// in notifications, requests to the daemon, results of background jobs and Ctrl+C.
+enum Message {
+    Change(Event),
+    Key(Key),
+    Action(Action),
+    Control(Control),
+    Mutants(Box<Report>),
+    /// The UI wants the names of the tests to pick from
+    ListTests,
+    /// Tests picked to run instead of all of them, none to run all of them again
+    Pick(Vec<String>),
+    Interrupt
 }
 
-impl<'a> Reactor<'a> {
-    pub fn new(config: Config<'a>) -> Self {
-        Self {
-            config,
-            last_run_at: Instant::now(),
-            report_builder: ReportBuilder::new()
+/// What has caused a test run.
+enum Trigger {
+    /// Testify has just started
+    Start,
+
+    /// A watched file has changed
+    Change(PathBuf),
+
+    /// The user has pressed the rerun key
+    Key,
+
+    /// git has checked out another branch or commit
+    Checkout(String)
+}
+
+impl Trigger {
+    /// Identifier of the trigger, used in machine readable output.
+    fn name(&self) -> &'static str {
+        match *self {
+            Trigger::Start => "start",
+            Trigger::Change(_) => "change",
+            Trigger::Key => "key",
+            Trigger::Checkout(_) => "checkout"
+        }
+    }
+
+    /// Changed file, relative to the project root.
+    fn file(&self) -> Option<String> {
+        match *self {
+            Trigger::Change(ref path) => Some(path.to_string_lossy().into_owned()),
+            Trigger::Start | Trigger::Key | Trigger::Checkout(_) => None
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Trigger::Start => write!(f, "started"),
+            Trigger::Change(ref path) => write!(f, "{} changed", path.display()),
+            Trigger::Key => write!(f, "rerun requested"),
+            Trigger::Checkout(ref head) => write!(f, "switched to {}", head)
         }
     }
+}
+
+/// A step of the pipeline run on every change.
+enum Stage {
+    /// Cargo subcommand with arguments, e.g. `check --tests`
+    Cargo(String),
+
+    /// Clippy with arguments, e.g. `clippy -- -D warnings`. Its warnings and errors are counted.
+    Clippy(String),
+
+    /// `cargo fmt` checking or fixing formatting
+    Fmt(FmtMode),
+
+    /// The tests run with Miri, which detects undefined behavior
+    Miri,
+
+    /// The configured test command
+    Tests
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Stage::Cargo(ref step) | Stage::Clippy(ref step) => write!(f, "cargo {}", step),
+            Stage::Fmt(FmtMode::Check) => write!(f, "cargo fmt --check"),
+            Stage::Fmt(_) => write!(f, "cargo fmt"),
+            Stage::Miri => write!(f, "cargo {}", MIRI_STEP),
+            Stage::Tests => write!(f, "tests")
+        }
+    }
+}
+
+/// A combination of toolchain, target and feature set the test suite is run with.
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Variant {
+    /// Rustup toolchain, e.g. `nightly`
+    toolchain: Option<String>,
+    /// Target triple, e.g. `x86_64-unknown-linux-musl`
+    target: Option<String>,
+    /// Cargo arguments selecting features, e.g. `--no-default-features`
+    features: Option<String>
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(ref toolchain) = self.toolchain {
+            parts.push(format!("+{}", toolchain));
+        }
+        if let Some(ref target) = self.target {
+            parts.push(target.clone());
+        }
+        if let Some(ref features) = self.features {
+            parts.push(features.clone());
+        }
+        if parts.is_empty() {
+            write!(f, "default features")
+        } else {
+            write!(f, "{}", parts.join(" "))
+        }
+    }
+}
+
+/// How a single run of the tests ended.
+#[derive(PartialEq)]
+enum RunStatus {
+    /// The tests finished and the report has been delivered
+    Finished,
+
+    /// The command was killed, because a newer relevant change arrived or a rerun was requested
+    Cancelled,
+
+    /// The command was killed, because the user wants to quit or has pressed Ctrl+C
+    Quit
+}
+
+/// Watches the project, runs the tests on change and delivers reports to the notifier.
+pub struct Reactor {
+    config: Config,
+    /// Rebuilt when `.testifyignore` changes
+    filter: RefCell<PathFilter>,
+    notifier: Box<dyn Notify>,
+    report_builder: ReportBuilder,
+    /// Tests which failed in the latest run
+    failed_tests: Vec<String>,
+    /// Exit code of the latest run
+    last_exit_code: Option<i32>,
+    /// Whether the latest run has succeeded, `None` before the first one
+    last_success: Option<bool>,
+    /// Missed mutants of the latest mutation testing which reported any
+    last_mutants: RefCell<Option<String>>,
+    /// Line coverage of the latest run it's known of
+    last_coverage: Option<f64>,
+    /// Recorded runs, oldest first, the usual duration of a run is computed from
+    recent_runs: Vec<history::Entry>,
+    /// File changes are ignored while paused
+    paused: Cell<bool>,
+    /// Notifications are turned off with the `n` key until it's pressed again
+    notifications_muted: Cell<bool>,
+    /// Tests picked to run instead of the whole suite
+    picked_tests: RefCell<Vec<String>>,
+    /// Failing test which runs alone with its output until it passes
+    followed_test: RefCell<Option<String>>,
+    /// The followed test passes now, so all tests run right away
+    widen: bool,
+    /// Snapshots which have changed in the latest run, to accept or reject with a key
+    pending_snapshots: Vec<String>,
+    /// Number of test runs so far
+    run_number: Cell<u32>,
+    /// A relevant change has arrived during a run, so the tests have to run again once it finishes
+    pending: Cell<bool>,
+    /// Ctrl+C has been pressed, testify is shutting down
+    interrupted: Cell<bool>,
+    trigger: RefCell<Trigger>,
+    /// State of the latest run, as reported by the daemon and the HTTP server
+    latest: Arc<Mutex<LatestRun>>,
+    /// Live updates for the dashboard
+    event_stream: EventStream,
+    /// Background jobs deliver their results as messages, once watching has started
+    background_tx: Option<Sender<Message>>,
+    /// `cargo mutants` running after the latest green run
+    mutants_job: Option<mutants::Job>,
+    /// Content of the watched files, to skip events which have not changed it
+    contents: RefCell<ContentCache>,
+    /// Files and directories cargo writes itself (`Cargo.lock`, the target directory), whose
+    /// changes during a run or right after it do not trigger another one
+    own_writes: Vec<PathBuf>,
+    /// A command is running now
+    command_running: Cell<bool>,
+    /// When the latest command has exited
+    command_exited_at: Cell<Option<Instant>>,
+    /// The `w` key has been pressed, so the next run is not restricted to the changes since `changed_since`
+    full_run: Cell<bool>,
+    /// Packages or modules the current run is restricted to, `None` runs the whole suite
+    scope: RefCell<Option<Scope>>,
+    /// The git repository the project is in
+    repository: Option<Repository>,
+    /// Branch or commit checked out when the tests ran last
+    head: RefCell<Option<String>>,
+    /// The lock of the git index has outlived the timeout, so it's likely left behind by a crashed git
+    stale_lock: Cell<bool>,
+    /// Command `command_map` has chosen for the current run, instead of the configured one
+    mapped_command: Option<String>,
+    /// Files changed since the current run has started, relative to the project root
+    changed_files: RefCell<Vec<PathBuf>>,
+    /// Files which have triggered the current run, passed to a custom command
+    changes: Changes,
+    /// Variables of the env file, loaded again before every run
+    env_file_vars: Vec<(String, String)>,
+    /// Set when several projects are watched at once
+    group: Option<Group>
+}
+
+/// A project watched together with others.
+struct Group {
+    /// Shown in the messages and the notifications
+    name: String,
+    /// Held while the tests of a project run, so their output does not mix
+    turn: Arc<Mutex<()>>
+}
+
+impl Reactor {
+    /// Create a reactor which notifies with the notifier chosen in the configuration.
+    pub fn new(config: Config) -> errors::Result<Self> {
+        let notifier = notifier::obtain_notifier(&config)?;
+        let report_builder = ReportBuilder::new().with_rules(&config.outcome_rules)?;
+        let recent_runs = if config.history { history::load(&config.project_dir).unwrap_or_default() } else { vec![] };
+        let mut watch = config.watch.clone();
+        let mut own_writes = vec![config.project_dir.join("Cargo.lock"), config.project_dir.join("target")];
+        // A workspace member is built with the lock file and the settings of the workspace
+        if let Some(root) = manifest::workspace_root(&config.project_dir).filter(|root| *root != config.project_dir) {
+            watch.extend(["Cargo.toml", "Cargo.lock"].iter().map(|file| root.join(file).to_string_lossy().into_owned()));
+            own_writes.extend(vec![root.join("Cargo.lock"), root.join("target")]);
+        }
+        if let Some(target_dir) = env::var_os("CARGO_TARGET_DIR") {
+            own_writes.push(normalize(&config.project_dir.join(target_dir)));
+        }
+        if config.watch_dependencies {
+            let sources = dependencies::path_dependencies(&config.project_dir).into_iter().map(|dir| dir.join("src"));
+            watch.extend(sources.map(|dir| dir.to_string_lossy().into_owned()));
+        }
+        // Usually ignored by git, but changing the variables affects the tests
+        if let Some(ref env_file) = config.env_file {
+            watch.push(env_file.to_string_lossy().into_owned());
+        }
+        let repository = Repository::find(&config.project_dir);
+        let head = repository.as_ref().and_then(Repository::head);
+        Ok(Self {
+            filter: RefCell::new(PathFilter::detect(&config.project_dir, &watch, &config.ignore).watch_patterns(&config.watch_patterns)),
+            notifier,
+            config,
+            report_builder,
+            failed_tests: vec![],
+            last_exit_code: None,
+            last_success: None,
+            last_mutants: RefCell::new(None),
+            last_coverage: None,
+            recent_runs,
+            paused: Cell::new(false),
+            notifications_muted: Cell::new(false),
+            picked_tests: RefCell::new(vec![]),
+            followed_test: RefCell::new(None),
+            widen: false,
+            pending_snapshots: vec![],
+            run_number: Cell::new(0),
+            pending: Cell::new(false),
+            interrupted: Cell::new(false),
+            trigger: RefCell::new(Trigger::Start),
+            latest: Arc::new(Mutex::new(LatestRun::default())),
+            event_stream: EventStream::default(),
+            background_tx: None,
+            mutants_job: None,
+            contents: RefCell::new(ContentCache::default()),
+            own_writes,
+            command_running: Cell::new(false),
+            command_exited_at: Cell::new(None),
+            full_run: Cell::new(false),
+            scope: RefCell::new(None),
+            repository,
+            head: RefCell::new(head),
+            stale_lock: Cell::new(false),
+            mapped_command: None,
+            changed_files: RefCell::new(vec![]),
+            changes: Changes::default(),
+            env_file_vars: vec![],
+            group: None
+        })
+    }
+
+    /// Deliver reports to `notifier` instead of the one chosen in the configuration.
+    ///
+    /// ```no_run
+    /// extern crate cargo_testify;
+    ///
+    /// use std::path::PathBuf;
+    /// use cargo_testify::Reactor;
+    /// use cargo_testify::config::ConfigBuilder;
+    /// use cargo_testify::errors::Result;
+    /// use cargo_testify::notifier::Notify;
+    /// use cargo_testify::report::Report;
+    ///
+    /// struct PrintNotifier;
+    ///
+    /// impl Notify for PrintNotifier {
+    ///     fn notify(&self, report: &Report) -> Result<()> {
+    ///         println!("{}", report.title());
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// fn main() {
+    ///     let config = ConfigBuilder::new().project_dir(PathBuf::from(".")).build().unwrap();
+    ///     let mut reactor = Reactor::new(config).unwrap().with_notifier(Box::new(PrintNotifier));
+    ///     reactor.run_once();
+    /// }
+    /// ```
+    pub fn with_notifier(mut self, notifier: Box<dyn Notify>) -> Self {
+        self.notifier = notifier;
+        self
+    }
 
-    pub fn start(&mut self) {
+    /// Run the tests a single time without watching for changes, notify about the result,
+    /// and return the exit code of the test command.
+    pub fn run_once(&mut self) -> i32 {
+        // Only Ctrl+C is expected, otherwise the command is just awaited
         let (tx, rx) = channel();
-        let mut watcher: RecommendedWatcher = Watcher::new(tx).expect("Failed to obtain a watcher");
-        watcher.watch(&self.config.project_dir).expect("Failed to start watcher");
+        handle_interrupt(tx);
+        self.run_tests(&rx);
+        if self.interrupted.get() { return INTERRUPTED_EXIT_CODE; }
+        self.last_exit_code.unwrap_or(1)
+    }
+
+    /// Watch for changes and run the tests until the user quits.
+    /// Returns the exit code for testify.
+    pub fn start(&mut self) -> i32 {
+        let (tx, rx) = channel();
+        let exit_code = self.watch(tx, rx);
+        self.cancel_mutants();
+        exit_code
+    }
+
+    /// Watch for changes and run the tests like `start`, but take requests of the `status`,
+    /// `run-now` and `stop` subcommands through the control interface of the daemon.
+    pub fn start_daemon(&mut self) -> i32 {
+        let (tx, rx) = channel();
+        let control_tx = tx.clone();
+        let listening = daemon::listen(&self.config.project_dir, self.latest.clone(), move |control| {
+            let _ = control_tx.send(Message::Control(control));
+        });
+        if let Err(err) = listening {
+            eprintln!("Error: {}", err);
+            return 1;
+        }
+        self.inform(&format!("Daemon is running (pid {})", process::id()));
+        let exit_code = self.watch(tx, rx);
+        self.cancel_mutants();
+        daemon::remove(&self.config.project_dir);
+        exit_code
+    }
 
-        self.last_run_at = Instant::now();
-        self.run_tests();
+    fn watch(&mut self, tx: Sender<Message>, rx: Receiver<Message>) -> i32 {
+        // Before Ctrl+C is handled, so it still aborts the prompt
+        let picked = if self.config.pick {
+            self.inform("Listing the tests");
+            let picked = self.list_tests()
+                .map_err(|err| err.to_string())
+                .and_then(|names| picker::prompt(&names).map_err(|err| err.to_string()));
+            match picked {
+                Ok(picked) => picked,
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    return 1;
+                }
+            }
+        } else {
+            vec![]
+        };
+        // Ctrl+C and the keys are shared by the projects watched together
+        if self.group.is_none() {
+            handle_interrupt(tx.clone());
+        }
+        self.background_tx = Some(tx.clone());
+
+        if let Some(ref address) = self.config.listen {
+            let run_tx = Mutex::new(tx.clone());
+            let listening = server::listen(address, self.latest.clone(), self.event_stream.clone(), move || {
+                let _ = run_tx.lock().unwrap().send(Message::Control(Control::Run));
+            });
+            match listening {
+                Ok(()) => self.inform(&format!("Listening on http://{}", address)),
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    return 1;
+                }
+            }
+        }
+
+        let action_tx = Mutex::new(tx.clone());
+        self.notifier.on_action(Arc::new(move |action| {
+            let _ = action_tx.lock().unwrap().send(Message::Action(action));
+        }));
+
+        let change_tx = tx.clone();
+        let external_paths = self.filter.borrow().external_paths().iter().map(|path| path.to_path_buf()).collect();
+        watcher::spawn(self.config.project_dir.clone(), external_paths, self.config.poll, move |event| {
+            change_tx.send(Message::Change(event)).is_ok()
+        });
+
+        // The UI reads the keys itself, and gives the terminal back when it's dropped on return
+        let (_ui, terminal_guard) = if self.config.ui {
+            let on_request = move |request| {
+                let _ = tx.send(match request {
+                    ui::Request::Key(key) => Message::Key(key),
+                    ui::Request::ListTests => Message::ListTests,
+                    ui::Request::Pick(names) => Message::Pick(names)
+                });
+            };
+            match ui::start(self.event_stream.subscribe(), on_request) {
+                Ok(ui) => (Some(ui), None),
+                Err(err) => {
+                    eprintln!("Error: failed to start the UI: {}", err);
+                    return 1;
+                }
+            }
+        } else if self.config.interactive && self.group.is_none() {
+            (None, keyboard::listen(move |key| { let _ = tx.send(Message::Key(key)); }))
+        } else {
+            (None, None)
+        };
+        if terminal_guard.is_some() {
+            self.inform(keyboard::HELP);
+        }
+        if self.config.skip_unchanged {
+            let filter = self.filter.borrow();
+            self.contents.borrow_mut().scan(&self.config.project_dir, |path| filter.allows(path));
+        }
+        if !picked.is_empty() {
+            self.pick(picked);
+        }
+
+        if self.run(&rx) == RunStatus::Quit { return self.exit_code(); }
 
         loop {
-            match rx.recv() {
-                Ok(event) => {
-                    if self.should_react(event) {
-                        self.run_tests();
-                        self.last_run_at = Instant::now();
-                    }
+            // The watcher thread never stops, so the channel can not be disconnected
+            let message = rx.recv().expect("file watcher has stopped");
+            let run_status = match message {
+                Message::Change(event) => {
+                    if self.paused.get() || !self.should_react(&event) { continue; }
+                    self.set_trigger(&event);
+                    if !self.settle(&rx) { return self.exit_code(); }
+                    if self.has_conflicts() { continue; }
+                    self.run(&rx)
                 },
-                Err(err) => {
-                    eprintln!("Unexpected error occurred:");
-                    eprintln!("  {:?}", err);
-                    process::exit(1);
+                Message::Key(Key::Rerun) | Message::Action(Action::Rerun) | Message::Control(Control::Run) => {
+                    self.trigger.replace(Trigger::Key);
+                    self.run(&rx)
+                },
+                Message::Key(Key::FullRun) => {
+                    self.full_run.set(true);
+                    self.trigger.replace(Trigger::Key);
+                    self.run(&rx)
+                },
+                Message::Action(Action::OpenOutput) => {
+                    self.open_output();
+                    continue;
+                },
+                Message::Key(Key::AcceptSnapshots) | Message::Action(Action::AcceptSnapshots) => {
+                    if !self.review_snapshots(true) { continue; }
+                    self.trigger.replace(Trigger::Key);
+                    self.run(&rx)
+                },
+                Message::Key(Key::RejectSnapshots) => {
+                    self.review_snapshots(false);
+                    continue;
+                },
+                Message::Mutants(report) => {
+                    self.report_mutants(&report);
+                    continue;
+                },
+                Message::ListTests => {
+                    self.offer_tests();
+                    continue;
+                },
+                Message::Pick(names) => {
+                    self.pick(names);
+                    self.trigger.replace(Trigger::Key);
+                    self.run(&rx)
+                },
+                Message::Key(Key::Quit) | Message::Control(Control::Stop) => RunStatus::Quit,
+                Message::Key(key) => {
+                    self.handle_key(key);
+                    continue;
+                },
+                Message::Interrupt => {
+                    self.interrupted.set(true);
+                    RunStatus::Quit
                 }
+            };
+            if run_status == RunStatus::Quit { return self.exit_code(); }
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.interrupted.get() { INTERRUPTED_EXIT_CODE } else { 0 }
+    }
+
+    /// Run the tests. With `restart` enabled a run interrupted by a new change
+    /// is started over, until one completes. Otherwise changes made during the run
+    /// cause one more run right after it, so the latest state of the code is always tested.
+    fn run(&mut self, rx: &Receiver<Message>) -> RunStatus {
+        loop {
+            let turn = self.group.as_ref().map(|group| group.turn.clone());
+            let _turn_guard = match turn {
+                Some(ref turn) => match self.wait_for_turn(turn, rx) {
+                    Some(guard) => Some(guard),
+                    None => return RunStatus::Quit
+                },
+                None => None
+            };
+            match self.run_tests(rx) {
+                RunStatus::Cancelled => {
+                    // The run starting over covers the changes of the cancelled one as well
+                    let cancelled = mem::take(&mut self.changes);
+                    self.changed_files.borrow_mut().splice(0..0, cancelled.paths);
+                    let by_change = matches!(*self.trigger.borrow(), Trigger::Change(_) | Trigger::Checkout(_));
+                    if by_change && !self.settle(rx) { return RunStatus::Quit; }
+                    if by_change && self.has_conflicts() {
+                        return RunStatus::Finished;
+                    }
+                    self.inform(&format!("Restarting `{}`", self.command_name()));
+                },
+                RunStatus::Finished if self.widen => {
+                    self.widen = false;
+                    self.pending.set(false);
+                },
+                RunStatus::Finished if self.pending.get() => {
+                    self.pending.set(false);
+                    if !self.settle(rx) { return RunStatus::Quit; }
+                    if self.has_conflicts() {
+                        return RunStatus::Finished;
+                    }
+                    self.inform(&format!("Files changed during the run, running `{}` again", self.command_name()));
+                },
+                status => return status
             }
         }
     }
 
-    fn should_react(&self, event: Event) -> bool {
-        // ignore event if tests just finished very recently
-        if Instant::now() - self.last_run_at < self.config.ignore_duration {
+    /// Wait until no other project watched together with this one is running its tests. Changes and
+    /// rerun requests arriving meanwhile are covered by the run which follows, other messages are
+    /// handled once it's over. Returns `None` if the user wants to quit.
+    fn wait_for_turn<'a>(&self, turn: &'a Mutex<()>, rx: &Receiver<Message>) -> Option<MutexGuard<'a, ()>> {
+        let mut deferred = vec![];
+        let guard = loop {
+            match turn.try_lock() {
+                Ok(guard) => break guard,
+                Err(TryLockError::Poisoned(err)) => break err.into_inner(),
+                Err(TryLockError::WouldBlock) => {}
+            }
+            match rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+                Ok(Message::Key(Key::Quit)) | Ok(Message::Control(Control::Stop)) => return None,
+                Ok(Message::Interrupt) => {
+                    self.interrupted.set(true);
+                    return None;
+                },
+                Ok(Message::Change(_)) | Ok(Message::Key(Key::Rerun)) | Ok(Message::Action(Action::Rerun)) | Ok(Message::Control(Control::Run)) => {},
+                Ok(Message::Key(Key::FullRun)) => self.full_run.set(true),
+                Ok(message) => deferred.push(message),
+                Err(_) => {}
+            }
+        };
+        if let Some(ref tx) = self.background_tx {
+            for message in deferred {
+                let _ = tx.send(message);
+            }
+        }
+        Some(guard)
+    }
+
+    /// Wait until no relevant changes arrive during `delay`, so a burst of events (atomic saves,
+    /// `git checkout`) results in a single run. Returns `false` if the user wants to quit.
+    ///
+    /// While git holds the lock of the index (checking out, rebasing) the wait goes on until it's released,
+    /// and once git has moved `HEAD` until it has stayed put for a while, so a branch switch runs the tests once.
+    fn settle(&self, rx: &Receiver<Message>) -> bool {
+        let mut deadline = Instant::now() + self.config.delay;
+        let mut locked_since: Option<Instant> = None;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(timeout) {
+                Ok(Message::Change(event)) => {
+                    if !self.paused.get() && self.should_react(&event) {
+                        // The files of a checkout keep changing for a while, the checkout stays the trigger
+                        let checkout = matches!(*self.trigger.borrow(), Trigger::Checkout(_));
+                        if checkout {
+                            self.record_change(&event);
+                        } else {
+                            self.set_trigger(&event);
+                        }
+                        deadline = Instant::now() + self.config.delay;
+                    }
+                },
+                Ok(Message::Key(Key::Quit)) | Ok(Message::Control(Control::Stop)) => return false,
+                Ok(Message::Interrupt) => {
+                    self.interrupted.set(true);
+                    return false;
+                },
+                Ok(Message::Key(Key::Rerun)) | Ok(Message::Action(Action::Rerun)) | Ok(Message::Control(Control::Run)) => return true,
+                Ok(Message::Key(Key::FullRun)) => {
+                    self.full_run.set(true);
+                    return true;
+                },
+                Ok(Message::Key(key)) => self.handle_key(key),
+                Ok(Message::ListTests) => self.offer_tests(),
+                Ok(Message::Pick(names)) => {
+                    self.pick(names);
+                    return true;
+                },
+                Ok(Message::Action(Action::OpenOutput)) => self.open_output(),
+                Ok(Message::Action(Action::AcceptSnapshots)) => self.handle_key(Key::AcceptSnapshots),
+                // The code has changed since, so the tests which would catch the mutants may exist already
+                Ok(Message::Mutants(_)) => {},
+                Err(RecvTimeoutError::Timeout) => {
+                    let locked = self.repository.as_ref().is_some_and(Repository::is_locked);
+                    if !locked {
+                        self.stale_lock.set(false);
+                    }
+                    // A lock left behind by a crashed git is waited for only once
+                    let locked = locked && !self.stale_lock.get();
+                    let waited = locked_since.map_or(Duration::from_secs(0), |since| since.elapsed());
+                    if locked && waited < Duration::from_secs(GIT_LOCK_TIMEOUT_SECS) {
+                        if locked_since.is_none() {
+                            self.inform("Waiting for git to finish");
+                            locked_since = Some(Instant::now());
+                        }
+                        deadline = Instant::now() + Duration::from_millis(POLL_INTERVAL_MS);
+                        continue;
+                    }
+                    if locked {
+                        eprintln!("Warning: .git/index.lock has been there for {}s, running the tests anyway \
+                                   and not waiting for it again until it's removed", GIT_LOCK_TIMEOUT_SECS);
+                        self.stale_lock.set(true);
+                    }
+                    if self.head_has_moved() {
+                        deadline = Instant::now() + Duration::from_millis(CHECKOUT_SETTLE_MS);
+                        continue;
+                    }
+                    return true;
+                },
+                Err(RecvTimeoutError::Disconnected) => return true
+            }
+        }
+    }
+
+    /// Has another branch or commit been checked out since the last check? It becomes the trigger of the run then.
+    fn head_has_moved(&self) -> bool {
+        let head = match self.repository.as_ref().and_then(Repository::head) {
+            Some(head) => head,
+            None => return false
+        };
+        if self.head.borrow().as_ref() == Some(&head) { return false; }
+        self.inform(&format!("Switched to {}", head));
+        self.head.replace(Some(head.clone()));
+        self.trigger.replace(Trigger::Checkout(head));
+        true
+    }
+
+    /// Is a merge, rebase, cherry-pick or revert stopped on conflicts? The code can't be built until they are
+    /// resolved, so changes do not run the tests meanwhile.
+    fn has_conflicts(&self) -> bool {
+        let repository = match self.repository {
+            Some(ref repository) => repository,
+            None => return false
+        };
+        match repository.operation() {
+            Some(operation) if repository.has_conflicts() => {
+                self.inform(&format!("Skipping the run, the {} has conflicts", operation));
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// Handle keys which do not affect a running command.
+    fn handle_key(&self, key: Key) {
+        match key {
+            Key::Clear => self.clear_screen(),
+            Key::Pause => {
+                let paused = !self.paused.get();
+                self.paused.set(paused);
+                self.inform(if paused { "Watching paused, press p to resume" } else { "Watching resumed" });
+            },
+            Key::ToggleNotifications => {
+                let muted = !self.notifications_muted.get();
+                self.notifications_muted.set(muted);
+                self.inform(if muted { "Notifications muted, press n to turn them back on" } else { "Notifications unmuted" });
+            },
+            Key::AllTests => {
+                if !self.picked_tests.borrow().is_empty() {
+                    self.pick(vec![]);
+                }
+            },
+            Key::Follow => self.toggle_follow(),
+            // The snapshots are reviewed between runs, the tests may be writing new ones now
+            Key::AcceptSnapshots | Key::RejectSnapshots => self.inform("Snapshots can be reviewed once the run has finished"),
+            Key::Rerun | Key::FullRun | Key::Quit => {}
+        }
+    }
+
+    /// Names of all the tests, as listed by the test binaries.
+    fn list_tests(&self) -> errors::Result<Vec<String>> {
+        if self.custom_command().is_some() || self.config.runner != Runner::Cargo {
+            return Err(errors::ErrorKind::TestListFailed("tests can only be listed with `cargo test`".to_string()).into());
+        }
+        let argv = self.command_line(&self.variants()[0], &["--list".to_string()], false);
+        let output = process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(&self.config.project_dir)
+            .output()
+            .map_err(|err| errors::ErrorKind::TestListFailed(format!("failed to run `{}`: {}", argv.join(" "), err)))?;
+        if !output.status.success() {
+            return Err(errors::ErrorKind::TestListFailed(format!("`{}` has failed, does the code compile?", argv.join(" "))).into());
+        }
+        Ok(picker::parse_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Send the names of the tests to the UI to pick from.
+    fn offer_tests(&self) {
+        self.inform("Listing the tests");
+        match self.list_tests() {
+            Ok(names) => self.event_stream.send("tests", &json!({ "names": names })),
+            Err(err) => eprintln!("Warning: {}", err)
+        }
+    }
+
+    /// Run only the `picked` tests from now on, or the whole suite again if none are picked.
+    fn pick(&self, picked: Vec<String>) {
+        if picked.is_empty() {
+            self.inform("Running all tests again");
+        } else {
+            self.inform(&format!("Running {} picked tests from now on, press a to run all tests again", picked.len()));
+        }
+        self.event_stream.send("picked", &json!({ "names": picked }));
+        self.picked_tests.replace(picked);
+    }
+
+    /// Follow the first failing test of the latest run, or stop following one.
+    fn toggle_follow(&self) {
+        let followed = self.followed_test.borrow().clone();
+        let name = match followed {
+            Some(name) => {
+                self.inform(&format!("Stopped following `{}`", name));
+                None
+            },
+            None if self.custom_command().is_some() => {
+                self.inform("Tests can not be followed with a custom command");
+                return;
+            },
+            None => match self.failed_tests.first() {
+                Some(name) => {
+                    self.inform(&format!("Following `{}`, it runs alone with its output until it passes", name));
+                    Some(name.clone())
+                },
+                None => {
+                    self.inform("There is no failing test to follow");
+                    return;
+                }
+            }
+        };
+        self.event_stream.send("followed", &json!({ "name": name }));
+        self.followed_test.replace(name);
+    }
+
+    /// Accept the snapshots which have changed in the latest run with `cargo insta`, or reject them.
+    /// Returns whether they have been accepted, so the tests have to run again.
+    fn review_snapshots(&mut self, accept: bool) -> bool {
+        if self.pending_snapshots.is_empty() {
+            self.inform("There are no snapshots to review");
             return false;
         }
+        let (verb, done) = if accept { ("accept", "Accepted") } else { ("reject", "Rejected") };
+        match snapshots::review(&self.config.project_dir, &self.pending_snapshots, accept) {
+            Ok(ref status) if status.success() => {
+                self.inform(&format!("{} {} snapshots", done, self.pending_snapshots.len()));
+                self.pending_snapshots.clear();
+                accept
+            },
+            Ok(status) => {
+                eprintln!("Warning: `cargo insta {}` has failed ({}), is cargo-insta installed?", verb, status);
+                false
+            },
+            Err(err) => {
+                eprintln!("Warning: failed to run `cargo insta {}`: {}", verb, err);
+                false
+            }
+        }
+    }
+
+    /// Open the output of the latest command in the default application for text files.
+    fn open_output(&self) {
+        let path = self.config.project_dir.join(OUTPUT_FILE);
+        if let Err(err) = open_file(&path) {
+            eprintln!("Warning: failed to open {:?}: {}", path, err);
+        }
+    }
 
-        match event.path {
-            Some(path) => filter_allows(self.config.project_dir.as_path(), path.as_path()),
-            None => false
-        }
-    }
-
-    /// Spawn `cargo test` and catch stdout and stderr, then build report and call notifier.
-    /// TODO: Number of things can and have to be improved here:
-    ///   * Preserve color output of `cargo test`
-    ///   * Is it possible intercept stdout and stderr in one thread using futures?
-    fn run_tests(&self) {
-        let mut args = self.config.cargo_test_args.clone();
-        args.insert(0, "test");
-
-        let result = Command::new("cargo")
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn();
-
-        match result {
-            Ok(mut child) => {
-                // Catch stdout
-                let stdout = child.stdout.take().unwrap();
-                let stdout_buf_reader = BufReader::new(stdout);
-                let stdout_buffer = Arc::new(Mutex::new(String::new()));
-                let stdout_buffer_clone = stdout_buffer.clone();
-                thread::spawn(move || {
-                    for line in stdout_buf_reader.lines() {
-                        let line = line.unwrap();
-                        let mut buffer = stdout_buffer_clone.lock().unwrap();
-                        buffer.push_str(&line);
-                        buffer.push('\n');
-                        println!("{}", line);
+    /// Save the output of the command without colors, with cargo JSON messages replaced by their rendering.
+    fn save_output(&self, stdout: &str, stderr: &str, cargo_json: bool) {
+        let mut output = String::new();
+        if stderr != stdout {
+            output.push_str(stderr);
+        }
+        for line in stdout.lines() {
+            if cargo_json && line.starts_with("{\"reason\":") {
+                if let Ok(cargo_metadata::Message::CompilerMessage(message)) = serde_json::from_str(line) {
+                    if let Some(rendered) = message.message.rendered {
+                        output.push_str(&rendered);
                     }
-                });
+                }
+            } else {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+        let path = self.config.project_dir.join(OUTPUT_FILE);
+        let output = strip_ansi_escapes::strip(output.as_bytes());
+        let result = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&path, output));
+        if let Err(err) = result {
+            eprintln!("Warning: failed to save output to {:?}: {}", path, err);
+        }
+    }
+
+    fn should_react(&self, event: &Event) -> bool {
+        let path = match event.path {
+            Some(ref path) => path,
+            None => return false
+        };
+        // A changed `.testifyignore` applies to the next changes, it's not a change of the sources itself
+        if self.filter.borrow_mut().reload_ignore_file(path) { return false; }
+        if !watcher::is_content_change(event) || !self.filter.borrow().allows(path) { return false; }
+        // The content is remembered even if cargo has written it, so it's not taken for a change later
+        let changed = !self.config.skip_unchanged || self.contents.borrow_mut().changed(path);
+        changed && !self.is_written_by_command(path)
+    }
+
+    /// Is `path` a file cargo writes itself, changed while a command runs or within `ignore_duration` after
+    /// it has exited, since the events may arrive with a delay? Otherwise updating `Cargo.lock` during a run
+    /// would trigger the next one, over and over. Events of other files are never dropped.
+    fn is_written_by_command(&self, path: &Path) -> bool {
+        let recently = self.command_exited_at.get()
+            .is_some_and(|exited_at| exited_at.elapsed() < self.config.ignore_duration);
+        (self.command_running.get() || recently) && self.own_writes.iter().any(|file| path.starts_with(file))
+    }
+
+    fn set_trigger(&self, event: &Event) {
+        if let Some(ref path) = event.path {
+            let relative_path = path.strip_prefix(&self.config.project_dir).unwrap_or(path);
+            self.trigger.replace(Trigger::Change(relative_path.to_path_buf()));
+        }
+        self.record_change(event);
+    }
+
+    /// Remember the changed file for the next run.
+    fn record_change(&self, event: &Event) {
+        if let Some(ref path) = event.path {
+            let relative_path = path.strip_prefix(&self.config.project_dir).unwrap_or(path);
+            self.changed_files.borrow_mut().push(relative_path.to_path_buf());
+        }
+    }
+
+    /// Print a divider with the run number, what has triggered the run and the current time.
+    fn print_header(&self) {
+        // The UI shows the run in its own title
+        if self.config.ui { return; }
+        let title = format!(" Run #{} · {} · {} ", self.run_number.get(), self.trigger.borrow(), Local::now().format("%H:%M:%S"));
+        self.inform(&format!("────{}{}", title, "─".repeat(72usize.saturating_sub(title.chars().count()))));
+    }
+
+    /// Print a divider with the outcome and the duration of the run.
+    fn print_footer(&self, report: &Report) {
+        if self.config.ui { return; }
+        let duration = report.duration.map(|duration| format!(" · {}", format_duration(duration))).unwrap_or_default();
+        let title = format!(" {}{} ", report.outcome.title(), duration);
+        self.inform(&format!("────{}{}", title, "─".repeat(72usize.saturating_sub(title.chars().count()))));
+    }
+
+    /// Warn when a passing run has taken much longer than the recent passing runs on average.
+    fn warn_on_slowdown(&self, report: &Report) {
+        let (duration, average) = match (report.duration, history::rolling_average(&self.recent_runs)) {
+            (Some(duration), Some(average)) => (duration, average),
+            _ => return
+        };
+        if report.outcome.is_success() && history::is_slowdown(duration, average, self.config.slowdown_threshold) {
+            eprintln!("Warning: the run has taken {}, usually it takes {}",
+                      format_duration(duration), format_duration(average));
+        }
+    }
+
+    /// Print a message for the user. With JSON output stdout is reserved for events,
+    /// so messages go to stderr. The UI shows them in its status bar instead.
+    fn inform(&self, message: &str) {
+        if let Some(ref group) = self.group {
+            let message = format!("[{}] {}", group.name, message);
+            match self.config.output {
+                OutputFormat::Human => println!("{}", message),
+                OutputFormat::Json => eprintln!("{}", message)
+            }
+            return;
+        }
+        if self.config.ui {
+            self.event_stream.send("message", &json!({ "text": message }));
+            return;
+        }
+        match self.config.output {
+            OutputFormat::Human => println!("{}", message),
+            OutputFormat::Json => eprintln!("{}", message)
+        }
+    }
+
+    /// Clear the terminal, unless the output is read by another program or the UI owns the terminal.
+    fn clear_screen(&self) {
+        if self.config.output == OutputFormat::Human && !self.config.ui {
+            clear_screen();
+        }
+    }
+
+    /// Build the command to run on change: either the custom `command` from the config,
+    /// executed by the system shell, or the test runner for the `variant` of the matrix
+    /// with the configured arguments, which runs only tests with the given names.
+    /// Test binary arguments and filters follow `--`. They are ignored for a custom command.
+    fn command_line_with_filters(&self, variant: &Variant, filters: &[String]) -> Vec<String> {
+        self.command_line(variant, filters, true)
+    }
+
+    /// Command line which runs only the tests of the workspace `packages`, instead of the default members
+    /// or the whole workspace.
+    fn command_line_with_packages(&self, variant: &Variant, packages: &[String]) -> Vec<String> {
+        let argv = self.command_line_with_filters(variant, &[]);
+        let separator = argv.iter().position(|arg| arg == "--").unwrap_or(argv.len());
+        let (cargo_args, test_args) = argv.split_at(separator);
+        let mut scoped: Vec<String> = cargo_args.iter().filter(|arg| *arg != "--workspace" && *arg != "--all").cloned().collect();
+        for package in packages {
+            scoped.push("-p".to_string());
+            scoped.push(package.clone());
+        }
+        scoped.extend(test_args.iter().cloned());
+        scoped
+    }
+
+    /// Command line which runs only the tests with names containing `filter`, e.g. `parser::`.
+    fn command_line_with_filter(&self, variant: &Variant, filter: &str) -> Vec<String> {
+        self.command_line(variant, &[filter.to_string()], false)
+    }
+
+    /// Test command with `filters` passed to the test binary, matching whole names if `exact`.
+    /// wasm-pack takes options of cargo after `--`, so the test binary's ones follow one more `--`.
+    fn command_line(&self, variant: &Variant, filters: &[String], exact: bool) -> Vec<String> {
+        match self.custom_command() {
+            Some(custom) => shell_command(&self.changes.expand(custom)),
+            None => {
+                let mut argv = vec![self.program().to_string()];
+                if let Some(ref toolchain) = variant.toolchain {
+                    argv.push(format!("+{}", toolchain));
+                }
+                argv.extend(self.subcommand().into_iter().map(String::from));
+
+                let mut cargo_args = vec![];
+                if self.uses_cargo_json() {
+                    cargo_args.push(CARGO_MESSAGE_FORMAT.to_string());
+                }
+                if let Some(ref target) = variant.target {
+                    cargo_args.push("--target".to_string());
+                    cargo_args.push(target.clone());
+                }
+                if let Some(ref features) = variant.features {
+                    cargo_args.extend(features.split_whitespace().map(String::from));
+                }
+                cargo_args.extend(self.config.cargo_test_args.iter().cloned());
+
+                let has_test_args = !self.config.test_args.is_empty() || !filters.is_empty();
+                if self.config.runner == Runner::WasmPack && (!cargo_args.is_empty() || has_test_args) {
+                    argv.push("--".to_string());
+                }
+                let separated = cargo_args.iter().any(|arg| arg == "--");
+                argv.extend(cargo_args);
+                if has_test_args && !separated {
+                    argv.push("--".to_string());
+                }
+                argv.extend(self.config.test_args.iter().cloned());
+                if exact && !filters.is_empty() {
+                    argv.push("--exact".to_string());
+                }
+                argv.extend(filters.iter().cloned());
+                argv
+            }
+        }
+    }
+
+    /// Filter selecting the tests related to the changed file, if the tests can be narrowed down.
+    fn targeted_filter(&self) -> Option<String> {
+        if !self.config.targeted || self.custom_command().is_some() {
+            return None;
+        }
+        match *self.trigger.borrow() {
+            Trigger::Change(ref path) => targeting::filter_for(&self.config.project_dir, &self.config.test_map, path),
+            Trigger::Start | Trigger::Key | Trigger::Checkout(_) => None
+        }
+    }
+
+    /// Command run instead of the test command: the one `command_map` has chosen for the changed file,
+    /// or the configured one.
+    fn custom_command(&self) -> Option<&String> {
+        self.mapped_command.as_ref().or(self.config.command.as_ref())
+    }
 
-                // Catch stderr
-                let stderr = child.stderr.take().unwrap();
-                let stderr_buf_reader = BufReader::new(stderr);
-                let stderr_buffer = Arc::new(Mutex::new(String::new()));
-                let stderr_buffer_clone = stderr_buffer.clone();
-                thread::spawn(move || {
-                    for line in stderr_buf_reader.lines() {
-                        let line = line.unwrap();
-                        let mut buffer = stderr_buffer_clone.lock().unwrap();
-                        buffer.push_str(&line);
-                        buffer.push('\n');
+    /// Command of the first rule of `command_map` matching the file which has triggered the run.
+    fn map_command(&self) -> Option<String> {
+        match *self.trigger.borrow() {
+            Trigger::Change(ref path) => targeting::command_for(&self.config.project_dir, &self.config.command_map, path),
+            Trigger::Start | Trigger::Key | Trigger::Checkout(_) => None
+        }
+    }
+
+    /// Human readable representation of the command, used in error messages.
+    fn command_name(&self) -> String {
+        match self.custom_command() {
+            Some(custom) => self.changes.expand(custom),
+            None => {
+                // The message format of nextest is left out
+                let subcommand: Vec<&str> = self.subcommand().into_iter()
+                    .take_while(|arg| *arg != "--message-format")
+                    .collect();
+                format!("{} {}", self.program(), subcommand.join(" "))
+            }
+        }
+    }
+
+    /// Program running the tests: `cross` accepts the same arguments as cargo.
+    fn program(&self) -> &'static str {
+        match self.config.runner {
+            Runner::WasmPack => "wasm-pack",
+            _ if self.config.cross => "cross",
+            _ => "cargo"
+        }
+    }
+
+    /// Subcommand of the program running the tests, with its options.
+    /// Coverage is not measured with wasm-pack.
+    fn subcommand(&self) -> Vec<&'static str> {
+        match (self.config.runner, self.config.coverage) {
+            (Runner::Nextest, false) => vec!["nextest", "run", "--message-format", "libtest-json"],
+            (Runner::Nextest, true) => vec!["llvm-cov", "nextest", "--message-format", "libtest-json"],
+            (Runner::WasmPack, _) => {
+                let mut subcommand = vec!["test"];
+                subcommand.extend(self.config.wasm_env.flags());
+                subcommand
+            },
+            (_, false) => vec!["test"],
+            (_, true) => vec!["llvm-cov"]
+        }
+    }
+
+    /// Nextest prints JSON results to stdout, while its human readable output goes to stderr.
+    fn uses_nextest(&self) -> bool {
+        self.custom_command().is_none() && self.config.runner == Runner::Nextest
+    }
+
+    /// Cargo reports compiler messages as JSON, so the report can point to the first error.
+    /// In a pseudo-terminal the output is passed as is, so the human readable format is kept,
+    /// and `cargo llvm-cov` prints its summary in the human readable format only.
+    /// wasm-pack builds the tests with its own cargo invocation, so it's not used there either.
+    fn uses_cargo_json(&self) -> bool {
+        let runner = self.config.runner;
+        self.custom_command().is_none() && runner != Runner::Nextest && runner != Runner::WasmPack && !self.config.pty && !self.config.coverage
+    }
+
+    /// Variables of the env file, none if it's turned off or invalid.
+    fn load_env_file(&self) -> Vec<(String, String)> {
+        let env_file = match self.config.env_file {
+            Some(ref env_file) => env_file,
+            None => return vec![]
+        };
+        dotenv::load(env_file).unwrap_or_else(|err| {
+            eprintln!("Warning: {}", err);
+            vec![]
+        })
+    }
+
+    /// Environment variables for the command, a custom one is told what has changed.
+    fn command_env(&self) -> Vec<(&'static str, String)> {
+        if self.uses_nextest() {
+            // The libtest-json message format is still experimental in nextest
+            vec![("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1".to_string())]
+        } else if self.custom_command().is_some() {
+            self.changes.env()
+        } else {
+            vec![]
+        }
+    }
+
+    /// Stages to run on every change: the configured `pipeline`, in which the `test` step stands
+    /// for the test command, or just the test command. With `clippy` the tests are preceded by
+    /// `cargo clippy`, and with `check_first` everything is preceded by `cargo check`, unless
+    /// the pipeline has such steps already. Formatting is checked before anything else,
+    /// and with `miri` the tests are run with Miri once they pass.
+    fn stages(&self) -> Vec<Stage> {
+        let mut stages: Vec<Stage> = if self.config.pipeline.is_empty() {
+            vec![Stage::Tests]
+        } else {
+            self.config.pipeline.iter().map(|step| {
+                let step = step.trim();
+                match step.split_whitespace().next() {
+                    Some("test") if step == "test" => Stage::Tests,
+                    Some("clippy") => Stage::Clippy(step.to_string()),
+                    _ => Stage::Cargo(step.to_string())
+                }
+            }).collect()
+        };
+        if self.custom_command().is_some() {
+            return stages;
+        }
+        let lints = stages.iter().any(|stage| matches!(*stage, Stage::Clippy(_)));
+        if self.config.clippy && !lints {
+            let index = stages.iter().position(|stage| matches!(*stage, Stage::Tests));
+            stages.insert(index.unwrap_or(stages.len()), Stage::Clippy(CLIPPY_STEP.to_string()));
+        }
+        let checks = stages.iter().any(|stage| match *stage {
+            Stage::Cargo(ref step) => step.split_whitespace().next() == Some("check"),
+            _ => false
+        });
+        if self.config.check_first && !checks {
+            stages.insert(0, Stage::Cargo(CHECK_STEP.to_string()));
+        }
+        if self.config.fmt != FmtMode::Off {
+            stages.insert(0, Stage::Fmt(self.config.fmt));
+        }
+        if self.config.miri {
+            let index = stages.iter().position(|stage| matches!(*stage, Stage::Tests));
+            stages.insert(index.map_or(stages.len(), |index| index + 1), Stage::Miri);
+        }
+        stages
+    }
+
+    /// Run the pipeline stages one by one and notify about the result. The first stage which does
+    /// not succeed aborts the pipeline, and the report names it.
+    fn run_tests(&mut self, rx: &Receiver<Message>) -> RunStatus {
+        let started_at = Instant::now();
+        self.mapped_command = self.map_command();
+        let changed_files = self.changed_files.replace(vec![]);
+        self.changes = Changes::new(&self.config.project_dir, changed_files);
+        self.env_file_vars = self.load_env_file();
+        self.cancel_mutants();
+        self.run_number.set(self.run_number.get() + 1);
+        if self.config.clear {
+            self.clear_screen();
+            self.print_header();
+        }
+        if self.mapped_command.is_some() {
+            self.inform(&format!("{}, running `{}`", self.trigger.borrow(), self.command_name()));
+        }
+        {
+            let trigger = self.trigger.borrow();
+            if self.config.output == OutputFormat::Json {
+                events::run_started(self.run_number.get(), trigger.name(), trigger.file().as_deref(), &self.command_name());
+            }
+            self.event_stream.send("run_started", &json!({
+                "run": self.run_number.get(),
+                "trigger": trigger.name(),
+                "file": trigger.file(),
+                "command": self.command_name()
+            }));
+        }
+
+        if let Some(ref hook) = self.config.hooks.before_run {
+            if !self.run_hook("before_run", hook) {
+                self.inform("Skipping the tests, because `before_run` hook has failed");
+                self.last_exit_code = None;
+                return RunStatus::Finished;
+            }
+        }
+
+        if self.config.runner == Runner::Ssh && self.config.ssh.rsync() && !self.sync() {
+            self.inform("Skipping the tests, because syncing to the remote host has failed");
+            self.last_exit_code = None;
+            return RunStatus::Finished;
+        }
+
+        self.write_status(&Status::running());
+        let scope = if self.full_run.replace(false) { None } else { self.changed_scope() };
+        self.scope.replace(scope);
+
+        let stages = self.stages();
+        let mut last_report = None;
+        let mut lints = None;
+        for stage in &stages {
+            let result = match *stage {
+                Stage::Tests => self.run_test_stage(rx),
+                Stage::Cargo(ref step) => {
+                    self.inform(&format!("Running `{}`", stage));
+                    let mut argv = vec!["cargo".to_string()];
+                    argv.extend(step.split_whitespace().map(String::from));
+                    self.execute(&argv, false, rx)
+                },
+                Stage::Clippy(ref step) => {
+                    self.inform(&format!("Running `{}`", stage));
+                    let mut argv = vec!["cargo".to_string()];
+                    argv.extend(step.split_whitespace().map(String::from));
+                    self.execute(&argv, false, rx).map(|report| self.judge_lints(report))
+                },
+                Stage::Fmt(mode) => {
+                    self.inform(&format!("Running `{}`", stage));
+                    Ok(self.run_fmt(mode))
+                },
+                Stage::Miri => {
+                    self.inform(&format!("Running `{}`", stage));
+                    let mut argv = vec!["cargo".to_string()];
+                    argv.extend(MIRI_STEP.split_whitespace().map(String::from));
+                    argv.extend(self.config.cargo_test_args.iter().cloned());
+                    self.execute(&argv, false, rx)
+                }
+            };
+            let mut report = match result {
+                Ok(report) => report,
+                Err(status) => return status
+            };
+            if report.lints.is_some() {
+                lints = report.lints;
+            }
+            if !report.outcome.is_success() {
+                if stages.len() > 1 {
+                    report.stage = Some(stage.to_string());
+                }
+                last_report = Some(report);
+                break;
+            }
+            // Miri passing adds nothing to the report of the tests
+            if let (&Stage::Miri, &Some(_)) = (stage, &last_report) {
+                continue;
+            }
+            last_report = Some(report);
+        }
+
+        if let Some(mut report) = last_report {
+            report.lints = lints;
+            // Wall-clock time of the whole run, with every stage and retry
+            report.duration = Some(started_at.elapsed());
+            self.complete(report);
+        }
+        RunStatus::Finished
+    }
+
+    /// Copy the working tree to the remote host of the `ssh` runner.
+    /// Returns `false` if rsync could not be run or has failed.
+    fn sync(&self) -> bool {
+        let ssh = &self.config.ssh;
+        let destination = format!("{}:{}", ssh.host.as_deref().unwrap_or_default(), ssh::remote_dir(ssh, &self.config.project_dir));
+        self.inform(&format!("Syncing to {}", destination));
+        match ssh::sync(ssh, &self.config.project_dir) {
+            Ok(ref status) if status.success() => true,
+            Ok(status) => {
+                eprintln!("Warning: rsync to {} has failed: {}", destination, status);
+                false
+            },
+            Err(err) => {
+                eprintln!("Warning: failed to run rsync: {}", err);
+                false
+            }
+        }
+    }
+
+    /// Check formatting, reporting the files which differ, or format the code. Failing to
+    /// format the code (e.g. because of a syntax error) is left for the compiler to report.
+    fn run_fmt(&self, mode: FmtMode) -> Report {
+        let args: &[&str] = match mode {
+            FmtMode::Check => &["fmt", "--check", "--", "--files-with-diff"],
+            FmtMode::Fix | FmtMode::Off => &["fmt"]
+        };
+        let output = match process::Command::new("cargo").args(args).current_dir(&self.config.project_dir).output() {
+            Ok(output) => output,
+            Err(err) => {
+                eprintln!("Warning: failed to run `cargo fmt`: {}", err);
+                return Report::new(Outcome::TestsPassed, None);
+            }
+        };
+        if mode == FmtMode::Fix || output.status.success() {
+            return Report::new(Outcome::TestsPassed, None);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let files: Vec<String> = stdout.lines()
+            .map(|line| {
+                let path = Path::new(line.trim());
+                path.strip_prefix(&self.config.project_dir).unwrap_or(path).to_string_lossy().into_owned()
+            })
+            .filter(|file| !file.is_empty())
+            .collect();
+        if files.is_empty() {
+            // `cargo fmt` has failed without listing files, e.g. on a syntax error
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            return Report::new(Outcome::TestsPassed, None);
+        }
+        for file in &files {
+            self.inform(&format!("Not formatted: {}", file));
+        }
+        let mut report = Report::new(Outcome::FormatError, Some(files.join("\n")));
+        report.exit_code = output.status.code();
+        report
+    }
+
+    /// Count the warnings and errors of a clippy stage. With `deny_warnings` any warning fails it.
+    fn judge_lints(&self, mut report: Report) -> Report {
+        let lints = report.lints.unwrap_or_default();
+        if self.config.deny_warnings && lints.warnings > 0 && report.outcome.is_success() {
+            report.outcome = Outcome::LintFailed(lints.warnings);
+        } else if report.outcome.is_success() {
+            // Warnings of clippy are reported as its counts, not as warnings of the tests
+            report.outcome = Outcome::TestsPassed;
+        }
+        report
+    }
+
+    /// Every combination of the toolchains, the targets and the feature sets of the matrix
+    /// the test suite is run with. None of them applies to a custom command.
+    fn variants(&self) -> Vec<Variant> {
+        if self.custom_command().is_some() {
+            return vec![Variant::default()];
+        }
+        // wasm-pack chooses the toolchain and the target itself
+        let wasm_pack = self.config.runner == Runner::WasmPack;
+        let toolchains: Vec<Option<String>> = if self.config.toolchains.is_empty() || wasm_pack {
+            vec![None]
+        } else {
+            self.config.toolchains.iter().cloned().map(Some).collect()
+        };
+        let targets: Vec<Option<String>> = if self.config.targets.is_empty() || wasm_pack {
+            vec![None]
+        } else {
+            self.config.targets.iter().cloned().map(Some).collect()
+        };
+        let feature_sets: Vec<Option<String>> = if self.config.matrix.is_empty() {
+            vec![None]
+        } else {
+            self.config.matrix.iter().cloned().map(Some).collect()
+        };
+        let mut variants = vec![];
+        for toolchain in &toolchains {
+            for target in &targets {
+                for features in &feature_sets {
+                    variants.push(Variant { toolchain: toolchain.clone(), target: target.clone(), features: features.clone() });
+                }
+            }
+        }
+        variants
+    }
+
+    /// Run the test suite once per toolchain, target and entry of the matrix, and combine the reports
+    /// into one which lists the outcome of every combination.
+    fn run_test_stage(&self, rx: &Receiver<Message>) -> Result<Report, RunStatus> {
+        let variants = self.variants();
+        if variants.len() == 1 {
+            return self.run_variant(&variants[0], rx);
+        }
+        let mut reports = vec![];
+        for variant in &variants {
+            self.inform(&format!("Running tests with {}", variant));
+            reports.push((variant.to_string(), self.run_variant(variant, rx)?));
+        }
+        Ok(Report::combine(reports))
+    }
+
+    /// Run the test suite, and retry the tests which have failed.
+    fn run_variant(&self, variant: &Variant, rx: &Receiver<Message>) -> Result<Report, RunStatus> {
+        let report = self.run_suite(variant, rx)?;
+        self.retry_failures(variant, report, rx)
+    }
+
+    /// Run the test command, only with the followed test or the picked tests if there are any.
+    /// Property tests with recorded failing cases are replayed first. With `failed_first` enabled,
+    /// tests which failed previously are run next, with `targeted` enabled the tests related to
+    /// the changed file are run after them, and the whole suite is run only if they pass now.
+    fn run_suite(&self, variant: &Variant, rx: &Receiver<Message>) -> Result<Report, RunStatus> {
+        if let Some(ref name) = *self.followed_test.borrow() {
+            let mut argv = self.command_line_with_filters(variant, slice::from_ref(name));
+            // Show the output of the test even when it passes
+            argv.push("--nocapture".to_string());
+            return self.execute(&argv, true, rx);
+        }
+        let picked = self.picked_tests.borrow().clone();
+        if !picked.is_empty() && self.custom_command().is_none() {
+            let argv = self.command_line_with_filters(variant, &picked);
+            return self.execute(&argv, true, rx);
+        }
+        let seeded = if self.custom_command().is_none() { self.recorded_seeds() } else { vec![] };
+        if !seeded.is_empty() {
+            self.inform(&format!("Replaying recorded failures: {}", seeded.join(", ")));
+            let argv = self.command_line_with_filters(variant, &seeded);
+            let report = self.execute(&argv, true, rx)?;
+            self.forget_removed_tests(&seeded, &report);
+            if !report.outcome.is_success() && report.outcome != Outcome::NoTests {
+                return Ok(report);
+            }
+            self.inform("Recorded failures pass now, running all tests");
+        }
+        if self.config.failed_first && self.custom_command().is_none() && !self.failed_tests.is_empty() {
+            self.inform(&format!("Running previously failed tests: {}", self.failed_tests.join(", ")));
+            let argv = self.command_line_with_filters(variant, &self.failed_tests);
+            let report = self.execute(&argv, true, rx)?;
+            // The tests may have been renamed or removed since, then there is nothing to run first
+            if !report.outcome.is_success() && report.outcome != Outcome::NoTests {
+                return Ok(report);
+            }
+            self.inform("Previously failed tests pass now, running all tests");
+        }
+        if let Some(filter) = self.targeted_filter() {
+            self.inform(&format!("Running related tests: {}", filter));
+            let argv = self.command_line_with_filter(variant, &filter);
+            let report = self.execute(&argv, true, rx)?;
+            if !report.outcome.is_success() && report.outcome != Outcome::NoTests {
+                return Ok(report);
+            }
+            self.inform("Related tests pass, running all tests");
+        }
+
+        let argv = match *self.scope.borrow() {
+            Some(Scope::Packages(ref packages)) => self.command_line_with_packages(variant, packages),
+            Some(Scope::Modules(ref filters)) => self.command_line(variant, filters, false),
+            Some(Scope::Everything) | None => self.command_line_with_filters(variant, &[])
+        };
+        self.execute(&argv, true, rx)
+    }
+
+    /// Packages or modules affected by the files changed since `changed_since`, which the run is restricted to.
+    /// `None` means the whole suite runs: the setting is off, the changes affect everything or git has failed.
+    fn changed_scope(&self) -> Option<Scope> {
+        let base = self.config.changed_since.as_ref()?;
+        // Neither a custom command nor wasm-pack can be told which packages to test
+        if self.custom_command().is_some() || self.config.runner == Runner::WasmPack {
+            return None;
+        }
+        let project_dir = &self.config.project_dir;
+        let found = scope::changed_files(project_dir, base).and_then(|files| {
+            // Files which never trigger a run do not affect the tests either
+            let files: Vec<PathBuf> = files.into_iter().filter(|file| self.filter.borrow().allows(file)).collect();
+            if files.is_empty() { return Ok(None); }
+            let packages = scope::workspace_packages(project_dir)?;
+            Ok(Some(scope::scope(project_dir, &packages, &self.config.test_map, &files)))
+        });
+        match found {
+            Ok(None) => {
+                self.inform(&format!("Nothing has changed since {}, running all tests", base));
+                None
+            },
+            Ok(Some(Scope::Packages(packages))) => {
+                self.inform(&format!("Running the tests of the packages changed since {}: {}", base, packages.join(", ")));
+                Some(Scope::Packages(packages))
+            },
+            Ok(Some(Scope::Modules(filters))) => {
+                self.inform(&format!("Running the tests of the modules changed since {}: {}", base, filters.join(", ")));
+                Some(Scope::Modules(filters))
+            },
+            Ok(Some(Scope::Everything)) => {
+                self.inform(&format!("The changes since {} affect the whole project, running all tests", base));
+                None
+            },
+            Err(err) => {
+                eprintln!("Warning: {}, running all tests", err);
+                None
+            }
+        }
+    }
+
+    /// Tests with failing cases recorded by proptest or quickcheck. The cases proptest has persisted
+    /// are put back into its regression files if they are missing there, so it replays them.
+    fn recorded_seeds(&self) -> Vec<String> {
+        let recorded = match seeds::load(&self.config.project_dir) {
+            Ok(recorded) => recorded,
+            Err(err) => {
+                eprintln!("Warning: {}", err);
+                return vec![];
+            }
+        };
+        match seeds::restore(&self.config.project_dir, &recorded) {
+            Ok(0) => {},
+            Ok(restored) => self.inform(&format!("Failing cases restored into the regression files of proptest: {}", restored)),
+            Err(err) => eprintln!("Warning: {}", err)
+        }
+        seeds::tests(&recorded)
+    }
+
+    /// Record the failing cases of property tests found in the output of a failed run.
+    fn record_seeds(&self, stdout: &str, stderr: &str) {
+        let output = if stderr != stdout { format!("{}\n{}", stderr, stdout) } else { stdout.to_string() };
+        let output = String::from_utf8_lossy(&strip_ansi_escapes::strip(output.as_bytes())).into_owned();
+        let found = seeds::parse(&output, &self.config.project_dir);
+        match seeds::record(&self.config.project_dir, &found) {
+            Ok(recorded) => for seed in recorded {
+                let input = seed.input.as_ref().map(|input| format!(": {}", input)).unwrap_or_default();
+                self.inform(&format!("Recorded failing case of `{}`{}", seed.test, input));
+            },
+            Err(err) => eprintln!("Warning: {}", err)
+        }
+    }
+
+    /// Forget the cases of the tests which have not run at all while replaying them, since they
+    /// are gone. Tests may exist only with some of the variants, then nothing can be told.
+    fn forget_removed_tests(&self, tests: &[String], report: &Report) {
+        if self.variants().len() > 1 || (report.results.is_empty() && report.outcome != Outcome::NoTests) {
+            return;
+        }
+        for test in tests.iter().filter(|test| !report.results.iter().any(|result| result.name == **test)) {
+            if let Err(err) = seeds::forget(&self.config.project_dir, test) {
+                eprintln!("Warning: {}", err);
+            }
+        }
+    }
+
+    /// Rerun the failed tests up to `retries` times. Tests which pass on a retry are flaky,
+    /// and if all the failed tests turn out to be flaky, the run is reported as such.
+    fn retry_failures(&self, variant: &Variant, mut report: Report, rx: &Receiver<Message>) -> Result<Report, RunStatus> {
+        if self.custom_command().is_some() || report.outcome != Outcome::TestsFailed || report.failures.is_empty() {
+            return Ok(report);
+        }
+        let mut failing = report.failed_test_names();
+        let mut flaky = vec![];
+        for attempt in 1..=self.config.retries {
+            self.inform(&format!("Retrying failed tests ({}/{}): {}", attempt, self.config.retries, failing.join(", ")));
+            let argv = self.command_line_with_filters(variant, &failing);
+            let retry = self.execute(&argv, true, rx)?;
+            match retry.outcome {
+                Outcome::TestsPassed | Outcome::TestsPassedWithWarnings(_) => {
+                    flaky.append(&mut failing);
+                    report.exit_code = retry.exit_code;
+                    break;
+                },
+                Outcome::TestsFailed if !retry.failures.is_empty() => {
+                    let still_failing = retry.failed_test_names();
+                    flaky.extend(failing.iter().filter(|name| !still_failing.contains(name)).cloned());
+                    failing = still_failing;
+                },
+                // Nothing can be told about the tests, e.g. the code does not compile anymore
+                _ => break
+            }
+        }
+        if flaky.is_empty() {
+            return Ok(report);
+        }
+
+        report.failures.retain(|failure| failing.contains(&failure.name));
+        if failing.is_empty() {
+            report.outcome = Outcome::Flaky(flaky.len());
+            if let Some(ref mut counts) = report.counts {
+                counts.passed += counts.failed;
+                counts.failed = 0;
+                report.detail = Some(counts.summary());
+            }
+        }
+        report.flaky = flaky;
+        Ok(report)
+    }
+
+    fn complete(&mut self, mut report: Report) {
+        report.project = self.group.as_ref().map(|group| group.name.clone());
+        if let Some(coverage) = report.coverage {
+            report.coverage_delta = self.last_coverage.map(|last| coverage - last);
+            self.last_coverage = Some(coverage);
+        }
+        self.failed_tests = report.failed_test_names();
+        self.last_exit_code = report.exit_code;
+        self.print_footer(&report);
+        // Following ends once the test passes, or if it's gone
+        let followed = self.followed_test.borrow().clone();
+        if let Some(name) = followed {
+            if report.outcome.is_success() || report.outcome == Outcome::NoTests {
+                let reason = if report.outcome == Outcome::NoTests { "is not found" } else { "passes now" };
+                self.inform(&format!("`{}` {}, running all tests", name, reason));
+                self.followed_test.replace(None);
+                self.event_stream.send("followed", &json!({ "name": null }));
+                self.widen = true;
+            }
+        }
+        // insta saves the snapshots which do not match next to the accepted ones
+        if report.outcome == Outcome::TestsFailed {
+            report.snapshots = snapshots::pending(&self.config.project_dir);
+        }
+        self.pending_snapshots = report.snapshots.clone();
+        if !self.pending_snapshots.is_empty() {
+            for snapshot in &self.pending_snapshots {
+                self.inform(&format!("Changed snapshot: {}", snapshot));
+            }
+            self.inform("Press s to accept the changed snapshots and run the tests again, x to reject them");
+        }
+        self.warn_on_slowdown(&report);
+        let entry = history::Entry::new(&report, self.trigger.borrow().file());
+        self.recent_runs.push(entry.clone());
+        if self.config.history {
+            if let Err(err) = history::record(&self.config.project_dir, &entry) {
+                eprintln!("Warning: {}", err);
+            }
+            if let Err(err) = stats::record(&self.config.project_dir, &report) {
+                eprintln!("Warning: {}", err);
+            }
+        }
+        self.write_status(&Status::finished(&report));
+        {
+            let mut latest = self.latest.lock().unwrap();
+            latest.finish(report.clone(), entry);
+            self.event_stream.send("run_finished", &json!({
+                "run": self.run_number.get(),
+                "report": events::report_json(&report),
+                "runs": latest.recent
+            }));
+        }
+        if let Some(ref path) = self.config.junit {
+            if let Err(err) = junit::write(path, &report) {
+                eprintln!("Warning: {}", err);
+            }
+        }
+        let success = report.outcome.is_success();
+        let changed = self.last_success != Some(success);
+        self.last_success = Some(success);
+        if (self.config.notify == NotifyMode::Always || changed) && !self.notifications_muted.get() {
+            if let Err(err) = self.notifier.notify(&report) {
+                eprintln!("Warning: {}", err);
+            }
+        }
+        if self.config.output == OutputFormat::Json {
+            events::run_finished(self.run_number.get(), &report);
+        }
+        if self.config.annotate == Some(AnnotationFormat::Github) {
+            for annotation in annotations::github(&report) {
+                println!("{}", annotation);
+            }
+        }
+
+        let hooks = &self.config.hooks;
+        let outcome_hook = if report.outcome.is_success() {
+            hooks.on_success.as_ref().map(|hook| ("on_success", hook))
+        } else {
+            hooks.on_failure.as_ref().map(|hook| ("on_failure", hook))
+        };
+        let after_run_hook = hooks.after_run.as_ref().map(|hook| ("after_run", hook));
+        for (name, hook) in after_run_hook.into_iter().chain(outcome_hook) {
+            self.run_hook(name, hook);
+        }
+
+        if self.config.mutants && success {
+            self.start_mutants();
+        }
+    }
+
+    /// Look for mutants of the changed source file the tests do not catch, in the background.
+    /// Only runs triggered by a change of a `.rs` file are followed by it.
+    fn start_mutants(&mut self) {
+        let file = match *self.trigger.borrow() {
+            Trigger::Change(ref path) if path.extension().is_some_and(|extension| extension == "rs") => path.clone(),
+            _ => PathBuf::from("src/lib.rs")
+        };
+        let tx = match self.background_tx {
+            Some(ref tx) => tx.clone(),
+            None => return
+        };
+        match mutants::spawn(&self.config.project_dir, &file, move |report| { let _ = tx.send(Message::Mutants(Box::new(report))); }) {
+            Ok(job) => self.mutants_job = Some(job),
+            Err(err) => eprintln!("Warning: failed to run `cargo mutants`: {}", err)
+        }
+    }
+
+    fn cancel_mutants(&mut self) {
+        if let Some(job) = self.mutants_job.take() {
+            job.cancel();
+        }
+    }
+
+    /// Follow up a green run with the mutants which have survived its tests.
+    fn report_mutants(&self, report: &Report) {
+        self.inform(&report.title());
+        if let Some(ref detail) = report.detail {
+            self.inform(detail);
+        }
+        let changed = self.last_mutants.replace(report.detail.clone()) != report.detail;
+        if (self.config.notify == NotifyMode::Always || changed) && !self.notifications_muted.get() {
+            if let Err(err) = self.notifier.notify(report) {
+                eprintln!("Warning: {}", err);
+            }
+        }
+    }
+
+    fn write_status(&self, status: &Status) {
+        self.latest.lock().unwrap().status = Some(status.clone());
+        if !self.config.status_file { return; }
+        if let Err(err) = status::write(&self.config.project_dir, status) {
+            eprintln!("Warning: {}", err);
+        }
+    }
+
+    /// Run a hook through the system shell in the project directory and wait for it.
+    /// Returns `false` if it could not be run or has failed.
+    fn run_hook(&self, name: &str, hook: &str) -> bool {
+        let argv = shell_command(hook);
+        let status = process::Command::new(&argv[0])
+            .args(&argv[1..])
+            .current_dir(&self.config.project_dir)
+            .status();
+        match status {
+            Ok(ref status) if status.success() => true,
+            Ok(status) => {
+                eprintln!("Warning: `{}` hook has failed: {}", name, status);
+                false
+            },
+            Err(err) => {
+                eprintln!("Warning: failed to run `{}` hook `{}`: {}", name, hook, err);
+                false
+            }
+        }
+    }
+
+    /// Spawn `argv`, catch stdout and stderr, then build the report.
+    /// While the command runs, pressed keys and file events are handled. Rerun, quit or (if
+    /// `restart` is enabled) a relevant change kill the whole process tree, in which case the
+    /// reason is returned as an error. Without `restart` a relevant change marks a pending run.
+    /// With `pty` enabled the command runs in a pseudo-terminal, so its colored output is
+    /// passed to the console as is, while the report is built from an ANSI-stripped copy.
+    /// A command running longer than `timeout` is killed and reported as timed out.
+    /// `is_runner` is false for pipeline steps, which are run as is.
+    /// With the `ssh` runner the command runs on the remote host, with its output streamed back,
+    /// and with the `docker` runner in a new container, which is removed if the command is killed.
+    fn execute(&self, argv: &[String], is_runner: bool, rx: &Receiver<Message>) -> Result<Report, RunStatus> {
+        let started_at = Instant::now();
+        let command_env = if is_runner { self.command_env() } else { vec![] };
+        // The variables apply to every stage, so cargo does not rebuild because of a changed one.
+        // The profiles override the env file.
+        let mut env: Vec<(&str, &str)> = self.env_file_vars.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .chain(self.config.env.iter().map(|(name, value)| (name.as_str(), value.as_str())))
+            .chain(command_env.iter().map(|&(name, ref value)| (name, value.as_str())))
+            .collect();
+        let container = if self.config.runner == Runner::Docker { Some(docker::container_name()) } else { None };
+        let command_line = match (self.config.runner, &container) {
+            (Runner::Ssh, _) => ssh::command_line(&self.config.ssh, &self.config.project_dir, argv, &env, self.config.pty),
+            (Runner::Docker, Some(name)) => docker::command_line(&self.config.docker, &self.config.project_dir, name, argv, &env, self.config.pty),
+            _ => argv.to_vec()
+        };
+        if self.config.runner == Runner::Ssh || self.config.runner == Runner::Docker {
+            // The environment is set on the remote host or in the container
+            env.clear();
+        }
+        let spawned = if self.config.pty {
+            ProcessTree::spawn_pty(&command_line, &env, &self.config.project_dir)
+        } else {
+            ProcessTree::spawn(&command_line, &env, &self.config.project_dir)
+        };
+        let (mut tree, output) = match spawned {
+            Ok(spawned) => spawned,
+            Err(err) => {
+                // A missing tool must not stop watching, nor the other projects watched along
+                let detail = format!("Failed to spawn `{}`: {}", command_line.join(" "), err);
+                eprintln!("{}", detail);
+                return Ok(Report::new(Outcome::Unknown, Some(detail)));
+            }
+        };
+
+        let echo_stdout = !(is_runner && self.uses_nextest());
+        let cargo_json = is_runner && self.uses_cargo_json();
+        let format = self.config.output;
+        // The UI shows the output it gets from the event stream
+        let quiet = self.config.quiet || self.config.ui;
+        let stdout_stream = self.event_stream.clone();
+        let stderr_stream = self.event_stream.clone();
+        let readers = match output {
+            Output::Pipes(stdout, stderr) => {
+                let stdout_reader = capture(stdout, move |line| {
+                    if cargo_json {
+                        echo_cargo_line(line, format, quiet, &stdout_stream);
+                    } else if echo_stdout {
+                        echo_line(line, format, quiet, &stdout_stream);
+                    }
+                });
+                let stderr_reader = capture(stderr, move |line| {
+                    if !quiet {
                         eprintln!("{}", line);
                     }
+                    stream_output(&stderr_stream, line);
                 });
+                (stdout_reader, Some(stderr_reader))
+            },
+            Output::Terminal(terminal) => (capture_terminal(terminal, format, quiet, stdout_stream), None)
+        };
+
+        let deadline = self.config.timeout.map(|timeout| started_at + timeout);
+        self.command_running.set(true);
+        let waited = self.wait_or_cancel(&mut tree, deadline, rx);
+        self.command_running.set(false);
+        self.command_exited_at.set(Some(Instant::now()));
+        if let (&Some(ref name), &Ok(Ok(None))) | (&Some(ref name), &Ok(Err(_))) = (&container, &waited) {
+            docker::remove(name);
+        }
+        let exit_code = match waited {
+            Ok(Ok(Some(exit_code))) => exit_code,
+            Ok(Ok(None)) => {
+                let _ = readers.0.join();
+                if let Some(reader) = readers.1 { let _ = reader.join(); }
+                return Ok(self.timed_out_report(started_at.elapsed()));
+            },
+            Ok(Err(status)) => return Err(status),
+            Err(err) => {
+                let _ = tree.kill();
+                if let Some(ref name) = container { docker::remove(name); }
+                let _ = readers.0.join();
+                if let Some(reader) = readers.1 { let _ = reader.join(); }
+                let detail = format!("Failed to wait for `{}`: {}", self.command_name(), err);
+                eprintln!("Warning: {}", detail);
+                let mut report = Report::new(Outcome::Unknown, Some(detail));
+                report.duration = Some(started_at.elapsed());
+                return Ok(report);
+            }
+        };
+        let stdout_output = readers.0.join().unwrap_or_default();
+        // Terminal output is not split, so the whole output is treated as stderr as well
+        let stderr_output = match readers.1 {
+            Some(reader) => reader.join().unwrap_or_default(),
+            None => stdout_output.clone()
+        };
+
+        self.save_output(&stdout_output, &stderr_output, cargo_json);
+        let mut report = self.report_builder.identify(exit_code, &stdout_output, &stderr_output);
+        if is_runner && report.outcome == Outcome::TestsFailed {
+            self.record_seeds(&stdout_output, &stderr_output);
+        }
+        if !is_runner && argv.get(1).map(String::as_str) == Some("clippy") {
+            report.lints = Some(self.report_builder.lints(&stdout_output, &stderr_output));
+        }
+        report.duration = Some(started_at.elapsed());
+        Ok(report)
+    }
 
-                let exit_status = child.wait().expect("failed to wait for child process `cargo test`");
-                let stdout_output = stdout_buffer.lock().unwrap().clone();
-                let stderr_output = stderr_buffer.lock().unwrap().clone();
+    fn timed_out_report(&self, duration: Duration) -> Report {
+        let detail = format!("Killed after running for {}.{}s", duration.as_secs(), duration.subsec_millis() / 100);
+        eprintln!("`{}` has timed out. {}", self.command_name(), detail);
+        let mut report = Report::new(Outcome::TimedOut, Some(detail));
+        report.duration = Some(duration);
+        report
+    }
 
-                let report = self.report_builder.identify(exit_status.success(), &stdout_output, &stderr_output);
-                notify(report)
+    /// Wait for the child process to exit, while listening for file events and keys.
+    /// Returns the reason as an error if the process tree was killed, and `None` if it was
+    /// killed because the `deadline` has passed.
+    fn wait_or_cancel(&self, tree: &mut ProcessTree, deadline: Option<Instant>, rx: &Receiver<Message>) -> io::Result<Result<Option<ExitCode>, RunStatus>> {
+        loop {
+            if let Some(exit_code) = tree.try_wait()? {
+                return Ok(Ok(Some(exit_code)));
             }
-            Err(err) => {
-                eprintln!("Failed to spawn `cargo test`");
-                eprintln!("{:?}", err);
-                process::exit(1);
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                tree.kill()?;
+                return Ok(Ok(None));
             }
+            let status = match rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+                Ok(Message::Change(event)) => {
+                    if self.paused.get() || !self.should_react(&event) { continue; }
+                    self.set_trigger(&event);
+                    if self.config.restart {
+                        RunStatus::Cancelled
+                    } else {
+                        self.pending.set(true);
+                        continue;
+                    }
+                },
+                Ok(Message::Key(Key::Rerun)) | Ok(Message::Action(Action::Rerun)) | Ok(Message::Control(Control::Run)) => {
+                    self.trigger.replace(Trigger::Key);
+                    RunStatus::Cancelled
+                },
+                Ok(Message::Key(Key::FullRun)) => {
+                    self.full_run.set(true);
+                    self.trigger.replace(Trigger::Key);
+                    RunStatus::Cancelled
+                },
+                Ok(Message::Action(Action::OpenOutput)) => {
+                    self.open_output();
+                    continue;
+                },
+                Ok(Message::Action(Action::AcceptSnapshots)) => {
+                    self.handle_key(Key::AcceptSnapshots);
+                    continue;
+                },
+                // Mutants of the code before this run
+                Ok(Message::Mutants(_)) => continue,
+                // Listing the tests would wait for the lock of the build directory
+                Ok(Message::ListTests) => {
+                    self.inform("The tests can be picked once the run has finished");
+                    continue;
+                },
+                Ok(Message::Pick(names)) => {
+                    self.pick(names);
+                    self.trigger.replace(Trigger::Key);
+                    RunStatus::Cancelled
+                },
+                Ok(Message::Key(Key::Quit)) | Ok(Message::Control(Control::Stop)) => RunStatus::Quit,
+                Ok(Message::Key(key)) => {
+                    self.handle_key(key);
+                    continue;
+                },
+                Ok(Message::Interrupt) => {
+                    self.interrupted.set(true);
+                    RunStatus::Quit
+                },
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+                    continue;
+                }
+            };
+            tree.kill()?;
+            return Ok(Err(status));
         }
     }
 }
 
-#[cfg(not(target_os = "windows"))]
-fn notify(report: Report) {
-    let icon = match report.outcome {
-        Outcome::TestsPassed => "face-angel",
-        Outcome::TestsFailed | Outcome::CompileError => "face-angry"
+/// Watch several projects at once, each in a thread of its own with its own watcher, configuration
+/// and debouncing. Only one of them runs its tests at a time, so the output does not mix, and the
+/// notifications tell which project they are about. Ctrl+C and the keys apply to all of them.
+/// Returns the exit code for testify.
+pub fn watch_projects(configs: Vec<Config>) -> i32 {
+    let turn = Arc::new(Mutex::new(()));
+    let interactive = configs.iter().any(|config| config.interactive);
+    let mut senders = vec![];
+    let mut threads = vec![];
+    for mut config in configs {
+        // The projects share the terminal
+        config.ui = false;
+        config.pick = false;
+        let (tx, rx) = channel();
+        senders.push(tx.clone());
+        let turn = turn.clone();
+        threads.push(thread::spawn(move || {
+            let name = config.project_dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| config.project_dir.display().to_string());
+            let mut reactor = match Reactor::new(config) {
+                Ok(reactor) => reactor,
+                Err(err) => {
+                    eprintln!("Error: {}: {}", name, err);
+                    return 1;
+                }
+            };
+            reactor.group = Some(Group { name, turn });
+            let exit_code = reactor.watch(tx, rx);
+            reactor.cancel_mutants();
+            exit_code
+        }));
+    }
+
+    let interrupt_senders = senders.clone();
+    let result = ctrlc::set_handler(move || {
+        for tx in &interrupt_senders {
+            let _ = tx.send(Message::Interrupt);
+        }
+    });
+    if let Err(err) = result {
+        eprintln!("Warning: failed to handle Ctrl+C: {}", err);
+    }
+    let _terminal_guard = if interactive {
+        let guard = keyboard::listen(move |key| {
+            for tx in &senders {
+                let _ = tx.send(Message::Key(key));
+            }
+        });
+        if guard.is_some() {
+            println!("{}", keyboard::HELP);
+        }
+        guard
+    } else {
+        None
     };
-    let mut notification = Notification::new()
-        .summary(report.title())
-        .icon(icon)
-        .finalize();
-    if let Some(detail) = report.detail {
-        notification.body(&detail);
+    threads.into_iter().map(|thread| thread.join().unwrap_or(1)).max().unwrap_or(0)
+}
+
+/// Deliver Ctrl+C to the reactor as a message, so it can kill the running process tree
+/// and restore the terminal before exiting.
+fn handle_interrupt(tx: Sender<Message>) {
+    let result = ctrlc::set_handler(move || { let _ = tx.send(Message::Interrupt); });
+    if let Err(err) = result {
+        eprintln!("Warning: failed to handle Ctrl+C: {}", err);
     }
-    notification
-        .show()
-        .expect("unable to send notification");
 }
 
-#[cfg(target_os = "windows")]
-fn notify(report: Report) {
-    let icon = match report.outcome {
-        Outcome::TestsPassed => "🔵",
-        Outcome::TestsFailed | Outcome::CompileError => "🔴"
-    };
-    let sound = match report.outcome {
-        Outcome::TestsPassed | Outcome::CompileError => None,
-        Outcome::TestsFailed => Some(winrt_notification::Sound::SMS)
-    };
-    winrt_notification::Toast::new("cargo-testify")
-        .title(&format!("{} {}", report.title(), icon))
-        .text1(&report.detail.unwrap_or("".to_owned()))
-        .sound(sound)
-        .duration(winrt_notification::Duration::Short)
-        .show()
-        .expect("unable to send notification");
-}
-
-/// Should changes in `path` file trigger running the test suite?
-fn filter_allows(project_dir: &Path, path: &Path) -> bool {
-    const FILES: &'static [&'static str] = &[
-        "src",
-        "tests",
-        "Cargo.toml",
-        "Cargo.lock",
-        "build.rs",
-    ];
-
-    FILES.iter().any(|file| {
-        let absolute_file_path = project_dir.join(file);
-        path.starts_with(absolute_file_path)
+/// Read `source` line by line in a separate thread, passing every line to `echo`.
+/// The thread returns everything it has read.
+fn capture<R, F>(source: R, echo: F) -> JoinHandle<String>
+    where R: Read + Send + 'static,
+          F: Fn(&str) + Send + 'static
+{
+    thread::spawn(move || {
+        let mut buffer = String::new();
+        let mut reader = BufReader::new(source);
+        let mut bytes = vec![];
+        loop {
+            bytes.clear();
+            match reader.read_until(b'\n', &mut bytes) {
+                Ok(0) => break,
+                Ok(_) => {},
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break
+            }
+            // Output which is not valid UTF-8 must not stop the capture
+            let line = String::from_utf8_lossy(&bytes);
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+            buffer.push_str(line);
+            buffer.push('\n');
+            echo(line);
+        }
+        buffer
     })
 }
 
+/// Print a line of the command's stdout as is, or as an event with JSON output.
+/// With `quiet` it's only sent to the dashboard.
+fn echo_line(line: &str, format: OutputFormat, quiet: bool, event_stream: &EventStream) {
+    match format {
+        OutputFormat::Human if quiet => {},
+        OutputFormat::Human => println!("{}", line),
+        OutputFormat::Json => events::stdout_line(line)
+    }
+    stream_output(event_stream, line);
+}
+
+/// Print a line of cargo output produced with JSON message format: compiler messages are
+/// rendered as usual, other cargo messages are skipped, and output of tests is echoed.
+fn echo_cargo_line(line: &str, format: OutputFormat, quiet: bool, event_stream: &EventStream) {
+    if !line.starts_with("{\"reason\":") {
+        echo_line(line, format, quiet, event_stream);
+        return;
+    }
+    if let Ok(cargo_metadata::Message::CompilerMessage(message)) = serde_json::from_str(line) {
+        if let Some(rendered) = message.message.rendered {
+            if !quiet {
+                eprint!("{}", rendered);
+            }
+            stream_output(event_stream, rendered.trim_end_matches('\n'));
+        }
+    }
+}
+
+/// Send a line of output to the dashboard, without colors.
+fn stream_output(event_stream: &EventStream, line: &str) {
+    let text = String::from_utf8_lossy(&strip_ansi_escapes::strip(line.as_bytes())).into_owned() + "\n";
+    event_stream.send("output", &json!({ "text": text }));
+}
+
+/// Copy raw terminal output to stdout in a separate thread, without waiting for complete
+/// lines, so progress bars are displayed properly. With JSON output it goes to stderr instead,
+/// and with `quiet` only to the dashboard. The thread returns the output with ANSI escape codes stripped.
+fn capture_terminal<R: Read + Send + 'static>(mut source: R, format: OutputFormat, quiet: bool, event_stream: EventStream) -> JoinHandle<String> {
+    thread::spawn(move || {
+        let mut raw = vec![];
+        let mut chunk = [0; 4096];
+        let stdout = io::stdout();
+        let stderr = io::stderr();
+        loop {
+            match source.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(size) => {
+                    raw.extend_from_slice(&chunk[..size]);
+                    match format {
+                        _ if quiet => {},
+                        OutputFormat::Human => write_raw(stdout.lock(), &chunk[..size]),
+                        OutputFormat::Json => write_raw(stderr.lock(), &chunk[..size])
+                    }
+                    let text = String::from_utf8_lossy(&strip_ansi_escapes::strip(&chunk[..size])).replace("\r\n", "\n");
+                    event_stream.send("output", &json!({ "text": text }));
+                },
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                // Reading a pseudo-terminal fails with EIO once the child has exited
+                Err(_) => break
+            }
+        }
+        let stripped = strip_ansi_escapes::strip(&raw);
+        String::from_utf8_lossy(&stripped).replace("\r\n", "\n")
+    })
+}
+
+fn write_raw<W: Write>(mut out: W, bytes: &[u8]) {
+    let _ = out.write_all(bytes);
+    let _ = out.flush();
+}
+
+fn clear_screen() {
+    // Clear the screen and the scrollback, then move the cursor to the top left corner
+    print!("\x1B[2J\x1B[3J\x1B[H");
+    let _ = io::stdout().flush();
+}
+
+/// Open `path` in the default application.
+#[cfg(target_os = "windows")]
+fn open_file(path: &Path) -> io::Result<process::ExitStatus> {
+    process::Command::new("cmd").arg("/C").arg("start").arg("").arg(path).status()
+}
+
+#[cfg(target_os = "macos")]
+fn open_file(path: &Path) -> io::Result<process::ExitStatus> {
+    process::Command::new("open").arg(path).status()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn open_file(path: &Path) -> io::Result<process::ExitStatus> {
+    process::Command::new("xdg-open").arg(path).status()
+}
+
+/// Command which runs `command_line` through the system shell, so pipes,
+/// quoting and scripts work the same way as in a terminal.
+#[cfg(not(target_os = "windows"))]
+pub fn shell_command(command_line: &str) -> Vec<String> {
+    vec!["sh".to_string(), "-c".to_string(), command_line.to_string()]
+}
+
+#[cfg(target_os = "windows")]
+pub fn shell_command(command_line: &str) -> Vec<String> {
+    vec!["cmd".to_string(), "/C".to_string(), command_line.to_string()]
+}
+
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
+    use config::{ConfigBuilder, WasmEnv};
+    use notifier::NullNotifier;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
 
-    const PROJECT_DIR: &'static str = "/project";
+    fn reactor(cargo_test_args: &[&str]) -> Reactor {
+        reactor_with_test_args(cargo_test_args, &[])
+    }
 
-    fn must_allow(path: &str) {
-        let project = PathBuf::from(PROJECT_DIR);
-        let path = PathBuf::from(path);
-        assert!(filter_allows(project.as_path(), path.as_path()));
+    fn reactor_with_test_args(cargo_test_args: &[&str], test_args: &[&str]) -> Reactor {
+        let config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .cargo_test_args(strings(cargo_test_args))
+            .test_args(strings(test_args))
+            .build()
+            .unwrap();
+        Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier))
     }
 
-    fn must_not_allow(path: &str) {
-        let project = PathBuf::from(PROJECT_DIR);
-        let path = PathBuf::from(path);
-        assert!(!filter_allows(project.as_path(), path.as_path()));
+    #[test]
+    fn test_stages() {
+        let stages: Vec<String> = reactor(&[]).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["tests"]);
+
+        let config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .pipeline(vec!["check".to_string(), "clippy -- -D warnings".to_string(), "test".to_string()])
+            .build()
+            .unwrap();
+        let stages: Vec<String> = Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier)).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["cargo check", "cargo clippy -- -D warnings", "tests"]);
+
+        let config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .check_first(true)
+            .build()
+            .unwrap();
+        let stages: Vec<String> = Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier)).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["cargo check --tests", "tests"]);
+
+        let config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .check_first(true)
+            .clippy(true)
+            .build()
+            .unwrap();
+        let stages: Vec<String> = Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier)).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["cargo check --tests", "cargo clippy --all-targets", "tests"]);
+
+        let mut config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .pipeline(vec!["check".to_string(), "test".to_string()])
+            .build()
+            .unwrap();
+        config.fmt = FmtMode::Check;
+        let stages: Vec<String> = Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier)).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["cargo fmt --check", "cargo check", "tests"]);
+
+        let config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .pipeline(vec!["test".to_string(), "doc".to_string()])
+            .miri(true)
+            .build()
+            .unwrap();
+        let stages: Vec<String> = Reactor::new(config).unwrap().with_notifier(Box::new(NullNotifier)).stages().iter().map(Stage::to_string).collect();
+        assert_eq!(stages, vec!["tests", "cargo +nightly miri test", "cargo doc"]);
+    }
+
+    #[test]
+    fn test_variants() {
+        let mut config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .toolchains(strings(&["stable", "nightly"]))
+            .build()
+            .unwrap();
+        config.matrix = strings(&["--no-default-features", "--all-features"]);
+        let variants: Vec<String> = Reactor::new(config).unwrap().variants().iter().map(Variant::to_string).collect();
+        assert_eq!(variants, vec![
+            "+stable --no-default-features", "+stable --all-features",
+            "+nightly --no-default-features", "+nightly --all-features"
+        ]);
+
+        let mut config = ConfigBuilder::new()
+            .project_dir(PathBuf::from("/project"))
+            .targets(strings(&["x86_64-unknown-linux-musl", "wasm32-wasip1"]))
+            .build()
+            .unwrap();
+        config.matrix = strings(&["--all-features"]);
+        let variants: Vec<String> = Reactor::new(config).unwrap().variants().iter().map(Variant::to_string).collect();
+        assert_eq!(variants, vec!["x86_64-unknown-linux-musl --all-features", "wasm32-wasip1 --all-features"]);
+
+        assert_eq!(reactor(&[]).variants(), vec![Variant::default()]);
+    }
+
+    #[test]
+    fn test_command_line_with_filters() {
+        let filters = vec!["tests::one".to_string(), "tests::two".to_string()];
+
+        assert_eq!(
+            reactor(&["--lib"]).command_line_with_filters(&Variant::default(), &filters),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "--lib", "--", "--exact", "tests::one", "tests::two"]
+        );
+        assert_eq!(
+            reactor(&["--", "--nocapture"]).command_line_with_filters(&Variant::default(), &filters),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "--", "--nocapture", "--exact", "tests::one", "tests::two"]
+        );
+        assert_eq!(reactor(&[]).command_line_with_filters(&Variant::default(), &[]), vec!["cargo", "test", CARGO_MESSAGE_FORMAT]);
+        assert_eq!(
+            reactor(&["--workspace", "--", "--nocapture"]).command_line_with_packages(&Variant::default(), &strings(&["app", "core"])),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "-p", "app", "-p", "core", "--", "--nocapture"]
+        );
+        assert_eq!(
+            reactor_with_test_args(&["--lib"], &["--nocapture"]).command_line_with_filters(&Variant::default(), &filters),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "--lib", "--", "--nocapture", "--exact", "tests::one", "tests::two"]
+        );
+        assert_eq!(
+            reactor_with_test_args(&[], &["--test-threads=1"]).command_line_with_filters(&Variant::default(), &[]),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "--", "--test-threads=1"]
+        );
+
+        assert_eq!(
+            reactor(&["--lib"]).command_line_with_filter(&Variant::default(), "parser::"),
+            vec!["cargo", "test", CARGO_MESSAGE_FORMAT, "--lib", "--", "parser::"]
+        );
+
+        let variant = Variant {
+            toolchain: Some("nightly".to_string()),
+            target: None,
+            features: Some("--no-default-features --features tokio".to_string())
+        };
+        assert_eq!(
+            reactor(&["--lib", "--", "--nocapture"]).command_line_with_filters(&variant, &[]),
+            vec!["cargo", "+nightly", "test", CARGO_MESSAGE_FORMAT, "--no-default-features", "--features", "tokio", "--lib", "--", "--nocapture"]
+        );
+
+        let mut reactor = reactor(&[]);
+        reactor.config.cross = true;
+        let variant = Variant { target: Some("aarch64-unknown-linux-gnu".to_string()), ..Variant::default() };
+        assert_eq!(
+            reactor.command_line_with_filters(&variant, &[]),
+            vec!["cross", "test", CARGO_MESSAGE_FORMAT, "--target", "aarch64-unknown-linux-gnu"]
+        );
+
+        let mut reactor = reactor_with_test_args(&["--lib"], &["--nocapture"]);
+        reactor.config.runner = Runner::WasmPack;
+        assert_eq!(
+            reactor.command_line_with_filters(&Variant::default(), &filters),
+            vec!["wasm-pack", "test", "--headless", "--chrome", "--", "--lib", "--", "--nocapture", "--exact", "tests::one", "tests::two"]
+        );
+        reactor.config.wasm_env = WasmEnv::Node;
+        reactor.config.cargo_test_args.clear();
+        reactor.config.test_args.clear();
+        assert_eq!(reactor.command_line_with_filters(&Variant::default(), &[]), vec!["wasm-pack", "test", "--node"]);
+        assert_eq!(reactor.command_name(), "wasm-pack test --node");
+    }
+
+    #[test]
+    fn test_is_written_by_command() {
+        let reactor = reactor(&[]);
+        let lock = Path::new("/project/Cargo.lock");
+        assert!(!reactor.is_written_by_command(lock));
+        reactor.command_running.set(true);
+        assert!(reactor.is_written_by_command(lock));
+        assert!(reactor.is_written_by_command(Path::new("/project/target/debug/.fingerprint")));
+        assert!(!reactor.is_written_by_command(Path::new("/project/src/lib.rs")));
+        reactor.command_running.set(false);
+        reactor.command_exited_at.set(Some(Instant::now()));
+        assert!(reactor.is_written_by_command(lock));
+        reactor.command_exited_at.set(Some(Instant::now() - reactor.config.ignore_duration));
+        assert!(!reactor.is_written_by_command(lock));
     }
 
     #[test]
-    fn test_filter_allows() {
-        must_allow("/project/src/main.rs");
-        must_allow("/project/src/lib/os.rs");
-        must_allow("/project/tests/watch.rs");
-        must_allow("/project/Cargo.toml");
-        must_allow("/project/Cargo.lock");
-        must_allow("/project/build.rs");
-
-        must_not_allow("/project/README.md");
-        must_not_allow("/tmp/file.rs");
-        must_not_allow("/tmp/src/file.rs");
+    fn test_capture_invalid_utf8() {
+        let output = io::Cursor::new(b"test a ... ok\r\nbinary \xff\xfe\nlast".to_vec());
+        let captured = capture(output, |_| {}).join().unwrap();
+        assert_eq!(captured, "test a ... ok\nbinary \u{fffd}\u{fffd}\nlast\n");
     }
 }
@@ -1,27 +1,450 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use errors::*;
+
 /// This enum represents an outcome of attempt to run tests.
 /// It's passed to a notifier in order to display a message to a user.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Outcome {
     /// Tests have passed successfully
     TestsPassed,
 
+    /// Tests have passed, but the compiler has emitted the given number of warnings
+    TestsPassedWithWarnings(usize),
+
+    /// Tests have passed, but the given number of them only after they had failed and were retried
+    Flaky(usize),
+
     /// Tests failed
     TestsFailed,
 
     /// Compilation error detected
-    CompileError
+    CompileError,
+
+    /// Clippy has reported the given number of warnings, and they are denied
+    LintFailed(usize),
+
+    /// Some files are not formatted with `rustfmt`
+    FormatError,
+
+    /// Miri has detected undefined behavior
+    UndefinedBehavior,
+
+    /// `cargo mutants` has found the given number of mutants of the code which no test catches
+    MutantsMissed(usize),
+
+    /// The test command has been killed, because it has run longer than the timeout
+    TimedOut,
+
+    /// The command has succeeded, but not a single test has run, e.g. because of a typo in a filter
+    NoTests,
+
+    /// The command has failed, but its output is not recognized, so only the exit status is known
+    Unknown
+}
+
+impl Outcome {
+    /// Names of all the outcomes, as returned by `name`.
+    pub const NAMES: &'static [&'static str] = &["passed", "passed_with_warnings", "flaky", "failed", "compile_error", "lint_failed",
+                                                 "format_error", "undefined_behavior", "mutants_missed", "timed_out", "no_tests", "unknown"];
+
+    /// Identifier of the outcome, used in machine readable output.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Outcome::TestsPassed => "passed",
+            Outcome::TestsPassedWithWarnings(_) => "passed_with_warnings",
+            Outcome::Flaky(_) => "flaky",
+            Outcome::TestsFailed => "failed",
+            Outcome::CompileError => "compile_error",
+            Outcome::LintFailed(_) => "lint_failed",
+            Outcome::FormatError => "format_error",
+            Outcome::UndefinedBehavior => "undefined_behavior",
+            Outcome::MutantsMissed(_) => "mutants_missed",
+            Outcome::TimedOut => "timed_out",
+            Outcome::NoTests => "no_tests",
+            Outcome::Unknown => "unknown"
+        }
+    }
+
+    /// Short summary of the outcome, e.g. `Tests passed with 2 warnings`.
+    pub fn title(&self) -> String {
+        match *self {
+            Outcome::TestsPassed => "Tests passed".to_string(),
+            Outcome::TestsPassedWithWarnings(1) => "Tests passed with 1 warning".to_string(),
+            Outcome::TestsPassedWithWarnings(count) => format!("Tests passed with {} warnings", count),
+            Outcome::Flaky(1) => "Tests passed, 1 flaky test".to_string(),
+            Outcome::Flaky(count) => format!("Tests passed, {} flaky tests", count),
+            Outcome::TestsFailed => "Tests failed".to_string(),
+            Outcome::CompileError => "Error".to_string(),
+            Outcome::LintFailed(1) => "1 clippy warning".to_string(),
+            Outcome::LintFailed(count) => format!("{} clippy warnings", count),
+            Outcome::FormatError => "Code is not formatted".to_string(),
+            Outcome::UndefinedBehavior => "Undefined behavior".to_string(),
+            Outcome::MutantsMissed(1) => "1 mutant survived".to_string(),
+            Outcome::MutantsMissed(count) => format!("{} mutants survived", count),
+            Outcome::TimedOut => "Tests timed out".to_string(),
+            Outcome::NoTests => "No tests ran".to_string(),
+            Outcome::Unknown => "Command failed".to_string()
+        }
+    }
+
+    /// Have all the tests passed, no matter if there were warnings or retries? A run without
+    /// tests is not a success.
+    pub fn is_success(&self) -> bool {
+        match *self {
+            Outcome::TestsPassed | Outcome::TestsPassedWithWarnings(_) | Outcome::Flaky(_) => true,
+            Outcome::TestsFailed | Outcome::CompileError | Outcome::LintFailed(_) | Outcome::FormatError | Outcome::UndefinedBehavior | Outcome::MutantsMissed(_) | Outcome::TimedOut | Outcome::NoTests | Outcome::Unknown => false
+        }
+    }
+}
+
+/// Outcomes without a count can be named in `outcome_rules`.
+impl FromStr for Outcome {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "passed" => Ok(Outcome::TestsPassed),
+            "failed" => Ok(Outcome::TestsFailed),
+            "compile_error" => Ok(Outcome::CompileError),
+            "format_error" => Ok(Outcome::FormatError),
+            "undefined_behavior" => Ok(Outcome::UndefinedBehavior),
+            "timed_out" => Ok(Outcome::TimedOut),
+            "no_tests" => Ok(Outcome::NoTests),
+            _ => Err(ErrorKind::UnknownOutcome(s.to_string()).into())
+        }
+    }
+}
+
+/// How many failed tests are listed in a notification body.
+const LISTED_FAILURES: usize = 3;
+
+/// Duration rounded to tenths of a second, e.g. `2.3s`.
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}.{}s", duration.as_secs(), duration.subsec_millis() / 100)
+}
+
+/// A failed test and the message it has panicked with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestFailure {
+    /// Full name of the test, e.g. `parser::tests::test_parse`
+    pub name: String,
+    pub message: Option<String>,
+    /// Where the test has panicked
+    pub location: Option<ErrorLocation>
+}
+
+/// Where the first compiler error is, known when cargo reports JSON diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorLocation {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    /// The whole error as printed by the compiler, without colors
+    pub rendered: Option<String>
+}
+
+/// A compiler error pointing to the code, known when cargo reports JSON diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilerError {
+    /// `error[E0425]: cannot find value `x` in this scope`
+    pub message: String,
+    pub location: ErrorLocation
+}
+
+/// Numbers of tests summed up over all test binaries: unit tests, every integration test and doc tests.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TestCounts {
+    pub passed: usize,
+    pub failed: usize,
+    /// Ignored or skipped tests
+    pub ignored: usize
+}
+
+impl TestCounts {
+    /// E.g. `5 passed; 1 failed; 0 ignored`
+    pub fn summary(&self) -> String {
+        format!("{} passed; {} failed; {} ignored", self.passed, self.failed, self.ignored)
+    }
+}
+
+/// Numbers of diagnostics reported by clippy.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LintCounts {
+    pub warnings: usize,
+    pub errors: usize
+}
+
+impl LintCounts {
+    /// E.g. `clippy: 1 error, 3 warnings`
+    pub fn summary(&self) -> String {
+        let mut parts = vec![];
+        if self.errors > 0 {
+            parts.push(plural(self.errors, "error"));
+        }
+        if self.warnings > 0 || self.errors == 0 {
+            parts.push(plural(self.warnings, "warning"));
+        }
+        format!("clippy: {}", parts.join(", "))
+    }
 }
 
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 { format!("1 {}", noun) } else { format!("{} {}s", count, noun) }
+}
+
+/// How long a single test has taken.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestTiming {
+    pub name: String,
+    pub duration: Duration
+}
+
+/// A single test which has run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool
+}
+
+/// Outcome of the test suite run with one entry of the matrix, e.g. `--all-features`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantOutcome {
+    pub name: String,
+    pub outcome: Outcome
+}
+
+/// Result of a test run, delivered to the notifier.
+#[derive(Debug, Clone)]
 pub struct Report {
     pub outcome: Outcome,
-    pub detail: Option<String>
+    pub detail: Option<String>,
+    pub failures: Vec<TestFailure>,
+    /// `None` if the numbers could not be parsed from the output
+    pub counts: Option<TestCounts>,
+    /// Durations of finished tests, empty unless the runner reports structured results
+    pub timings: Vec<TestTiming>,
+    /// Every test which has run, empty if the output could not be parsed
+    pub results: Vec<TestResult>,
+    /// How long the test command has been running
+    pub duration: Option<Duration>,
+    /// Pipeline stage which has failed, e.g. `cargo clippy`
+    pub stage: Option<String>,
+    pub error_location: Option<ErrorLocation>,
+    /// Every compiler error pointing to the code, the first one is also `error_location`
+    pub compile_errors: Vec<CompilerError>,
+    /// Exit code of the test command, `None` if it was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Outcomes per entry of the matrix, empty unless the suite has been run several times
+    pub variants: Vec<VariantOutcome>,
+    /// Tests which have failed, but passed when retried
+    pub flaky: Vec<String>,
+    /// Line coverage in percent, known when the tests are run with `cargo llvm-cov`
+    pub coverage: Option<f64>,
+    /// Change of the line coverage since the previous run, in percentage points
+    pub coverage_delta: Option<f64>,
+    /// Warnings and errors of the clippy stage, if it has run
+    pub lints: Option<LintCounts>,
+    /// Title rendered from the template of the configuration, replacing the default one
+    pub custom_title: Option<String>,
+    /// Body rendered from the template of the configuration, replacing the default one
+    pub custom_body: Option<String>,
+    /// Snapshots of insta which have changed, relative to the project root
+    pub snapshots: Vec<String>,
+    /// Name of the project, when several projects are watched at once
+    pub project: Option<String>
 }
 
 impl Report {
-    pub fn title(&self) -> &'static str {
-        match self.outcome {
-            Outcome::TestsPassed => "Tests passed",
-            Outcome::TestsFailed => "Tests failed",
-            Outcome::CompileError => "Error"
+    pub fn new(outcome: Outcome, detail: Option<String>) -> Self {
+        Self { outcome, detail, failures: vec![], counts: None, timings: vec![], results: vec![], duration: None, stage: None, error_location: None, compile_errors: vec![], exit_code: None, variants: vec![], flaky: vec![], coverage: None, coverage_delta: None, lints: None, custom_title: None, custom_body: None, snapshots: vec![], project: None }
+    }
+
+    /// Combine reports of the suite run with every entry of the matrix into one. It's based on the
+    /// report of the first failed entry (or the one with the most warnings if all have passed),
+    /// and lists the outcome of every entry together with failed tests of all of them.
+    pub fn combine(reports: Vec<(String, Report)>) -> Report {
+        let variants: Vec<VariantOutcome> = reports.iter()
+            .map(|(name, report)| VariantOutcome { name: name.clone(), outcome: report.outcome })
+            .collect();
+        let duration = reports.iter()
+            .filter_map(|(_, report)| report.duration)
+            .fold(None, |total: Option<Duration>, duration| Some(total.map_or(duration, |total| total + duration)));
+        let mut failures: Vec<TestFailure> = vec![];
+        let mut flaky: Vec<String> = vec![];
+        // A test has passed only if it has passed with every entry
+        let mut results: Vec<TestResult> = vec![];
+        for (_, report) in &reports {
+            for result in &report.results {
+                match results.iter().position(|known| known.name == result.name) {
+                    Some(index) => results[index].passed &= result.passed,
+                    None => results.push(result.clone())
+                }
+            }
+            for failure in &report.failures {
+                if !failures.iter().any(|known| known.name == failure.name) {
+                    failures.push(failure.clone());
+                }
+            }
+            for name in &report.flaky {
+                if !flaky.contains(name) {
+                    flaky.push(name.clone());
+                }
+            }
+        }
+
+        let base_index = reports.iter().position(|(_, report)| !report.outcome.is_success())
+            .or_else(|| {
+                (0..reports.len()).max_by_key(|&index| match reports[index].1.outcome {
+                    Outcome::TestsPassedWithWarnings(count) => count,
+                    _ => 0
+                })
+            })
+            .expect("at least one report is required");
+        let mut report = reports.into_iter().nth(base_index).unwrap().1;
+        report.variants = variants;
+        report.duration = duration;
+        report.failures = failures;
+        report.flaky = flaky;
+        report.results = results;
+        report
+    }
+
+    pub fn failed_test_names(&self) -> Vec<String> {
+        self.failures.iter().map(|failure| failure.name.clone()).collect()
+    }
+
+    /// Text to display in a notification: the detail followed by the location of a compiler error
+    /// or names of the first few failed tests.
+    pub fn body(&self) -> Option<String> {
+        if let Some(ref body) = self.custom_body {
+            return if body.is_empty() { None } else { Some(body.clone()) };
+        }
+        let mut lines: Vec<String> = self.detail.iter().cloned().collect();
+        if let Some(ref location) = self.error_location {
+            lines.push(format!("at {}:{}:{}", location.file, location.line, location.column));
+        }
+        if let Some(lints) = self.lints {
+            lines.push(lints.summary());
+        }
+        if let Some(coverage) = self.coverage {
+            match self.coverage_delta {
+                Some(delta) if delta.abs() >= 0.05 => lines.push(format!("Coverage: {:.1}% ({:+.1}%)", coverage, delta)),
+                _ => lines.push(format!("Coverage: {:.1}%", coverage))
+            }
+        }
+        for variant in &self.variants {
+            if variant.outcome.is_success() {
+                lines.push(format!("✓ {}", variant.name));
+            } else {
+                lines.push(format!("✗ {}: {}", variant.name, variant.outcome.title()));
+            }
+        }
+        for failure in self.failures.iter().take(LISTED_FAILURES) {
+            lines.push(format!("✗ {}", failure.name));
+        }
+        if self.failures.len() > LISTED_FAILURES {
+            lines.push(format!("and {} more", self.failures.len() - LISTED_FAILURES));
+        }
+        if !self.flaky.is_empty() {
+            lines.push(format!("Flaky: {}", self.flaky.join(", ")));
+        }
+        match self.snapshots.len() {
+            0 => {},
+            1 => lines.push("1 snapshot to review".to_string()),
+            count => lines.push(format!("{} snapshots to review", count))
         }
+        // The detail of a timed out run already tells how long it has been running
+        if let (Some(duration), false) = (self.duration, self.outcome == Outcome::TimedOut) {
+            lines.push(format!("Finished in {}", format_duration(duration)));
+        }
+
+        if lines.is_empty() { None } else { Some(lines.join("\n")) }
+    }
+
+    pub fn title(&self) -> String {
+        let title = match (&self.custom_title, &self.stage) {
+            (Some(title), _) => title.clone(),
+            (&None, Some(stage)) => format!("{} ({})", self.outcome.title(), stage),
+            (&None, &None) => self.outcome.title()
+        };
+        match self.project {
+            Some(ref project) => format!("{}: {}", project, title),
+            None => title
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failure(name: &str) -> TestFailure {
+        TestFailure { name: name.to_string(), message: None, location: None }
+    }
+
+    #[test]
+    fn test_body() {
+        let report = Report::new(Outcome::TestsPassed, None);
+        assert_eq!(report.body(), None);
+
+        let mut report = Report::new(Outcome::TestsFailed, Some("1 passed; 2 failed".to_string()));
+        report.failures = vec![failure("a"), failure("b")];
+        assert_eq!(report.body().unwrap(), "1 passed; 2 failed\n✗ a\n✗ b");
+
+        report.failures = vec![failure("a"), failure("b"), failure("c"), failure("d"), failure("e")];
+        assert_eq!(report.body().unwrap(), "1 passed; 2 failed\n✗ a\n✗ b\n✗ c\nand 2 more");
+
+        report.failures = vec![failure("a")];
+        report.snapshots = vec!["src/snapshots/app__a.snap.new".to_string(), "src/.lib.rs.pending-snap".to_string()];
+        assert_eq!(report.body().unwrap(), "1 passed; 2 failed\n✗ a\n2 snapshots to review");
+
+        let mut report = Report::new(Outcome::Flaky(2), Some("3 passed; 0 failed; 0 ignored".to_string()));
+        report.flaky = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(report.title(), "Tests passed, 2 flaky tests");
+        assert_eq!(report.body().unwrap(), "3 passed; 0 failed; 0 ignored\nFlaky: a, b");
+
+        let mut report = Report::new(Outcome::TestsPassed, None);
+        report.coverage = Some(84.25);
+        report.coverage_delta = Some(-1.5);
+        assert_eq!(report.body().unwrap(), "Coverage: 84.2% (-1.5%)");
+
+        let mut report = Report::new(Outcome::LintFailed(3), None);
+        report.lints = Some(LintCounts { warnings: 3, errors: 1 });
+        assert_eq!(report.title(), "3 clippy warnings");
+        assert_eq!(report.body().unwrap(), "clippy: 1 error, 3 warnings");
+
+        report.project = Some("billing".to_string());
+        assert_eq!(report.title(), "billing: 3 clippy warnings");
+    }
+
+    #[test]
+    fn test_combine() {
+        let passed = Report::new(Outcome::TestsPassedWithWarnings(1), None);
+        let mut failed = Report::new(Outcome::TestsFailed, Some("1 passed; 1 failed".to_string()));
+        failed.failures = vec![failure("a")];
+        failed.duration = Some(Duration::from_secs(2));
+        let error = Report::new(Outcome::CompileError, Some("error[E0425]".to_string()));
+
+        let report = Report::combine(vec![
+            ("--no-default-features".to_string(), passed),
+            ("--all-features".to_string(), failed),
+            ("--features tokio".to_string(), error)
+        ]);
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert_eq!(report.duration, Some(Duration::from_secs(2)));
+        assert_eq!(
+            report.body().unwrap(),
+            "1 passed; 1 failed\n✓ --no-default-features\n✗ --all-features: Tests failed\n✗ --features tokio: Error\n✗ a\nFinished in 2.0s"
+        );
+
+        let report = Report::combine(vec![
+            ("--no-default-features".to_string(), Report::new(Outcome::TestsPassed, None)),
+            ("--all-features".to_string(), Report::new(Outcome::TestsPassedWithWarnings(2), None))
+        ]);
+        assert_eq!(report.outcome, Outcome::TestsPassedWithWarnings(2));
     }
 }
@@ -1,41 +1,853 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::str::Lines;
+use std::time::Duration;
+
+use cargo_metadata::Message;
+use cargo_metadata::diagnostic::{Diagnostic, DiagnosticLevel};
 use regex::Regex;
-use report::{Report, Outcome};
+use serde_json;
+use strip_ansi_escapes;
+use config::OutcomeRule;
+use errors::*;
+use report::{CompilerError, ErrorLocation, LintCounts, Report, Outcome, TestCounts, TestFailure, TestResult, TestTiming};
+
+/// A line of libtest JSON output, as printed by `cargo nextest run --message-format libtest-json`:
+///
+/// ```text
+/// {"type":"test","event":"failed","name":"app::bin/app$tests::bad","exec_time":0.002,"stdout":"..."}
+/// {"type":"suite","event":"failed","passed":1,"failed":1,"ignored":0,"measured":0,"filtered_out":0}
+/// ```
+#[derive(Debug, Deserialize)]
+struct LibtestEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    name: Option<String>,
+    passed: Option<usize>,
+    failed: Option<usize>,
+    ignored: Option<usize>,
+    /// Seconds
+    exec_time: Option<f64>,
+    /// Captured output of a failed test
+    stdout: Option<String>
+}
+
+/// An entry of `outcome_rules` with its pattern compiled.
+struct Rule {
+    pattern: Regex,
+    outcome: Outcome,
+    /// Capture group used as the detail of the report, `0` is the whole match
+    message: usize
+}
 
 /// Determines what is result of running tests, based on the following information:
-/// * Did process finish successfully?
+/// * Exit code of the process
 /// * Stdout
 /// * Stderr
 ///
 /// The structure just keeps compiled regular expressions, so they can be used
 /// every time `identify` function is called.
 pub struct ReportBuilder {
-    result_re: Regex,
-    error_re: Regex
+    /// Result of a single test: `test tests::bad ... FAILED`, or `... FAIL` with wasm-bindgen-test
+    test_re: Regex,
+    /// Total of the coverage summary printed by `cargo llvm-cov`
+    coverage_re: Regex,
+    /// Summary printed by every test binary: `test result: ok. 3 passed; 0 failed; 1 ignored; ...`
+    counts_re: Regex,
+    error_re: Regex,
+    /// Header of the captured output of a failed test: `---- tests::bad stdout ----`,
+    /// or `---- tests::bad output ----` with wasm-bindgen-test
+    failure_header_re: Regex,
+    /// Panic message in the format used before Rust 1.73: `thread 'x' panicked at 'message', src/lib.rs:2:5`
+    legacy_panic_re: Regex,
+    /// Panic location, followed by the message on the next line: `thread 'x' panicked at src/lib.rs:2:5:`.
+    /// Newer versions print the thread id too: `thread 'x' (1234) panicked at src/lib.rs:2:5:`
+    panic_re: Regex,
+    /// Panic location in the indented output of wasm-bindgen-test, followed by the message:
+    /// `        panicked at tests/web.rs:10:5:`
+    wasm_panic_re: Regex,
+    /// Undefined behavior detected by Miri: `error: Undefined Behavior: out-of-bounds pointer use: ...`
+    undefined_behavior_re: Regex,
+    /// Location of an error printed by rustc or Miri: `  --> src/lib.rs:5:18`
+    error_location_re: Regex,
+    /// Thread of the backtrace Miri prints, which is named after the test: ``= note: BACKTRACE on thread `tests::ub`:``
+    miri_thread_re: Regex,
+    /// Test which has not finished, because Miri has aborted the test binary: `test tests::ub ... `
+    unfinished_test_re: Regex,
+    warning_re: Regex,
+    /// Error reported by the compiler or clippy: `error: unused variable: `x``
+    lint_error_re: Regex,
+    /// Errors which only sum up the other ones: `error: could not compile `app` due to 2 previous errors`
+    error_summary_re: Regex,
+    /// Summary printed by cargo after the warnings of a crate: `warning: `app` (lib) generated 2 warnings`
+    warning_summary_re: Regex,
+    /// Rules of the configuration, tried before the built-in detection
+    rules: Vec<Rule>
 }
 
 impl ReportBuilder {
     pub fn new() -> Self {
         // Unwrap here is always safe, because the regexps are valid
         Self {
-            result_re: Regex::new(r"\d{1,} passed.*filtered out").unwrap(),
-            error_re: Regex::new(r"error(:|\[).*").unwrap()
+            test_re: Regex::new(r"(?m)^test (.+) \.\.\. (ok|FAILED|FAIL)$").unwrap(),
+            coverage_re: Regex::new(r"(?m)^TOTAL\s.*$").unwrap(),
+            counts_re: Regex::new(r"(?m)^test result: \w+\. (\d+) passed; (\d+) failed; (\d+) ignored").unwrap(),
+            error_re: Regex::new(r"error(:|\[).*").unwrap(),
+            failure_header_re: Regex::new(r"^---- (.+) (?:stdout|output) ----$").unwrap(),
+            legacy_panic_re: Regex::new(r"^thread '.*'(?: \(\d+\))? panicked at '(.*)', (\S+)$").unwrap(),
+            panic_re: Regex::new(r"^thread '.*'(?: \(\d+\))? panicked at (\S+):$").unwrap(),
+            wasm_panic_re: Regex::new(r"^\s+panicked at (\S+):$").unwrap(),
+            undefined_behavior_re: Regex::new(r"(?m)error: Undefined Behavior: (.*)$").unwrap(),
+            error_location_re: Regex::new(r"^\s*--> (\S+)$").unwrap(),
+            miri_thread_re: Regex::new(r"BACKTRACE on thread `([^`]+)`").unwrap(),
+            unfinished_test_re: Regex::new(r"(?m)^test (\S+) \.\.\. ?(?:error: Undefined Behavior|$)").unwrap(),
+            warning_re: Regex::new(r"^warning(\[\w+\])?: ").unwrap(),
+            lint_error_re: Regex::new(r"^error(\[\w+\])?: ").unwrap(),
+            error_summary_re: Regex::new(r"^error: (could not compile|aborting due to)").unwrap(),
+            warning_summary_re: Regex::new(r"generated \d+ warnings?( \(.*\))?$").unwrap(),
+            rules: vec![]
+        }
+    }
+
+    /// Decide the outcome with `rules` first, so output of other tools than cargo (or cargo
+    /// in another locale) is recognized too. The first rule matching stdout or stderr wins.
+    pub fn with_rules(mut self, rules: &[OutcomeRule]) -> Result<Self> {
+        for rule in rules {
+            let pattern = Regex::new(&rule.pattern)
+                .map_err(|err| ErrorKind::InvalidOutcomeRule(rule.pattern.clone(), err.to_string()))?;
+            self.rules.push(Rule { pattern, outcome: rule.outcome.parse()?, message: rule.message.unwrap_or(0) });
+        }
+        Ok(self)
+    }
+
+    /// `exit_code` is `None` when the process was terminated by a signal.
+    /// If the output does not look like output of `cargo test` (e.g. a custom command is used),
+    /// the outcome is based on the exit code only: a failure is reported as `Unknown`
+    /// with the exit status.
+    /// Passed tests are reported with a distinct outcome if the compiler has emitted warnings,
+    /// and a run which has not found a single test to run is reported as such.
+    /// Colors are stripped from the output before it's parsed.
+    pub fn identify(&self, exit_code: Option<i32>, stdout: &str, stderr: &str) -> Report {
+        let stdout = strip_ansi(stdout);
+        let stderr = strip_ansi(stderr);
+        let (stdout, stderr) = (stdout.as_ref(), stderr.as_ref());
+        let mut report = self.analyze(exit_code, stdout, stderr);
+        report.exit_code = exit_code;
+        if report.results.is_empty() {
+            report.results = self.results(stdout);
+        }
+        report.coverage = self.coverage(stdout);
+        if let Some(counts) = report.counts {
+            if report.outcome == Outcome::TestsPassed && counts.passed == 0 && counts.failed == 0 {
+                report.outcome = Outcome::NoTests;
+            }
+        }
+        if report.outcome == Outcome::TestsPassed {
+            let warnings = self.count_warnings(stdout, stderr);
+            if warnings > 0 {
+                report.outcome = Outcome::TestsPassedWithWarnings(warnings);
+            }
         }
+        report
     }
 
-    pub fn identify(&self, process_success: bool, stdout: &str, stderr: &str) -> Report {
-        if process_success {
-            let detail  = self.result_re.find(stdout).map(|m| m.as_str().to_string() );
-            Report { outcome: Outcome::TestsPassed, detail: detail }
+    fn analyze(&self, exit_code: Option<i32>, stdout: &str, stderr: &str) -> Report {
+        if let Some(report) = self.apply_rules(stdout, stderr) {
+            return report;
+        }
+        if exit_code != Some(0) {
+            if let Some(report) = compile_error(stdout) {
+                return report;
+            }
+            if let Some(report) = self.undefined_behavior(stdout, stderr) {
+                return report;
+            }
+        }
+        if let Some(report) = self.structured(exit_code, stdout) {
+            return report;
+        }
+
+        // Unit tests, every integration test and doc tests print their own results, the notification
+        // shows the numbers of the whole suite
+        let counts = self.counts(stdout);
+        if exit_code == Some(0) {
+            let mut report = Report::new(Outcome::TestsPassed, counts.map(|counts| counts.summary()));
+            report.counts = counts;
+            report
         } else {
-            match self.result_re.find(stdout) {
-                Some(matched) => {
-                    Report { outcome: Outcome::TestsFailed, detail: Some(matched.as_str().to_string()) }
+            match counts {
+                Some(counts) => {
+                    let mut report = Report::new(Outcome::TestsFailed, Some(counts.summary()));
+                    report.failures = self.failures(stdout);
+                    report.counts = Some(counts);
+                    report
                 },
                 None => {
-                    let detail = self.error_re.find(stderr).map(|m| m.as_str().to_string() );
-                    Report { outcome: Outcome::CompileError, detail: detail }
+                    match self.error_re.find(stderr) {
+                        Some(matched) => {
+                            Report::new(Outcome::CompileError, Some(matched.as_str().to_string()))
+                        },
+                        None => {
+                            Report::new(Outcome::Unknown, Some(exit_code_detail(exit_code)))
+                        }
+                    }
                 }
             }
         }
     }
 }
+
+impl ReportBuilder {
+    /// Report of the first rule of the configuration which matches the output, with the chosen
+    /// capture group as its detail.
+    fn apply_rules(&self, stdout: &str, stderr: &str) -> Option<Report> {
+        self.rules.iter().filter_map(|rule| {
+            let caps = rule.pattern.captures(stdout).or_else(|| rule.pattern.captures(stderr))?;
+            let message = caps.get(rule.message).map(|matched| matched.as_str().trim().to_string());
+            Some(Report::new(rule.outcome, message))
+        }).next()
+    }
+
+    /// Build the report from libtest JSON events, if the output contains any suite results.
+    /// Every test binary reports its own suite, so the numbers are summed up.
+    fn structured(&self, exit_code: Option<i32>, stdout: &str) -> Option<Report> {
+        let events: Vec<LibtestEvent> = stdout.lines()
+            .filter(|line| line.starts_with('{'))
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let mut suites = events.iter().filter(|event| event.kind == "suite" && event.event != "started").peekable();
+        suites.peek()?;
+
+        let mut counts = TestCounts::default();
+        for suite in suites {
+            counts.passed += suite.passed.unwrap_or(0);
+            counts.failed += suite.failed.unwrap_or(0);
+            counts.ignored += suite.ignored.unwrap_or(0);
+        }
+
+        let mut failures = vec![];
+        let mut timings = vec![];
+        let mut results = vec![];
+        for event in events.iter().filter(|event| event.kind == "test") {
+            let name = match event.name {
+                Some(ref name) => test_name(name),
+                None => continue
+            };
+            if let Some(seconds) = event.exec_time {
+                timings.push(TestTiming { name: name.clone(), duration: Duration::from_millis((seconds * 1000.0).round() as u64) });
+            }
+            if event.event == "ok" || event.event == "failed" {
+                results.push(TestResult { name: name.clone(), passed: event.event == "ok" });
+            }
+            if event.event == "failed" {
+                let panic = event.stdout.as_ref().and_then(|output| self.first_panic(output));
+                let (message, location) = match panic {
+                    Some((message, location)) => (Some(message), location),
+                    None => (None, None)
+                };
+                failures.push(TestFailure { name, message, location });
+            }
+        }
+
+        let outcome = if counts.failed == 0 && exit_code == Some(0) { Outcome::TestsPassed } else { Outcome::TestsFailed };
+        let mut report = Report::new(outcome, Some(counts.summary()));
+        report.failures = failures;
+        report.counts = Some(counts);
+        report.timings = timings;
+        report.results = results;
+        Some(report)
+    }
+
+    /// Failed tests in the order they are listed in the summary, with the first line
+    /// of the panic message of each and where it has panicked.
+    fn failures(&self, stdout: &str) -> Vec<TestFailure> {
+        let mut panics = self.panics(stdout);
+        failed_tests(stdout).into_iter().map(|name| {
+            match panics.remove(&name) {
+                Some((message, location)) => TestFailure { name, message: Some(message), location },
+                None => TestFailure { name, message: None, location: None }
+            }
+        }).collect()
+    }
+
+    /// Find panic messages and locations in the captured output of failed tests:
+    ///
+    /// ```text
+    /// ---- tests::bad stdout ----
+    /// thread 'tests::bad' panicked at src/lib.rs:10:9:
+    /// assertion failed: false
+    /// ```
+    fn panics(&self, stdout: &str) -> HashMap<String, (String, Option<ErrorLocation>)> {
+        let mut panics = HashMap::new();
+        let mut current_test: Option<String> = None;
+        let mut lines = stdout.lines();
+        while let Some(line) = lines.next() {
+            if let Some(caps) = self.failure_header_re.captures(line) {
+                current_test = Some(caps[1].to_string());
+                continue;
+            }
+            let name = match current_test {
+                Some(ref name) => name.clone(),
+                None => continue
+            };
+            if let Some(panic) = self.panic(line, &mut lines) {
+                panics.entry(name).or_insert(panic);
+                current_test = None;
+            }
+        }
+        panics
+    }
+
+    /// Undefined behavior reported by Miri, which aborts the test binary. The test it was detected in
+    /// is listed as failed, with the message and the location of the offending code:
+    ///
+    /// ```text
+    /// error: Undefined Behavior: out-of-bounds pointer use: expected a pointer to 4 bytes of memory
+    ///   --> src/lib.rs:5:18
+    /// ...
+    ///    = note: BACKTRACE on thread `tests::ub`:
+    /// ```
+    fn undefined_behavior(&self, stdout: &str, stderr: &str) -> Option<Report> {
+        let caps = self.undefined_behavior_re.captures(stderr)?;
+        let message = caps[1].trim().to_string();
+        let location = stderr[caps.get(0)?.end()..].lines()
+            .filter_map(|line| self.error_location_re.captures(line))
+            .next()
+            .and_then(|caps| parse_location(&caps[1]));
+        let test = self.miri_thread_re.captures(stderr).map(|caps| caps[1].to_string())
+            .filter(|thread| thread != "main")
+            .or_else(|| self.unfinished_test_re.captures_iter(stdout).last().map(|caps| caps[1].to_string()));
+
+        let mut report = Report::new(Outcome::UndefinedBehavior, Some(message.clone()));
+        if let Some(name) = test {
+            report.failures.push(TestFailure { name, message: Some(message), location: location.clone() });
+        }
+        report.error_location = location;
+        Some(report)
+    }
+
+    /// Results of the tests listed in the human readable output of libtest.
+    fn results(&self, stdout: &str) -> Vec<TestResult> {
+        self.test_re.captures_iter(stdout)
+            .map(|caps| TestResult { name: caps[1].to_string(), passed: &caps[2] == "ok" })
+            .collect()
+    }
+
+    /// Line coverage from the `TOTAL` row of the `cargo llvm-cov` summary, which has percentages
+    /// of covered regions, functions, lines and, optionally, branches:
+    ///
+    /// ```text
+    /// TOTAL    14    2    85.71%    3    0    100.00%    10    1    90.00%    0    0    -
+    /// ```
+    fn coverage(&self, stdout: &str) -> Option<f64> {
+        let total = self.coverage_re.find_iter(stdout).last()?;
+        total.as_str().split_whitespace()
+            .filter(|column| column.ends_with('%'))
+            .nth(2)
+            .and_then(|column| column.trim_end_matches('%').parse().ok())
+    }
+
+    /// Numbers of tests summed up over the `test result:` lines of every test binary.
+    fn counts(&self, stdout: &str) -> Option<TestCounts> {
+        let mut counts: Option<TestCounts> = None;
+        for caps in self.counts_re.captures_iter(stdout) {
+            let total = counts.get_or_insert_with(TestCounts::default);
+            total.passed += caps[1].parse::<usize>().unwrap_or(0);
+            total.failed += caps[2].parse::<usize>().unwrap_or(0);
+            total.ignored += caps[3].parse::<usize>().unwrap_or(0);
+        }
+        counts
+    }
+
+    /// Numbers of warnings and errors reported by clippy or the compiler.
+    pub fn lints(&self, stdout: &str, stderr: &str) -> LintCounts {
+        let stdout = strip_ansi(stdout);
+        let stderr = strip_ansi(stderr);
+        let (stdout, stderr) = (stdout.as_ref(), stderr.as_ref());
+        let errors = stderr.lines()
+            .filter(|line| self.lint_error_re.is_match(line) && !self.error_summary_re.is_match(line))
+            .count();
+        LintCounts { warnings: self.count_warnings(stdout, stderr), errors }
+    }
+
+    /// Number of compiler warnings, without cargo's per-crate summaries. Warnings are printed to
+    /// stderr, or reported in stdout when cargo emits JSON diagnostics.
+    fn count_warnings(&self, stdout: &str, stderr: &str) -> usize {
+        let printed = stderr.lines()
+            .filter(|line| self.warning_re.is_match(line) && !self.warning_summary_re.is_match(line))
+            .count();
+        // Summaries like "2 warnings emitted" do not point to any code. The same warning is reported
+        // for every target a file is compiled for (e.g. lib and lib test), so duplicates are skipped.
+        let diagnostics = diagnostics(stdout);
+        let reported: HashSet<(&str, &str, usize, usize)> = diagnostics.iter()
+            .filter(|diagnostic| diagnostic.level == DiagnosticLevel::Warning && !diagnostic.spans.is_empty())
+            .map(|diagnostic| {
+                let span = &diagnostic.spans[0];
+                (diagnostic.message.as_str(), span.file_name.as_str(), span.line_start, span.column_start)
+            })
+            .collect();
+        printed + reported.len()
+    }
+
+    /// Panic message and location in the output of a single test.
+    fn first_panic(&self, output: &str) -> Option<(String, Option<ErrorLocation>)> {
+        let mut lines = output.lines();
+        while let Some(line) = lines.next() {
+            if let Some(panic) = self.panic(line, &mut lines) {
+                return Some(panic);
+            }
+        }
+        None
+    }
+
+    /// If `line` reports a panic, return its message and location. Since Rust 1.73 the message
+    /// follows on the next line, so it's taken from `lines`.
+    fn panic(&self, line: &str, lines: &mut Lines) -> Option<(String, Option<ErrorLocation>)> {
+        if let Some(caps) = self.legacy_panic_re.captures(line) {
+            Some((caps[1].to_string(), parse_location(&caps[2])))
+        } else if let Some(caps) = self.panic_re.captures(line) {
+            lines.next().map(|message| (message.to_string(), parse_location(&caps[1])))
+        } else if let Some(caps) = self.wasm_panic_re.captures(line) {
+            lines.next().map(|message| (message.trim().to_string(), parse_location(&caps[1])))
+        } else {
+            None
+        }
+    }
+}
+
+/// Extract names of failed tests from the summary `failures:` sections of libtest output:
+///
+/// ```text
+/// failures:
+///     tests::test_one
+///     tests::test_two
+///
+/// test result: FAILED. 0 passed; 2 failed; 0 ignored; 0 measured; 0 filtered out
+/// ```
+///
+/// Every test binary (unit tests, integration tests, doc tests) prints its own section.
+/// wasm-bindgen-test separates the names from the header with an empty line.
+fn failed_tests(stdout: &str) -> Vec<String> {
+    let mut names = vec![];
+    let mut in_section = false;
+    let mut listed = 0;
+    for line in stdout.lines() {
+        if line.trim_end() == "failures:" {
+            in_section = true;
+            listed = 0;
+        } else if in_section && line.starts_with("    ") {
+            names.push(line.trim().to_string());
+            listed += 1;
+        } else if in_section && listed == 0 && line.trim().is_empty() {
+            continue;
+        } else {
+            in_section = false;
+        }
+    }
+    names
+}
+
+/// Compiler diagnostics from cargo JSON messages (`--message-format=json-diagnostic-rendered-ansi`).
+/// Lines which are not cargo messages (e.g. output of tests) are skipped.
+fn diagnostics(stdout: &str) -> Vec<Diagnostic> {
+    stdout.lines()
+        .filter(|line| line.starts_with("{\"reason\":"))
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(Message::CompilerMessage(message)) => Some(message.message),
+            _ => None
+        })
+        .collect()
+}
+
+/// Report about the first compiler error with its location, if cargo has reported any.
+/// All the errors pointing to the code are kept in the report too, without duplicates
+/// reported for every target a file is compiled for.
+fn compile_error(stdout: &str) -> Option<Report> {
+    let mut errors: Vec<CompilerError> = vec![];
+    for diagnostic in diagnostics(stdout) {
+        if diagnostic.level != DiagnosticLevel::Error || diagnostic.spans.is_empty() { continue; }
+        let error = compiler_error(&diagnostic);
+        if !errors.contains(&error) {
+            errors.push(error);
+        }
+    }
+    let first = errors.first()?.clone();
+
+    let mut report = Report::new(Outcome::CompileError, Some(first.message));
+    report.error_location = Some(first.location);
+    report.compile_errors = errors;
+    Some(report)
+}
+
+fn compiler_error(diagnostic: &Diagnostic) -> CompilerError {
+    let message = match diagnostic.code {
+        Some(ref code) => format!("error[{}]: {}", code.code, diagnostic.message),
+        None => format!("error: {}", diagnostic.message)
+    };
+    let span = diagnostic.spans.iter().find(|span| span.is_primary).unwrap_or(&diagnostic.spans[0]);
+    let location = ErrorLocation {
+        file: span.file_name.clone(),
+        line: span.line_start,
+        column: span.column_start,
+        rendered: diagnostic.rendered.as_ref().map(|rendered| {
+            String::from_utf8_lossy(&strip_ansi_escapes::strip(rendered)).into_owned()
+        })
+    };
+    CompilerError { message, location }
+}
+
+/// Remove ANSI escape codes, which cargo prints when colors are forced (`CARGO_TERM_COLOR=always`).
+/// Output without them is returned as is.
+fn strip_ansi<'a>(text: &'a str) -> Cow<'a, str> {
+    if text.contains('\x1b') {
+        Cow::Owned(String::from_utf8_lossy(&strip_ansi_escapes::strip(text.as_bytes())).into_owned())
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Parse a location like `src/lib.rs:10:9`.
+fn parse_location(text: &str) -> Option<ErrorLocation> {
+    let mut parts = text.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = parts.next()?.to_string();
+    Some(ErrorLocation { file, line, column, rendered: None })
+}
+
+/// Nextest prefixes test names with the binary: `app::bin/app$tests::bad`.
+/// Only the test path is kept, so it can be used as a filter.
+fn test_name(name: &str) -> String {
+    match name.find('$') {
+        Some(index) => name[index + 1..].to_string(),
+        None => name.to_string()
+    }
+}
+
+fn exit_code_detail(exit_code: Option<i32>) -> String {
+    match exit_code {
+        Some(code) => format!("Process exited with code {}", code),
+        None => "Process was terminated by a signal".to_string()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_cargo_output() {
+        let builder = ReportBuilder::new();
+
+        let stdout = "test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out\n";
+        let report = builder.identify(Some(0), stdout, "");
+        assert_eq!(report.outcome, Outcome::TestsPassed);
+        assert_eq!(report.detail.unwrap(), "3 passed; 0 failed; 0 ignored");
+
+        let stdout = "test result: FAILED. 2 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out\n";
+        let report = builder.identify(Some(101), stdout, "");
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert!(report.failures.is_empty());
+
+        let stderr = "error[E0425]: cannot find value `x` in this scope\n";
+        let report = builder.identify(Some(101), "", stderr);
+        assert_eq!(report.outcome, Outcome::CompileError);
+        assert_eq!(report.detail.unwrap(), "error[E0425]: cannot find value `x` in this scope");
+    }
+
+    #[test]
+    fn test_identify_json_diagnostics() {
+        let stdout = concat!(
+            r#"{"reason":"compiler-message","package_id":"app 0.1.0 (path+file:///app)","manifest_path":"/app/Cargo.toml","#,
+            r#""target":{"kind":["lib"],"crate_types":["lib"],"name":"app","src_path":"/app/src/lib.rs","edition":"2021","doc":true,"doctest":true,"test":true},"#,
+            r#""message":{"message":"cannot find value `x` in this scope","code":{"code":"E0425","explanation":null},"level":"error","#,
+            r#""spans":[{"file_name":"src/lib.rs","byte_start":20,"byte_end":21,"line_start":1,"line_end":1,"column_start":21,"column_end":22,"#,
+            r#""is_primary":true,"text":[],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"#,
+            r#""children":[],"rendered":"\u001b[1merror[E0425]\u001b[0m: cannot find value `x` in this scope\n"}}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":false}"#,
+            "\n"
+        );
+        let report = ReportBuilder::new().identify(Some(101), stdout, "error: could not compile `app`\n");
+        assert_eq!(report.outcome, Outcome::CompileError);
+        assert_eq!(report.detail.unwrap(), "error[E0425]: cannot find value `x` in this scope");
+        assert_eq!(report.error_location, Some(ErrorLocation {
+            file: "src/lib.rs".to_string(),
+            line: 1,
+            column: 21,
+            rendered: Some("error[E0425]: cannot find value `x` in this scope\n".to_string())
+        }));
+        assert_eq!(report.compile_errors.len(), 1);
+    }
+
+    #[test]
+    fn test_identify_no_tests() {
+        let builder = ReportBuilder::new();
+        let stdout = "\
+test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 12 filtered out; finished in 0.00s
+test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 3 filtered out; finished in 0.00s
+";
+        let report = builder.identify(Some(0), stdout, "");
+        assert_eq!(report.outcome, Outcome::NoTests);
+        assert_eq!(report.title(), "No tests ran");
+
+        let report = builder.identify(Some(0), "All good\n", "");
+        assert_eq!(report.outcome, Outcome::TestsPassed);
+    }
+
+    #[test]
+    fn test_identify_coverage() {
+        let stdout = "\
+test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+
+Filename      Regions    Missed Regions     Cover   Functions  Missed Functions  Executed       Lines      Missed Lines     Cover    Branches   Missed Branches     Cover
+------------------------------------------------------------------------------------------------------------------------------------------------------------------------
+lib.rs             14                 2    85.71%           3                 0   100.00%          10                 1    90.00%           0                 0         -
+------------------------------------------------------------------------------------------------------------------------------------------------------------------------
+TOTAL              14                 2    85.71%           3                 0   100.00%          10                 1    90.00%           0                 0         -
+";
+        let report = ReportBuilder::new().identify(Some(0), stdout, "");
+        assert_eq!(report.coverage, Some(90.0));
+        assert_eq!(report.outcome, Outcome::TestsPassed);
+    }
+
+    #[test]
+    fn test_identify_warnings() {
+        let builder = ReportBuilder::new();
+        let stdout = "test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out\n";
+        let stderr = "\
+warning: unused variable: `x`
+ --> src/lib.rs:2:9
+warning[E0170]: pattern binding `A` is named the same as one of the variants
+warning: `app` (lib test) generated 2 warnings (run `cargo fix --lib -p app --tests` to apply 1 suggestion)
+    Finished test [unoptimized + debuginfo] target(s) in 0.5s
+";
+        let report = builder.identify(Some(0), stdout, stderr);
+        assert_eq!(report.outcome, Outcome::TestsPassedWithWarnings(2));
+        assert_eq!(report.title(), "Tests passed with 2 warnings");
+
+        let report = builder.identify(Some(101), "", stderr);
+        assert_eq!(report.outcome, Outcome::Unknown);
+    }
+
+    #[test]
+    fn test_lints() {
+        let stderr = "\
+    Checking app v0.1.0 (/app)
+warning: this `if` has identical blocks
+error: this comparison involving the minimum or maximum element for this type contains a case that is always true or always false
+error[E0425]: cannot find value `x` in this scope
+warning: `app` (lib) generated 1 warning
+error: could not compile `app` (lib) due to 2 previous errors; 1 warning emitted
+";
+        assert_eq!(ReportBuilder::new().lints("", stderr), LintCounts { warnings: 1, errors: 2 });
+    }
+
+    #[test]
+    fn test_identify_colored_output() {
+        let stdout = "\
+test tests::bad ... \x1b[31mFAILED\x1b[0m
+
+failures:
+    tests::bad
+
+test result: \x1b[31mFAILED\x1b[0m. 2 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out
+";
+        let stderr = "\x1b[1m\x1b[33mwarning\x1b[0m\x1b[1m: unused variable: `x`\x1b[0m\n";
+        let builder = ReportBuilder::new();
+        let report = builder.identify(Some(101), stdout, stderr);
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert_eq!(report.detail, Some("2 passed; 1 failed; 0 ignored".to_string()));
+        assert_eq!(report.failed_test_names(), vec!["tests::bad"]);
+        assert_eq!(builder.lints("", stderr).warnings, 1);
+    }
+
+    #[test]
+    fn test_identify_falls_back_to_exit_code() {
+        let builder = ReportBuilder::new();
+
+        let report = builder.identify(Some(0), "All good\n", "");
+        assert_eq!(report.outcome, Outcome::TestsPassed);
+        assert!(report.detail.is_none());
+
+        let report = builder.identify(Some(2), "make: *** [check] Error 2\n", "");
+        assert_eq!(report.outcome, Outcome::Unknown);
+        assert_eq!(report.detail.unwrap(), "Process exited with code 2");
+        assert_eq!(report.exit_code, Some(2));
+
+        let report = builder.identify(None, "", "");
+        assert_eq!(report.outcome, Outcome::Unknown);
+        assert_eq!(report.detail.unwrap(), "Process was terminated by a signal");
+    }
+
+    #[test]
+    fn test_failed_tests() {
+        let stdout = "\
+running 3 tests
+test tests::ok ... ok
+test tests::bad ... FAILED
+test parser::tests::worse ... FAILED
+
+failures:
+
+---- tests::bad stdout ----
+thread 'tests::bad' panicked at 'assertion failed', src/lib.rs:10:9
+
+---- parser::tests::worse stdout ----
+thread 'parser::tests::worse' (4242) panicked at src/parser.rs:20:5:
+assertion `left == right` failed: unexpected token
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+failures:
+    parser::tests::worse
+    tests::bad
+
+test result: FAILED. 1 passed; 2 failed; 0 ignored; 0 measured; 0 filtered out
+
+running 1 test
+test it_works ... FAILED
+
+failures:
+    it_works
+
+test result: FAILED. 0 passed; 1 failed; 0 ignored; 0 measured; 0 filtered out
+";
+        let report = ReportBuilder::new().identify(Some(101), stdout, "");
+        assert_eq!(report.failed_test_names(), vec!["parser::tests::worse", "tests::bad", "it_works"]);
+        assert_eq!(report.failures[0].message, Some("assertion `left == right` failed: unexpected token".to_string()));
+        assert_eq!(report.failures[0].location, Some(ErrorLocation { file: "src/parser.rs".to_string(), line: 20, column: 5, rendered: None }));
+        assert_eq!(report.failures[1].message, Some("assertion failed".to_string()));
+        assert_eq!(report.failures[1].location.as_ref().map(|location| location.line), Some(10));
+        assert_eq!(report.failures[2].message, None);
+        assert_eq!(report.results[0], TestResult { name: "tests::ok".to_string(), passed: true });
+        assert_eq!(report.results[1], TestResult { name: "tests::bad".to_string(), passed: false });
+        assert_eq!(report.results.len(), 4);
+        assert_eq!(report.counts, Some(TestCounts { passed: 1, failed: 3, ignored: 0 }));
+        assert_eq!(report.detail.unwrap(), "1 passed; 3 failed; 0 ignored");
+    }
+
+    #[test]
+    fn test_identify_wasm_bindgen_test_output() {
+        let stdout = "\
+running 2 tests
+test web::pass ... ok
+test web::fail ... FAIL
+
+failures:
+
+---- web::fail output ----
+    error output:
+        panicked at tests/web.rs:10:5:
+        assertion failed: 1 + 1 == 3
+
+    JS exception that was thrown:
+        RuntimeError: unreachable
+
+failures:
+
+    web::fail
+
+test result: FAILED. 1 passed; 1 failed; 0 ignored; 0 filtered out; finished in 0.02s
+";
+        let report = ReportBuilder::new().identify(Some(1), stdout, "");
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert_eq!(report.failed_test_names(), vec!["web::fail"]);
+        assert_eq!(report.failures[0].message, Some("assertion failed: 1 + 1 == 3".to_string()));
+        assert_eq!(report.failures[0].location.as_ref().map(|location| location.file.as_str()), Some("tests/web.rs"));
+        assert_eq!(report.results[1], TestResult { name: "web::fail".to_string(), passed: false });
+        assert_eq!(report.counts, Some(TestCounts { passed: 1, failed: 1, ignored: 0 }));
+    }
+
+    #[test]
+    fn test_identify_undefined_behavior() {
+        let stdout = "\
+running 2 tests
+test tests::ok ... ok
+test tests::ub ... ";
+        let stderr = "\
+error: Undefined Behavior: out-of-bounds pointer use: expected a pointer to 4 bytes of memory, but got alloc1 which is only 4 bytes from the end of the allocation
+  --> src/lib.rs:5:18
+   |
+5  |         unsafe { *ptr.add(1) }
+   |                  ^^^^^^^^^^^ Undefined Behavior occurred here
+   |
+   = help: this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior
+   = note: BACKTRACE on thread `tests::ub`:
+   = note: inside `tests::ub` at src/lib.rs:5:18: 5:29
+
+error: aborting due to 1 previous error
+";
+        let builder = ReportBuilder::new();
+        let report = builder.identify(Some(1), stdout, stderr);
+        assert_eq!(report.outcome, Outcome::UndefinedBehavior);
+        assert!(report.detail.as_ref().unwrap().starts_with("out-of-bounds pointer use: expected a pointer"));
+        assert_eq!(report.failed_test_names(), vec!["tests::ub"]);
+        assert_eq!(report.error_location, Some(ErrorLocation { file: "src/lib.rs".to_string(), line: 5, column: 18, rendered: None }));
+
+        // Older versions of Miri do not name the thread
+        let stderr = stderr.replace(" on thread `tests::ub`", "");
+        assert_eq!(builder.identify(Some(1), stdout, &stderr).failed_test_names(), vec!["tests::ub"]);
+    }
+
+    #[test]
+    fn test_outcome_rules() {
+        let rule = |pattern: &str, outcome: &str, message| OutcomeRule { pattern: pattern.to_string(), outcome: outcome.to_string(), message };
+        let rules = vec![
+            rule(r"(?m)^FAIL: (.*)$", "failed", Some(1)),
+            rule(r"(?m)^Ran \d+ tests$", "passed", None)
+        ];
+        let builder = ReportBuilder::new().with_rules(&rules).unwrap();
+
+        let report = builder.identify(Some(1), "Ran 2 tests\n", "FAIL: test_parse (tests.ParserTest)\n");
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert_eq!(report.detail, Some("test_parse (tests.ParserTest)".to_string()));
+        assert_eq!(builder.identify(Some(0), "Ran 2 tests\n", "").detail, Some("Ran 2 tests".to_string()));
+
+        // The built-in detection is used when no rule matches
+        let stdout = "test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out\n";
+        assert_eq!(builder.identify(Some(0), stdout, "").outcome, Outcome::TestsPassed);
+
+        assert!(ReportBuilder::new().with_rules(&[rule("(", "failed", None)]).is_err());
+        assert!(ReportBuilder::new().with_rules(&[rule("FAIL", "lint_failed", None)]).is_err());
+    }
+
+    #[test]
+    fn test_counts_of_all_test_binaries() {
+        let stdout = "\
+     Running unittests src/lib.rs (target/debug/deps/app-1a2b)
+test result: ok. 4 passed; 0 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+
+     Running tests/api.rs (target/debug/deps/api-3c4d)
+test result: ok. 2 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.01s
+
+   Doc-tests app
+test result: ok. 3 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.20s
+";
+        let report = ReportBuilder::new().identify(Some(0), stdout, "");
+        assert_eq!(report.outcome, Outcome::TestsPassed);
+        assert_eq!(report.detail.unwrap(), "9 passed; 0 failed; 1 ignored");
+        assert_eq!(report.counts, Some(TestCounts { passed: 9, failed: 0, ignored: 1 }));
+    }
+
+    #[test]
+    fn test_identify_libtest_json() {
+        let stdout = r#"{"type":"suite","event":"started","test_count":3}
+{"type":"test","event":"started","name":"app::bin/app$tests::ok"}
+{"type":"test","event":"ok","name":"app::bin/app$tests::ok","exec_time":0.25}
+{"type":"test","event":"failed","name":"app::bin/app$tests::bad","exec_time":0.001,"stdout":"thread 'tests::bad' panicked at src/main.rs:9:9:\nboom\n"}
+{"type":"test","event":"ignored","name":"app::bin/app$tests::slow"}
+{"type":"suite","event":"failed","passed":1,"failed":1,"ignored":1,"measured":0,"filtered_out":0,"exec_time":0.3}
+"#;
+        let report = ReportBuilder::new().identify(Some(100), stdout, "");
+        assert_eq!(report.outcome, Outcome::TestsFailed);
+        assert_eq!(report.detail.unwrap(), "1 passed; 1 failed; 1 ignored");
+        assert_eq!(report.counts, Some(TestCounts { passed: 1, failed: 1, ignored: 1 }));
+        let location = ErrorLocation { file: "src/main.rs".to_string(), line: 9, column: 9, rendered: None };
+        assert_eq!(report.failures, vec![TestFailure { name: "tests::bad".to_string(), message: Some("boom".to_string()), location: Some(location) }]);
+        assert_eq!(report.timings[0], TestTiming { name: "tests::ok".to_string(), duration: Duration::from_millis(250) });
+    }
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use cargo_metadata::MetadataCommand;
+
+use config::TestMapping;
+use errors::*;
+use manifest;
+use targeting;
+
+/// Part of the project affected by the changes since a git ref.
+#[derive(Debug, PartialEq)]
+pub enum Scope {
+    /// The changes can not be narrowed down, e.g. `Cargo.toml` or a crate root has changed
+    Everything,
+    /// Packages of the workspace whose tests are affected: the changed ones and the ones depending on them
+    Packages(Vec<String>),
+    /// Filters of the modules whose tests are affected, within a single package
+    Modules(Vec<String>)
+}
+
+/// A package of the workspace.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Names of the packages of the workspace it depends on
+    pub dependencies: Vec<String>
+}
+
+/// Files changed since `base` in the git repository the project is in, as absolute paths: the modified,
+/// added and deleted ones, including changes which are not staged, and files git does not track yet.
+pub fn changed_files(project_dir: &Path, base: &str) -> Result<Vec<PathBuf>> {
+    let root = PathBuf::from(git(project_dir, &["rev-parse", "--show-toplevel"])?.trim());
+    let modified = git(project_dir, &["diff", "--name-only", "--no-renames", base, "--"])?;
+    let untracked = git(project_dir, &["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+    let mut files: Vec<PathBuf> = modified.lines().chain(untracked.lines())
+        .filter(|line| !line.is_empty())
+        .map(|line| root.join(line))
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .map_err(|err| ErrorKind::ChangesUnknown(format!("failed to run `git {}`: {}", args.join(" "), err)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(ErrorKind::ChangesUnknown(format!("`git {}` has failed: {}", args.join(" "), stderr.trim())).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Packages of the workspace, if the project is the root of a workspace with several of them.
+/// Otherwise the tests of a single package run, and there is nothing to choose from.
+pub fn workspace_packages(project_dir: &Path) -> Result<Vec<Package>> {
+    if manifest::workspace_root(project_dir).is_none_or(|root| root != project_dir) {
+        return Ok(vec![]);
+    }
+    let metadata = MetadataCommand::new()
+        .manifest_path(project_dir.join("Cargo.toml"))
+        .no_deps()
+        .exec()
+        .map_err(|err| ErrorKind::ChangesUnknown(format!("failed to list the packages of the workspace: {}", err)))?;
+    let packages: Vec<Package> = metadata.packages.iter()
+        .map(|package| Package {
+            name: package.name.clone(),
+            dir: package.manifest_path.parent().map(|dir| dir.as_std_path().to_path_buf()).unwrap_or_default(),
+            dependencies: package.dependencies.iter()
+                .filter(|dependency| dependency.path.is_some())
+                .map(|dependency| dependency.name.clone())
+                .collect()
+        })
+        .collect();
+    Ok(if packages.len() > 1 { packages } else { vec![] })
+}
+
+/// What the changed `files` affect. With the `packages` of a workspace it's the packages containing
+/// the files and the ones depending on them, otherwise the modules of the files, as `test_map` or
+/// their paths tell. Anything else, e.g. `Cargo.lock` or a crate root, affects everything.
+pub fn scope(project_dir: &Path, packages: &[Package], test_map: &[TestMapping], files: &[PathBuf]) -> Scope {
+    if files.is_empty() {
+        return Scope::Everything;
+    }
+    if !packages.is_empty() {
+        let mut names: Vec<String> = vec![];
+        for file in files {
+            // The innermost package, since packages may be nested in the directory of the root one
+            let package = packages.iter()
+                .filter(|package| file.starts_with(&package.dir))
+                .max_by_key(|package| package.dir.components().count());
+            match package {
+                Some(package) => names.push(package.name.clone()),
+                None => return Scope::Everything
+            }
+        }
+        return Scope::Packages(with_dependents(packages, names));
+    }
+    let mut filters: Vec<String> = vec![];
+    for file in files {
+        let filter = file.strip_prefix(project_dir).ok()
+            .and_then(|path| targeting::filter_for(project_dir, test_map, path));
+        match filter {
+            Some(filter) => filters.push(filter),
+            None => return Scope::Everything
+        }
+    }
+    filters.sort();
+    filters.dedup();
+    // The tests of `parser::` include the ones of `parser::expr::`
+    let outer = filters.clone();
+    filters.retain(|filter| !outer.iter().any(|other| other != filter && filter.starts_with(other.as_str())));
+    Scope::Modules(filters)
+}
+
+/// `names` and the names of the packages which depend on them, directly or not, sorted.
+fn with_dependents(packages: &[Package], mut names: Vec<String>) -> Vec<String> {
+    loop {
+        let dependents: Vec<String> = packages.iter()
+            .filter(|package| !names.contains(&package.name))
+            .filter(|package| package.dependencies.iter().any(|dependency| names.contains(dependency)))
+            .map(|package| package.name.clone())
+            .collect();
+        if dependents.is_empty() { break; }
+        names.extend(dependents);
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, dir: &str, dependencies: &[&str]) -> Package {
+        Package {
+            name: name.to_string(),
+            dir: PathBuf::from(dir),
+            dependencies: dependencies.iter().map(|name| name.to_string()).collect()
+        }
+    }
+
+    fn files(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_scope_of_packages() {
+        let project_dir = Path::new("/ws");
+        let packages = vec![
+            package("app", "/ws", &["core", "cli"]),
+            package("core", "/ws/crates/core", &[]),
+            package("cli", "/ws/crates/cli", &["core"]),
+            package("docs", "/ws/crates/docs", &[])
+        ];
+        let scope = |paths: &[&str]| scope(project_dir, &packages, &[], &files(paths));
+
+        assert_eq!(scope(&["/ws/crates/docs/src/lib.rs"]), Scope::Packages(vec!["docs".to_string()]));
+        assert_eq!(scope(&["/ws/crates/cli/src/main.rs"]), Scope::Packages(vec!["app".to_string(), "cli".to_string()]));
+        assert_eq!(scope(&["/ws/crates/core/src/lib.rs", "/ws/crates/docs/README.md"]),
+                   Scope::Packages(vec!["app".to_string(), "cli".to_string(), "core".to_string(), "docs".to_string()]));
+        assert_eq!(scope(&["/shared/fixtures/users.json"]), Scope::Everything);
+        assert_eq!(scope(&[]), Scope::Everything);
+    }
+
+    #[test]
+    fn test_scope_of_modules() {
+        let project_dir = Path::new("/project");
+        let test_map = vec![TestMapping { path: "migrations/**".to_string(), filter: "db::".to_string() }];
+        let scope = |paths: &[&str]| scope(project_dir, &[], &test_map, &files(paths));
+
+        assert_eq!(scope(&["/project/src/parser/mod.rs", "/project/src/lexer.rs", "/project/src/parser/expr.rs"]),
+                   Scope::Modules(vec!["lexer::".to_string(), "parser::".to_string()]));
+        assert_eq!(scope(&["/project/migrations/001_init.sql"]), Scope::Modules(vec!["db::".to_string()]));
+        assert_eq!(scope(&["/project/src/parser.rs", "/project/src/lib.rs"]), Scope::Everything);
+        assert_eq!(scope(&["/project/Cargo.toml"]), Scope::Everything);
+    }
+}
@@ -0,0 +1,67 @@
+use std::io::{self, BufRead};
+
+use keyring::{self, Entry};
+
+use keyboard;
+use errors::*;
+
+/// Service the secrets are stored under in the credential store of the OS.
+const SERVICE: &str = "cargo-testify";
+
+/// Prefix of settings which name a secret in the credential store instead of containing it,
+/// e.g. `webhook = "keyring:slack_webhook"`.
+pub const PREFIX: &str = "keyring:";
+
+/// The secret named by a `keyring:<name>` setting, other values are returned as they are.
+pub fn resolve(value: String) -> Result<String> {
+    if !value.starts_with(PREFIX) {
+        return Ok(value);
+    }
+    let name = &value[PREFIX.len()..];
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(secret),
+        Err(keyring::Error::NoEntry) => Err(store_error(name, format!("not found, store it with `cargo testify secret set {}`", name))),
+        Err(err) => Err(store_error(name, err.to_string()))
+    }
+}
+
+/// Store a secret typed into the terminal without echo, or piped into stdin.
+pub fn set(name: &str) -> Result<()> {
+    let hidden = keyboard::hide_input();
+    if hidden.is_some() {
+        eprint!("Value of {}: ", name);
+    }
+    let mut value = String::new();
+    io::stdin().lock().read_line(&mut value).map_err(|err| store_error(name, err.to_string()))?;
+    if hidden.is_some() {
+        eprintln!();
+    }
+    let value = value.trim_end_matches(['\r', '\n']);
+    if value.is_empty() {
+        return Err(store_error(name, "the value is empty".to_string()));
+    }
+    entry(name)?.set_password(value).map_err(|err| store_error(name, err.to_string()))
+}
+
+pub fn delete(name: &str) -> Result<()> {
+    entry(name)?.delete_password().map_err(|err| store_error(name, err.to_string()))
+}
+
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(SERVICE, name).map_err(|err| store_error(name, err.to_string()))
+}
+
+fn store_error(name: &str, reason: String) -> Error {
+    ErrorKind::SecretStore(name.to_string(), reason).into()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_values_are_kept() {
+        assert_eq!(resolve("https://hooks.slack.com/services/x".to_string()).unwrap(), "https://hooks.slack.com/services/x");
+    }
+}
@@ -0,0 +1,295 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde_json;
+
+use errors::*;
+
+/// Directory the failing cases of property tests are recorded in, relative to the project root.
+/// Each test has a file of its own, each line is a JSON object describing a case.
+pub const SEEDS_DIR: &str = ".testify/seeds";
+
+/// Header of the regression files of proptest, written when testify restores one.
+const REGRESSIONS_HEADER: &str = "\
+# Seeds for failure cases proptest has generated in the past. It is
+# automatically read and these particular cases re-run before any
+# novel cases are generated.
+#
+# It is recommended to check this file in to source control so that
+# everyone who runs the test benefits from these saved cases.
+";
+
+/// A failing case found by proptest or quickcheck.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Seed {
+    pub test: String,
+    /// `proptest` or `quickcheck`
+    pub framework: String,
+    /// Minimal failing input, e.g. `x = 5` with proptest or `(5)` with quickcheck
+    pub input: Option<String>,
+    /// Line proptest persists the failure with: `cc 4c3d...ab # shrinks to x = 5`
+    pub persisted: Option<String>,
+    /// Regression file proptest reads the persisted failures from, relative to the project root
+    pub regressions: Option<String>
+}
+
+impl Seed {
+    /// The framework is not known until a failing case is found.
+    fn new(test: &str) -> Self {
+        Self { test: test.to_string(), framework: String::new(), input: None, persisted: None, regressions: None }
+    }
+
+    /// Is it the same case as `other`? The seed of proptest tells it, quickcheck only has the input.
+    fn is_same(&self, other: &Seed) -> bool {
+        match (&self.persisted, &other.persisted) {
+            (Some(a), Some(b)) => persisted_seed(a) == persisted_seed(b),
+            _ => self.input == other.input
+        }
+    }
+}
+
+/// Failing cases in the captured output of the failed tests:
+///
+/// ```text
+/// ---- tests::prop stdout ----
+/// proptest: Saving this and future failures in /app/proptest-regressions/lib.txt
+/// cc 4c3d...ab # shrinks to x = 5
+/// Test failed: assertion failed: x < 5; minimal failing input: x = 5
+///
+/// ---- tests::check stdout ----
+/// [quickcheck] TEST FAILED. Arguments: (5)
+/// ```
+pub fn parse(output: &str, project_dir: &Path) -> Vec<Seed> {
+    let header_re = Regex::new(r"^---- (.+) (?:stdout|output) ----$").unwrap();
+    let saving_re = Regex::new(r"^proptest: Saving this and future failures in (.+)$").unwrap();
+    let persisted_re = Regex::new(r"^cc [0-9a-f]+").unwrap();
+    let proptest_re = Regex::new(r"minimal failing input: (.+)$").unwrap();
+    let quickcheck_re = Regex::new(r"^\[quickcheck\] TEST FAILED.*Arguments: (.+)$").unwrap();
+
+    let mut seeds = vec![];
+    // The failed test whose output is being read
+    let mut current: Option<Seed> = None;
+    for line in output.lines() {
+        let line = line.trim_end();
+        if let Some(caps) = header_re.captures(line) {
+            seeds.extend(current.take().filter(|seed| !seed.framework.is_empty()));
+            current = Some(Seed::new(&caps[1]));
+            continue;
+        }
+        // The list of the failed tests follows the captured output
+        if line == "failures:" {
+            seeds.extend(current.take().filter(|seed| !seed.framework.is_empty()));
+            continue;
+        }
+        let seed = match current {
+            Some(ref mut seed) => seed,
+            None => continue
+        };
+        if let Some(caps) = saving_re.captures(line) {
+            let path = PathBuf::from(caps[1].trim());
+            // Regression files outside of the project, e.g. in a container, can't be restored
+            seed.regressions = path.strip_prefix(project_dir).ok().map(|path| path.to_string_lossy().into_owned());
+            seed.framework = "proptest".to_string();
+        } else if persisted_re.is_match(line) {
+            seed.persisted = Some(line.to_string());
+            seed.framework = "proptest".to_string();
+        } else if let Some(caps) = proptest_re.captures(line) {
+            seed.input = Some(caps[1].trim().to_string());
+            seed.framework = "proptest".to_string();
+        } else if let Some(caps) = quickcheck_re.captures(line) {
+            seed.input = Some(caps[1].trim().to_string());
+            seed.framework = "quickcheck".to_string();
+        }
+    }
+    seeds.extend(current.take().filter(|seed| !seed.framework.is_empty()));
+    seeds
+}
+
+/// Record the cases which have not been recorded yet, and return them.
+pub fn record(project_dir: &Path, seeds: &[Seed]) -> Result<Vec<Seed>> {
+    let recorded = load(project_dir)?;
+    let mut new = vec![];
+    for seed in seeds {
+        if recorded.iter().chain(new.iter()).any(|other: &Seed| other.test == seed.test && other.is_same(seed)) {
+            continue;
+        }
+        let path = seeds_path(project_dir, &seed.test);
+        let write = || -> ::std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let line = serde_json::to_string(seed).expect("seed is always serializable");
+            writeln!(file, "{}", line)
+        };
+        write().chain_err(|| ErrorKind::SeedsFile(path.clone()))?;
+        new.push(seed.clone());
+    }
+    Ok(new)
+}
+
+/// Read all recorded cases, ordered by the name of the test. Lines which can't be parsed are skipped.
+pub fn load(project_dir: &Path) -> Result<Vec<Seed>> {
+    let dir = project_dir.join(SEEDS_DIR);
+    if !dir.is_dir() { return Ok(vec![]); }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(&dir).chain_err(|| ErrorKind::SeedsFile(dir.clone()))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "jsonl"))
+        .collect();
+    paths.sort();
+    let mut seeds = vec![];
+    for path in paths {
+        let file = File::open(&path).chain_err(|| ErrorKind::SeedsFile(path.clone()))?;
+        for line in BufReader::new(file).lines() {
+            let line = line.chain_err(|| ErrorKind::SeedsFile(path.clone()))?;
+            if let Ok(seed) = serde_json::from_str(&line) {
+                seeds.push(seed);
+            }
+        }
+    }
+    Ok(seeds)
+}
+
+/// Names of the tests with recorded cases, without duplicates.
+pub fn tests(seeds: &[Seed]) -> Vec<String> {
+    let mut names: Vec<String> = seeds.iter().map(|seed| seed.test.clone()).collect();
+    names.dedup();
+    names
+}
+
+/// Forget the cases of a test which does not exist anymore.
+pub fn forget(project_dir: &Path, test: &str) -> Result<()> {
+    let path = seeds_path(project_dir, test);
+    fs::remove_file(&path).chain_err(|| ErrorKind::SeedsFile(path.clone()))
+}
+
+/// Put the persisted cases back into the regression files of proptest, which replays them
+/// before generating new ones, if they are missing, e.g. when the files have been deleted.
+/// Returns how many cases have been restored.
+pub fn restore(project_dir: &Path, seeds: &[Seed]) -> Result<usize> {
+    let mut restored = 0;
+    for seed in seeds {
+        let (persisted, regressions) = match (&seed.persisted, &seed.regressions) {
+            (Some(persisted), Some(regressions)) => (persisted, regressions),
+            _ => continue
+        };
+        let path = project_dir.join(regressions);
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        if content.lines().any(|line| persisted_seed(line) == persisted_seed(persisted)) {
+            continue;
+        }
+        let write = || -> ::std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            if content.is_empty() {
+                write!(file, "{}", REGRESSIONS_HEADER)?;
+            }
+            writeln!(file, "{}", persisted)
+        };
+        write().chain_err(|| ErrorKind::SeedsFile(path.clone()))?;
+        restored += 1;
+    }
+    Ok(restored)
+}
+
+/// The seed of a persisted line, without the comment: `cc 4c3d...ab`.
+fn persisted_seed(line: &str) -> &str {
+    line.split('#').next().unwrap_or("").trim()
+}
+
+/// File of the cases of a test: `tests::prop` is recorded in `.testify/seeds/tests.prop.jsonl`,
+/// since `:` is not allowed in file names on Windows.
+fn seeds_path(project_dir: &Path, test: &str) -> PathBuf {
+    project_dir.join(SEEDS_DIR).join(format!("{}.jsonl", test.replace("::", ".")))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const OUTPUT: &str = "\
+running 3 tests
+test tests::ok ... ok
+test tests::prop ... FAILED
+test tests::check ... FAILED
+
+failures:
+
+---- tests::prop stdout ----
+proptest: Saving this and future failures in /app/proptest-regressions/lib.txt
+proptest: If this test was run on a CI system, you may wish to add the following line to your copy of the file.
+cc 4c3d8ab0 # shrinks to x = 5
+
+thread 'tests::prop' panicked at src/lib.rs:9:5:
+Test failed: assertion failed: x < 5; minimal failing input: x = 5
+	successes: 5
+	local rejects: 0
+	global rejects: 0
+
+---- tests::check stdout ----
+thread 'tests::check' panicked at src/lib.rs:14:5:
+[quickcheck] TEST FAILED. Arguments: (0)
+
+---- tests::plain stdout ----
+thread 'tests::plain' panicked at src/lib.rs:18:5:
+assertion failed: false
+
+failures:
+    tests::check
+    tests::plain
+    tests::prop
+";
+
+    #[test]
+    fn test_parse() {
+        let seeds = parse(OUTPUT, Path::new("/app"));
+        assert_eq!(seeds, vec![
+            Seed {
+                test: "tests::prop".to_string(),
+                framework: "proptest".to_string(),
+                input: Some("x = 5".to_string()),
+                persisted: Some("cc 4c3d8ab0 # shrinks to x = 5".to_string()),
+                regressions: Some("proptest-regressions/lib.txt".to_string())
+            },
+            Seed {
+                test: "tests::check".to_string(),
+                framework: "quickcheck".to_string(),
+                input: Some("(0)".to_string()),
+                persisted: None,
+                regressions: None
+            }
+        ]);
+        assert_eq!(parse(OUTPUT, Path::new("/elsewhere"))[0].regressions, None);
+    }
+
+    #[test]
+    fn test_record_and_restore() {
+        let dir = env::temp_dir().join("testify-seeds-test");
+        let _ = fs::remove_dir_all(&dir);
+        let seeds = parse(OUTPUT, Path::new("/app"));
+        assert_eq!(record(&dir, &seeds).unwrap().len(), 2);
+        assert!(record(&dir, &seeds).unwrap().is_empty());
+        assert!(dir.join(".testify/seeds/tests.prop.jsonl").is_file());
+
+        let recorded = load(&dir).unwrap();
+        assert_eq!(tests(&recorded), vec!["tests::check", "tests::prop"]);
+
+        assert_eq!(restore(&dir, &recorded).unwrap(), 1);
+        assert_eq!(restore(&dir, &recorded).unwrap(), 0);
+        let regressions = fs::read_to_string(dir.join("proptest-regressions/lib.txt")).unwrap();
+        assert!(regressions.starts_with("# Seeds for failure cases"));
+        assert!(regressions.ends_with("cc 4c3d8ab0 # shrinks to x = 5\n"));
+
+        forget(&dir, "tests::check").unwrap();
+        assert_eq!(tests(&load(&dir).unwrap()), vec!["tests::prop"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{self, Value};
+
+use events;
+use status::LatestRun;
+use websocket;
+use errors::*;
+
+/// How long the server waits for a request after a client has connected.
+const REQUEST_TIMEOUT_MS: u64 = 5000;
+
+/// Request bodies are not used, but the ones up to this size are read, so clients are not cut off.
+const MAX_BODY_LENGTH: u64 = 64 * 1024;
+
+/// An idle event stream gets a comment this often, so a closed connection is noticed.
+const KEEP_ALIVE_MS: u64 = 15_000;
+
+/// Single page dashboard showing the status, the output of the running command and recent runs.
+const DASHBOARD: &str = include_str!("dashboard.html");
+
+/// Live events for the dashboard and other clients: `run_started`, `output` and `run_finished`.
+/// They are delivered as server-sent events, or as WebSocket messages.
+#[derive(Clone, Default)]
+pub struct EventStream {
+    clients: Arc<Mutex<Vec<Sender<Event>>>>
+}
+
+/// An event with its name and data, e.g. `output` with `{"text": "..."}`.
+pub type Event = (String, Value);
+
+impl EventStream {
+    /// Send the event to every connected client, and forget the disconnected ones.
+    pub fn send(&self, event: &str, data: &Value) {
+        let mut clients = self.clients.lock().unwrap();
+        if clients.is_empty() { return; }
+        let event = (event.to_string(), data.clone());
+        clients.retain(|client| client.send(event.clone()).is_ok());
+    }
+
+    /// Receive the events sent from now on.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (tx, rx) = channel();
+        self.clients.lock().unwrap().push(tx);
+        rx
+    }
+}
+
+/// Request line and the headers the server cares about.
+struct Request {
+    method: String,
+    /// Path without the query string
+    path: String,
+    /// `Sec-WebSocket-Key` header of a WebSocket handshake
+    websocket_key: Option<String>
+}
+
+/// A response: status line, content type and body.
+#[derive(Debug, PartialEq)]
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String
+}
+
+impl Response {
+    fn json(status: &'static str, value: &Value) -> Self {
+        Self { status, content_type: "application/json", body: value.to_string() }
+    }
+}
+
+/// Start the HTTP server on `address` in a separate thread. `GET /status` returns the state
+/// of the latest run with its report, `GET /runs` the recent runs, `POST /run` calls `on_run`,
+/// and `GET /` serves the dashboard, which is updated live through `GET /events`.
+/// The same events are sent as JSON messages to WebSocket clients of `GET /ws`.
+pub fn listen<F>(address: &str, latest: Arc<Mutex<LatestRun>>, events: EventStream, on_run: F) -> Result<()>
+    where F: Fn() + Send + Sync + 'static
+{
+    let listener = TcpListener::bind(address).chain_err(|| ErrorKind::ServerFailed(address.to_string()))?;
+    let on_run = Arc::new(on_run);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue
+            };
+            let latest = latest.clone();
+            let events = events.clone();
+            let on_run = on_run.clone();
+            thread::spawn(move || serve(stream, &latest, &events, &*on_run));
+        }
+    });
+    Ok(())
+}
+
+fn serve(mut stream: TcpStream, latest: &Mutex<LatestRun>, events: &EventStream, on_run: &dyn Fn()) {
+    let _ = stream.set_read_timeout(Some(Duration::from_millis(REQUEST_TIMEOUT_MS)));
+    let request = match read_request(&stream) {
+        Some(request) => request,
+        None => return
+    };
+    if request.method == "GET" && request.path == "/events" {
+        stream_events(stream, events.subscribe());
+        return;
+    }
+    if request.method == "GET" && request.path == "/ws" {
+        if let Some(ref key) = request.websocket_key {
+            websocket::serve(stream, key, events.subscribe());
+            return;
+        }
+    }
+    let response = route(&request.method, &request.path, &latest.lock().unwrap());
+    if response.status.starts_with("202") {
+        on_run();
+    }
+    let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   response.status, response.content_type, response.body.len(), response.body);
+}
+
+/// Keep writing events to the client until it disconnects.
+fn stream_events(mut stream: TcpStream, events: Receiver<Event>) {
+    if write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n").is_err() {
+        return;
+    }
+    loop {
+        let message = match events.recv_timeout(Duration::from_millis(KEEP_ALIVE_MS)) {
+            Ok((event, data)) => format!("event: {}\ndata: {}\n\n", event, data),
+            Err(RecvTimeoutError::Timeout) => ": keep-alive\n\n".to_string(),
+            Err(RecvTimeoutError::Disconnected) => return
+        };
+        if stream.write_all(message.as_bytes()).and_then(|_| stream.flush()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Read the request line and the headers, and skip the body.
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?;
+    let path = target.split('?').next().unwrap_or(target).to_string();
+
+    let mut content_length = 0;
+    let mut websocket_key = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 { break; }
+        let header = header.trim_end();
+        if header.is_empty() { break; }
+        let mut pair = header.splitn(2, ':');
+        let name = pair.next().unwrap_or("");
+        let value = pair.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().unwrap_or(0);
+        } else if name.eq_ignore_ascii_case("sec-websocket-key") {
+            websocket_key = Some(value.to_string());
+        }
+    }
+    let _ = reader.take(content_length.min(MAX_BODY_LENGTH)).read_to_end(&mut vec![]);
+    Some(Request { method, path, websocket_key })
+}
+
+fn route(method: &str, path: &str, latest: &LatestRun) -> Response {
+    match (method, path) {
+        ("GET", "/status") => Response::json("200 OK", &json!({
+            "status": latest.status,
+            "report": latest.report.as_ref().map(events::report_json)
+        })),
+        ("GET", "/runs") => Response::json("200 OK", &serde_json::to_value(&latest.recent).expect("runs are always serializable")),
+        ("POST", "/run") => Response::json("202 Accepted", &json!({ "ok": true })),
+        ("GET", "/") => Response { status: "200 OK", content_type: "text/html; charset=utf-8", body: DASHBOARD.to_string() },
+        ("GET", "/metrics") => Response { status: "200 OK", content_type: "text/plain; version=0.0.4", body: metrics(latest) },
+        (_, "/status") | (_, "/runs") | (_, "/run") | (_, "/") | (_, "/metrics") => Response::json("405 Method Not Allowed", &json!({ "error": "method not allowed" })),
+        _ => Response::json("404 Not Found", &json!({ "error": "not found" }))
+    }
+}
+
+/// Metrics in the Prometheus text format. Gauges of the latest run are omitted until it's known.
+fn metrics(latest: &LatestRun) -> String {
+    let report = latest.report.as_ref();
+    let counts = report.and_then(|report| report.counts);
+    let duration = report.and_then(|report| report.duration)
+        .map(|duration| duration.as_secs() as f64 + f64::from(duration.subsec_millis()) / 1000.0);
+    let metrics: Vec<(&str, &str, &str, Option<f64>)> = vec![
+        ("testify_runs_total", "counter", "Test runs finished since testify has started.", Some(latest.runs as f64)),
+        ("testify_failures_total", "counter", "Test runs which did not succeed.", Some(latest.failures as f64)),
+        ("testify_last_run_duration_seconds", "gauge", "Duration of the latest run.", duration),
+        ("testify_tests_passed", "gauge", "Tests passed in the latest run.", counts.map(|counts| counts.passed as f64)),
+        ("testify_tests_failed", "gauge", "Tests failed in the latest run.", counts.map(|counts| counts.failed as f64))
+    ];
+    let mut text = String::new();
+    for (name, kind, help, value) in metrics {
+        if let Some(value) = value {
+            text.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n{} {}\n", name, help, name, kind, name, value));
+        }
+    }
+    text
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use history::Entry;
+    use report::{Outcome, Report, TestCounts};
+
+    #[test]
+    fn test_route() {
+        let mut latest = LatestRun::default();
+        let response = route("GET", "/status", &latest);
+        assert_eq!(response.status, "200 OK");
+        assert_eq!(response.body, r#"{"report":null,"status":null}"#);
+
+        latest.report = Some(Report::new(Outcome::TestsPassed, None));
+        let response = route("GET", "/status", &latest);
+        assert!(response.body.contains(r#""outcome":"passed""#));
+
+        assert_eq!(route("POST", "/run", &latest).status, "202 Accepted");
+        assert_eq!(route("GET", "/run", &latest).status, "405 Method Not Allowed");
+        assert_eq!(route("GET", "/runs", &latest).body, "[]");
+        assert_eq!(route("GET", "/", &latest).content_type, "text/html; charset=utf-8");
+        assert_eq!(route("GET", "/favicon.ico", &latest).status, "404 Not Found");
+    }
+
+    #[test]
+    fn test_metrics() {
+        let mut latest = LatestRun::default();
+        assert_eq!(metrics(&latest).lines().filter(|line| !line.starts_with('#')).collect::<Vec<_>>(),
+                   vec!["testify_runs_total 0", "testify_failures_total 0"]);
+
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.counts = Some(TestCounts { passed: 41, failed: 1, ignored: 0 });
+        report.duration = Some(Duration::from_millis(1500));
+        let entry = Entry::new(&report, None);
+        latest.finish(report, entry);
+        let text = metrics(&latest);
+        assert!(text.contains("# TYPE testify_runs_total counter\ntestify_runs_total 1\n"));
+        assert!(text.contains("testify_failures_total 1\n"));
+        assert!(text.contains("testify_last_run_duration_seconds 1.5\n"));
+        assert!(text.contains("testify_tests_passed 41\n"));
+        assert!(text.contains("testify_tests_failed 1\n"));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use ignore::WalkBuilder;
+
+/// Directories which never contain snapshots of the project.
+const SKIPPED_DIRS: &[&str] = &["target", ".git", ".testify"];
+
+/// Snapshots of insta which have changed and wait to be reviewed, relative to the project root:
+/// `.snap.new` files next to the `.snap` ones, and `.pending-snap` files of inline snapshots.
+/// They are often ignored by git, so the ignore files are not respected.
+pub fn pending(project_dir: &Path) -> Vec<String> {
+    let walker = WalkBuilder::new(project_dir)
+        .standard_filters(false)
+        .filter_entry(|entry| !SKIPPED_DIRS.iter().any(|dir| entry.file_name() == *dir))
+        .build();
+    let mut snapshots: Vec<String> = walker
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+        .map(|entry| entry.into_path())
+        .filter(|path| is_pending(path))
+        .map(|path| path.strip_prefix(project_dir).unwrap_or(&path).to_string_lossy().into_owned())
+        .collect();
+    snapshots.sort();
+    snapshots
+}
+
+/// File a pending snapshot is meant for, which `cargo insta` selects snapshots by:
+/// the `.snap` file, or the source file of inline snapshots.
+fn target(pending: &str) -> PathBuf {
+    let path = Path::new(pending);
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = if name.ends_with(".pending-snap") {
+        name.trim_start_matches('.').trim_end_matches(".pending-snap").to_string()
+    } else {
+        name.trim_end_matches(".new").to_string()
+    };
+    path.with_file_name(name)
+}
+
+fn is_pending(path: &Path) -> bool {
+    let name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    name.ends_with(".snap.new") || name.ends_with(".pending-snap")
+}
+
+/// Accept the pending `snapshots` with `cargo insta`, or reject them.
+pub fn review(project_dir: &Path, snapshots: &[String], accept: bool) -> io::Result<ExitStatus> {
+    let mut command = Command::new("cargo");
+    command.args(["insta", if accept { "accept" } else { "reject" }]);
+    for snapshot in snapshots {
+        command.arg("--snapshot").arg(project_dir.join(target(snapshot)));
+    }
+    command.current_dir(project_dir).status()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_pending() {
+        let dir = env::temp_dir().join("testify-snapshots-test");
+        let _ = fs::remove_dir_all(&dir);
+        for file in &["src/snapshots/app__parse.snap", "src/snapshots/app__parse.snap.new", "src/.lib.rs.pending-snap", "target/app__old.snap.new"] {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "").unwrap();
+        }
+        assert_eq!(pending(&dir), vec!["src/.lib.rs.pending-snap", "src/snapshots/app__parse.snap.new"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_target() {
+        assert_eq!(target("src/snapshots/app__parse.snap.new"), PathBuf::from("src/snapshots/app__parse.snap"));
+        assert_eq!(target("src/.lib.rs.pending-snap"), PathBuf::from("src/lib.rs"));
+    }
+}
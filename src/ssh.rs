@@ -0,0 +1,98 @@
+use std::io;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+use config::Ssh;
+
+/// Paths never copied to the remote host: build output, the repository and testify's own files.
+/// Files ignored by git are skipped as well.
+const RSYNC_EXCLUDES: &[&str] = &["/target", "/.git", "/.testify"];
+
+/// Command line running `argv` with `env` on the remote host, in the remote project directory.
+/// With `pty` a terminal is allocated for it, so it prints colors and is hung up on when killed.
+pub fn command_line(ssh: &Ssh, project_dir: &Path, argv: &[String], env: &[(&str, &str)], pty: bool) -> Vec<String> {
+    let mut script = format!("cd {} && exec", cd_target(&remote_dir(ssh, project_dir)));
+    if !env.is_empty() {
+        script.push_str(" env");
+        for &(name, value) in env {
+            script.push_str(&format!(" {}={}", name, quote(value)));
+        }
+    }
+    for arg in argv {
+        script.push(' ');
+        script.push_str(&quote(arg));
+    }
+
+    let mut command_line = vec!["ssh".to_string()];
+    if pty {
+        command_line.push("-t".to_string());
+    }
+    command_line.push(ssh.host.clone().unwrap_or_default());
+    command_line.push(script);
+    command_line
+}
+
+/// Mirror the working tree into the remote project directory with rsync. Files removed locally
+/// are removed there too, while its `target` directory is kept, so builds stay incremental.
+pub fn sync(ssh: &Ssh, project_dir: &Path) -> io::Result<ExitStatus> {
+    let mut command = Command::new("rsync");
+    command.arg("-az").arg("--delete");
+    for exclude in RSYNC_EXCLUDES {
+        command.arg(format!("--exclude={}", exclude));
+    }
+    command.arg("--filter=:- .gitignore")
+        .arg("./")
+        .arg(format!("{}:{}/", ssh.host.as_deref().unwrap_or_default(), remote_dir(ssh, project_dir)))
+        .current_dir(project_dir)
+        .stdout(Stdio::null())
+        .status()
+}
+
+/// Configured remote directory, or the name of the project directory in the remote home.
+pub fn remote_dir(ssh: &Ssh, project_dir: &Path) -> String {
+    match ssh.dir {
+        Some(ref dir) => dir.clone(),
+        None => project_dir.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| ".".to_string())
+    }
+}
+
+/// Quoted directory, with a leading `~/` left for the remote shell to expand.
+fn cd_target(dir: &str) -> String {
+    match dir.strip_prefix("~/") {
+        Some(relative) => format!("~/{}", quote(relative)),
+        None => quote(dir)
+    }
+}
+
+/// Quote an argument for the POSIX shell of the remote host.
+pub fn quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_=+./:,@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_command_line() {
+        let ssh = Ssh { host: Some("build@box".to_string()), dir: None, rsync: None };
+        let argv = strings(&["cargo", "test", "--", "it's", "tests::"]);
+        assert_eq!(command_line(&ssh, Path::new("/home/me/app"), &argv, &[], false),
+                   strings(&["ssh", "build@box", r"cd app && exec cargo test -- 'it'\''s' tests::"]));
+
+        let ssh = Ssh { dir: Some("~/src/my app".to_string()), ..ssh };
+        let argv = strings(&["cargo", "nextest", "run"]);
+        assert_eq!(command_line(&ssh, Path::new("/home/me/app"), &argv, &[("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1")], true),
+                   strings(&["ssh", "-t", "build@box", "cd ~/'src/my app' && exec env NEXTEST_EXPERIMENTAL_LIBTEST_JSON=1 cargo nextest run"]));
+    }
+}
@@ -0,0 +1,183 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json;
+
+use report::Report;
+use errors::*;
+
+/// Statistics file, relative to the project root. It keeps per-test results over all recorded runs.
+pub const STATS_FILE: &str = ".testify/stats.json";
+
+/// Recorded results of a single test.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestStats {
+    pub runs: usize,
+    /// Runs in which the test has failed, including the ones it has passed on a retry
+    pub failures: usize,
+    /// Runs in which the test has passed only on a retry
+    pub flaky: usize,
+    /// How many times the result has changed from the previous run
+    pub flips: usize,
+    pub last_passed: bool,
+    /// Sum of the durations of the runs the duration is known of
+    pub total_ms: u64,
+    pub timed_runs: usize
+}
+
+impl TestStats {
+    /// Share of runs in which the test has changed its result or passed only on a retry, from 0 to 1.
+    /// A test which always fails is broken rather than intermittent, and scores 0.
+    pub fn intermittency(&self) -> f64 {
+        if self.runs == 0 { return 0.0; }
+        ((self.flips + self.flaky) as f64 / self.runs as f64).min(1.0)
+    }
+
+    pub fn average_duration(&self) -> Option<Duration> {
+        if self.timed_runs == 0 { return None; }
+        Some(Duration::from_millis(self.total_ms / self.timed_runs as u64))
+    }
+}
+
+/// Per-test results of all recorded runs, by test name.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub tests: BTreeMap<String, TestStats>
+}
+
+impl Stats {
+    /// Add results of the tests which have run in `report`.
+    pub fn add(&mut self, report: &Report) {
+        for result in &report.results {
+            let flaky = report.flaky.contains(&result.name);
+            let passed = result.passed || flaky;
+            let stats = self.tests.entry(result.name.clone()).or_default();
+            if stats.runs > 0 && stats.last_passed != passed {
+                stats.flips += 1;
+            }
+            stats.runs += 1;
+            if !result.passed {
+                stats.failures += 1;
+            }
+            if flaky {
+                stats.flaky += 1;
+            }
+            stats.last_passed = passed;
+        }
+        for timing in &report.timings {
+            if let Some(stats) = self.tests.get_mut(&timing.name) {
+                stats.total_ms += as_millis(timing.duration);
+                stats.timed_runs += 1;
+            }
+        }
+    }
+
+    /// Tests which have ever failed, the most intermittent ones first, slower ones first among equals.
+    pub fn ranking(&self) -> Vec<(&String, &TestStats)> {
+        let mut ranking: Vec<(&String, &TestStats)> = self.tests.iter()
+            .filter(|&(_, stats)| stats.failures > 0)
+            .collect();
+        ranking.sort_by(|&(_, a), &(_, b)| {
+            b.intermittency().partial_cmp(&a.intermittency()).unwrap_or(Ordering::Equal)
+                .then_with(|| b.average_duration().cmp(&a.average_duration()))
+        });
+        ranking
+    }
+}
+
+/// Read the statistics of the project, empty if nothing has been recorded yet.
+pub fn load(project_dir: &Path) -> Result<Stats> {
+    let path = project_dir.join(STATS_FILE);
+    if !path.is_file() { return Ok(Stats::default()); }
+
+    let file = File::open(&path).chain_err(|| ErrorKind::StatsFile(path.clone()))?;
+    serde_json::from_reader(file).chain_err(|| ErrorKind::StatsFile(path.clone()))
+}
+
+/// Add results of the tests in `report` to the statistics file of the project.
+pub fn record(project_dir: &Path, report: &Report) -> Result<()> {
+    if report.results.is_empty() { return Ok(()); }
+
+    let mut stats = load(project_dir)?;
+    stats.add(report);
+    let path = project_dir.join(STATS_FILE);
+    let temp_path = path.with_extension("json.tmp");
+    let write = || -> ::std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&temp_path)?;
+        let content = serde_json::to_string_pretty(&stats).expect("statistics are always serializable");
+        writeln!(file, "{}", content)?;
+        drop(file);
+        fs::rename(&temp_path, &path)
+    };
+    write().chain_err(|| ErrorKind::StatsFile(path.clone()))
+}
+
+/// Print the `limit` most intermittent tests.
+pub fn print(project_dir: &Path, limit: usize) -> Result<()> {
+    let stats = load(project_dir)?;
+    let ranking = stats.ranking();
+    if ranking.is_empty() {
+        println!("No failed tests among {} recorded in {}", stats.tests.len(), project_dir.join(STATS_FILE).display());
+        return Ok(());
+    }
+
+    println!("{:>13}  {:>5}  {:>8}  {:>5}  {:>8}  Test", "Intermittency", "Runs", "Failures", "Flaky", "Duration");
+    for &(name, test) in ranking.iter().take(limit) {
+        println!("{}", format_test(name, test));
+    }
+    Ok(())
+}
+
+fn format_test(name: &str, test: &TestStats) -> String {
+    let duration = match test.average_duration() {
+        Some(duration) => format!("{}.{}s", duration.as_secs(), duration.subsec_millis() / 100),
+        None => "-".to_string()
+    };
+    let intermittency = format!("{:.0}%", test.intermittency() * 100.0);
+    format!("{:>13}  {:>5}  {:>8}  {:>5}  {:>8}  {}", intermittency, test.runs, test.failures, test.flaky, duration, name)
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use report::{Outcome, TestResult, TestTiming};
+
+    fn report(results: &[(&str, bool)], flaky: &[&str]) -> Report {
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.results = results.iter()
+            .map(|&(name, passed)| TestResult { name: name.to_string(), passed })
+            .collect();
+        report.flaky = flaky.iter().map(|name| name.to_string()).collect();
+        report
+    }
+
+    #[test]
+    fn test_ranking() {
+        let mut stats = Stats::default();
+        stats.add(&report(&[("stable", true), ("broken", false), ("flip", true), ("retried", false)], &["retried"]));
+        stats.add(&report(&[("stable", true), ("broken", false), ("flip", false), ("retried", true)], &[]));
+        let mut last = report(&[("stable", true), ("broken", false), ("flip", true), ("retried", true)], &[]);
+        last.timings.push(TestTiming { name: "broken".to_string(), duration: Duration::from_millis(300) });
+        stats.add(&last);
+
+        let flip = &stats.tests["flip"];
+        assert_eq!((flip.runs, flip.failures, flip.flips), (3, 1, 2));
+        assert_eq!(stats.tests["retried"].flaky, 1);
+        assert_eq!(stats.tests["broken"].average_duration(), Some(Duration::from_millis(300)));
+
+        let names: Vec<&str> = stats.ranking().iter().map(|&(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["flip", "retried", "broken"]);
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Local;
+use serde_json;
+
+use history::Entry;
+use report::Report;
+use errors::*;
+
+/// Status file, relative to the project root. It always holds the state of the latest run,
+/// for shell prompts and status bars.
+pub const STATUS_FILE: &str = ".testify/status.json";
+
+/// Number of recent runs kept in memory for the dashboard.
+const RECENT_RUNS: usize = 20;
+
+/// State of the latest run, as written to the status file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Status {
+    /// `running` while the tests run, afterwards the outcome: `passed`, `failed`, ...
+    pub state: String,
+    /// Whether the latest finished run has succeeded, `None` while running
+    pub success: Option<bool>,
+    pub passed: Option<usize>,
+    pub failed: Option<usize>,
+    pub ignored: Option<usize>,
+    pub duration_ms: Option<u64>,
+    /// Local time of the last change of the state, RFC 3339
+    pub timestamp: String
+}
+
+impl Status {
+    pub fn running() -> Self {
+        Self {
+            state: "running".to_string(),
+            success: None,
+            passed: None,
+            failed: None,
+            ignored: None,
+            duration_ms: None,
+            timestamp: Local::now().to_rfc3339()
+        }
+    }
+
+    pub fn finished(report: &Report) -> Self {
+        Self {
+            state: report.outcome.name().to_string(),
+            success: Some(report.outcome.is_success()),
+            passed: report.counts.map(|counts| counts.passed),
+            failed: report.counts.map(|counts| counts.failed),
+            ignored: report.counts.map(|counts| counts.ignored),
+            duration_ms: report.duration.map(|duration| duration.as_secs() * 1000 + u64::from(duration.subsec_millis())),
+            timestamp: Local::now().to_rfc3339()
+        }
+    }
+}
+
+/// The latest run as known to the daemon and the HTTP server, updated by the reactor.
+#[derive(Debug, Default)]
+pub struct LatestRun {
+    /// `None` before the first run has started
+    pub status: Option<Status>,
+    /// Report of the latest finished run
+    pub report: Option<Report>,
+    /// Recently finished runs, the latest first
+    pub recent: Vec<Entry>,
+    /// Number of runs finished since testify has started
+    pub runs: u64,
+    /// Number of those runs which did not succeed
+    pub failures: u64
+}
+
+impl LatestRun {
+    /// Remember a finished run.
+    pub fn finish(&mut self, report: Report, entry: Entry) {
+        self.runs += 1;
+        if !report.outcome.is_success() {
+            self.failures += 1;
+        }
+        self.report = Some(report);
+        self.recent.insert(0, entry);
+        self.recent.truncate(RECENT_RUNS);
+    }
+}
+
+/// Replace the status file of the project. The new content is written to a temporary file
+/// first and then renamed, so readers never see a half written file.
+pub fn write(project_dir: &Path, status: &Status) -> Result<()> {
+    let path = project_dir.join(STATUS_FILE);
+    let temp_path = path.with_extension("json.tmp");
+    let write = || -> ::std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = fs::File::create(&temp_path)?;
+        let content = serde_json::to_string(status).expect("status is always serializable");
+        writeln!(file, "{}", content)?;
+        drop(file);
+        fs::rename(&temp_path, &path)
+    };
+    write().chain_err(|| ErrorKind::StatusFile(path.clone()))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use report::{Outcome, TestCounts};
+
+    #[test]
+    fn test_finished() {
+        let mut report = Report::new(Outcome::TestsFailed, None);
+        report.counts = Some(TestCounts { passed: 3, failed: 1, ignored: 2 });
+        report.duration = Some(Duration::from_millis(1250));
+
+        let status = Status::finished(&report);
+        assert_eq!(status.state, "failed");
+        assert_eq!(status.success, Some(false));
+        assert_eq!(status.passed, Some(3));
+        assert_eq!(status.failed, Some(1));
+        assert_eq!(status.ignored, Some(2));
+        assert_eq!(status.duration_ms, Some(1250));
+    }
+}
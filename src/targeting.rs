@@ -0,0 +1,97 @@
+use std::path::{Component, Path};
+
+use ignore::overrides::OverrideBuilder;
+
+use config::{CommandMapping, TestMapping};
+
+/// Filter which selects the tests related to the changed `path` (relative to the project root).
+/// The first rule of `test_map` matching the path wins. Otherwise, for a source file the module
+/// is guessed from the path, e.g. `src/parser/mod.rs` gives `parser::`.
+/// `None` means the tests can not be narrowed down, e.g. for `src/lib.rs` or `Cargo.toml`.
+pub fn filter_for(project_dir: &Path, test_map: &[TestMapping], path: &Path) -> Option<String> {
+    test_map.iter()
+        .find(|mapping| matches(project_dir, &mapping.path, path, "test_map"))
+        .map(|mapping| mapping.filter.clone())
+        .or_else(|| module_filter(path))
+}
+
+/// Command of the first rule of `command_map` matching the changed `path` (relative to the project root),
+/// `None` if the configured test command should run.
+pub fn command_for(project_dir: &Path, command_map: &[CommandMapping], path: &Path) -> Option<String> {
+    command_map.iter()
+        .find(|mapping| matches(project_dir, &mapping.path, path, "command_map"))
+        .map(|mapping| mapping.command.clone())
+}
+
+/// Does `glob` of the `setting` match `path`? An invalid glob matches nothing.
+fn matches(project_dir: &Path, glob: &str, path: &Path, setting: &str) -> bool {
+    let mut builder = OverrideBuilder::new(project_dir);
+    match builder.add(glob).and_then(|builder| builder.build()) {
+        Ok(matcher) => matcher.matched(path, false).is_whitelist(),
+        Err(err) => {
+            eprintln!("Warning: invalid glob {:?} in {}: {}", glob, setting, err);
+            false
+        }
+    }
+}
+
+/// Module path of a source file, as it's prefixed to the names of the tests within it.
+fn module_filter(path: &Path) -> Option<String> {
+    let relative = path.strip_prefix("src").ok()?;
+    if relative.extension()? != "rs" {
+        return None;
+    }
+    let mut modules = vec![];
+    for component in relative.with_extension("").components() {
+        match component {
+            Component::Normal(name) => modules.push(name.to_str()?.to_string()),
+            _ => return None
+        }
+    }
+    if modules.last().map(String::as_str) == Some("mod") {
+        modules.pop();
+    }
+    // Crate roots contain tests of any module, and binaries are separate crates
+    match modules.first().map(String::as_str) {
+        None | Some("lib") | Some("main") | Some("bin") => return None,
+        _ => {}
+    }
+    Some(format!("{}::", modules.join("::")))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_for() {
+        let project_dir = Path::new("/project");
+        let test_map = vec![TestMapping { path: "src/storage/**".to_string(), filter: "db::".to_string() }];
+        let filter = |path: &str| filter_for(project_dir, &test_map, Path::new(path));
+
+        assert_eq!(filter("src/storage/postgres.rs"), Some("db::".to_string()));
+        assert_eq!(filter("src/parser/mod.rs"), Some("parser::".to_string()));
+        assert_eq!(filter("src/parser/lexer.rs"), Some("parser::lexer::".to_string()));
+        assert_eq!(filter("src/report.rs"), Some("report::".to_string()));
+        assert_eq!(filter("src/lib.rs"), None);
+        assert_eq!(filter("src/bin/tool.rs"), None);
+        assert_eq!(filter("tests/api.rs"), None);
+        assert_eq!(filter("Cargo.toml"), None);
+    }
+
+    #[test]
+    fn test_command_for() {
+        let project_dir = Path::new("/project");
+        let command_map = vec![
+            CommandMapping { path: "docs/**".to_string(), command: "cargo doc --no-deps".to_string() },
+            CommandMapping { path: "benches/**".to_string(), command: "cargo bench".to_string() },
+            CommandMapping { path: "benches/fixtures/**".to_string(), command: "never reached".to_string() }
+        ];
+        let command = |path: &str| command_for(project_dir, &command_map, Path::new(path));
+
+        assert_eq!(command("docs/guide/intro.md"), Some("cargo doc --no-deps".to_string()));
+        assert_eq!(command("benches/fixtures/input.json"), Some("cargo bench".to_string()));
+        assert_eq!(command("src/lib.rs"), None);
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::{self, Stdout};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::ExecutableCommand;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+
+use keyboard::Key;
+use server;
+
+mod state;
+mod view;
+
+use self::state::{Input, State};
+pub use self::state::Request;
+
+/// How long to wait for a key before new events are drawn.
+const TICK: Duration = Duration::from_millis(100);
+
+/// The full-screen UI, drawn by a thread of its own. The terminal is given back on drop.
+pub struct Ui {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    stderr: Option<StderrRedirect>
+}
+
+/// Switch to the alternate screen to show the `events` of the reactor, and call `on_request`
+/// for the keys the reactor has to act on.
+pub fn start<F>(events: Receiver<server::Event>, on_request: F) -> io::Result<Ui>
+    where F: Fn(Request) + Send + 'static
+{
+    let mut terminal = match enter() {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            leave();
+            return Err(err);
+        }
+    };
+    // Warnings printed to stderr would scramble the screen, so they go to the status bar instead
+    let (warnings_tx, warnings) = channel();
+    let stderr = StderrRedirect::start(move |line| { let _ = warnings_tx.send(line); });
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let thread = thread::spawn(move || {
+        let mut state = State::default();
+        while !thread_stop.load(Ordering::SeqCst) {
+            for (name, data) in events.try_iter() {
+                state.apply(&name, &data);
+            }
+            if let Some(warning) = warnings.try_iter().last() {
+                state.message = Some(warning);
+            }
+            let _ = terminal.draw(|frame| view::draw(frame, &state));
+            if !event::poll(TICK).unwrap_or(false) { continue; }
+            if let Ok(Event::Key(key)) = event::read() {
+                if key.kind != KeyEventKind::Press { continue; }
+                // There is no SIGINT in raw mode
+                if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+                    on_request(Request::Key(Key::Quit));
+                    continue;
+                }
+                if let Some(request) = input(key.code).and_then(|input| state.input(input)) {
+                    on_request(request);
+                }
+            }
+        }
+        let _ = terminal.show_cursor();
+    });
+    Ok(Ui { stop, thread: Some(thread), stderr })
+}
+
+impl Drop for Ui {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.stderr.take();
+        leave();
+    }
+}
+
+fn enter() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
+    terminal::enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    Terminal::new(CrosstermBackend::new(io::stdout()))
+}
+
+/// Restore the terminal, as much as it has been changed.
+fn leave() {
+    let _ = io::stdout().execute(LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+fn input(code: KeyCode) -> Option<Input> {
+    match code {
+        KeyCode::Char(c) => Some(Input::Char(c)),
+        KeyCode::Enter => Some(Input::Enter),
+        KeyCode::Esc => Some(Input::Escape),
+        KeyCode::Backspace => Some(Input::Backspace),
+        KeyCode::Tab => Some(Input::Tab),
+        KeyCode::Up => Some(Input::Up),
+        KeyCode::Down => Some(Input::Down),
+        KeyCode::PageUp => Some(Input::PageUp),
+        KeyCode::PageDown => Some(Input::PageDown),
+        KeyCode::Home => Some(Input::Home),
+        KeyCode::End => Some(Input::End),
+        _ => None
+    }
+}
+
+/// Sends what is written to stderr, including by child processes, line by line to a callback,
+/// until it's dropped.
+#[cfg(not(target_os = "windows"))]
+struct StderrRedirect {
+    /// Duplicate of the original stderr, to put it back
+    original: ::libc::c_int
+}
+
+#[cfg(not(target_os = "windows"))]
+impl StderrRedirect {
+    fn start<F>(on_line: F) -> Option<Self>
+        where F: Fn(String) + Send + 'static
+    {
+        use libc;
+        use std::fs::File;
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::io::FromRawFd;
+
+        unsafe {
+            let mut pipe = [0; 2];
+            if libc::pipe(pipe.as_mut_ptr()) != 0 { return None; }
+            let original = libc::dup(libc::STDERR_FILENO);
+            if original < 0 || libc::dup2(pipe[1], libc::STDERR_FILENO) < 0 {
+                libc::close(pipe[0]);
+                libc::close(pipe[1]);
+                return None;
+            }
+            libc::close(pipe[1]);
+            let reader = BufReader::new(File::from_raw_fd(pipe[0]));
+            // Ends once stderr is restored and the write end of the pipe is closed
+            thread::spawn(move || {
+                for line in reader.lines() {
+                    match line {
+                        Ok(line) => on_line(line),
+                        Err(_) => break
+                    }
+                }
+            });
+            Some(Self { original })
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Drop for StderrRedirect {
+    fn drop(&mut self) {
+        unsafe {
+            ::libc::dup2(self.original, ::libc::STDERR_FILENO);
+            ::libc::close(self.original);
+        }
+    }
+}
+
+/// Console output can not be redirected as easily on Windows, warnings are printed over the UI there.
+#[cfg(target_os = "windows")]
+struct StderrRedirect;
+
+#[cfg(target_os = "windows")]
+impl StderrRedirect {
+    fn start<F>(_on_line: F) -> Option<Self>
+        where F: Fn(String) + Send + 'static
+    {
+        None
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use serde_json::{self, Value};
+
+use history::Entry;
+use keyboard::Key;
+use picker;
+
+/// Older lines of the output are dropped, the full output is in `.testify/output.log` anyway.
+const MAX_LINES: usize = 10_000;
+
+/// Lines scrolled by Page Up and Page Down.
+const PAGE: usize = 20;
+
+/// A key pressed in the UI, independent of the terminal library.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Char(char),
+    Enter,
+    Escape,
+    Backspace,
+    Tab,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End
+}
+
+/// What the UI asks the reactor for.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    Key(Key),
+    /// The names of the tests, to open the picker with
+    ListTests,
+    /// Run only these tests from now on
+    Pick(Vec<String>)
+}
+
+/// Tests to pick from, narrowed down by a fuzzy query.
+#[derive(Debug, Default)]
+pub struct Picker {
+    names: Vec<String>,
+    pub query: String,
+    /// Position of the highlighted match
+    pub cursor: usize,
+    pub chosen: BTreeSet<String>
+}
+
+impl Picker {
+    pub fn matches(&self) -> Vec<&String> {
+        picker::search(&self.query, &self.names)
+    }
+
+    /// Handle a key, and set `done` once the picker is to be closed.
+    /// Returns the picked tests, unless picking has been cancelled.
+    fn input(&mut self, input: Input, done: &mut bool) -> Option<Vec<String>> {
+        match input {
+            Input::Char(c) => {
+                self.query.push(c);
+                self.cursor = 0;
+            },
+            Input::Backspace => {
+                self.query.pop();
+                self.cursor = 0;
+            },
+            Input::Up => self.cursor = self.cursor.saturating_sub(1),
+            Input::Down => self.cursor = (self.cursor + 1).min(self.matches().len().saturating_sub(1)),
+            Input::Tab => {
+                if let Some(name) = self.matches().get(self.cursor).map(|name| name.to_string()) {
+                    if !self.chosen.remove(&name) {
+                        self.chosen.insert(name);
+                    }
+                }
+            },
+            Input::Enter => {
+                // Without any test chosen, the highlighted one is meant
+                if self.chosen.is_empty() {
+                    let highlighted = self.matches().get(self.cursor).map(|name| name.to_string());
+                    self.chosen.extend(highlighted);
+                }
+                *done = true;
+                if !self.chosen.is_empty() {
+                    return Some(self.chosen.iter().cloned().collect());
+                }
+            },
+            Input::Escape => *done = true,
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Everything the UI shows, built from the events of the reactor and the keys pressed.
+#[derive(Debug, Default)]
+pub struct State {
+    /// Output of the current run
+    pub lines: VecDeque<String>,
+    /// The last line is still incomplete
+    partial: bool,
+    /// Number of the current or the latest run
+    pub run: Option<u64>,
+    pub running: bool,
+    /// Title of the latest report, e.g. `Tests failed`
+    pub title: Option<String>,
+    pub success: Option<bool>,
+    /// Names of the tests which have failed in the latest run
+    pub failures: Vec<String>,
+    /// Recent runs, newest first
+    pub runs: Vec<Entry>,
+    /// Latest message of the reactor during the current run, e.g. that watching is paused
+    pub message: Option<String>,
+    /// Only lines of the output containing the filter are shown
+    pub filter: String,
+    /// The filter is being typed
+    pub editing_filter: bool,
+    /// Lines scrolled up from the end of the output, 0 follows new output
+    pub scroll: usize,
+    pub paused: bool,
+    pub muted: bool,
+    /// Tests the runs are restricted to
+    pub picked: Vec<String>,
+    /// Failing test which runs alone until it passes
+    pub followed: Option<String>,
+    /// Open while tests are being picked
+    pub picker: Option<Picker>
+}
+
+impl State {
+    /// Update the state with an event of the reactor's event stream.
+    pub fn apply(&mut self, event: &str, data: &Value) {
+        match event {
+            "run_started" => {
+                self.lines.clear();
+                self.partial = false;
+                self.scroll = 0;
+                self.run = data["run"].as_u64();
+                self.running = true;
+                self.message = None;
+            },
+            "output" => {
+                if let Some(text) = data["text"].as_str() {
+                    self.push_output(text);
+                }
+            },
+            "run_finished" => {
+                let report = &data["report"];
+                self.running = false;
+                self.title = report["title"].as_str().map(String::from);
+                self.success = report["success"].as_bool();
+                self.failures = report["failures"].as_array().map(|failures| {
+                    failures.iter().filter_map(|failure| failure["name"].as_str().map(String::from)).collect()
+                }).unwrap_or_default();
+                self.runs = serde_json::from_value(data["runs"].clone()).unwrap_or_default();
+            },
+            "message" => self.message = data["text"].as_str().map(String::from),
+            "tests" => {
+                let names = serde_json::from_value(data["names"].clone()).unwrap_or_default();
+                let chosen = self.picked.iter().cloned().collect();
+                self.picker = Some(Picker { names, chosen, ..Picker::default() });
+            },
+            "picked" => self.picked = serde_json::from_value(data["names"].clone()).unwrap_or_default(),
+            "followed" => self.followed = data["name"].as_str().map(String::from),
+            _ => {}
+        }
+    }
+
+    /// Handle a key, and return what the reactor has to do about it.
+    pub fn input(&mut self, input: Input) -> Option<Request> {
+        if let Some(ref mut picker) = self.picker {
+            let mut done = false;
+            let picked = picker.input(input, &mut done);
+            if done {
+                self.picker = None;
+            }
+            return picked.map(Request::Pick);
+        }
+        if self.editing_filter {
+            match input {
+                Input::Char(c) => self.filter.push(c),
+                Input::Backspace => { self.filter.pop(); },
+                Input::Enter => self.editing_filter = false,
+                Input::Escape => {
+                    self.filter.clear();
+                    self.editing_filter = false;
+                },
+                _ => {}
+            }
+            self.scroll = 0;
+            return None;
+        }
+        match input {
+            Input::Char('r') => return Some(Request::Key(Key::Rerun)),
+            Input::Char('p') => {
+                self.paused = !self.paused;
+                return Some(Request::Key(Key::Pause));
+            },
+            Input::Char('n') => {
+                self.muted = !self.muted;
+                return Some(Request::Key(Key::ToggleNotifications));
+            },
+            Input::Char('t') => return Some(Request::ListTests),
+            Input::Char('a') => return Some(Request::Key(Key::AllTests)),
+            Input::Char('w') => return Some(Request::Key(Key::FullRun)),
+            Input::Char('f') => return Some(Request::Key(Key::Follow)),
+            Input::Char('s') => return Some(Request::Key(Key::AcceptSnapshots)),
+            Input::Char('x') => return Some(Request::Key(Key::RejectSnapshots)),
+            Input::Char('q') => return Some(Request::Key(Key::Quit)),
+            Input::Char('c') => {
+                self.lines.clear();
+                self.partial = false;
+                self.scroll = 0;
+            },
+            Input::Char('/') => self.editing_filter = true,
+            Input::Escape => self.filter.clear(),
+            Input::Up | Input::Char('k') => self.scroll_by(1),
+            Input::Down | Input::Char('j') => self.scroll = self.scroll.saturating_sub(1),
+            Input::PageUp => self.scroll_by(PAGE),
+            Input::PageDown => self.scroll = self.scroll.saturating_sub(PAGE),
+            Input::Home => self.scroll_by(MAX_LINES),
+            Input::End => self.scroll = 0,
+            _ => {}
+        }
+        None
+    }
+
+    /// Lines of the output matching the filter.
+    pub fn visible_lines(&self) -> Vec<&str> {
+        let filter = self.filter.to_lowercase();
+        self.lines.iter()
+            .map(String::as_str)
+            .filter(|line| filter.is_empty() || line.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    /// The last `height` lines of the output above the scroll position.
+    pub fn window(&self, height: usize) -> Vec<&str> {
+        let lines = self.visible_lines();
+        let end = lines.len() - self.scroll.min(lines.len().saturating_sub(height));
+        lines[end.saturating_sub(height)..end].to_vec()
+    }
+
+    fn scroll_by(&mut self, lines: usize) {
+        self.scroll = (self.scroll + lines).min(self.lines.len());
+    }
+
+    /// Append a chunk of output, which does not have to end with a complete line.
+    fn push_output(&mut self, text: &str) {
+        let count = self.lines.len();
+        for (index, chunk) in text.split('\n').enumerate() {
+            match self.lines.back_mut() {
+                Some(last) if index == 0 && self.partial => last.push_str(chunk),
+                _ => self.lines.push_back(chunk.to_string())
+            }
+            // A carriage return redraws the line, e.g. a progress bar
+            if let Some(last) = self.lines.back_mut() {
+                if let Some(position) = last.rfind('\r') {
+                    last.drain(..=position);
+                }
+            }
+        }
+        // A complete chunk leaves an empty line behind, which is the start of the next one
+        self.partial = !text.ends_with('\n');
+        if !self.partial {
+            self.lines.pop_back();
+        }
+        // Keep the same lines in view while scrolled up
+        if self.scroll > 0 {
+            self.scroll += self.lines.len() - count;
+        }
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.scroll = self.scroll.min(self.lines.len());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with_output(text: &str) -> State {
+        let mut state = State::default();
+        state.apply("output", &json!({ "text": text }));
+        state
+    }
+
+    #[test]
+    fn test_output_chunks() {
+        let mut state = state_with_output("running 2 tests\ntest a ... ");
+        state.apply("output", &json!({ "text": "ok\ntest b ... ok\n" }));
+        assert_eq!(state.visible_lines(), vec!["running 2 tests", "test a ... ok", "test b ... ok"]);
+        state.apply("run_started", &json!({ "run": 2 }));
+        assert!(state.lines.is_empty());
+        assert_eq!(state.run, Some(2));
+    }
+
+    #[test]
+    fn test_run_finished() {
+        let mut state = State::default();
+        state.apply("run_finished", &json!({
+            "run": 1,
+            "report": { "title": "Tests failed", "success": false, "failures": [{ "name": "tests::a", "message": null }] },
+            "runs": [{ "timestamp": "2024-05-01T10:00:00+00:00", "file": "src/lib.rs", "outcome": "failed",
+                       "duration_ms": 1200, "passed": 3, "failed": 1 }]
+        }));
+        assert_eq!(state.title, Some("Tests failed".to_string()));
+        assert_eq!(state.failures, vec!["tests::a"]);
+        assert_eq!(state.runs[0].duration_ms, 1200);
+    }
+
+    #[test]
+    fn test_filter_and_scroll() {
+        let mut state = state_with_output("a 1\nb 2\na 3\nb 4\n");
+        assert_eq!(state.window(2), vec!["a 3", "b 4"]);
+        state.input(Input::Up);
+        assert_eq!(state.window(2), vec!["b 2", "a 3"]);
+        state.input(Input::Home);
+        assert_eq!(state.window(2), vec!["a 1", "b 2"]);
+
+        for input in [Input::Char('/'), Input::Char('A'), Input::Enter].iter() {
+            assert_eq!(state.input(*input), None);
+        }
+        assert_eq!(state.window(5), vec!["a 1", "a 3"]);
+        assert_eq!(state.input(Input::Char('r')), Some(Request::Key(Key::Rerun)));
+        state.input(Input::Escape);
+        assert_eq!(state.visible_lines().len(), 4);
+    }
+
+    #[test]
+    fn test_toggles() {
+        let mut state = State::default();
+        assert_eq!(state.input(Input::Char('n')), Some(Request::Key(Key::ToggleNotifications)));
+        assert_eq!(state.input(Input::Char('p')), Some(Request::Key(Key::Pause)));
+        assert!(state.muted && state.paused);
+        assert_eq!(state.input(Input::Char('f')), Some(Request::Key(Key::Follow)));
+        state.apply("followed", &json!({ "name": "tests::a" }));
+        assert_eq!(state.followed, Some("tests::a".to_string()));
+    }
+
+    #[test]
+    fn test_picker() {
+        let mut state = State::default();
+        assert_eq!(state.input(Input::Char('t')), Some(Request::ListTests));
+        state.apply("tests", &json!({ "names": ["db::tests::test_insert", "db::tests::test_delete", "parser::tests::test_parse"] }));
+        for input in [Input::Char('d'), Input::Char('b'), Input::Tab, Input::Down, Input::Tab].iter() {
+            assert_eq!(state.input(*input), None);
+        }
+        assert_eq!(state.input(Input::Enter), Some(Request::Pick(vec!["db::tests::test_delete".to_string(), "db::tests::test_insert".to_string()])));
+        assert!(state.picker.is_none());
+
+        // The highlighted test is picked if none is chosen
+        state.apply("tests", &json!({ "names": ["a", "b"] }));
+        state.input(Input::Down);
+        assert_eq!(state.input(Input::Enter), Some(Request::Pick(vec!["b".to_string()])));
+        state.apply("tests", &json!({ "names": ["a", "b"] }));
+        assert_eq!(state.input(Input::Escape), None);
+        assert!(state.picker.is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+
+use report::format_duration;
+use super::State;
+use super::state::Picker;
+
+const KEYS: &str = "r run · t pick tests · a all tests · w whole suite · f follow failing test · s/x accept/reject snapshots · / filter · ↑↓ PgUp PgDn scroll · c clear · p pause · n notifications · q quit";
+
+/// Output on the left, the failing tests and the recent runs on the right, and the status bar below.
+pub fn draw(frame: &mut Frame, state: &State) {
+    let rows = Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).split(frame.area());
+    let columns = Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).split(rows[0]);
+    let panes = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).split(columns[1]);
+
+    match state.picker {
+        Some(ref picker) => draw_picker(frame, picker, columns[0]),
+        None => draw_output(frame, state, columns[0])
+    }
+    draw_failures(frame, state, panes[0]);
+    draw_runs(frame, state, panes[1]);
+    draw_status(frame, state, rows[1]);
+}
+
+fn draw_output(frame: &mut Frame, state: &State, area: Rect) {
+    let mut title = match state.run {
+        Some(run) if state.running => format!(" Run #{} · running ", run),
+        Some(run) => format!(" Run #{} ", run),
+        None => " Output ".to_string()
+    };
+    if state.editing_filter || !state.filter.is_empty() {
+        title.push_str(&format!("· filter: {}{} ", state.filter, if state.editing_filter { "▏" } else { "" }));
+    }
+    if state.scroll > 0 {
+        title.push_str(&format!("· {} lines up ", state.scroll));
+    }
+    // Inside the borders
+    let height = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = state.window(height).into_iter().map(|line| Line::from(line.to_string())).collect();
+    frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)), area);
+}
+
+fn draw_picker(frame: &mut Frame, picker: &Picker, area: Rect) {
+    let title = format!(" Pick tests: {}▏ · Tab pick · Enter run · Esc cancel ", picker.query);
+    let height = area.height.saturating_sub(2) as usize;
+    let matches = picker.matches();
+    // Scrolled just enough to keep the highlighted match in view
+    let start = (picker.cursor + 1).saturating_sub(height);
+    let items: Vec<ListItem> = matches.iter().enumerate().skip(start).take(height).map(|(index, name)| {
+        let mark = if picker.chosen.contains(*name) { "[x]" } else { "[ ]" };
+        let style = if index == picker.cursor { Style::default().add_modifier(Modifier::REVERSED) } else { Style::default() };
+        ListItem::new(Line::from(Span::styled(format!("{} {}", mark, name), style)))
+    }).collect();
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title(title)), area);
+}
+
+fn draw_failures(frame: &mut Frame, state: &State, area: Rect) {
+    let items: Vec<ListItem> = state.failures.iter()
+        .map(|name| ListItem::new(Line::from(Span::styled(name.clone(), Style::default().fg(Color::Red)))))
+        .collect();
+    let title = format!(" Failing tests ({}) ", state.failures.len());
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title(title)), area);
+}
+
+fn draw_runs(frame: &mut Frame, state: &State, area: Rect) {
+    let items: Vec<ListItem> = state.runs.iter().map(|run| {
+        let (mark, color) = match run.outcome.as_str() {
+            "passed" | "passed_with_warnings" | "flaky" => ("✔", Color::Green),
+            _ => ("✘", Color::Red)
+        };
+        // The time of the RFC 3339 timestamp
+        let time = run.timestamp.get(11..19).unwrap_or("");
+        let duration = format_duration(Duration::from_millis(run.duration_ms));
+        let file = run.file.clone().unwrap_or_default();
+        ListItem::new(Line::from(vec![
+            Span::styled(mark, Style::default().fg(color)),
+            Span::raw(format!(" {}  {:>6}  {}", time, duration, file))
+        ]))
+    }).collect();
+    frame.render_widget(List::new(items).block(Block::default().borders(Borders::ALL).title(" Runs ")), area);
+}
+
+fn draw_status(frame: &mut Frame, state: &State, area: Rect) {
+    let (status, color) = match (state.running, state.success, &state.title) {
+        (true, _, _) => ("Running".to_string(), Color::Yellow),
+        (false, Some(true), Some(title)) => (title.clone(), Color::Green),
+        (false, Some(false), Some(title)) => (title.clone(), Color::Red),
+        _ => ("Waiting".to_string(), Color::Gray)
+    };
+    let mut spans = vec![Span::styled(format!(" {} ", status), Style::default().fg(Color::Black).bg(color).add_modifier(Modifier::BOLD))];
+    if state.paused {
+        spans.push(Span::styled(" paused ", Style::default().fg(Color::Yellow)));
+    }
+    if state.muted {
+        spans.push(Span::styled(" muted ", Style::default().fg(Color::Yellow)));
+    }
+    if let Some(ref name) = state.followed {
+        spans.push(Span::styled(format!(" following {} ", name), Style::default().fg(Color::Cyan)));
+    }
+    if !state.picked.is_empty() {
+        spans.push(Span::styled(format!(" {} picked ", state.picked.len()), Style::default().fg(Color::Cyan)));
+    }
+    let text = state.message.as_deref().unwrap_or(KEYS);
+    spans.push(Span::styled(format!(" {}", text), Style::default().fg(Color::DarkGray)));
+    frame.render_widget(Paragraph::new(Line::from(spans)), area);
+}
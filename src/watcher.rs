@@ -0,0 +1,313 @@
+use std::cmp;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use notify::{self, op, Event, PollWatcher, RecommendedWatcher, Watcher};
+
+use filter::IGNORED_DIRS;
+
+/// Delay before watches are re-established after a failure, doubled after every failed attempt.
+const INITIAL_BACKOFF_MS: u64 = 500;
+
+/// Upper limit of the delay between attempts to re-establish watches.
+const MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Directories, newly created subdirectories of which are added to the watch explicitly,
+/// since not every backend picks them up by itself.
+const SOURCE_DIRS: &[&str] = &["src", "tests"];
+
+/// Interval of polling the file system when native events are not available.
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+
+/// Types of the file systems in `/proc/mounts` which do not deliver native events for changes made
+/// elsewhere: network shares, and the folders shared with virtual machines and Docker Desktop.
+const NETWORK_FILE_SYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smb3", "smbfs", "afs", "ceph", "glusterfs", "fuse.sshfs", "sshfs",
+    "9p", "vboxsf", "vmhgfs", "fuse.vmhgfs-fuse", "prl_fs", "fakeowner", "fuse.grpcfuse",
+];
+
+/// Backend delivering the events.
+enum Backend {
+    Native(RecommendedWatcher),
+    Poll {
+        watcher: PollWatcher,
+        interval: Duration,
+        /// Entries of the project directory which are watched, the directory is scanned for new ones on every poll
+        entries: Vec<PathBuf>
+    }
+}
+
+impl Backend {
+    fn watch(&mut self, path: &Path) -> notify::Result<()> {
+        match *self {
+            Backend::Native(ref mut watcher) => watcher.watch(path),
+            Backend::Poll { ref mut watcher, .. } => watcher.watch(path)
+        }
+    }
+
+    /// Watch the entries of the project directory which are not watched yet, skipping `target/`, `.git/`
+    /// and `.testify/`, and forget the ones which are gone, so they are watched again once they are recreated.
+    /// Returns the new entries. The native backend watches the whole directory, so there are none.
+    fn watch_new_entries(&mut self, project_dir: &Path) -> notify::Result<Vec<PathBuf>> {
+        let (watcher, entries) = match *self {
+            Backend::Native(_) => return Ok(vec![]),
+            Backend::Poll { ref mut watcher, ref mut entries, .. } => (watcher, entries)
+        };
+        for entry in entries.iter().filter(|entry| !entry.exists()) {
+            let _ = watcher.unwatch(entry);
+        }
+        entries.retain(|entry| entry.exists());
+        let mut new_entries: Vec<PathBuf> = fs::read_dir(project_dir).map_err(notify::Error::Io)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !IGNORED_DIRS.iter().any(|dir| entry.file_name() == *dir))
+            .map(|entry| entry.path())
+            .filter(|path| !entries.contains(path))
+            .collect();
+        new_entries.sort();
+        for entry in &new_entries {
+            watcher.watch(entry)?;
+            entries.push(entry.clone());
+        }
+        Ok(new_entries)
+    }
+}
+
+/// Watch the project directory and `external_paths` recursively in a separate thread, passing
+/// every file system event to `forward` until it returns `false`.
+///
+/// With `poll` set the file system is scanned with that interval instead of waiting for native
+/// events. Polling is also used when the project is on a network file system, or when the native
+/// backend can not be started at all.
+///
+/// The thread never gives up: when the backend fails (the inotify watch limit is reached,
+/// a watched directory is deleted and recreated, a network file system hiccups) the error
+/// is logged, and the watches are re-established with an exponential back-off.
+pub fn spawn<F>(project_dir: PathBuf, external_paths: Vec<PathBuf>, poll: Option<Duration>, forward: F)
+    where F: Fn(Event) -> bool + Send + 'static
+{
+    thread::spawn(move || {
+        let default_interval = Duration::from_millis(DEFAULT_POLL_INTERVAL_MS);
+        let mut poll = poll.or_else(|| {
+            let file_system = network_file_system(&fs::read_to_string("/proc/mounts").unwrap_or_default(), &project_dir)?;
+            eprintln!("Note: {:?} is on {} file system, polling it for changes every {}ms", project_dir, file_system, DEFAULT_POLL_INTERVAL_MS);
+            Some(default_interval)
+        });
+        let mut established = false;
+        let mut backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+        loop {
+            match establish(&project_dir, &external_paths, poll) {
+                Ok((mut watcher, rx)) => {
+                    established = true;
+                    backoff = Duration::from_millis(INITIAL_BACKOFF_MS);
+                    if !pump(&mut watcher, &rx, &project_dir, &forward) { return; }
+                },
+                Err(ref err) if !established && poll.is_none() => {
+                    eprintln!("Warning: native file events are not available ({}), polling for changes every {}ms", err, DEFAULT_POLL_INTERVAL_MS);
+                    poll = Some(default_interval);
+                    continue;
+                },
+                Err(err) => eprintln!("Warning: failed to watch {:?}: {}", project_dir, err)
+            }
+            eprintln!("Warning: re-establishing file watches in {}ms", as_millis(backoff));
+            thread::sleep(backoff);
+            backoff = cmp::min(backoff * 2, Duration::from_millis(MAX_BACKOFF_MS));
+        }
+    });
+}
+
+/// Create a watcher and watch all the paths. Only failing to watch the project directory
+/// is an error, paths outside of it may be missing.
+///
+/// The polling backend rescans everything it watches on every poll, so instead of the whole project
+/// directory it watches its entries one by one, skipping `target/`, `.git/` and `.testify/`. Entries
+/// created later, e.g. a new `.env` or `benches/`, are picked up when the directory is scanned again.
+fn establish(project_dir: &Path, external_paths: &[PathBuf], poll: Option<Duration>) -> notify::Result<(Backend, Receiver<Event>)> {
+    let (tx, rx) = channel();
+    let mut watcher = match poll {
+        Some(interval) => Backend::Poll {
+            watcher: PollWatcher::with_delay(tx, as_millis(interval) as u32)?,
+            interval,
+            entries: vec![]
+        },
+        None => Backend::Native(Watcher::new(tx)?)
+    };
+    match watcher {
+        Backend::Native(_) => watcher.watch(project_dir)?,
+        Backend::Poll { .. } => { watcher.watch_new_entries(project_dir)?; }
+    }
+    for path in external_paths {
+        if let Err(err) = watcher.watch(path) {
+            eprintln!("Warning: failed to watch {:?}: {}", path, err);
+        }
+    }
+    Ok((watcher, rx))
+}
+
+/// Pass events to `forward` until the backend fails. Returns `false` once `forward` does not
+/// want more events, and `true` if the watches have to be re-established.
+///
+/// While polling, the project directory is scanned for new entries with the same interval, and
+/// a creation event is passed on for each of them.
+fn pump<F>(watcher: &mut Backend, rx: &Receiver<Event>, project_dir: &Path, forward: &F) -> bool
+    where F: Fn(Event) -> bool
+{
+    let mut scanned_at = Instant::now();
+    loop {
+        let received = match *watcher {
+            Backend::Native(_) => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Backend::Poll { interval, .. } => {
+                let received = rx.recv_timeout(interval.saturating_sub(scanned_at.elapsed()));
+                if scanned_at.elapsed() >= interval {
+                    scanned_at = Instant::now();
+                    match watcher.watch_new_entries(project_dir) {
+                        Ok(new_entries) => {
+                            for path in new_entries {
+                                if !forward(Event { path: Some(path), op: Ok(op::CREATE) }) { return false; }
+                            }
+                        },
+                        Err(err) => {
+                            eprintln!("Warning: failed to scan {:?}: {}", project_dir, err);
+                            return true;
+                        }
+                    }
+                }
+                received
+            }
+        };
+        let event = match received {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            // The backend has stopped sending events
+            Err(RecvTimeoutError::Disconnected) => return true
+        };
+        let op = match event.op {
+            Ok(op) => op,
+            Err(ref err) => {
+                eprintln!("Warning: file watcher has failed: {}", err);
+                return true;
+            }
+        };
+        let is_native = match *watcher { Backend::Native(_) => true, Backend::Poll { .. } => false };
+        if op.contains(op::CREATE) && is_native {
+            if let Some(ref path) = event.path {
+                if path.is_dir() && is_in_source_dir(project_dir, path) {
+                    if let Err(err) = watcher.watch(path) {
+                        eprintln!("Warning: failed to watch {:?}: {}", path, err);
+                    }
+                }
+            }
+        }
+        if !forward(event) { return false; }
+    }
+}
+
+/// Can the event mean the content of a file has changed? Changes of the permissions only can not,
+/// and neither can creating or writing a file which is gone already: such files exist only for
+/// a moment during an atomic save, which replaces the saved file by renaming a temporary one.
+pub fn is_content_change(event: &Event) -> bool {
+    let (path, op) = match (&event.path, &event.op) {
+        (Some(path), &Ok(op)) => (path, op),
+        _ => return false
+    };
+    if op == op::CHMOD { return false; }
+    op.contains(op::REMOVE) || op.contains(op::RENAME) || path.exists()
+}
+
+/// Type of the network file system `path` is on, according to the content of `/proc/mounts`.
+/// The mount point closest to the path decides.
+fn network_file_system(mounts: &str, path: &Path) -> Option<String> {
+    mounts.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            // Spaces in mount points are escaped
+            let mount_point = PathBuf::from(fields.next()?.replace("\\040", " "));
+            let file_system = fields.next()?;
+            Some((mount_point, file_system))
+        })
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.components().count())
+        .map(|(_, file_system)| file_system)
+        .filter(|file_system| NETWORK_FILE_SYSTEMS.contains(file_system))
+        .map(String::from)
+}
+
+fn is_in_source_dir(project_dir: &Path, path: &Path) -> bool {
+    SOURCE_DIRS.iter().any(|dir| path.starts_with(project_dir.join(dir)))
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_is_content_change() {
+        let event = |path: &str, op| Event { path: Some(PathBuf::from(path)), op: Ok(op) };
+        let existing = file!();
+        let gone = "src/.watcher.rs.tmp";
+
+        assert!(is_content_change(&event(existing, op::WRITE)));
+        assert!(is_content_change(&event(existing, op::CREATE)));
+        assert!(is_content_change(&event(gone, op::REMOVE)));
+        assert!(is_content_change(&event(gone, op::RENAME)));
+
+        assert!(!is_content_change(&event(existing, op::CHMOD)));
+        assert!(!is_content_change(&event(gone, op::CREATE)));
+        assert!(!is_content_change(&event(gone, op::WRITE)));
+    }
+
+    #[test]
+    fn test_watch_new_entries() {
+        let dir = env::temp_dir().join("testify-watch-new-entries-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        let (tx, _rx) = channel();
+        let mut watcher = Backend::Poll { watcher: PollWatcher::with_delay(tx, 1000).unwrap(), interval: Duration::from_secs(1), entries: vec![] };
+        assert_eq!(watcher.watch_new_entries(&dir).unwrap(), vec![dir.join("src")]);
+
+        fs::write(dir.join(".env"), "RUST_LOG=debug\n").unwrap();
+        fs::create_dir_all(dir.join("benches")).unwrap();
+        assert_eq!(watcher.watch_new_entries(&dir).unwrap(), vec![dir.join(".env"), dir.join("benches")]);
+        assert_eq!(watcher.watch_new_entries(&dir).unwrap(), Vec::<PathBuf>::new());
+
+        // A recreated entry is watched again
+        fs::remove_file(dir.join(".env")).unwrap();
+        assert_eq!(watcher.watch_new_entries(&dir).unwrap(), Vec::<PathBuf>::new());
+        fs::write(dir.join(".env"), "RUST_LOG=info\n").unwrap();
+        assert_eq!(watcher.watch_new_entries(&dir).unwrap(), vec![dir.join(".env")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_network_file_system() {
+        let mounts = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+server:/export /home/dev/shared nfs4 rw,relatime 0 0
+/dev/sdb1 /home/dev/shared/local ext4 rw 0 0
+dev@host:/work /mnt/remote\\040work fuse.sshfs rw 0 0
+";
+        let file_system = |path: &str| network_file_system(mounts, Path::new(path));
+        assert_eq!(file_system("/home/dev/shared/app"), Some("nfs4".to_string()));
+        assert_eq!(file_system("/mnt/remote work/app"), Some("fuse.sshfs".to_string()));
+        assert_eq!(file_system("/home/dev/shared/local/app"), None);
+        assert_eq!(file_system("/home/dev/app"), None);
+    }
+
+    #[test]
+    fn test_is_in_source_dir() {
+        let project_dir = Path::new("/project");
+        assert!(is_in_source_dir(project_dir, Path::new("/project/src/parser")));
+        assert!(is_in_source_dir(project_dir, Path::new("/project/tests/fixtures")));
+        assert!(!is_in_source_dir(project_dir, Path::new("/project/target/debug")));
+        assert!(!is_in_source_dir(project_dir, Path::new("/project/srcs")));
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde_json::Value;
+use sha1_smol::Sha1;
+
+use server::Event;
+
+/// Appended to the key of the client to compute the accept key of the handshake (RFC 6455).
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// An idle connection gets a ping this often, so a closed one is noticed.
+const PING_INTERVAL_MS: u64 = 15_000;
+
+/// Frames from clients are only read to notice them closing the connection, so larger ones are refused.
+const MAX_CLIENT_FRAME_LENGTH: u64 = 64 * 1024;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Complete the handshake and send every event as a JSON text message, e.g.
+/// `{"event":"output","text":"test tests::ok ... ok\n"}`, until the client disconnects.
+pub fn serve(mut stream: TcpStream, key: &str, events: Receiver<Event>) {
+    let handshake = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept_key(key));
+    if stream.write_all(handshake.as_bytes()).is_err() { return; }
+
+    // Messages of the client are not used, but it has to be answered when it pings or closes
+    let mut reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(_) => return
+    };
+    let _ = reader.set_read_timeout(None);
+    // Both threads write whole frames under the lock, so a pong never lands within a message
+    let writer = Arc::new(Mutex::new(stream));
+    let reply_writer = writer.clone();
+    thread::spawn(move || {
+        loop {
+            match read_frame(&mut reader) {
+                Ok((OPCODE_PING, payload)) => {
+                    if send(&reply_writer, &frame(OPCODE_PONG, &payload)).is_err() { break; }
+                },
+                Ok((OPCODE_CLOSE, _)) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+        let _ = send(&reply_writer, &frame(OPCODE_CLOSE, &[]));
+        let _ = reader.shutdown(Shutdown::Both);
+    });
+
+    loop {
+        let bytes = match events.recv_timeout(Duration::from_millis(PING_INTERVAL_MS)) {
+            Ok((event, data)) => frame(OPCODE_TEXT, message(&event, data).to_string().as_bytes()),
+            Err(RecvTimeoutError::Timeout) => frame(OPCODE_PING, &[]),
+            Err(RecvTimeoutError::Disconnected) => return
+        };
+        if send(&writer, &bytes).is_err() { return; }
+    }
+}
+
+/// Write a whole frame to the shared stream.
+fn send(writer: &Mutex<TcpStream>, bytes: &[u8]) -> io::Result<()> {
+    let mut stream = writer.lock().unwrap_or_else(|err| err.into_inner());
+    stream.write_all(bytes)
+}
+
+/// `Sec-WebSocket-Accept` for the `Sec-WebSocket-Key` of the client.
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(HANDSHAKE_GUID.as_bytes());
+    BASE64.encode(sha1.digest().bytes())
+}
+
+/// The event as a single JSON object: its data with the name of the event added.
+fn message(event: &str, data: Value) -> Value {
+    match data {
+        Value::Object(mut fields) => {
+            fields.insert("event".to_string(), Value::String(event.to_string()));
+            Value::Object(fields)
+        },
+        data => json!({ "event": event, "data": data })
+    }
+}
+
+/// An unfragmented, unmasked frame, as sent by a server.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x80 | opcode];
+    let length = payload.len();
+    if length < 126 {
+        bytes.push(length as u8);
+    } else if length <= 0xFFFF {
+        bytes.push(126);
+        bytes.extend_from_slice(&[(length >> 8) as u8, length as u8]);
+    } else {
+        bytes.push(127);
+        for shift in (0..8).rev() {
+            bytes.push((length as u64 >> (shift * 8)) as u8);
+        }
+    }
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Read a frame of the client, which is always masked. Returns its opcode and the unmasked payload.
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 2];
+    reader.read_exact(&mut header)?;
+    let opcode = header[0] & 0x0F;
+    let mut length = u64::from(header[1] & 0x7F);
+    if length == 126 {
+        let mut extended = [0; 2];
+        reader.read_exact(&mut extended)?;
+        length = extended.iter().fold(0, |length, &byte| (length << 8) | u64::from(byte));
+    } else if length == 127 {
+        let mut extended = [0; 8];
+        reader.read_exact(&mut extended)?;
+        length = extended.iter().fold(0, |length, &byte| (length << 8) | u64::from(byte));
+    }
+    if length > MAX_CLIENT_FRAME_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame is too large"));
+    }
+    let mut mask = [0; 4];
+    if header[1] & 0x80 != 0 {
+        reader.read_exact(&mut mask)?;
+    }
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload)?;
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+    Ok((opcode, payload))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_frames() {
+        assert_eq!(frame(OPCODE_TEXT, b"hi"), vec![0x81, 2, b'h', b'i']);
+        assert_eq!(&frame(OPCODE_TEXT, &[0; 300])[..4], &[0x81, 126, 1, 44]);
+
+        // "Hello" masked by a client, from RFC 6455
+        let masked: &[u8] = &[0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        assert_eq!(read_frame(&mut &masked[..]).unwrap(), (OPCODE_TEXT, b"Hello".to_vec()));
+
+        assert_eq!(message("output", json!({ "text": "ok\n" })), json!({ "event": "output", "text": "ok\n" }));
+    }
+}